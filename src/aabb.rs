@@ -5,6 +5,7 @@ use vecmath::{vec3_add, vec3_cross, vec3_sub, vec3_dot, vec3_scale, vec3_neg};
 
 use ray::RayTraceRay;
 
+/// Axis aligned bounding box used by objects to skip rays which can not hit them.
 #[allow(dead_code)]
 pub struct AABB {
 	start: Vector3<f64>,
@@ -13,6 +14,7 @@ pub struct AABB {
 
 #[allow(dead_code)]
 impl AABB {
+	/// Creates the box spanned by two opposite corners in any order.
 	pub fn new(x1: Vector3<f64>, x2: Vector3<f64>) -> Self {
 		Self {
 			start: [x1[0].min(x2[0]), x1[1].min(x2[1]), x1[2].min(x2[2])],
@@ -20,6 +22,21 @@ impl AABB {
 		}
 	}
 
+	/// Creates the smallest box containing all points or nothing if there are no points.
+	pub fn from_points(points: &[Vector3<f64>]) -> Option<Self> {
+		let mut aabb = match points.first() {
+			Some(point) => AABB::new(*point, *point),
+			None => { return None; }
+		};
+
+		for point in points[1..].iter() {
+			aabb.expand(*point);
+		}
+
+		Some(aabb)
+	}
+
+	/// Grows the box so that it contains the point.
 	pub fn expand(&mut self, x: Vector3<f64>) {
 		self.start[0] = self.start[0].min(x[0]);
 		self.start[1] = self.start[1].min(x[1]);
@@ -39,6 +56,7 @@ impl AABB {
 		true
 	}
 
+	/// Checks if the ray passes through the box.
 	pub fn is_hit(&self, ray: &RayTraceRay) -> bool {
 		let [x, y, z] = *ray.get_position();
 		let (x_start, x_end, y_start, y_end, z_start, z_end) = project_points_onto_ray(ray, (self.start, self.end));
@@ -96,6 +114,7 @@ impl AABB {
 	}
 
 
+	/// Returns the distance along the ray where it enters the box.
 	pub fn get_first_hit(&self, ray: &RayTraceRay) -> Option<f64> {
 		let [x, y, z] = *ray.get_position();
 		let (x_start, x_end, y_start, y_end, z_start, z_end) = project_points_onto_ray(ray, (self.start, self.end));
@@ -179,6 +198,18 @@ impl AABB {
 		&self.end
 	}
 
+	pub fn get_center(&self) -> Vector3<f64> {
+		vec3_scale(vec3_add(self.start, self.end), 0.5)
+	}
+
+	pub fn get_size(&self) -> Vector3<f64> {
+		vec3_sub(self.end, self.start)
+	}
+
+	pub fn contains(&self, point: Vector3<f64>) -> bool {
+		(0..3).all(|i| point[i] >= self.start[i] && point[i] <= self.end[i])
+	}
+
 	pub fn is_intersecting_plane(&self, position: Vector3<f64>, norm: Vector3<f64>) -> bool {
 		let size = vec3_sub(self.end, self.start);
 		let center = vec3_add(self.start, vec3_scale(size, 0.5));
@@ -0,0 +1,28 @@
+use anim::RayTraceAnimation;
+
+// Precomputed value for each frame, the last value is kept after the end
+pub struct RayTraceAnimFrames<T> {
+	frames: Vec<T>
+}
+
+impl<T> RayTraceAnimFrames<T> {
+	pub fn new(frames: Vec<T>) -> Self {
+		if frames.is_empty() {
+			panic!("No frames given!");
+		}
+
+		Self {
+			frames: frames
+		}
+	}
+
+	pub fn get_frames(&self) -> &Vec<T> {
+		&self.frames
+	}
+}
+
+impl<T: Clone + Send + Sync> RayTraceAnimation<T> for RayTraceAnimFrames<T> {
+	fn next_frame(&self, frame: usize) -> T {
+		self.frames[frame.min(self.frames.len() - 1)].clone()
+	}
+}
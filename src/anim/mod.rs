@@ -1,8 +1,10 @@
 mod seq;
 mod func;
+mod frames;
 
 pub use self::seq::RayTraceAnimSequence;
 pub use self::func::RayTraceAnimFunc;
+pub use self::frames::RayTraceAnimFrames;
 
 use vecmath::Vector3;
 use vecmath::vec3_add;
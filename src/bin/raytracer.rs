@@ -0,0 +1,363 @@
+extern crate ray_tracer;
+
+use std::env;
+use std::io::Write;
+use std::io::stderr;
+use std::path::Path;
+use std::process::exit;
+use std::thread;
+use std::time::Duration;
+
+use ray_tracer::params::RayTraceRandomSampling;
+use ray_tracer::render::RayTraceProgress;
+use ray_tracer::render::RayTracer;
+use ray_tracer::scene_file::RayTraceSceneAssets;
+use ray_tracer::scene_file::RayTraceSceneFile;
+use ray_tracer::scene_file::RayTraceSceneFileError;
+use ray_tracer::scene_file::RayTraceSceneFileErrorKind;
+use ray_tracer::scene_file::RayTraceSceneFileOptions;
+use ray_tracer::scene_file::scene_file_load_cached;
+use ray_tracer::scene_file::scene_file_load_with;
+use ray_tracer::sink::JpegSink;
+use ray_tracer::sink::PngSink;
+use ray_tracer::sink::RayTraceSink;
+use ray_tracer::sink::Y4mSink;
+use ray_tracer::sink::y4m::Ratio;
+
+const EXIT_USAGE: i32 = 1;
+const EXIT_SCENE: i32 = 2;
+const EXIT_IO: i32 = 3;
+
+// Samples given on the command line are jittered within their pixel
+const SAMPLE_SIZE: f64 = 0.5;
+
+// Previews are rendered with a fraction of the output size, files are checked for changes in this interval
+const PREVIEW_SCALE: usize = 4;
+const WATCH_INTERVAL: u64 = 500;
+
+const USAGE: &'static str = "Usage: raytracer [options] <scene.json>
+
+Options:
+  --size <width>x<height>   Override the output resolution
+  --frame-count <count>     Override the number of frames of the animation
+  --frames <start>:<end>    Render only the frames from start up to (excluding) end
+  --samples <count>         Number of randomly jittered rays per pixel
+  --threads <count>         Number of worker threads
+  -o, --output <path>       Output file, frame numbers are appended for images
+  --format <png|jpeg|y4m>   Output format, guessed from the output file by default
+  -q, --quiet               Do not print the progress
+  -w, --watch               Render again when the scene or its assets change, starting with a preview
+                            which is written to <output>_preview.png, only the first frame is rendered
+                            unless --frames is given
+  --help                    Print this message
+
+Exit codes: 1 for invalid arguments, 2 for errors in the scene file, 3 for files which can not be read or written";
+
+struct Arguments {
+	scene: String,
+	size: Option<(usize, usize)>,
+	frame_count: Option<usize>,
+	frames: Option<(usize, usize)>,
+	samples: Option<usize>,
+	threads: Option<u32>,
+	output: Option<String>,
+	format: Option<String>,
+	quiet: bool,
+	watch: bool
+}
+
+fn main() {
+	let args = match parse_arguments(env::args().skip(1).collect()) {
+		Ok(args) => args,
+		Err(message) => {
+			eprintln!("{}\n\n{}", message, USAGE);
+			exit(EXIT_USAGE);
+		}
+	};
+
+	let mut options = RayTraceSceneFileOptions::new();
+	if let Some((width, height)) = args.size {
+		options.set_size(width, height);
+	}
+	if let Some(frame_count) = args.frame_count {
+		options.set_frames(frame_count);
+	}
+
+	if args.watch {
+		watch(&args, &options);
+	}
+
+	let scene_file = match scene_file_load_with(&args.scene, &options) {
+		Ok(scene_file) => scene_file,
+		Err(err) => {
+			eprintln!("{}", err);
+			exit(get_exit_code(&err));
+		}
+	};
+
+	if let Err((message, code)) = render(scene_file, &args, args.frames, None) {
+		eprintln!("{}", message);
+		exit(code);
+	}
+}
+
+// Renders a preview with a reduced size and then the full frames whenever the scene or its assets change
+fn watch(args: &Arguments, options: &RayTraceSceneFileOptions) -> ! {
+	let frames = Some(args.frames.unwrap_or((0, 1)));
+	let mut assets = RayTraceSceneAssets::new();
+
+	loop {
+		match scene_file_load_cached(&args.scene, options, &mut assets) {
+			Ok(mut scene_file) => {
+				let (width, height) = {
+					let source = scene_file.get_source_mut().get();
+					(source.out_params.get_width(), source.out_params.get_height())
+				};
+
+				let mut preview_options = options.clone();
+				preview_options.set_size((width / PREVIEW_SCALE).max(1), (height / PREVIEW_SCALE).max(1));
+				let result = scene_file_load_cached(&args.scene, &preview_options, &mut assets)
+					.map_err(|err| (format!("{}", err), get_exit_code(&err)))
+					.and_then(|preview| {
+						render(preview, args, frames, Some(Box::new(PngSink::new(get_preview_file(args)))))
+					})
+					.and_then(|_| render(scene_file, args, frames, None));
+
+				if let Err((message, _)) = result {
+					eprintln!("{}", message);
+				}
+			},
+			Err(err) => { eprintln!("{}", err); }
+		}
+
+		if !args.quiet {
+			eprintln!("Waiting for changes of {}", assets.get_files().join(", "));
+		}
+		while !assets.has_changes() {
+			thread::sleep(Duration::from_millis(WATCH_INTERVAL));
+		}
+		if !args.quiet {
+			eprintln!("Changed {}", assets.get_changed_files().join(", "));
+		}
+
+	}
+}
+
+// The sink replaces the output of the arguments and the scene file if it is given
+fn render(scene_file: RayTraceSceneFile, args: &Arguments, frames: Option<(usize, usize)>,
+		sink: Option<Box<RayTraceSink>>) -> Result<(), (String, i32)> {
+	let frame_rate = scene_file.get_frame_rate();
+	let (mut source, scene_sink) = scene_file.into_parts();
+
+	let frame_count = source.get().out_params.get_frames();
+	if let Some((start, _)) = frames {
+		if start >= frame_count {
+			return Err((format!("The frame range starts after the last frame {} of the scene\n\n{}",
+				frame_count.max(1) - 1, USAGE), EXIT_USAGE));
+		}
+	}
+
+	let mut sink: Box<RayTraceSink> = match (sink, args.output.clone(), scene_sink) {
+		(Some(sink), _, _) => sink,
+		(None, Some(output), _) => {
+			let format = match args.format {
+				Some(ref format) => format.clone(),
+				None => guess_format(&output)
+			};
+
+			try!(create_sink(&format, output, frame_rate))
+		},
+		(None, None, Some(sink)) => sink,
+		(None, None, None) => {
+			return Err((format!("The scene file has no sink, an output file is needed\n\n{}", USAGE), EXIT_USAGE));
+		}
+	};
+
+	if let Some(samples) = args.samples {
+		source.get().params.set_sampling(Some(Box::new(RayTraceRandomSampling::new_with(SAMPLE_SIZE, samples))));
+	}
+
+	let mut tracer = RayTracer::new();
+	if let Some(threads) = args.threads {
+		tracer.set_thread_count(threads);
+	}
+	if let Some((start, end)) = frames {
+		tracer.set_frame_range(start, end);
+	}
+	if !args.quiet {
+		tracer.set_progress_hook(Box::new(print_progress));
+	}
+
+	let result = tracer.render(&mut source, &mut sink);
+	if !args.quiet {
+		eprintln!("");
+	}
+
+	match result {
+		Ok(_) => Ok(()),
+		Err(err) => Err((format!("Rendering failed: {}", err), EXIT_IO))
+	}
+}
+
+fn get_exit_code(err: &RayTraceSceneFileError) -> i32 {
+	match err.get_kind() {
+		RayTraceSceneFileErrorKind::Io => EXIT_IO,
+		RayTraceSceneFileErrorKind::Syntax | RayTraceSceneFileErrorKind::Semantic => EXIT_SCENE
+	}
+}
+
+fn parse_arguments(args: Vec<String>) -> Result<Arguments, String> {
+	let mut result = Arguments {
+		scene: String::new(),
+		size: None,
+		frame_count: None,
+		frames: None,
+		samples: None,
+		threads: None,
+		output: None,
+		format: None,
+		quiet: false,
+		watch: false
+	};
+
+	let mut scene = None;
+	let mut iter = args.into_iter();
+	while let Some(arg) = iter.next() {
+		match &arg[..] {
+			"--help" => {
+				println!("{}", USAGE);
+				exit(0);
+			},
+			"-q" | "--quiet" => { result.quiet = true; },
+			"-w" | "--watch" => { result.watch = true; },
+			"--size" => {
+				let value = try!(next_value(&mut iter, &arg));
+				let parts: Vec<&str> = value.split('x').collect();
+				if parts.len() != 2 {
+					return Err(format!("Invalid size {}, expected <width>x<height>", value));
+				}
+
+				let width = try!(parse_count(parts[0], &arg));
+				let height = try!(parse_count(parts[1], &arg));
+				result.size = Some((width, height));
+			},
+			"--frame-count" => {
+				result.frame_count = Some(try!(parse_count(&try!(next_value(&mut iter, &arg)), &arg)));
+			},
+			"--frames" => {
+				let value = try!(next_value(&mut iter, &arg));
+				let parts: Vec<&str> = value.split(':').collect();
+				if parts.len() != 2 {
+					return Err(format!("Invalid frame range {}, expected <start>:<end>", value));
+				}
+
+				let start = try!(parse_number(parts[0], &arg));
+				let end = try!(parse_number(parts[1], &arg));
+				if end <= start {
+					return Err(format!("The frame range {} is empty", value));
+				}
+
+				result.frames = Some((start, end));
+			},
+			"--samples" => {
+				result.samples = Some(try!(parse_count(&try!(next_value(&mut iter, &arg)), &arg)));
+			},
+			"--threads" => {
+				result.threads = Some(try!(parse_count(&try!(next_value(&mut iter, &arg)), &arg)) as u32);
+			},
+			"-o" | "--output" => { result.output = Some(try!(next_value(&mut iter, &arg))); },
+			"--format" => {
+				let format = try!(next_value(&mut iter, &arg));
+				match &format[..] {
+					"png" | "jpeg" | "y4m" => { result.format = Some(format.clone()); },
+					_ => { return Err(format!("Unknown format {}", format)); }
+				}
+			},
+			_ if arg.starts_with("-") => { return Err(format!("Unknown option {}", arg)); },
+			_ => {
+				if scene.is_some() {
+					return Err(format!("Only one scene file can be rendered, got {}", arg));
+				}
+				scene = Some(arg.clone());
+			}
+		}
+	}
+
+	match scene {
+		Some(scene) => { result.scene = scene; },
+		None => { return Err("No scene file given".to_string()); }
+	}
+
+	Ok(result)
+}
+
+fn next_value<I: Iterator<Item=String>>(iter: &mut I, option: &str) -> Result<String, String> {
+	match iter.next() {
+		Some(value) => Ok(value),
+		None => Err(format!("Missing value for {}", option))
+	}
+}
+
+fn parse_number(value: &str, option: &str) -> Result<usize, String> {
+	match value.parse::<usize>() {
+		Ok(number) => Ok(number),
+		Err(_) => Err(format!("Invalid number {} for {}", value, option))
+	}
+}
+
+fn parse_count(value: &str, option: &str) -> Result<usize, String> {
+	match try!(parse_number(value, option)) {
+		0 => Err(format!("The value for {} must be greater than zero", option)),
+		number => Ok(number)
+	}
+}
+
+// Previews are written next to the output or the scene file so they do not replace the rendered frames
+fn get_preview_file(args: &Arguments) -> String {
+	let base = Path::new(args.output.as_ref().unwrap_or(&args.scene));
+	let stem = base.file_stem().map_or("scene".to_string(), |s| s.to_string_lossy().into_owned());
+	base.with_file_name(format!("{}_preview.png", stem)).to_string_lossy().into_owned()
+}
+
+fn guess_format(output: &str) -> String {
+	let lower = output.to_lowercase();
+	if lower.ends_with(".jpg") || lower.ends_with(".jpeg") {
+		"jpeg".to_string()
+	} else if lower.ends_with(".y4m") {
+		"y4m".to_string()
+	} else {
+		"png".to_string()
+	}
+}
+
+fn create_sink(format: &str, output: String, frame_rate: f64) -> Result<Box<RayTraceSink>, (String, i32)> {
+	match format {
+		"jpeg" => Ok(Box::new(JpegSink::new(output))),
+		"y4m" => {
+			let ratio = Ratio::new((frame_rate * 1000.0).round() as usize, 1000);
+			match Y4mSink::new(output.clone(), ratio) {
+				Ok(sink) => Ok(Box::new(sink)),
+				Err(err) => Err((format!("Could not create {}: {}", output, err), EXIT_IO))
+			}
+		},
+		_ => Ok(Box::new(PngSink::new(output)))
+	}
+}
+
+fn print_progress(progress: &RayTraceProgress) {
+	let remaining = match progress.get_remaining() {
+		Some(remaining) => format_seconds(remaining),
+		None => "--:--:--".to_string()
+	};
+
+	let mut err = stderr();
+	let _ = write!(err, "\rFrame {} ({}/{}) {:5.1}% elapsed {} remaining {} ", progress.get_frame(),
+		progress.get_frame_index() + 1, progress.get_frame_count(), progress.get_fraction() * 100.0,
+		format_seconds(progress.get_elapsed()), remaining);
+	let _ = err.flush();
+}
+
+fn format_seconds(seconds: f64) -> String {
+	let seconds = seconds.max(0.0).round() as u64;
+	format!("{:02}:{:02}:{:02}", seconds / 3600, (seconds / 60) % 60, seconds % 60)
+}
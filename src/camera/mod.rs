@@ -7,9 +7,25 @@ pub use self::orthographic::RayTracerCameraOrthographic;
 use vecmath::Vector3;
 
 use ray::RayTraceRay;
+use ray::RayTraceRayDifferentials;
+use scene_file::RayTraceSceneWriteError;
+use scene_file::RayTraceSceneWriter;
 
 pub trait RayTraceCamera: Send + Sync {
 	fn init(&mut self, frame: usize);
 	fn make_ray(&self, x: f64, y: f64) -> RayTraceRay;
 	fn get_direction(&self) -> Vector3<f64>;
+
+	// Adds the rays through the neighbouring pixels which are used to filter textures
+	fn make_ray_differential(&self, x: f64, y: f64) -> RayTraceRay {
+		let mut ray = self.make_ray(x, y);
+		ray.set_differentials(Some(RayTraceRayDifferentials::new(&self.make_ray(x + 1.0, y),
+			&self.make_ray(x, y + 1.0))));
+		ray
+	}
+
+	// Cameras which do not override this can not be written to a scene file
+	fn serialize(&self, writer: &mut RayTraceSceneWriter) -> Result<(), RayTraceSceneWriteError> {
+		Err(writer.unsupported("camera"))
+	}
 }
\ No newline at end of file
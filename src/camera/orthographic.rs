@@ -7,21 +7,21 @@ use anim::RayTraceAnimation;
 use camera::RayTraceCamera;
 use params::RayTraceOutputParams;
 use ray::RayTraceRay;
+use scene_file::RayTraceSceneWriteError;
+use scene_file::RayTraceSceneWriter;
 
 #[allow(dead_code)]
 pub struct RayTracerCameraOrthographic {
-	position: Vector3<f64>,
-	rotation: Vector3<f64>,
+	transform: RayTraceTransform,
 	width: f64,
 	height: f64,
 	screen_width: f64,
 	screen_height: f64,
-	anim_pos: Option<Box<RayTraceAnimation<Vector3<f64>>>>,
-	anim_rot: Option<Box<RayTraceAnimation<Vector3<f64>>>>,
 	data: Option<WorkingData>
 }
 
 struct WorkingData {
+	position: Vector3<f64>,
 	plane_vec: [Vector3<f64>; 2],
 	normal_vec: Vector3<f64>
 }
@@ -34,65 +34,77 @@ impl<'a> RayTracerCameraOrthographic {
 
 	pub fn new_with(screen: &RayTraceOutputParams, width: f64, height: f64) -> Self {
 		Self {
-			position: [0.0, 0.0, 0.0],
-			rotation: [0.0, 0.0, 0.0],
+			transform: RayTraceTransform::new(),
 			width: width,
 			height: height,
 			screen_width: screen.get_width() as f64,
 			screen_height: screen.get_height() as f64,
-			anim_rot: None,
-			anim_pos: None,
 			data: None
 		}
 	}
 
-	pub fn set_position(&mut self, postion: Vector3<f64>) {
-		self.position = postion;
+	#[deprecated(note = "Use the transform instead")]
+	pub fn get_position(&self) -> &Vector3<f64> {
+		self.transform.get_translation()
+	}
+
+	#[deprecated(note = "Use the transform instead")]
+	pub fn set_position(&mut self, position: Vector3<f64>) {
+		self.transform.set_translation(position);
 		self.data = None;
 	}
 
+	#[deprecated(note = "Use the transform instead")]
 	pub fn set_rotation(&mut self, rotation: Vector3<f64>) {
-		self.rotation = rotation;
+		self.transform.set_rotation(rotation);
 		self.data = None;
 	}
 
+	#[deprecated(note = "Use the transform instead")]
 	pub fn set_anim_pos_opt(&mut self, anim: Option<Box<RayTraceAnimation<Vector3<f64>>>>) {
-		self.anim_pos = anim;
+		self.transform.set_anim_translation_opt(anim);
 	}
 
+	#[deprecated(note = "Use the transform instead")]
 	pub fn set_anim_pos(&mut self, anim: Box<RayTraceAnimation<Vector3<f64>>>) {
-		self.anim_pos = Some(anim);
+		self.transform.set_anim_translation(anim);
 	}
 
+	#[deprecated(note = "Use the transform instead")]
 	pub fn set_anim_rot_opt(&mut self, anim: Option<Box<RayTraceAnimation<Vector3<f64>>>>) {
-		self.anim_rot = anim;
+		self.transform.set_anim_rotation_opt(anim);
 	}
 
+	#[deprecated(note = "Use the transform instead")]
 	pub fn set_anim_rot(&mut self, anim: Box<RayTraceAnimation<Vector3<f64>>>) {
-		self.anim_rot = Some(anim);
+		self.transform.set_anim_rotation(anim);
+	}
+}
+
+impl RayTraceTransformable for RayTracerCameraOrthographic {
+	fn get_transform(&self) -> &RayTraceTransform {
+		&self.transform
+	}
+
+	fn get_transform_mut(&mut self) -> &mut RayTraceTransform {
+		&mut self.transform
 	}
 }
 
 #[allow(unused_variables)]
 impl RayTraceCamera for RayTracerCameraOrthographic {
 	fn init(&mut self, frame: usize) {
-		if let Some(ref anim_pos) = self.anim_pos {
-			self.position = anim_pos.next_frame(frame);
-		}
-		if let Some(ref anim_rot) = self.anim_rot {
-			self.rotation = anim_rot.next_frame(frame);
-		}
+		self.transform.init(frame);
 
 		// Start with a view into neg z-axis
 		let plane_vec1 = [self.width / self.screen_width, 0.0, 0.0];
 		let plane_vec2 = [0.0, -self.height / self.screen_height, 0.0];
 		let normal_vec = [0.0, 0.0, -1.0];
 
-		let rot = rotate_xyz(self.rotation);
-
 		self.data = Some(WorkingData {
-			plane_vec: [row_mat3_transform(rot, plane_vec1), row_mat3_transform(rot, plane_vec2)],
-			normal_vec: row_mat3_transform(rot, normal_vec)
+			position: self.transform.get_position(),
+			plane_vec: [self.transform.transform_direction(plane_vec1), self.transform.transform_direction(plane_vec2)],
+			normal_vec: vec3_normalized(self.transform.transform_direction(normal_vec))
 		});
 	}
 
@@ -102,7 +114,7 @@ impl RayTraceCamera for RayTracerCameraOrthographic {
 			let offset_y = vec3_scale(data.plane_vec[1], (y - self.screen_height as f64 / 2.0));
 			let offset = vec3_add(offset_x, offset_y);
 
-			return RayTraceRay::new(vec3_add(self.position, offset), data.normal_vec);
+			return RayTraceRay::new(vec3_add(data.position, offset), data.normal_vec);
 		} else {
 			panic!("Camera was not initialized!");
 		}
@@ -115,4 +127,12 @@ impl RayTraceCamera for RayTracerCameraOrthographic {
 			[0.0, 0.0, 0.0]
 		}
 	}
+
+	fn serialize(&self, writer: &mut RayTraceSceneWriter) -> Result<(), RayTraceSceneWriteError> {
+		writer.set_type("orthographic");
+		try!(writer.set_f64("width", self.width));
+		try!(writer.set_f64("height", self.height));
+		writer.set_transform(&self.transform);
+		Ok(())
+	}
 }
\ No newline at end of file
@@ -5,22 +5,22 @@ use anim::RayTraceAnimation;
 use camera::RayTraceCamera;
 use params::RayTraceOutputParams;
 use ray::RayTraceRay;
+use scene_file::RayTraceSceneWriteError;
+use scene_file::RayTraceSceneWriter;
 
 #[allow(dead_code)]
 pub struct RayTracerCameraPerspective {
-	position: Vector3<f64>,
-	rotation: Vector3<f64>,
+	transform: RayTraceTransform,
 	width: f64,
 	height: f64,
 	distance: f64,
 	screen_width: f64,
 	screen_height: f64,
-	anim_pos: Option<Box<RayTraceAnimation<Vector3<f64>>>>,
-	anim_rot: Option<Box<RayTraceAnimation<Vector3<f64>>>>,
 	data: Option<WorkingData>
 }
 
 struct WorkingData {
+	position: Vector3<f64>,
 	plane_vec: [Vector3<f64>; 2],
 	plane_offset: Vector3<f64>,
 	plane_normal: Vector3<f64>
@@ -34,66 +34,78 @@ impl RayTracerCameraPerspective {
 
 	pub fn new_with(screen: &RayTraceOutputParams, width: f64, height: f64, distance: f64) -> Self {
 		Self {
-			position: [0.0, 0.0, 0.0],
-			rotation: [0.0, 0.0, 0.0],
+			transform: RayTraceTransform::new(),
 			width: width,
 			height: height,
 			distance: distance,
 			screen_width: screen.get_width() as f64,
 			screen_height: screen.get_height() as f64,
-			anim_rot: None,
-			anim_pos: None,
 			data: None
 		}
 	}
 
-	pub fn set_position(&mut self, postion: Vector3<f64>) {
-		self.position = postion;
+	#[deprecated(note = "Use the transform instead")]
+	pub fn get_position(&self) -> &Vector3<f64> {
+		self.transform.get_translation()
+	}
+
+	#[deprecated(note = "Use the transform instead")]
+	pub fn set_position(&mut self, position: Vector3<f64>) {
+		self.transform.set_translation(position);
 		self.data = None;
 	}
 
+	#[deprecated(note = "Use the transform instead")]
 	pub fn set_rotation(&mut self, rotation: Vector3<f64>) {
-		self.rotation = rotation;
+		self.transform.set_rotation(rotation);
 		self.data = None;
 	}
 
+	#[deprecated(note = "Use the transform instead")]
 	pub fn set_anim_pos_opt(&mut self, anim: Option<Box<RayTraceAnimation<Vector3<f64>>>>) {
-		self.anim_pos = anim;
+		self.transform.set_anim_translation_opt(anim);
 	}
 
+	#[deprecated(note = "Use the transform instead")]
 	pub fn set_anim_pos(&mut self, anim: Box<RayTraceAnimation<Vector3<f64>>>) {
-		self.anim_pos = Some(anim);
+		self.transform.set_anim_translation(anim);
 	}
 
+	#[deprecated(note = "Use the transform instead")]
 	pub fn set_anim_rot_opt(&mut self, anim: Option<Box<RayTraceAnimation<Vector3<f64>>>>) {
-		self.anim_rot = anim;
+		self.transform.set_anim_rotation_opt(anim);
 	}
 
+	#[deprecated(note = "Use the transform instead")]
 	pub fn set_anim_rot(&mut self, anim: Box<RayTraceAnimation<Vector3<f64>>>) {
-		self.anim_rot = Some(anim);
+		self.transform.set_anim_rotation(anim);
+	}
+}
+
+impl RayTraceTransformable for RayTracerCameraPerspective {
+	fn get_transform(&self) -> &RayTraceTransform {
+		&self.transform
+	}
+
+	fn get_transform_mut(&mut self) -> &mut RayTraceTransform {
+		&mut self.transform
 	}
 }
 
 #[allow(unused_variables)]
 impl RayTraceCamera for RayTracerCameraPerspective {
 	fn init(&mut self, frame: usize) {
-		if let Some(ref anim_pos) = self.anim_pos {
-			self.position = anim_pos.next_frame(frame);
-		}
-		if let Some(ref anim_rot) = self.anim_rot {
-			self.rotation = anim_rot.next_frame(frame);
-		}
+		self.transform.init(frame);
 
 		// Start with a view into neg z-axis
 		let plane_vec1 = [self.width / self.screen_width, 0.0, 0.0];
 		let plane_vec2 = [0.0, -self.height / self.screen_height, 0.0];
 		let normal_vec = [0.0, 0.0, -1.0];
 
-		let rot = rotate_xyz(self.rotation);
-
-		let plane_normal = row_mat3_transform(rot, normal_vec);
+		let plane_normal = vec3_normalized(self.transform.transform_direction(normal_vec));
 		self.data = Some(WorkingData {
-			plane_vec: [row_mat3_transform(rot, plane_vec1), row_mat3_transform(rot, plane_vec2)],
+			position: self.transform.get_position(),
+			plane_vec: [self.transform.transform_direction(plane_vec1), self.transform.transform_direction(plane_vec2)],
 			plane_normal: plane_normal,
 			plane_offset: vec3_scale(plane_normal, self.distance)
 		});
@@ -105,7 +117,7 @@ impl RayTraceCamera for RayTracerCameraPerspective {
 			let offset_y = vec3_scale(data.plane_vec[1], (y - self.screen_height / 2.0));
 			let offset = vec3_add(offset_x, offset_y);
 
-			return RayTraceRay::new(self.position, vec3_normalized(vec3_add(data.plane_offset, offset)));
+			return RayTraceRay::new(data.position, vec3_normalized(vec3_add(data.plane_offset, offset)));
 		} else {
 			panic!("Camera was not initialized!");
 		}
@@ -118,4 +130,13 @@ impl RayTraceCamera for RayTracerCameraPerspective {
 			[0.0, 0.0, 0.0]
 		}
 	}
+
+	fn serialize(&self, writer: &mut RayTraceSceneWriter) -> Result<(), RayTraceSceneWriteError> {
+		writer.set_type("perspective");
+		try!(writer.set_f64("width", self.width));
+		try!(writer.set_f64("height", self.height));
+		try!(writer.set_f64("distance", self.distance));
+		writer.set_transform(&self.transform);
+		Ok(())
+	}
 }
\ No newline at end of file
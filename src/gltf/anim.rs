@@ -0,0 +1,206 @@
+use std::io::Error as IOError;
+use std::io::ErrorKind;
+
+use json::JsonValue;
+
+use gltf::buffer::read_accessor;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum GltfPath {
+	Translation,
+	Rotation,
+	Scale
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum GltfInterpolation {
+	Step,
+	Linear,
+	CubicSpline
+}
+
+// Keyframes of one animated property of a node
+pub struct GltfChannel {
+	node: usize,
+	path: GltfPath,
+	times: Vec<f64>,
+	values: Vec<f64>,
+	interpolation: GltfInterpolation
+}
+
+// Reads the translation, rotation and scale channels of all animations
+pub fn read_channels(document: &JsonValue, buffers: &Vec<Vec<u8>>) -> Result<Vec<GltfChannel>, IOError> {
+	let mut channels = Vec::new();
+
+	let animations = match document.get("animations").and_then(|a| a.as_array()) {
+		Some(animations) => animations,
+		None => { return Ok(channels); }
+	};
+
+	for (a, animation) in animations.iter().enumerate() {
+		let samplers = animation.get("samplers").and_then(|s| s.as_array());
+		let list = match animation.get("channels").and_then(|c| c.as_array()) {
+			Some(list) => list,
+			None => { continue; }
+		};
+
+		for channel in list.iter() {
+			let target = channel.get("target");
+			let node = match target.and_then(|t| t.get("node")).and_then(|n| n.as_usize()) {
+				Some(node) => node,
+				None => { continue; }
+			};
+
+			let path = match target.and_then(|t| t.get("path")).and_then(|p| p.as_str()) {
+				Some("translation") => GltfPath::Translation,
+				Some("rotation") => GltfPath::Rotation,
+				Some("scale") => GltfPath::Scale,
+				Some(path) => {
+					info!("Ignored {} channel of animation {}", path, a);
+					continue;
+				},
+				None => { continue; }
+			};
+
+			let sampler = channel.get("sampler").and_then(|s| s.as_usize())
+				.and_then(|s| samplers.and_then(|samplers| samplers.get(s)));
+			let sampler = match sampler {
+				Some(sampler) => sampler,
+				None => { return format_err(&format!("Invalid sampler in animation {}", a)); }
+			};
+
+			let interpolation = match sampler.get("interpolation").and_then(|i| i.as_str()) {
+				Some("STEP") => GltfInterpolation::Step,
+				Some("CUBICSPLINE") => GltfInterpolation::CubicSpline,
+				_ => GltfInterpolation::Linear
+			};
+
+			let (input, output) = match (sampler.get("input").and_then(|i| i.as_usize()),
+					sampler.get("output").and_then(|o| o.as_usize())) {
+				(Some(input), Some(output)) => (input, output),
+				_ => { return format_err(&format!("Missing sampler data in animation {}", a)); }
+			};
+
+			let (times, _) = try!(read_accessor(document, buffers, input));
+			let (values, components) = try!(read_accessor(document, buffers, output));
+
+			let expected = if path == GltfPath::Rotation { 4 } else { 3 };
+			let per_key = if interpolation == GltfInterpolation::CubicSpline { 3 } else { 1 };
+			if components != expected || values.len() != times.len() * expected * per_key || times.is_empty() {
+				return format_err(&format!("Invalid keyframes in animation {}", a));
+			}
+
+			channels.push(GltfChannel {
+				node: node,
+				path: path,
+				times: times,
+				values: values,
+				interpolation: interpolation
+			});
+		}
+	}
+
+	Ok(channels)
+}
+
+#[allow(dead_code)]
+impl GltfChannel {
+	pub fn get_node(&self) -> usize {
+		self.node
+	}
+
+	pub fn get_path(&self) -> GltfPath {
+		self.path
+	}
+
+	pub fn get_end_time(&self) -> f64 {
+		self.times[self.times.len() - 1]
+	}
+
+	pub fn sample(&self, time: f64) -> Vec<f64> {
+		let last = self.times.len() - 1;
+		if time <= self.times[0] {
+			return self.get_value(0);
+		}
+		if time >= self.times[last] {
+			return self.get_value(last);
+		}
+
+		let k = match self.times.iter().position(|t| *t > time) {
+			Some(next) => next - 1,
+			None => last
+		};
+
+		let dt = self.times[k + 1] - self.times[k];
+		let t = if dt > 0.0 { (time - self.times[k]) / dt } else { 0.0 };
+		let (v0, v1) = (self.get_value(k), self.get_value(k + 1));
+
+		let value = match self.interpolation {
+			GltfInterpolation::Step => v0,
+			GltfInterpolation::Linear => {
+				if self.path == GltfPath::Rotation {
+					slerp(&v0, &v1, t)
+				} else {
+					v0.iter().zip(v1.iter()).map(|(a, b)| a + (b - a) * t).collect()
+				}
+			},
+			GltfInterpolation::CubicSpline => {
+				// Hermite spline with the out tangent of the start and the in tangent of the end key
+				let (m0, m1) = (self.get_tangent(k, 2), self.get_tangent(k + 1, 0));
+				let (t2, t3) = (t * t, t * t * t);
+				let h = [2.0 * t3 - 3.0 * t2 + 1.0, t3 - 2.0 * t2 + t, -2.0 * t3 + 3.0 * t2, t3 - t2];
+
+				(0..v0.len())
+					.map(|i| h[0] * v0[i] + h[1] * dt * m0[i] + h[2] * v1[i] + h[3] * dt * m1[i])
+					.collect()
+			}
+		};
+
+		if self.path == GltfPath::Rotation { normalize(value) } else { value }
+	}
+
+	fn get_value(&self, key: usize) -> Vec<f64> {
+		if self.interpolation == GltfInterpolation::CubicSpline {
+			self.get_tangent(key, 1)
+		} else {
+			let size = self.get_size();
+			self.values[key * size..(key + 1) * size].to_vec()
+		}
+	}
+
+	// Cubic splines store the in tangent, the value and the out tangent for each key
+	fn get_tangent(&self, key: usize, part: usize) -> Vec<f64> {
+		let size = self.get_size();
+		let start = (key * 3 + part) * size;
+		self.values[start..start + size].to_vec()
+	}
+
+	fn get_size(&self) -> usize {
+		if self.path == GltfPath::Rotation { 4 } else { 3 }
+	}
+}
+
+fn slerp(q0: &Vec<f64>, q1: &Vec<f64>, t: f64) -> Vec<f64> {
+	let mut dot: f64 = q0.iter().zip(q1.iter()).map(|(a, b)| a * b).sum();
+
+	// Take the shorter path around the sphere
+	let sign = if dot < 0.0 { -1.0 } else { 1.0 };
+	dot *= sign;
+
+	if dot > 0.9995 {
+		return q0.iter().zip(q1.iter()).map(|(a, b)| a + (b * sign - a) * t).collect();
+	}
+
+	let theta = dot.acos();
+	let (w0, w1) = (((1.0 - t) * theta).sin() / theta.sin(), (t * theta).sin() / theta.sin() * sign);
+	q0.iter().zip(q1.iter()).map(|(a, b)| a * w0 + b * w1).collect()
+}
+
+fn normalize(q: Vec<f64>) -> Vec<f64> {
+	let len = q.iter().map(|v| v * v).sum::<f64>().sqrt();
+	if len > 0.0 { q.iter().map(|v| v / len).collect() } else { q }
+}
+
+fn format_err<T>(message: &str) -> Result<T, IOError> {
+	Err(IOError::new(ErrorKind::InvalidData, message.to_string()))
+}
@@ -0,0 +1,284 @@
+use std::fs::File;
+use std::io::Read;
+use std::io::Error as IOError;
+use std::io::ErrorKind;
+use std::path::Path;
+
+use json::JsonValue;
+
+const GLB_MAGIC: u32 = 0x46546C67;
+const GLB_CHUNK_JSON: u32 = 0x4E4F534A;
+const GLB_CHUNK_BIN: u32 = 0x004E4942;
+
+// Accessors without a buffer view are filled with zeros, larger ones are rejected instead of allocated
+const MAX_ZERO_ACCESSOR_VALUES: usize = 1 << 24;
+
+// Splits a binary .glb container into the JSON document and the embedded buffer
+pub fn read_glb(content: &[u8]) -> Result<(String, Option<Vec<u8>>), IOError> {
+	if content.len() < 12 || read_u32(content, 0) != GLB_MAGIC {
+		return format_err("Not a binary glTF file");
+	}
+	if read_u32(content, 4) != 2 {
+		return format_err("Only glTF version 2 is supported");
+	}
+
+	let length = (read_u32(content, 8) as usize).min(content.len());
+	let mut position = 12;
+	let mut json = None;
+	let mut binary = None;
+
+	while position + 8 <= length {
+		let chunk_length = read_u32(content, position) as usize;
+		let chunk_type = read_u32(content, position + 4);
+		let start = position + 8;
+
+		if start + chunk_length > length {
+			return format_err("Chunk exceeds the file size");
+		}
+
+		let data = &content[start..start + chunk_length];
+		if chunk_type == GLB_CHUNK_JSON && json.is_none() {
+			match String::from_utf8(data.to_vec()) {
+				Ok(text) => { json = Some(text); },
+				Err(_) => { return format_err("Invalid JSON chunk"); }
+			}
+		} else if chunk_type == GLB_CHUNK_BIN && binary.is_none() {
+			binary = Some(data.to_vec());
+		}
+
+		// Chunks are aligned to 4 bytes
+		position = start + ((chunk_length + 3) & !3);
+	}
+
+	match json {
+		Some(json) => Ok((json, binary)),
+		None => format_err("Missing JSON chunk")
+	}
+}
+
+// Loads all buffers from data uris, external files or the embedded binary chunk
+pub fn load_buffers(document: &JsonValue, directory: &Path, mut binary: Option<Vec<u8>>)
+		-> Result<Vec<Vec<u8>>, IOError> {
+	let mut buffers = Vec::new();
+
+	if let Some(list) = document.get("buffers").and_then(|b| b.as_array()) {
+		for (i, buffer) in list.iter().enumerate() {
+			let data = match buffer.get("uri").and_then(|u| u.as_str()) {
+				Some(uri) => try!(load_uri(uri, directory)),
+				None => {
+					match binary.take() {
+						Some(data) => data,
+						None => { return format_err(&format!("Buffer {} has no data", i)); }
+					}
+				}
+			};
+
+			let length = buffer.get("byteLength").and_then(|l| l.as_usize()).unwrap_or(data.len());
+			if data.len() < length {
+				return format_err(&format!("Buffer {} is shorter than its byte length", i));
+			}
+
+			buffers.push(data);
+		}
+	}
+
+	Ok(buffers)
+}
+
+pub fn load_uri(uri: &str, directory: &Path) -> Result<Vec<u8>, IOError> {
+	if uri.starts_with("data:") {
+		return match uri.find(";base64,") {
+			Some(index) => base64_decode(&uri[index + 8..]),
+			None => format_err("Only base64 encoded data uris are supported")
+		};
+	}
+
+	let mut content = Vec::new();
+	let mut file = try!(File::open(directory.join(decode_uri(uri))));
+	try!(file.read_to_end(&mut content));
+
+	Ok(content)
+}
+
+// Reads the data of a buffer view which is used for images
+pub fn read_buffer_view<'a>(document: &JsonValue, buffers: &'a Vec<Vec<u8>>, index: usize)
+		-> Result<&'a [u8], IOError> {
+	let view = match document.get("bufferViews").and_then(|v| v.as_array()).and_then(|v| v.get(index)) {
+		Some(view) => view,
+		None => { return format_err(&format!("Missing buffer view {}", index)); }
+	};
+
+	let buffer = match view.get("buffer").and_then(|b| b.as_usize()).and_then(|b| buffers.get(b)) {
+		Some(buffer) => buffer,
+		None => { return format_err(&format!("Invalid buffer of buffer view {}", index)); }
+	};
+
+	let offset = view.get("byteOffset").and_then(|o| o.as_usize()).unwrap_or(0);
+	let length = view.get("byteLength").and_then(|l| l.as_usize()).unwrap_or(0);
+	let end = match offset.checked_add(length) {
+		Some(end) if end <= buffer.len() => end,
+		_ => { return format_err(&format!("Buffer view {} exceeds its buffer", index)); }
+	};
+
+	Ok(&buffer[offset..end])
+}
+
+// Reads all elements of an accessor as floating point values with the number of components per element
+pub fn read_accessor(document: &JsonValue, buffers: &Vec<Vec<u8>>, index: usize)
+		-> Result<(Vec<f64>, usize), IOError> {
+	let accessor = match document.get("accessors").and_then(|a| a.as_array()).and_then(|a| a.get(index)) {
+		Some(accessor) => accessor,
+		None => { return format_err(&format!("Missing accessor {}", index)); }
+	};
+
+	let count = accessor.get("count").and_then(|c| c.as_usize()).unwrap_or(0);
+	let components = match accessor.get("type").and_then(|t| t.as_str()) {
+		Some("SCALAR") => 1,
+		Some("VEC2") => 2,
+		Some("VEC3") => 3,
+		Some("VEC4") | Some("MAT2") => 4,
+		Some("MAT3") => 9,
+		Some("MAT4") => 16,
+		_ => { return format_err(&format!("Invalid type of accessor {}", index)); }
+	};
+	let component_type = accessor.get("componentType").and_then(|c| c.as_usize()).unwrap_or(0);
+	let size = match component_type {
+		5120 | 5121 => 1,
+		5122 | 5123 => 2,
+		5125 | 5126 => 4,
+		_ => { return format_err(&format!("Invalid component type of accessor {}", index)); }
+	};
+	let normalized = accessor.get("normalized").and_then(|n| n.as_bool()).unwrap_or(false);
+
+	if accessor.get("sparse").is_some() {
+		warn!("Sparse accessor {} is read without its sparse values", index);
+	}
+
+	// Accessors without a buffer view contain only zeros
+	let view_index = match accessor.get("bufferView").and_then(|v| v.as_usize()) {
+		Some(view_index) => view_index,
+		None => {
+			return match count.checked_mul(components) {
+				Some(length) if length <= MAX_ZERO_ACCESSOR_VALUES => Ok((vec![0.0; length], components)),
+				_ => format_err(&format!("Accessor {} without a buffer view is too large", index))
+			};
+		}
+	};
+
+	let data = try!(read_buffer_view(document, buffers, view_index));
+	let view = &document.get("bufferViews").and_then(|v| v.as_array()).unwrap()[view_index];
+	let stride = view.get("byteStride").and_then(|s| s.as_usize()).unwrap_or(components * size);
+	let offset = accessor.get("byteOffset").and_then(|o| o.as_usize()).unwrap_or(0);
+
+	if stride < components * size {
+		return format_err(&format!("Invalid byte stride of buffer view {}", view_index));
+	}
+
+	// The elements have to fit into the buffer view before anything is allocated for them
+	let end = if count > 0 {
+		stride.checked_mul(count - 1)
+			.and_then(|e| e.checked_add(offset))
+			.and_then(|e| e.checked_add(components * size))
+	} else {
+		Some(0)
+	};
+	if end.map_or(true, |end| end > data.len()) {
+		return format_err(&format!("Accessor {} exceeds its buffer view", index));
+	}
+
+	let mut values = vec![0.0; count * components];
+
+	for i in 0..count {
+		for c in 0..components {
+			let position = offset + i * stride + c * size;
+			let value = match component_type {
+				5120 => data[position] as i8 as f64,
+				5121 => data[position] as f64,
+				5122 => read_u16(data, position) as i16 as f64,
+				5123 => read_u16(data, position) as f64,
+				5125 => read_u32(data, position) as f64,
+				_ => f32::from_bits(read_u32(data, position)) as f64
+			};
+
+			values[i * components + c] = if normalized {
+				match component_type {
+					5120 => (value / 127.0).max(-1.0),
+					5121 => value / 255.0,
+					5122 => (value / 32767.0).max(-1.0),
+					5123 => value / 65535.0,
+					_ => value
+				}
+			} else {
+				value
+			};
+		}
+	}
+
+	Ok((values, components))
+}
+
+pub fn base64_decode(text: &str) -> Result<Vec<u8>, IOError> {
+	let mut data = Vec::with_capacity(text.len() * 3 / 4);
+	let mut bits: u32 = 0;
+	let mut count = 0;
+
+	for c in text.bytes() {
+		let value = match c {
+			b'A'...b'Z' => c - b'A',
+			b'a'...b'z' => c - b'a' + 26,
+			b'0'...b'9' => c - b'0' + 52,
+			b'+' | b'-' => 62,
+			b'/' | b'_' => 63,
+			b'=' => { break; },
+			b' ' | b'\n' | b'\r' | b'\t' => { continue; },
+			_ => { return format_err("Invalid base64 data"); }
+		};
+
+		bits = (bits << 6) | value as u32;
+		count += 6;
+
+		if count >= 8 {
+			count -= 8;
+			data.push((bits >> count) as u8);
+		}
+	}
+
+	Ok(data)
+}
+
+// Resolves percent encoded characters of relative uris
+fn decode_uri(uri: &str) -> String {
+	let bytes = uri.as_bytes();
+	let mut result = Vec::with_capacity(bytes.len());
+	let mut i = 0;
+
+	while i < bytes.len() {
+		if bytes[i] == b'%' && i + 2 < bytes.len() {
+			let high = (bytes[i + 1] as char).to_digit(16);
+			let low = (bytes[i + 2] as char).to_digit(16);
+			if let (Some(high), Some(low)) = (high, low) {
+				result.push((high << 4 | low) as u8);
+				i += 3;
+				continue;
+			}
+		}
+
+		result.push(bytes[i]);
+		i += 1;
+	}
+
+	String::from_utf8_lossy(&result).into_owned()
+}
+
+fn read_u16(data: &[u8], position: usize) -> u16 {
+	(data[position] as u16) | (data[position + 1] as u16) << 8
+}
+
+fn read_u32(data: &[u8], position: usize) -> u32 {
+	(data[position] as u32) | (data[position + 1] as u32) << 8 | (data[position + 2] as u32) << 16
+		| (data[position + 3] as u32) << 24
+}
+
+fn format_err<T>(message: &str) -> Result<T, IOError> {
+	Err(IOError::new(ErrorKind::InvalidData, message.to_string()))
+}
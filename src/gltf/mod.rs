@@ -0,0 +1,572 @@
+mod anim;
+mod buffer;
+
+use self::anim::GltfChannel;
+use self::anim::GltfPath;
+use self::anim::read_channels;
+use self::buffer::load_buffers;
+use self::buffer::load_uri;
+use self::buffer::read_accessor;
+use self::buffer::read_buffer_view;
+use self::buffer::read_glb;
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::io::Error as IOError;
+use std::io::ErrorKind;
+use std::path::Path;
+
+use vecmath::Matrix4;
+use vecmath::Vector2;
+use vecmath::Vector3;
+use vecmath::{mat4_id, row_mat4_mul};
+
+use anim::RayTraceAnimFrames;
+use camera::RayTraceCamera;
+use camera::RayTracerCameraOrthographic;
+use camera::RayTracerCameraPerspective;
+use color::RayTraceColor;
+use json::JsonValue;
+use json::json_parse;
+use material::RayTraceMaterial;
+use material::RayTracePbrMaterial;
+use object::RayTraceObjectModel;
+use params::RayTraceOutputParams;
+use scene::RayTraceScene;
+
+use sink::image;
+use sink::image::RgbaImage;
+
+use math_util::{translate_mat4, scale_mat4, mat3_to_mat4};
+use math_util::RayTraceTransform;
+use math_util::RayTraceTransformable;
+
+// Models and cameras of a glTF scene with the names of their nodes
+pub struct RayTraceGltfImport {
+	objects: Vec<(String, RayTraceObjectModel)>,
+	cameras: Vec<(String, Box<RayTraceCamera>)>
+}
+
+struct GltfMaterial {
+	base_color: RayTraceColor,
+	metallic: f32,
+	roughness: f32,
+	emission: RayTraceColor,
+	texture: Option<usize>
+}
+
+struct GltfContext<'a> {
+	document: &'a JsonValue,
+	buffers: Vec<Vec<u8>>,
+	directory: &'a Path,
+	materials: Vec<GltfMaterial>,
+	images: HashMap<usize, Option<RgbaImage>>
+}
+
+// Loads a .gltf or .glb file, animations are sampled at the given frame rate for the frames of the output
+pub fn gltf_load(file_name: &str, screen: &RayTraceOutputParams, fps: f64) -> Result<RayTraceGltfImport, IOError> {
+	let mut content = Vec::new();
+	let mut file = try!(File::open(file_name));
+	try!(file.read_to_end(&mut content));
+
+	let (text, binary) = if content.starts_with(b"glTF") {
+		try!(read_glb(&content))
+	} else {
+		match String::from_utf8(content) {
+			Ok(text) => (text, None),
+			Err(_) => { return format_err("Invalid JSON document"); }
+		}
+	};
+
+	let document = match json_parse(&text) {
+		Ok(document) => document,
+		Err(err) => { return format_err(&format!("{}", err)); }
+	};
+
+	match document.get("asset").and_then(|a| a.get("version")).and_then(|v| v.as_str()) {
+		Some(version) if version.starts_with("2.") => { },
+		_ => { return format_err("Only glTF version 2 is supported"); }
+	}
+
+	let directory = Path::new(file_name).parent().unwrap_or(Path::new(""));
+	let mut context = GltfContext {
+		document: &document,
+		buffers: try!(load_buffers(&document, directory, binary)),
+		directory: directory,
+		materials: Vec::new(),
+		images: HashMap::new()
+	};
+	context.materials = read_materials(&document);
+
+	let nodes = match document.get("nodes").and_then(|n| n.as_array()) {
+		Some(nodes) => nodes.iter().collect::<Vec<_>>(),
+		None => Vec::new()
+	};
+
+	// Parents are needed to find the root nodes and the nodes affected by animations
+	let mut parents = vec![None; nodes.len()];
+	for (i, node) in nodes.iter().enumerate() {
+		for child in node.get("children").and_then(|c| c.as_array()).unwrap_or(&Vec::new()).iter() {
+			match child.as_usize() {
+				Some(child) if child < nodes.len() => {
+					if parents[child].is_some() {
+						return format_err(&format!("Node {} has more than one parent", child));
+					}
+					parents[child] = Some(i);
+				},
+				_ => { return format_err(&format!("Invalid child of node {}", i)); }
+			}
+		}
+	}
+
+	let scene_index = document.get("scene").and_then(|s| s.as_usize()).unwrap_or(0);
+	let roots: Vec<usize> = match document.get("scenes").and_then(|s| s.as_array()).and_then(|s| s.get(scene_index)) {
+		Some(scene) => {
+			let mut roots = Vec::new();
+			for root in scene.get("nodes").and_then(|n| n.as_array()).unwrap_or(&Vec::new()).iter() {
+				match root.as_usize() {
+					Some(root) if root < nodes.len() => { roots.push(root); },
+					_ => { return format_err(&format!("Invalid node of scene {}", scene_index)); }
+				}
+			}
+			roots
+		},
+		None => (0..nodes.len()).filter(|n| parents[*n].is_none()).collect()
+	};
+
+	// Order the nodes of the scene so that parents are visited before their children, nodes which are reached twice
+	// are part of a cycle or a root of the scene as well as a child
+	let mut order = Vec::new();
+	let mut visited = vec![false; nodes.len()];
+	let mut stack: Vec<usize> = roots.iter().rev().cloned().collect();
+	while let Some(node) = stack.pop() {
+		if visited[node] {
+			return format_err(&format!("Node {} is reached more than once from the scene", node));
+		}
+		visited[node] = true;
+		order.push(node);
+		for child in nodes[node].get("children").and_then(|c| c.as_array()).unwrap_or(&Vec::new()).iter().rev() {
+			if let Some(child) = child.as_usize() {
+				stack.push(child);
+			}
+		}
+	}
+
+	let world = compute_world_matrices(&nodes, &parents, &order, &Vec::new(), 0.0);
+
+	// Animated nodes and their descendants get a matrix for every frame
+	let channels = try!(read_channels(&document, &context.buffers));
+	let mut animated = vec![false; nodes.len()];
+	for node in order.iter() {
+		animated[*node] = channels.iter().any(|c| c.get_node() == *node)
+			|| parents[*node].map_or(false, |p| animated[p]);
+	}
+
+	let mut frames = Vec::<Vec<Matrix4<f64>>>::new();
+	if !channels.is_empty() && fps > 0.0 {
+		for frame in 0..screen.get_frames().max(1) {
+			frames.push(compute_world_matrices(&nodes, &parents, &order, &channels, frame as f64 / fps));
+		}
+	}
+
+	let mut objects = Vec::new();
+	let mut cameras = Vec::new();
+
+	for node in order.iter().cloned() {
+		let name = nodes[node].get("name").and_then(|n| n.as_str()).map_or(format!("node{}", node), |n| n.to_string());
+		let anim: Option<Vec<Matrix4<f64>>> = if animated[node] && !frames.is_empty() {
+			Some(frames.iter().map(|f| f[node]).collect())
+		} else {
+			None
+		};
+
+		if let Some(mesh) = nodes[node].get("mesh").and_then(|m| m.as_usize()) {
+			let mut model = try!(create_model(&mut context, mesh));
+			model.get_transform_mut().set_matrix(world[node]);
+			if let Some(ref anim) = anim {
+				model.get_transform_mut().set_anim_matrix(Box::new(RayTraceAnimFrames::new(anim.clone())));
+			}
+
+			objects.push((name.clone(), model));
+		}
+
+		if let Some(camera) = nodes[node].get("camera").and_then(|c| c.as_usize()) {
+			let mut camera = try!(create_camera(&document, camera, screen));
+			camera.get_transform_mut().set_matrix(world[node]);
+			if let Some(anim) = anim {
+				camera.get_transform_mut().set_anim_matrix(Box::new(RayTraceAnimFrames::new(anim)));
+			}
+
+			cameras.push((name, camera.into_camera()));
+		}
+	}
+
+	info!("Loaded {} models and {} cameras from {}", objects.len(), cameras.len(), file_name);
+
+	Ok(RayTraceGltfImport {
+		objects: objects,
+		cameras: cameras
+	})
+}
+
+#[allow(dead_code)]
+impl RayTraceGltfImport {
+	pub fn get_objects(&self) -> &Vec<(String, RayTraceObjectModel)> {
+		&self.objects
+	}
+
+	pub fn get_cameras(&self) -> &Vec<(String, Box<RayTraceCamera>)> {
+		&self.cameras
+	}
+
+	// Moves all models into the scene named after their nodes and returns the cameras
+	pub fn add_to_scene(self, scene: &mut RayTraceScene) -> Vec<(String, Box<RayTraceCamera>)> {
+		for (name, model) in self.objects {
+			scene.add_named_object(&name, Box::new(model));
+		}
+
+		self.cameras
+	}
+
+	pub fn into_objects(self) -> Vec<(String, RayTraceObjectModel)> {
+		self.objects
+	}
+}
+
+enum GltfCamera {
+	Perspective(RayTracerCameraPerspective),
+	Orthographic(RayTracerCameraOrthographic)
+}
+
+impl GltfCamera {
+	fn get_transform_mut(&mut self) -> &mut RayTraceTransform {
+		match *self {
+			GltfCamera::Perspective(ref mut camera) => camera.get_transform_mut(),
+			GltfCamera::Orthographic(ref mut camera) => camera.get_transform_mut()
+		}
+	}
+
+	fn into_camera(self) -> Box<RayTraceCamera> {
+		match self {
+			GltfCamera::Perspective(camera) => Box::new(camera),
+			GltfCamera::Orthographic(camera) => Box::new(camera)
+		}
+	}
+}
+
+fn create_camera(document: &JsonValue, index: usize, screen: &RayTraceOutputParams) -> Result<GltfCamera, IOError> {
+	let camera = match document.get("cameras").and_then(|c| c.as_array()).and_then(|c| c.get(index)) {
+		Some(camera) => camera,
+		None => { return format_err(&format!("Missing camera {}", index)); }
+	};
+
+	let screen_aspect = screen.get_width() as f64 / screen.get_height() as f64;
+	match camera.get("type").and_then(|t| t.as_str()) {
+		Some("perspective") => {
+			let perspective = camera.get("perspective");
+			let yfov = perspective.and_then(|p| p.get("yfov")).and_then(|y| y.as_f64()).unwrap_or(0.8);
+			let aspect = perspective.and_then(|p| p.get("aspectRatio")).and_then(|a| a.as_f64())
+				.unwrap_or(screen_aspect);
+
+			// The image plane is placed at a distance of one unit
+			let height = 2.0 * (yfov / 2.0).tan();
+			Ok(GltfCamera::Perspective(RayTracerCameraPerspective::new_with(screen, height * aspect, height, 1.0)))
+		},
+		Some("orthographic") => {
+			let orthographic = camera.get("orthographic");
+			let xmag = orthographic.and_then(|o| o.get("xmag")).and_then(|x| x.as_f64()).unwrap_or(1.0);
+			let ymag = orthographic.and_then(|o| o.get("ymag")).and_then(|y| y.as_f64()).unwrap_or(1.0);
+
+			Ok(GltfCamera::Orthographic(RayTracerCameraOrthographic::new_with(screen, 2.0 * xmag, 2.0 * ymag)))
+		},
+		_ => format_err(&format!("Invalid type of camera {}", index))
+	}
+}
+
+fn read_materials(document: &JsonValue) -> Vec<GltfMaterial> {
+	let mut materials = Vec::new();
+
+	for material in document.get("materials").and_then(|m| m.as_array()).unwrap_or(&Vec::new()).iter() {
+		let pbr = material.get("pbrMetallicRoughness");
+		let factor = pbr.and_then(|p| p.get("baseColorFactor")).and_then(|f| f.as_f64_vec());
+		let base_color = match factor {
+			Some(ref f) if f.len() == 4 => RayTraceColor::new_with(f[0] as f32, f[1] as f32, f[2] as f32, f[3] as f32),
+			_ => RayTraceColor::white()
+		};
+		let emission = match material.get("emissiveFactor").and_then(|f| f.as_f64_vec()) {
+			Some(ref f) if f.len() == 3 => RayTraceColor::new_with(f[0] as f32, f[1] as f32, f[2] as f32, 1.0),
+			_ => RayTraceColor::black()
+		};
+
+		materials.push(GltfMaterial {
+			base_color: base_color,
+			metallic: pbr.and_then(|p| p.get("metallicFactor")).and_then(|m| m.as_f64()).unwrap_or(1.0) as f32,
+			roughness: pbr.and_then(|p| p.get("roughnessFactor")).and_then(|r| r.as_f64()).unwrap_or(1.0) as f32,
+			emission: emission,
+			texture: pbr.and_then(|p| p.get("baseColorTexture")).and_then(|t| t.get("index")).and_then(|i| i.as_usize())
+		});
+	}
+
+	materials
+}
+
+impl<'a> GltfContext<'a> {
+	// Primitives without a material use the default material of glTF
+	fn create_material(&mut self, index: Option<usize>) -> Box<RayTraceMaterial> {
+		let (base_color, metallic, roughness, emission, texture) = match index.and_then(|i| self.materials.get(i)) {
+			Some(m) => (m.base_color.clone(), m.metallic, m.roughness, m.emission.clone(), m.texture),
+			None => (RayTraceColor::white(), 1.0, 1.0, RayTraceColor::black(), None)
+		};
+
+		let mut material = RayTracePbrMaterial::new(base_color, metallic, roughness);
+		material.set_emission(emission);
+		if let Some(texture) = texture {
+			material.set_base_color_map(self.get_texture(texture));
+		}
+
+		Box::new(material)
+	}
+
+	// Images are loaded once and copied for each material using them
+	fn get_texture(&mut self, texture: usize) -> Option<RgbaImage> {
+		let document = self.document;
+		let source = document.get("textures").and_then(|t| t.as_array()).and_then(|t| t.get(texture))
+			.and_then(|t| t.get("source")).and_then(|s| s.as_usize());
+		let source = match source {
+			Some(source) => source,
+			None => { return None; }
+		};
+
+		if !self.images.contains_key(&source) {
+			let image = document.get("images").and_then(|i| i.as_array()).and_then(|i| i.get(source));
+			let data = match image {
+				Some(image) => {
+					let uri = image.get("uri").and_then(|u| u.as_str());
+					match (uri, image.get("bufferView").and_then(|b| b.as_usize())) {
+						(Some(uri), _) => load_uri(uri, self.directory),
+						(None, Some(view)) => read_buffer_view(document, &self.buffers, view).map(|d| d.to_vec()),
+						_ => format_err("Image has no data")
+					}
+				},
+				None => format_err("Missing image")
+			};
+
+			let decoded = match data {
+				Ok(data) => {
+					match image::load_from_memory(&data) {
+						Ok(decoded) => Some(decoded.to_rgba()),
+						Err(err) => {
+							warn!("Could not decode image {}: {:?}", source, err);
+							None
+						}
+					}
+				},
+				Err(err) => {
+					warn!("Could not load image {}: {}", source, err);
+					None
+				}
+			};
+
+			self.images.insert(source, decoded);
+		}
+
+		self.images[&source].clone()
+	}
+}
+
+// Combines all primitives of a mesh into one model with a material for each primitive
+fn create_model(context: &mut GltfContext, index: usize) -> Result<RayTraceObjectModel, IOError> {
+	let document = context.document;
+	let mesh = match document.get("meshes").and_then(|m| m.as_array()).and_then(|m| m.get(index)) {
+		Some(mesh) => mesh,
+		None => { return format_err(&format!("Missing mesh {}", index)); }
+	};
+
+	let mut vertices = Vec::<Vector3<f64>>::new();
+	let mut vertex_normals = Vec::<Vector3<f64>>::new();
+	let mut texture_normals = Vec::<Vector2<f64>>::new();
+	let mut vertex_colors = Vec::<RayTraceColor>::new();
+	let mut faces = Vec::<[Vector3<usize>; 3]>::new();
+	let mut has_colors = false;
+
+	let mut materials = Vec::<Box<RayTraceMaterial>>::new();
+	let mut material_slots = HashMap::<usize, usize>::new();
+	let mut face_materials = Vec::<usize>::new();
+
+	for (p, primitive) in mesh.get("primitives").and_then(|p| p.as_array()).unwrap_or(&Vec::new()).iter().enumerate() {
+		let mode = primitive.get("mode").and_then(|m| m.as_usize()).unwrap_or(4);
+		if mode < 4 || mode > 6 {
+			info!("Ignored primitive {} of mesh {} with mode {}", p, index, mode);
+			continue;
+		}
+
+		let attributes = primitive.get("attributes");
+		let attribute = |name: &str| attributes.and_then(|a| a.get(name)).and_then(|a| a.as_usize());
+
+		let positions = match attribute("POSITION") {
+			Some(accessor) => try!(read_accessor(document, &context.buffers, accessor)).0,
+			None => { return format_err(&format!("Primitive {} of mesh {} has no positions", p, index)); }
+		};
+		let count = positions.len() / 3;
+
+		let normals = match attribute("NORMAL") {
+			Some(accessor) => Some(try!(read_accessor(document, &context.buffers, accessor)).0),
+			None => None
+		};
+		let textures = match attribute("TEXCOORD_0") {
+			Some(accessor) => Some(try!(read_accessor(document, &context.buffers, accessor)).0),
+			None => None
+		};
+		let colors = match attribute("COLOR_0") {
+			Some(accessor) => Some(try!(read_accessor(document, &context.buffers, accessor))),
+			None => None
+		};
+
+		if normals.as_ref().map_or(false, |n| n.len() != positions.len())
+				|| textures.as_ref().map_or(false, |t| t.len() != count * 2) {
+			return format_err(&format!("Attributes of primitive {} of mesh {} differ in size", p, index));
+		}
+
+		let (vertex_base, normal_base, texture_base) = (vertices.len(), vertex_normals.len(), texture_normals.len());
+		for i in 0..count {
+			vertices.push([positions[i * 3], positions[i * 3 + 1], positions[i * 3 + 2]]);
+
+			match colors {
+				Some((ref c, size)) if c.len() >= (i + 1) * size && size >= 3 => {
+					has_colors = true;
+					let a = if size > 3 { c[i * size + 3] as f32 } else { 1.0 };
+					vertex_colors.push(RayTraceColor::new_with(c[i * size] as f32, c[i * size + 1] as f32,
+						c[i * size + 2] as f32, a));
+				},
+				_ => { vertex_colors.push(RayTraceColor::white()); }
+			}
+		}
+		if let Some(ref n) = normals {
+			for i in 0..n.len() / 3 {
+				vertex_normals.push([n[i * 3], n[i * 3 + 1], n[i * 3 + 2]]);
+			}
+		}
+		if let Some(ref t) = textures {
+			// Texture coordinates start at the top left corner of the image
+			for i in 0..t.len() / 2 {
+				texture_normals.push([t[i * 2], 1.0 - t[i * 2 + 1]]);
+			}
+		}
+
+		let indices: Vec<usize> = match primitive.get("indices").and_then(|i| i.as_usize()) {
+			Some(accessor) => try!(read_accessor(document, &context.buffers, accessor)).0.iter()
+				.map(|i| *i as usize).collect(),
+			None => (0..count).collect()
+		};
+
+		let triangles: Vec<[usize; 3]> = match mode {
+			5 => (2..indices.len()).map(|i| if i % 2 == 0 {
+				[indices[i - 2], indices[i - 1], indices[i]]
+			} else {
+				[indices[i - 1], indices[i - 2], indices[i]]
+			}).collect(),
+			6 => (2..indices.len()).map(|i| [indices[0], indices[i - 1], indices[i]]).collect(),
+			_ => (0..indices.len() / 3).map(|i| [indices[i * 3], indices[i * 3 + 1], indices[i * 3 + 2]]).collect()
+		};
+
+		let slot = match primitive.get("material").and_then(|m| m.as_usize()) {
+			Some(material) => {
+				if !material_slots.contains_key(&material) {
+					materials.push(context.create_material(Some(material)));
+					material_slots.insert(material, materials.len());
+				}
+
+				material_slots[&material]
+			},
+			None => 0
+		};
+
+		for triangle in triangles.iter() {
+			if triangle.iter().any(|v| *v >= count) {
+				return format_err(&format!("Primitive {} of mesh {} has invalid indices", p, index));
+			}
+
+			let mut face = [[0; 3]; 3];
+			for (f, v) in triangle.iter().enumerate() {
+				face[f] = [
+					vertex_base + v + 1,
+					if normals.is_some() { normal_base + v + 1 } else { 0 },
+					if textures.is_some() { texture_base + v + 1 } else { 0 }
+				];
+			}
+
+			faces.push(face);
+			face_materials.push(slot);
+		}
+	}
+
+	let mut model = RayTraceObjectModel::new(context.create_material(None), vertices, vertex_normals,
+		texture_normals, faces);
+	model.set_materials(materials, face_materials);
+	if has_colors {
+		model.set_vertex_colors(vertex_colors);
+	}
+
+	Ok(model)
+}
+
+fn compute_world_matrices(nodes: &Vec<&JsonValue>, parents: &Vec<Option<usize>>, order: &Vec<usize>,
+		channels: &Vec<GltfChannel>, time: f64) -> Vec<Matrix4<f64>> {
+	let mut world = vec![mat4_id(); nodes.len()];
+
+	for node in order.iter().cloned() {
+		let local = get_local_matrix(nodes[node], node, channels, time);
+		world[node] = match parents[node] {
+			Some(parent) => row_mat4_mul(world[parent], local),
+			None => local
+		};
+	}
+
+	world
+}
+
+fn get_local_matrix(node: &JsonValue, index: usize, channels: &Vec<GltfChannel>, time: f64) -> Matrix4<f64> {
+	if let Some(m) = node.get("matrix").and_then(|m| m.as_f64_vec()) {
+		if m.len() == 16 {
+			// Matrices are stored in column major order
+			return [
+				[m[0], m[4], m[8], m[12]],
+				[m[1], m[5], m[9], m[13]],
+				[m[2], m[6], m[10], m[14]],
+				[m[3], m[7], m[11], m[15]]
+			];
+		}
+	}
+
+	let mut translation = node.get("translation").and_then(|t| t.as_f64_vec()).unwrap_or(vec![0.0, 0.0, 0.0]);
+	let mut rotation = node.get("rotation").and_then(|r| r.as_f64_vec()).unwrap_or(vec![0.0, 0.0, 0.0, 1.0]);
+	let mut scale = node.get("scale").and_then(|s| s.as_f64_vec()).unwrap_or(vec![1.0, 1.0, 1.0]);
+
+	for channel in channels.iter().filter(|c| c.get_node() == index) {
+		match channel.get_path() {
+			GltfPath::Translation => { translation = channel.sample(time); },
+			GltfPath::Rotation => { rotation = channel.sample(time); },
+			GltfPath::Scale => { scale = channel.sample(time); }
+		}
+	}
+
+	if translation.len() != 3 || rotation.len() != 4 || scale.len() != 3 {
+		warn!("Ignored invalid transformation of node {}", index);
+		return mat4_id();
+	}
+
+	let (x, y, z, w) = (rotation[0], rotation[1], rotation[2], rotation[3]);
+	let rotation = [
+		[1.0 - 2.0 * (y * y + z * z), 2.0 * (x * y - z * w), 2.0 * (x * z + y * w)],
+		[2.0 * (x * y + z * w), 1.0 - 2.0 * (x * x + z * z), 2.0 * (y * z - x * w)],
+		[2.0 * (x * z - y * w), 2.0 * (y * z + x * w), 1.0 - 2.0 * (x * x + y * y)]
+	];
+
+	row_mat4_mul(translate_mat4([translation[0], translation[1], translation[2]]),
+		row_mat4_mul(mat3_to_mat4(rotation), scale_mat4([scale[0], scale[1], scale[2]])))
+}
+
+fn format_err<T>(message: &str) -> Result<T, IOError> {
+	Err(IOError::new(ErrorKind::InvalidData, message.to_string()))
+}
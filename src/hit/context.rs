@@ -0,0 +1,143 @@
+use vecmath::Vector2;
+use vecmath::Vector3;
+use vecmath::{vec3_cross, vec3_dot, vec3_len, vec3_neg, vec3_normalized, vec3_scale, vec3_sub};
+
+use material::RayTraceFootprint;
+use math_util::compute_tangent_frame;
+use ray::RayTraceRay;
+
+/// Surface at a hit point which is passed to `RayTraceMaterial::get_hit_context`.
+/// Positions, normals and tangents are in world space unless noted otherwise.
+#[allow(dead_code)]
+pub struct RayTraceShadingContext<'a> {
+	ray: &'a RayTraceRay,
+	distance: f64,
+	position: Vector3<f64>,
+	object_position: Vector3<f64>,
+	geometric_normal: Vector3<f64>,
+	shading_normal: Vector3<f64>,
+	texcoord: Vector2<f64>,
+	tangent: Vector3<f64>,
+	bitangent: Vector3<f64>,
+	footprint: Option<RayTraceFootprint>,
+	primitive: usize
+}
+
+#[allow(dead_code)]
+impl<'a> RayTraceShadingContext<'a> {
+	/// Creates the context of a hit at the distance along the ray in world space.
+	/// The normal is used as geometric and shading normal, the tangents are chosen arbitrarily until they are set.
+	pub fn new(ray: &'a RayTraceRay, distance: f64, object_position: Vector3<f64>, normal: Vector3<f64>,
+			texcoord: Vector2<f64>) -> Self {
+		let normal = vec3_normalized(normal);
+		let tangents = compute_tangent_frame(normal);
+
+		Self {
+			ray: ray,
+			distance: distance,
+			position: ray.get_position_on_ray(distance),
+			object_position: object_position,
+			geometric_normal: normal,
+			shading_normal: normal,
+			texcoord: texcoord,
+			tangent: tangents[0],
+			bitangent: tangents[1],
+			footprint: None,
+			primitive: 0
+		}
+	}
+
+	/// Ray in world space which hit the surface.
+	pub fn get_ray(&self) -> &RayTraceRay {
+		self.ray
+	}
+
+	pub fn get_distance(&self) -> f64 {
+		self.distance
+	}
+
+	pub fn get_position(&self) -> &Vector3<f64> {
+		&self.position
+	}
+
+	/// Position in the space of the object before its transformation.
+	pub fn get_object_position(&self) -> &Vector3<f64> {
+		&self.object_position
+	}
+
+	/// Normalized direction from the hit position towards the origin of the ray.
+	pub fn get_view_direction(&self) -> Vector3<f64> {
+		vec3_normalized(vec3_neg(*self.ray.get_direction()))
+	}
+
+	/// Normal of the hit surface without any interpolation.
+	pub fn get_geometric_normal(&self) -> &Vector3<f64> {
+		&self.geometric_normal
+	}
+
+	pub fn set_geometric_normal(&mut self, normal: Vector3<f64>) {
+		self.geometric_normal = vec3_normalized(normal);
+	}
+
+	/// Interpolated normal which is used for the lighting.
+	pub fn get_shading_normal(&self) -> &Vector3<f64> {
+		&self.shading_normal
+	}
+
+	/// Sets the shading normal and keeps the tangents perpendicular to it.
+	pub fn set_shading_normal(&mut self, normal: Vector3<f64>) {
+		self.shading_normal = vec3_normalized(normal);
+		let (tangent, bitangent) = (self.tangent, self.bitangent);
+		self.set_tangents(tangent, bitangent);
+	}
+
+	/// Texture coordinates of the object, e.g. angles on spheres and interpolated coordinates on models.
+	pub fn get_texcoord(&self) -> &Vector2<f64> {
+		&self.texcoord
+	}
+
+	/// Normalized direction in which the first texture coordinate grows.
+	pub fn get_tangent(&self) -> &Vector3<f64> {
+		&self.tangent
+	}
+
+	/// Normalized direction in which the second texture coordinate grows.
+	pub fn get_bitangent(&self) -> &Vector3<f64> {
+		&self.bitangent
+	}
+
+	/// Sets the directions of the texture coordinates, which are made orthonormal to the shading normal while the
+	/// bitangent keeps its side of the tangent.
+	pub fn set_tangents(&mut self, tangent: Vector3<f64>, bitangent: Vector3<f64>) {
+		let n = self.shading_normal;
+		let t = vec3_sub(tangent, vec3_scale(n, vec3_dot(n, tangent)));
+		if vec3_len(t) < 1e-12 {
+			let frame = compute_tangent_frame(n);
+			self.tangent = frame[0];
+			self.bitangent = frame[1];
+			return;
+		}
+
+		self.tangent = vec3_normalized(t);
+		let b = vec3_cross(n, self.tangent);
+		self.bitangent = if vec3_dot(b, bitangent) < 0.0 { vec3_neg(b) } else { b };
+	}
+
+	/// Change of the texture coordinates to the neighbouring pixels if the ray has differentials.
+	pub fn get_footprint(&self) -> Option<&RayTraceFootprint> {
+		self.footprint.as_ref()
+	}
+
+	pub fn set_footprint(&mut self, footprint: Option<RayTraceFootprint>) {
+		self.footprint = footprint;
+	}
+
+	/// Index of the hit part of the object, e.g. the triangle of a model or the side of a cube.
+	pub fn get_primitive(&self) -> usize {
+		self.primitive
+	}
+
+	pub fn set_primitive(&mut self, primitive: usize) {
+		self.primitive = primitive;
+	}
+}
@@ -1,15 +1,32 @@
+use vecmath::Vector3;
+
 use color::RayTraceColor;
+use material::RayTraceMicrofacetBrdf;
+
+// Phong exponents from which on reflections are perfect mirrors
+const MIRROR_EXPONENT: f32 = 1000.0;
 
+/// Surface properties at a hit point which are used for the shading.
 #[derive(Debug, Clone)]
 pub struct RayTraceMaterialHit {
 	color: RayTraceColor,
 	diffuse_light: f32,
 	specular_light: f32,
 	surface_roughness: f32,
-	reflectance: f32
+	reflectance: f32,
+	glossiness: Option<f32>,
+	normal: Option<Vector3<f64>>,
+	brdf: Option<RayTraceMicrofacetBrdf>,
+	emission: RayTraceColor
 }
 
 impl<'a> RayTraceMaterialHit {
+	/// Creates a diffuse and specular surface without reflection.
+	pub fn new(color: RayTraceColor) -> Self {
+		Self::new_with(color, 0.0, 1.0, 1.0, 100.0)
+	}
+
+	/// The surface roughness is used as the exponent of the specular highlight.
 	pub fn new_with(color: RayTraceColor, reflectance: f32, diffuse_light: f32, specular_light: f32,
 			surface_roughness: f32) -> Self {
 		Self {
@@ -17,7 +34,11 @@ impl<'a> RayTraceMaterialHit {
 			diffuse_light: diffuse_light,
 			specular_light: specular_light,
 			surface_roughness: surface_roughness,
-			reflectance: reflectance
+			reflectance: reflectance,
+			glossiness: None,
+			normal: None,
+			brdf: None,
+			emission: RayTraceColor::black()
 		}
 	}
 
@@ -60,4 +81,49 @@ impl<'a> RayTraceMaterialHit {
 	pub fn set_reflectance(&mut self, reflectance: f32) {
 		self.reflectance = reflectance;
 	}
+
+	/// Phong exponent of the lobe around the mirror direction from which glossy reflections are sampled, without it
+	/// reflections are sharp.
+	pub fn get_glossiness(&self) -> Option<f32> {
+		self.glossiness
+	}
+
+	pub fn set_glossiness(&mut self, glossiness: Option<f32>) {
+		self.glossiness = glossiness;
+	}
+
+	/// Whether a single reflected ray is enough, which depends on the BRDF if it is set.
+	pub fn is_mirror(&self) -> bool {
+		match self.brdf {
+			Some(ref brdf) => brdf.is_mirror(),
+			None => self.glossiness.map_or(true, |glossiness| glossiness >= MIRROR_EXPONENT)
+		}
+	}
+
+	/// Shading normal in world space which replaces the normal of the object, e.g. from a normal map.
+	pub fn get_normal(&self) -> Option<&Vector3<f64>> {
+		self.normal.as_ref()
+	}
+
+	pub fn set_normal(&mut self, normal: Option<Vector3<f64>>) {
+		self.normal = normal;
+	}
+
+	/// Physically based reflection which shadings and integrators use instead of the phong parameters if it is set.
+	pub fn get_brdf(&self) -> Option<&RayTraceMicrofacetBrdf> {
+		self.brdf.as_ref()
+	}
+
+	pub fn set_brdf(&mut self, brdf: Option<RayTraceMicrofacetBrdf>) {
+		self.brdf = brdf;
+	}
+
+	/// Light emitted by the surface which is added to the shaded color and lights other objects.
+	pub fn get_emission(&self) -> &RayTraceColor {
+		&self.emission
+	}
+
+	pub fn set_emission(&mut self, emission: RayTraceColor) {
+		self.emission = emission;
+	}
 }
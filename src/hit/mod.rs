@@ -1,12 +1,15 @@
 use std::cmp::Ord;
 use std::cmp::Ordering;
 
+mod context;
 mod ray;
 mod material;
 
+pub use self::context::RayTraceShadingContext;
 pub use self::ray::RayTraceRayHit;
 pub use self::material::RayTraceMaterialHit;
 
+/// Entry of a binary heap which pops the entry with the smallest distance first.
 pub struct RayTraceHitHeapEntry<T> {
 	pub distance: f64,
 	pub value: T
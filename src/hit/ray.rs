@@ -2,21 +2,31 @@ use vecmath::Vector3;
 
 use hit::RayTraceMaterialHit;
 
+/// Hit of a ray with an object which is returned by `RayTraceHitable::next_hit`.
 #[allow(dead_code)]
 pub struct RayTraceRayHit {
 	distance: f64,
 	position: Vector3<f64>,
 	surface_normal: Vector3<f64>,
+	geometric_normal: Vector3<f64>,
+	triangle: Option<usize>,
+	barycentric: Option<Vector3<f64>>,
 	surface: RayTraceMaterialHit
 }
 
 #[allow(dead_code)]
 impl RayTraceRayHit {
+	/// Creates a hit at the distance along the ray with the normal in world space.
+	/// The geometric normal is the same as the normal until it is set, a normal of the material replaces the
+	/// surface normal.
 	pub fn new(distance: f64, position: Vector3<f64>, normal: Vector3<f64>, surface: RayTraceMaterialHit) -> Self {
 		Self {
 			distance: distance,
 			position: position,
-			surface_normal: normal,
+			surface_normal: surface.get_normal().cloned().unwrap_or(normal),
+			geometric_normal: normal,
+			triangle: None,
+			barycentric: None,
 			surface: surface
 		}
 	}
@@ -33,7 +43,39 @@ impl RayTraceRayHit {
 		&self.surface_normal
 	}
 
+	pub fn set_surface_normal(&mut self, normal: Vector3<f64>) {
+		self.surface_normal = normal;
+	}
+
+	/// Normal of the hit surface without any interpolation.
+	pub fn get_geometric_normal(&self) -> &Vector3<f64> {
+		&self.geometric_normal
+	}
+
+	pub fn set_geometric_normal(&mut self, normal: Vector3<f64>) {
+		self.geometric_normal = normal;
+	}
+
+	/// Index of the hit triangle if the object consists of triangles.
+	pub fn get_triangle(&self) -> Option<usize> {
+		self.triangle
+	}
+
+	/// Barycentric coordinates for the vertices of the hit triangle.
+	pub fn get_barycentric(&self) -> Option<&Vector3<f64>> {
+		self.barycentric.as_ref()
+	}
+
+	pub fn set_triangle(&mut self, triangle: usize, barycentric: Vector3<f64>) {
+		self.triangle = Some(triangle);
+		self.barycentric = Some(barycentric);
+	}
+
 	pub fn get_surface_material(&self) -> &RayTraceMaterialHit {
 		&self.surface
 	}
-}
\ No newline at end of file
+
+	pub fn get_surface_material_mut(&mut self) -> &mut RayTraceMaterialHit {
+		&mut self.surface
+	}
+}
@@ -0,0 +1,492 @@
+use std::char;
+use std::fmt;
+use std::iter::Peekable;
+use std::str::Chars;
+
+pub enum JsonData {
+	Null,
+	Bool(bool),
+	Number(f64),
+	String(String),
+	Array(Vec<JsonValue>),
+	// Members are kept in the order of the document
+	Object(Vec<(String, JsonValue)>)
+}
+
+// Value of a JSON document with the position where it starts for error messages
+pub struct JsonValue {
+	data: JsonData,
+	line: usize,
+	column: usize
+}
+
+#[derive(Debug)]
+pub struct JsonError {
+	message: String,
+	line: usize,
+	column: usize
+}
+
+struct JsonParser<'a> {
+	chars: Peekable<Chars<'a>>,
+	line: usize,
+	column: usize
+}
+
+const MAX_DEPTH: usize = 256;
+
+pub fn json_parse(text: &str) -> Result<JsonValue, JsonError> {
+	let mut parser = JsonParser {
+		chars: text.chars().peekable(),
+		line: 1,
+		column: 1
+	};
+
+	let value = try!(parser.parse_value(0));
+	parser.skip_whitespace();
+
+	if parser.chars.peek().is_some() {
+		return Err(parser.error("Unexpected data after the end of the document"));
+	}
+
+	Ok(value)
+}
+
+// Writes a document with tab indentation, arrays of numbers or strings are kept on a single line
+pub fn json_write(value: &JsonValue) -> String {
+	let mut text = String::new();
+	write_value(&mut text, value, 0);
+	text.push('\n');
+	text
+}
+
+#[allow(dead_code)]
+impl JsonValue {
+	pub fn new(data: JsonData) -> Self {
+		Self {
+			data: data,
+			line: 0,
+			column: 0
+		}
+	}
+
+	pub fn new_number(value: f64) -> Self {
+		Self::new(JsonData::Number(value))
+	}
+
+	pub fn new_string(value: &str) -> Self {
+		Self::new(JsonData::String(value.to_string()))
+	}
+
+	pub fn new_array(values: Vec<JsonValue>) -> Self {
+		Self::new(JsonData::Array(values))
+	}
+
+	pub fn new_object(members: Vec<(String, JsonValue)>) -> Self {
+		Self::new(JsonData::Object(members))
+	}
+
+	pub fn get_data(&self) -> &JsonData {
+		&self.data
+	}
+
+	pub fn get_line(&self) -> usize {
+		self.line
+	}
+
+	pub fn get_column(&self) -> usize {
+		self.column
+	}
+
+	pub fn get(&self, key: &str) -> Option<&JsonValue> {
+		match self.data {
+			JsonData::Object(ref members) => members.iter().find(|m| m.0 == key).map(|m| &m.1),
+			_ => None
+		}
+	}
+
+	pub fn is_null(&self) -> bool {
+		match self.data {
+			JsonData::Null => true,
+			_ => false
+		}
+	}
+
+	pub fn as_bool(&self) -> Option<bool> {
+		match self.data {
+			JsonData::Bool(value) => Some(value),
+			_ => None
+		}
+	}
+
+	pub fn as_f64(&self) -> Option<f64> {
+		match self.data {
+			JsonData::Number(value) => Some(value),
+			_ => None
+		}
+	}
+
+	pub fn as_usize(&self) -> Option<usize> {
+		match self.data {
+			JsonData::Number(value) if value >= 0.0 && value.fract() == 0.0 => Some(value as usize),
+			_ => None
+		}
+	}
+
+	pub fn as_str(&self) -> Option<&str> {
+		match self.data {
+			JsonData::String(ref value) => Some(&value[..]),
+			_ => None
+		}
+	}
+
+	pub fn as_array(&self) -> Option<&Vec<JsonValue>> {
+		match self.data {
+			JsonData::Array(ref values) => Some(values),
+			_ => None
+		}
+	}
+
+	pub fn as_object(&self) -> Option<&Vec<(String, JsonValue)>> {
+		match self.data {
+			JsonData::Object(ref members) => Some(members),
+			_ => None
+		}
+	}
+
+	// Reads an array of numbers like vectors or matrices
+	pub fn as_f64_vec(&self) -> Option<Vec<f64>> {
+		match self.as_array() {
+			Some(values) => values.iter().map(|v| v.as_f64()).collect(),
+			None => None
+		}
+	}
+}
+
+#[allow(dead_code)]
+impl JsonError {
+	pub fn new(message: String, line: usize, column: usize) -> Self {
+		Self {
+			message: message,
+			line: line,
+			column: column
+		}
+	}
+
+	pub fn get_message(&self) -> &str {
+		&self.message
+	}
+
+	pub fn get_line(&self) -> usize {
+		self.line
+	}
+
+	pub fn get_column(&self) -> usize {
+		self.column
+	}
+}
+
+impl fmt::Display for JsonError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "Error on line {} column {}: {}", self.line, self.column, self.message)
+	}
+}
+
+impl<'a> JsonParser<'a> {
+	fn next(&mut self) -> Option<char> {
+		let c = self.chars.next();
+		match c {
+			Some('\n') => {
+				self.line += 1;
+				self.column = 1;
+			},
+			Some(_) => {
+				self.column += 1;
+			},
+			None => { }
+		}
+
+		c
+	}
+
+	fn error(&self, message: &str) -> JsonError {
+		JsonError::new(message.to_string(), self.line, self.column)
+	}
+
+	fn skip_whitespace(&mut self) {
+		loop {
+			match self.chars.peek() {
+				Some(&' ') | Some(&'\t') | Some(&'\n') | Some(&'\r') => { },
+				_ => { return; }
+			}
+
+			self.next();
+		}
+	}
+
+	fn expect(&mut self, expected: &str) -> Result<(), JsonError> {
+		for c in expected.chars() {
+			if self.next() != Some(c) {
+				return Err(self.error(&format!("Expected {}", expected)));
+			}
+		}
+
+		Ok(())
+	}
+
+	fn parse_value(&mut self, depth: usize) -> Result<JsonValue, JsonError> {
+		if depth > MAX_DEPTH {
+			return Err(self.error("Maximum nesting depth exceeded"));
+		}
+
+		self.skip_whitespace();
+		let (line, column) = (self.line, self.column);
+
+		let data = match self.chars.peek().cloned() {
+			Some('n') => {
+				try!(self.expect("null"));
+				JsonData::Null
+			},
+			Some('t') => {
+				try!(self.expect("true"));
+				JsonData::Bool(true)
+			},
+			Some('f') => {
+				try!(self.expect("false"));
+				JsonData::Bool(false)
+			},
+			Some('"') => JsonData::String(try!(self.parse_string())),
+			Some('[') => {
+				self.next();
+				let mut values = Vec::new();
+
+				self.skip_whitespace();
+				if self.chars.peek() == Some(&']') {
+					self.next();
+				} else {
+					loop {
+						values.push(try!(self.parse_value(depth + 1)));
+						self.skip_whitespace();
+
+						match self.next() {
+							Some(',') => { },
+							Some(']') => { break; },
+							_ => { return Err(self.error("Expected , or ]")); }
+						}
+					}
+				}
+
+				JsonData::Array(values)
+			},
+			Some('{') => {
+				self.next();
+				let mut members = Vec::new();
+
+				self.skip_whitespace();
+				if self.chars.peek() == Some(&'}') {
+					self.next();
+				} else {
+					loop {
+						self.skip_whitespace();
+						if self.chars.peek() != Some(&'"') {
+							return Err(self.error("Expected member name"));
+						}
+
+						let key = try!(self.parse_string());
+						self.skip_whitespace();
+						if self.next() != Some(':') {
+							return Err(self.error("Expected :"));
+						}
+
+						members.push((key, try!(self.parse_value(depth + 1))));
+						self.skip_whitespace();
+
+						match self.next() {
+							Some(',') => { },
+							Some('}') => { break; },
+							_ => { return Err(self.error("Expected , or }")); }
+						}
+					}
+				}
+
+				JsonData::Object(members)
+			},
+			Some(c) if c == '-' || c.is_digit(10) => JsonData::Number(try!(self.parse_number())),
+			Some(_) => {
+				return Err(self.error("Unexpected character"));
+			},
+			None => {
+				return Err(self.error("Unexpected end of document"));
+			}
+		};
+
+		Ok(JsonValue {
+			data: data,
+			line: line,
+			column: column
+		})
+	}
+
+	fn parse_number(&mut self) -> Result<f64, JsonError> {
+		let mut text = String::new();
+		loop {
+			match self.chars.peek().cloned() {
+				Some(c) if c.is_digit(10) || c == '-' || c == '+' || c == '.' || c == 'e' || c == 'E' => {
+					text.push(c);
+					self.next();
+				},
+				_ => { break; }
+			}
+		}
+
+		match text.parse::<f64>() {
+			Ok(value) => Ok(value),
+			Err(_) => Err(self.error(&format!("Invalid number {}", text)))
+		}
+	}
+
+	fn parse_string(&mut self) -> Result<String, JsonError> {
+		self.next(); // Opening quote
+		let mut text = String::new();
+
+		loop {
+			match self.next() {
+				Some('"') => { return Ok(text); },
+				Some('\\') => {
+					match self.next() {
+						Some('"') => { text.push('"'); },
+						Some('\\') => { text.push('\\'); },
+						Some('/') => { text.push('/'); },
+						Some('b') => { text.push('\x08'); },
+						Some('f') => { text.push('\x0c'); },
+						Some('n') => { text.push('\n'); },
+						Some('r') => { text.push('\r'); },
+						Some('t') => { text.push('\t'); },
+						Some('u') => {
+							let mut code = try!(self.parse_hex());
+
+							// Characters outside of the basic plane are encoded as surrogate pairs
+							if code >= 0xD800 && code < 0xDC00 {
+								try!(self.expect("\\u"));
+								let low = try!(self.parse_hex());
+								code = 0x10000 + ((code - 0xD800) << 10) + (low.wrapping_sub(0xDC00) & 0x3FF);
+							}
+
+							match char::from_u32(code) {
+								Some(c) => { text.push(c); },
+								None => { return Err(self.error("Invalid unicode character")); }
+							}
+						},
+						_ => { return Err(self.error("Invalid escape sequence")); }
+					}
+				},
+				Some(c) if (c as u32) < 0x20 => {
+					return Err(self.error("Control character in string"));
+				},
+				Some(c) => { text.push(c); },
+				None => {
+					return Err(self.error("Unterminated string"));
+				}
+			}
+		}
+	}
+
+	fn parse_hex(&mut self) -> Result<u32, JsonError> {
+		let mut code = 0;
+		for _ in 0..4 {
+			match self.next().and_then(|c| c.to_digit(16)) {
+				Some(digit) => { code = (code << 4) | digit; },
+				None => { return Err(self.error("Invalid unicode escape")); }
+			}
+		}
+
+		Ok(code)
+	}
+}
+
+fn is_scalar(value: &JsonValue) -> bool {
+	match value.data {
+		JsonData::Array(_) | JsonData::Object(_) => false,
+		_ => true
+	}
+}
+
+fn write_indent(text: &mut String, indent: usize) {
+	for _ in 0..indent {
+		text.push('\t');
+	}
+}
+
+fn write_value(text: &mut String, value: &JsonValue, indent: usize) {
+	match value.data {
+		JsonData::Null => { text.push_str("null"); },
+		JsonData::Bool(b) => { text.push_str(if b { "true" } else { "false" }); },
+		JsonData::Number(number) => {
+			// The scene writer rejects non-finite numbers, other documents get null as JSON has no value for them
+			if !number.is_finite() {
+				text.push_str("null");
+			} else if number.fract() == 0.0 && number.abs() < 1e15 {
+				text.push_str(&format!("{}", number as i64));
+			} else {
+				text.push_str(&format!("{}", number));
+			}
+		},
+		JsonData::String(ref string) => { write_string(text, string); },
+		JsonData::Array(ref values) => {
+			if values.is_empty() {
+				text.push_str("[]");
+			} else if values.iter().all(is_scalar) {
+				text.push('[');
+				for (i, v) in values.iter().enumerate() {
+					if i > 0 {
+						text.push_str(", ");
+					}
+					write_value(text, v, indent);
+				}
+				text.push(']');
+			} else {
+				text.push_str("[\n");
+				for (i, v) in values.iter().enumerate() {
+					write_indent(text, indent + 1);
+					write_value(text, v, indent + 1);
+					text.push_str(if i + 1 < values.len() { ",\n" } else { "\n" });
+				}
+				write_indent(text, indent);
+				text.push(']');
+			}
+		},
+		JsonData::Object(ref members) => {
+			if members.is_empty() {
+				text.push_str("{}");
+				return;
+			}
+
+			text.push_str("{\n");
+			for (i, &(ref key, ref v)) in members.iter().enumerate() {
+				write_indent(text, indent + 1);
+				write_string(text, key);
+				text.push_str(": ");
+				write_value(text, v, indent + 1);
+				text.push_str(if i + 1 < members.len() { ",\n" } else { "\n" });
+			}
+			write_indent(text, indent);
+			text.push('}');
+		}
+	}
+}
+
+fn write_string(text: &mut String, string: &str) {
+	text.push('"');
+	for c in string.chars() {
+		match c {
+			'"' => { text.push_str("\\\""); },
+			'\\' => { text.push_str("\\\\"); },
+			'\n' => { text.push_str("\\n"); },
+			'\r' => { text.push_str("\\r"); },
+			'\t' => { text.push_str("\\t"); },
+			c if (c as u32) < 0x20 => { text.push_str(&format!("\\u{:04x}", c as u32)); },
+			c => { text.push(c); }
+		}
+	}
+	text.push('"');
+}
@@ -11,21 +11,23 @@ extern crate scoped_threadpool;
 
 pub extern crate vecmath;
 
-mod aabb;
-mod hit;
-mod ray;
-mod nonsync;
+mod json;
 
+pub mod aabb;
 pub mod anim;
 pub mod camera;
 pub mod color;
+pub mod gltf;
+pub mod hit;
 pub mod light;
 pub mod material;
 pub mod math_util;
 pub mod object;
 pub mod params;
+pub mod ray;
 pub mod render;
 pub mod sample;
 pub mod sink;
 pub mod scene;
+pub mod scene_file;
 pub mod source;
\ No newline at end of file
@@ -9,6 +9,8 @@ use hit::RayTraceRayHit;
 use light::RayTraceShading;
 use params::RayTraceParams;
 use scene::RayTraceScene;
+use scene_file::RayTraceSceneWriteError;
+use scene_file::RayTraceSceneWriter;
 
 use math_util::PI;
 use math_util::HALF_PI;
@@ -51,6 +53,14 @@ impl RayTraceShading for RayTraceDebugAxisShading {
 
 		return (RayTraceColor::chroma(factor as f32), RayTraceColor::transparent());
 	}
+
+	fn serialize(&self, writer: &mut RayTraceSceneWriter) -> Result<(), RayTraceSceneWriteError> {
+		writer.set_type("debug_axis");
+		try!(writer.set_vec3("axis", self.axis));
+		try!(writer.set_f64("scale", self.scale));
+		try!(writer.set_f64("offset", self.offset));
+		Ok(())
+	}
 }
 
 pub enum RayTraceDebugNormalType {
@@ -98,4 +108,14 @@ impl RayTraceShading for RayTraceDebugNormalShading {
 			}
 		}, RayTraceColor::transparent())
 	}
+
+	fn serialize(&self, writer: &mut RayTraceSceneWriter) -> Result<(), RayTraceSceneWriteError> {
+		writer.set_type("debug_normal");
+		writer.set_str("normal", match self.normal {
+			RayTraceDebugNormalType::XZ => "xz",
+			RayTraceDebugNormalType::Y => "y",
+			RayTraceDebugNormalType::Both => "both"
+		});
+		Ok(())
+	}
 }
@@ -5,15 +5,29 @@ use color::RayTraceColor;
 use hit::RayTraceRayHit;
 use params::RayTraceParams;
 use ray::RayTraceRay;
+use scene::RayTraceAny;
 use scene::RayTraceScene;
+use scene_file::RayTraceSceneWriteError;
+use scene_file::RayTraceSceneWriter;
 
+/// Illumination model which computes the light and the specular color of a hit.
 pub trait RayTraceShading {
 	fn apply(&self, ray: &RayTraceRay, ray_hit: &RayTraceRayHit, camera: &Box<RayTraceCamera>, scene: &RayTraceScene,
 		params: &RayTraceParams) -> (RayTraceColor, RayTraceColor);
+	/// Writes the shading into a scene file, shadings which do not override this can not be saved.
+	fn serialize(&self, writer: &mut RayTraceSceneWriter) -> Result<(), RayTraceSceneWriteError> {
+		Err(writer.unsupported("shading"))
+	}
 }
 
-pub trait RayTraceLight: Sync + Send {
+/// Light of a scene which is initialized for every frame.
+pub trait RayTraceLight: Sync + Send + RayTraceAny {
 	fn init(&mut self, frame: usize);
 	fn get_position(&self) -> Vector3<f64>;
+	/// Returns the light arriving at the start of the ray which points from a hit towards the light.
 	fn get_light(&self, ray: &RayTraceRay) -> RayTraceColor;
+	/// Writes the light into a scene file, lights which do not override this can not be saved.
+	fn serialize(&self, writer: &mut RayTraceSceneWriter) -> Result<(), RayTraceSceneWriteError> {
+		Err(writer.unsupported("light"))
+	}
 }
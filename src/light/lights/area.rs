@@ -0,0 +1,127 @@
+use std::f64;
+
+use vecmath::Vector3;
+use vecmath::{vec3_add, vec3_cross, vec3_dot, vec3_len, vec3_normalized, vec3_scale, vec3_square_len, vec3_sub};
+
+use math_util::compute_tangent_frame;
+
+// Emissive surface of an object in world space
+#[derive(Debug, Clone)]
+pub enum RayTraceAreaLightShape {
+	// Triangles which are sampled by their area
+	Triangles(Vec<[Vector3<f64>; 3]>),
+	// Sphere with center and radius which is sampled by the solid angle it covers
+	Sphere(Vector3<f64>, f64)
+}
+
+// Emissive object of a scene which lights the other objects, the emitted light is taken from the material hit at
+// the sampled point so emission maps work as well, area lights are collected by the scene on initialization
+pub struct RayTraceAreaLight {
+	object: usize,
+	shape: RayTraceAreaLightShape,
+	// Summed areas of the triangles to choose them by their area
+	areas: Vec<f64>
+}
+
+#[allow(dead_code)]
+impl RayTraceAreaLight {
+	pub fn new(object: usize, shape: RayTraceAreaLightShape) -> Self {
+		let areas = match shape {
+			RayTraceAreaLightShape::Triangles(ref triangles) => {
+				let mut sum = 0.0;
+				triangles.iter().map(|t| {
+					sum += 0.5 * vec3_len(vec3_cross(vec3_sub(t[1], t[0]), vec3_sub(t[2], t[0])));
+					sum
+				}).collect()
+			},
+			RayTraceAreaLightShape::Sphere(_, _) => Vec::new()
+		};
+
+		Self {
+			object: object,
+			shape: shape,
+			areas: areas
+		}
+	}
+
+	// Index of the emitting object in the scene
+	pub fn get_object(&self) -> usize {
+		self.object
+	}
+
+	pub fn get_shape(&self) -> &RayTraceAreaLightShape {
+		&self.shape
+	}
+
+	pub fn get_area(&self) -> f64 {
+		match self.shape {
+			RayTraceAreaLightShape::Triangles(_) => self.areas.last().cloned().unwrap_or(0.0),
+			RayTraceAreaLightShape::Sphere(_, radius) => 4.0 * f64::consts::PI * radius * radius
+		}
+	}
+
+	// Chooses a point on the light as seen from the position using three uniform random numbers, returns the point
+	// and the probability density per solid angle of the direction towards it
+	pub fn sample(&self, position: Vector3<f64>, u: Vector3<f64>) -> Option<(Vector3<f64>, f64)> {
+		match self.shape {
+			RayTraceAreaLightShape::Triangles(ref triangles) => {
+				let area = self.get_area();
+				if triangles.is_empty() || area <= 0.0 {
+					return None;
+				}
+
+				let target = u[0] * area;
+				let index = match self.areas.binary_search_by(|a| a.partial_cmp(&target).unwrap()) {
+					Ok(index) => index,
+					Err(index) => index.min(triangles.len() - 1)
+				};
+
+				// Uniform barycentric coordinates
+				let t = triangles[index];
+				let s = u[1].sqrt();
+				let (b1, b2) = (1.0 - s, u[2] * s);
+				let point = vec3_add(vec3_add(vec3_scale(t[0], 1.0 - b1 - b2), vec3_scale(t[1], b1)),
+					vec3_scale(t[2], b2));
+
+				let to_point = vec3_sub(point, position);
+				let distance2 = vec3_square_len(to_point);
+				let normal = vec3_normalized(vec3_cross(vec3_sub(t[1], t[0]), vec3_sub(t[2], t[0])));
+				let cos = vec3_dot(normal, to_point).abs() / distance2.sqrt();
+				if distance2 <= 0.0 || cos < 1e-9 {
+					return None;
+				}
+
+				Some((point, distance2 / (cos * area)))
+			},
+			RayTraceAreaLightShape::Sphere(center, radius) => {
+				let to_center = vec3_sub(center, position);
+				let distance2 = vec3_square_len(to_center);
+				if distance2 <= radius * radius {
+					return None;
+				}
+
+				// Uniform directions in the cone around the sphere
+				let cos_max = (1.0 - radius * radius / distance2).max(0.0).sqrt();
+				if 1.0 - cos_max <= 0.0 {
+					return None;
+				}
+
+				let cos_theta = 1.0 - u[0] * (1.0 - cos_max);
+				let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+				let phi = 2.0 * f64::consts::PI * u[1];
+
+				let w = vec3_normalized(to_center);
+				let frame = compute_tangent_frame(w);
+				let direction = vec3_add(vec3_add(vec3_scale(frame[0], sin_theta * phi.cos()),
+					vec3_scale(frame[1], sin_theta * phi.sin())), vec3_scale(w, cos_theta));
+
+				// Nearest intersection with the sphere
+				let b = vec3_dot(direction, to_center);
+				let distance = b - (radius * radius - (distance2 - b * b)).max(0.0).sqrt();
+				let point = vec3_add(position, vec3_scale(direction, distance));
+
+				Some((point, 1.0 / (2.0 * f64::consts::PI * (1.0 - cos_max))))
+			}
+		}
+	}
+}
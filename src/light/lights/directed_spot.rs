@@ -1,53 +1,42 @@
 use vecmath::Vector3;
-use vecmath::vec3_dot;
-use vecmath::row_mat3_transform;
+use vecmath::{vec3_dot, vec3_normalized};
 
 use anim::RayTraceAnimation;
 use color::RayTraceColor;
 use light::RayTraceLight;
 use ray::RayTraceRay;
+use scene_file::RayTraceSceneWriteError;
+use scene_file::RayTraceSceneWriter;
 
-use math_util::rotate_xyz;
 use math_util::PI;
 use math_util::DEG_TO_RAD;
+use math_util::RayTraceTransform;
+use math_util::RayTraceTransformable;
 
 pub struct RayTraceDirectedSpotLight {
-	position: Vector3<f64>,
+	transform: RayTraceTransform,
 	size: f64,
-	rotation: Vector3<f64>,
-	anim_pos: Option<Box<RayTraceAnimation<Vector3<f64>>>>,
 	anim_size: Option<Box<RayTraceAnimation<f64>>>,
-	anim_rotation: Option<Box<RayTraceAnimation<Vector3<f64>>>>,
 	color: RayTraceColor,
 	data: Option<WorkingData>
 }
 
 struct WorkingData {
+	position: Vector3<f64>,
 	direction: Vector3<f64>
 }
 
 impl RayTraceDirectedSpotLight {
 	pub fn new(position: Vector3<f64>, color: RayTraceColor) -> Self {
 		Self {
-			position: position,
+			transform: RayTraceTransform::new_with(position, [0.0, 0.0, 0.0], [1.0, 1.0, 1.0]),
 			size: 0.0,
-			rotation: [0.0, 0.0, 0.0],
-			anim_pos: None,
 			anim_size: None,
-			anim_rotation: None,
 			color: color,
 			data: None
 		}
 	}
 
-	pub fn set_anim_pos_opt(&mut self, anim: Option<Box<RayTraceAnimation<Vector3<f64>>>>) {
-		self.anim_pos = anim;
-	}
-
-	pub fn set_anim_pos(&mut self, anim: Box<RayTraceAnimation<Vector3<f64>>>) {
-		self.anim_pos = Some(anim);
-	}
-
 	pub fn set_anim_size_opt(&mut self, anim: Option<Box<RayTraceAnimation<f64>>>) {
 		self.anim_size = anim;
 	}
@@ -60,56 +49,69 @@ impl RayTraceDirectedSpotLight {
 		&self.color
 	}
 
-	pub fn get_position(&self) -> &Vector3<f64> {
-		&self.position
-	}
-
 	pub fn get_size(&self) -> f64 {
 		(self.size * 2.0) + 180.0
 	}
 
-	pub fn get_rotation(&self) -> &Vector3<f64> {
-		&self.rotation
-	}
-
 	pub fn set_color(&mut self, color: RayTraceColor) {
 		self.color = color;
 	}
 
-	pub fn set_position(&mut self, position: Vector3<f64>) {
-		self.position = position;
-	}
-
 	pub fn set_size(&mut self, size: f64) {
 		self.size = (size - 180.0) / 2.0;
 	}
 
+	#[deprecated(note = "Use the transform instead")]
+	pub fn set_position(&mut self, position: Vector3<f64>) {
+		self.transform.set_translation(position);
+	}
+
+	#[deprecated(note = "Use the transform instead")]
 	pub fn set_rotation(&mut self, rotation: Vector3<f64>) {
-		self.rotation = rotation;
+		self.transform.set_rotation(rotation);
+	}
+
+	#[deprecated(note = "Use the transform instead")]
+	pub fn set_anim_pos_opt(&mut self, anim: Option<Box<RayTraceAnimation<Vector3<f64>>>>) {
+		self.transform.set_anim_translation_opt(anim);
+	}
+
+	#[deprecated(note = "Use the transform instead")]
+	pub fn set_anim_pos(&mut self, anim: Box<RayTraceAnimation<Vector3<f64>>>) {
+		self.transform.set_anim_translation(anim);
+	}
+}
+
+impl RayTraceTransformable for RayTraceDirectedSpotLight {
+	fn get_transform(&self) -> &RayTraceTransform {
+		&self.transform
+	}
+
+	fn get_transform_mut(&mut self) -> &mut RayTraceTransform {
+		&mut self.transform
 	}
 }
 
 impl RayTraceLight for RayTraceDirectedSpotLight {
 	fn init(&mut self, frame: usize) {
-		if let Some(ref anim) = self.anim_pos {
-			self.position = anim.next_frame(frame);
-		}
+		self.transform.init(frame);
 
 		if let Some(ref anim) = self.anim_size {
 			self.size = (anim.next_frame(frame) - 180.0) / 2.0;
 		}
 
-		if let Some(ref anim) = self.anim_rotation {
-			self.rotation = anim.next_frame(frame);
-		}
-
 		self.data = Some(WorkingData {
-				direction: row_mat3_transform(rotate_xyz(self.rotation), [1.0, 0.0, 0.0])
+				position: self.transform.get_position(),
+				direction: vec3_normalized(self.transform.transform_direction([1.0, 0.0, 0.0]))
 			});
 	}
 
 	fn get_position(&self) -> Vector3<f64> {
-		self.position
+		if let Some(ref data) = self.data {
+			data.position
+		} else {
+			self.transform.get_position()
+		}
 	}
 
 	fn get_light(&self, ray: &RayTraceRay) -> RayTraceColor {
@@ -124,4 +126,15 @@ impl RayTraceLight for RayTraceDirectedSpotLight {
 			panic!("Light source was not initialized!");
 		}
 	}
+
+	fn serialize(&self, writer: &mut RayTraceSceneWriter) -> Result<(), RayTraceSceneWriteError> {
+		writer.set_type("directed_spot");
+		try!(writer.set_color("color", &self.color));
+		try!(writer.set_f64("size", self.size));
+		if let Some(ref anim) = self.anim_size {
+			writer.set_anim_f64("size_animation", &**anim);
+		}
+		writer.set_transform(&self.transform);
+		Ok(())
+	}
 }
@@ -1,5 +1,7 @@
+mod area;
 mod directed_spot;
 mod spot;
 
+pub use self::area::*;
 pub use self::directed_spot::*;
 pub use self::spot::*;
\ No newline at end of file
@@ -4,60 +4,77 @@ use anim::RayTraceAnimation;
 use color::RayTraceColor;
 use light::RayTraceLight;
 use ray::RayTraceRay;
+use scene_file::RayTraceSceneWriteError;
+use scene_file::RayTraceSceneWriter;
+
+use math_util::RayTraceTransform;
+use math_util::RayTraceTransformable;
 
 pub struct RayTraceSpotLight {
-	position: Vector3<f64>,
-	anim_pos: Option<Box<RayTraceAnimation<Vector3<f64>>>>,
+	transform: RayTraceTransform,
 	color: RayTraceColor
 }
 
 impl RayTraceSpotLight {
 	pub fn new(position: Vector3<f64>, color: RayTraceColor) -> Self {
 		Self {
-			position: position,
-			anim_pos: None,
+			transform: RayTraceTransform::new_with(position, [0.0, 0.0, 0.0], [1.0, 1.0, 1.0]),
 			color: color
 		}
 	}
 
-	pub fn set_anim_pos_opt(&mut self, anim: Option<Box<RayTraceAnimation<Vector3<f64>>>>) {
-		self.anim_pos = anim;
+	pub fn get_color(&self) -> &RayTraceColor {
+		&self.color
 	}
 
-	pub fn set_anim_pos(&mut self, anim: Box<RayTraceAnimation<Vector3<f64>>>) {
-		self.anim_pos = Some(anim);
+	pub fn set_color(&mut self, color: RayTraceColor) {
+		self.color = color;
 	}
 
-	pub fn get_color(&self) -> &RayTraceColor {
-		&self.color
+	#[deprecated(note = "Use the transform instead")]
+	pub fn set_position(&mut self, position: Vector3<f64>) {
+		self.transform.set_translation(position);
 	}
 
-	pub fn get_position(&self) -> &Vector3<f64> {
-		&self.position
+	#[deprecated(note = "Use the transform instead")]
+	pub fn set_anim_pos_opt(&mut self, anim: Option<Box<RayTraceAnimation<Vector3<f64>>>>) {
+		self.transform.set_anim_translation_opt(anim);
 	}
 
-	pub fn set_color(&mut self, color: RayTraceColor) {
-		self.color = color;
+	#[deprecated(note = "Use the transform instead")]
+	pub fn set_anim_pos(&mut self, anim: Box<RayTraceAnimation<Vector3<f64>>>) {
+		self.transform.set_anim_translation(anim);
 	}
+}
 
-	pub fn set_position(&mut self, position: Vector3<f64>) {
-		self.position = position;
+impl RayTraceTransformable for RayTraceSpotLight {
+	fn get_transform(&self) -> &RayTraceTransform {
+		&self.transform
+	}
+
+	fn get_transform_mut(&mut self) -> &mut RayTraceTransform {
+		&mut self.transform
 	}
 }
 
 #[allow(unused_variables)]
 impl RayTraceLight for RayTraceSpotLight {
 	fn init(&mut self, frame: usize) {
-		if let Some(ref anim) = self.anim_pos {
-			self.position = anim.next_frame(frame);
-		}
+		self.transform.init(frame);
 	}
 
 	fn get_position(&self) -> Vector3<f64> {
-		self.position
+		self.transform.get_position()
 	}
 
 	fn get_light(&self, ray: &RayTraceRay) -> RayTraceColor {
 		self.color.clone()
 	}
+
+	fn serialize(&self, writer: &mut RayTraceSceneWriter) -> Result<(), RayTraceSceneWriteError> {
+		writer.set_type("spot");
+		try!(writer.set_color("color", &self.color));
+		writer.set_transform(&self.transform);
+		Ok(())
+	}
 }
@@ -1,5 +1,13 @@
+use std::f32;
+
+use rand::{Rng, thread_rng};
+
+use vecmath::Vector3;
 use vecmath::vec3_dot;
+use vecmath::vec3_neg;
+use vecmath::vec3_normalized;
 use vecmath::vec3_normalized_sub;
+use vecmath::vec3_scale;
 use vecmath::vec3_sub;
 use vecmath::vec3_len;
 
@@ -12,8 +20,8 @@ use hit::RayTraceRayHit;
 use light::RayTraceShading;
 use params::RayTraceParams;
 use scene::RayTraceScene;
-
-use math_util::compute_reflected_ray;
+use scene_file::RayTraceSceneWriteError;
+use scene_file::RayTraceSceneWriter;
 
 pub struct RayTracePhongShading {
 	ambient: f32,
@@ -54,7 +62,7 @@ impl RayTracePhongShading {
 impl RayTraceShading for RayTracePhongShading {
 	fn apply(&self, ray: &RayTraceRay, ray_hit: &RayTraceRayHit, camera: &Box<RayTraceCamera>, scene: &RayTraceScene,
 			params: &RayTraceParams) -> (RayTraceColor, RayTraceColor) {
-		let surface_normal = ray_hit.get_surface_normal();
+		let surface_normal = *ray_hit.get_surface_normal();
 		let hit_distance = ray_hit.get_distance();
 		let light_ray_start = ray.get_position_on_ray(hit_distance - 1e-10);
 		let camera_direction = camera.get_direction();
@@ -65,6 +73,8 @@ impl RayTraceShading for RayTracePhongShading {
 		let diffuse_light = material.get_diffuse_light();
 		let specular_light = material.get_specular_light();
 		let surface_roughness = material.get_surface_roughness();
+		let brdf = material.get_brdf();
+		let view_direction = vec3_normalized(vec3_neg(*ray.get_direction()));
 
 		// Ambient offset
 		let ambient_color = ambient_light * material_color;
@@ -72,6 +82,34 @@ impl RayTraceShading for RayTracePhongShading {
 
 		let mut specular_component = RayTraceColor::new_with(0.0, 0.0, 0.0, 0.0);
 		let mut diffuse_component = RayTraceColor::new_with(0.0, 0.0, 0.0, 0.0);
+		let mut reflected_component = RayTraceColor::new_with(0.0, 0.0, 0.0, 0.0);
+
+		// Diffuse, reflected and specular overlay light for light arriving from the direction
+		let shade = |light_ray_direction: Vector3<f64>, light_color: &RayTraceColor| {
+			let black = || RayTraceColor::new_with(0.0, 0.0, 0.0, 0.0);
+
+			if let Some(brdf) = brdf {
+				// The specular light is part of the reflected energy instead of an overlay
+				let (diffuse, specular) = brdf.evaluate_lobes(surface_normal, view_direction, light_ray_direction);
+				let light_color = light_color * light_color.get_a();
+				return (diffuse * &light_color, specular * &light_color, black());
+			}
+
+			let mut result = (black(), black(), black());
+			let diffuse = vec3_dot(surface_normal, light_ray_direction) as f32;
+			if diffuse > 0.0 {
+				result.0 = material_color * light_color.clone() * diffuse * light_color.get_a() * diffuse_light;
+			}
+
+			let reflected_direction = vec3_sub(light_ray_direction,
+				vec3_scale(surface_normal, 2.0 * vec3_dot(light_ray_direction, surface_normal)));
+			let specular = vec3_dot(reflected_direction, camera_direction) as f32;
+			if specular > 0.0 {
+				result.2 = light_color * (surface_roughness + 2.0) / (2.0 * 3.14159265359)
+					* specular.powf(surface_roughness) * light_color.get_a() * specular_light;
+			}
+			result
+		};
 
 		for light in scene.get_lights() {
 			let light_position = light.get_position();
@@ -79,47 +117,90 @@ impl RayTraceShading for RayTracePhongShading {
 
 			let light_ray_direction = vec3_normalized_sub(light_position.clone(), light_ray_start);
 			let light_ray = RayTraceRay::new(light_ray_start, light_ray_direction);
-			let reflected_ray = compute_reflected_ray(surface_normal.clone(), &light_ray, 0.0);
-			let mut light_ray_intersected = false;
-
-			for object in scene.get_objects().iter() {
-				if let Some(aabb) = object.get_aabb() {
-					if !aabb.is_hit(&light_ray) {
-						continue;
-					}
-				}
-
-				if let Some(hit) = object.next_hit(&light_ray) {
-					let dist = hit.get_distance();
-					if dist > 0.0 && dist < light_distance {
-						light_ray_intersected = true;
-						break;
-					}
-				}
+			if is_occluded(scene, &light_ray, light_distance) {
+				continue;
 			}
 
-			if !light_ray_intersected {
-				let light_color = light.get_light(&light_ray);
-				let diffuse = vec3_dot(surface_normal.clone(), light_ray_direction) as f32;
-				if diffuse > 0.0 {
-					diffuse_component += material_color * light_color.clone() * diffuse * light_color.get_a()
-						* diffuse_light;
-				}
+			let (diffuse, reflected, specular) = shade(light_ray_direction, &light.get_light(&light_ray));
+			diffuse_component += diffuse;
+			reflected_component += reflected;
+			specular_component += specular;
+		}
 
-				let specular = vec3_dot(reflected_ray.get_direction().clone(), camera_direction) as f32;
-				if specular > 0.0 {
-					specular_component += &light_color * (surface_roughness + 2.0) / (2.0 * 3.14159265359)
-						* specular.powf(surface_roughness) * light_color.get_a() * specular_light;
+		// Emissive objects are sampled with several shadow rays for soft shadows
+		let light_samples = params.get_light_samples();
+		let mut rng = thread_rng();
+		for (area_light, object) in scene.get_area_lights() {
+			for _ in 0..light_samples {
+				let u = [rng.gen::<f64>(), rng.gen::<f64>(), rng.gen::<f64>()];
+				let (point, pdf) = match area_light.sample(light_ray_start, u) {
+					Some(sample) => sample,
+					None => { continue; }
+				};
+
+				let light_distance = vec3_len(vec3_sub(point, light_ray_start));
+				let light_ray_direction = vec3_normalized_sub(point, light_ray_start);
+				let light_ray = RayTraceRay::new(light_ray_start, light_ray_direction);
+
+				// The sampled point has to be the nearest one of the emitter in this direction
+				let emitter_hit = match object.next_hit(&light_ray) {
+					Some(hit) => hit,
+					None => { continue; }
+				};
+				let emitter_distance = emitter_hit.get_distance();
+				if (emitter_distance - light_distance).abs() > 1e-6 * light_distance.max(1.0)
+						|| is_occluded(scene, &light_ray, emitter_distance * (1.0 - 1e-6)) {
+					continue;
 				}
+
+				let emission = emitter_hit.get_surface_material().get_emission();
+				let weight = (1.0 / (pdf * light_samples as f64)) as f32;
+				let light_color = RayTraceColor::new_with(emission.get_r() * weight, emission.get_g() * weight,
+					emission.get_b() * weight, 1.0);
+
+				// The sampled light is irradiance, the Lambert term of the microfacet BRDF already divides it by pi
+				let (diffuse, reflected, specular) = shade(light_ray_direction, &light_color);
+				diffuse_component += if brdf.is_some() { diffuse } else { diffuse * f32::consts::FRAC_1_PI };
+				reflected_component += reflected;
+				specular_component += specular;
 			}
 		}
 
 		// Mix the colors with respect to the maximum color levels
-		let mut final_color = ambient_component * self.ambient + diffuse_component * self.diffuse;
+		let mut final_color = ambient_component * self.ambient + diffuse_component * self.diffuse
+			+ reflected_component * self.specular;
 		let final_overlay = specular_component * self.specular;
 
 		final_color.set_a(material_color.get_a());
 
 		return (final_color, final_overlay);
 	}
-}
\ No newline at end of file
+
+	fn serialize(&self, writer: &mut RayTraceSceneWriter) -> Result<(), RayTraceSceneWriteError> {
+		writer.set_type("phong");
+		try!(writer.set_f64("ambient", self.ambient as f64));
+		try!(writer.set_f64("diffuse", self.diffuse as f64));
+		try!(writer.set_f64("specular", self.specular as f64));
+		Ok(())
+	}
+}
+
+// Tests whether any object is hit before the distance along the ray
+fn is_occluded(scene: &RayTraceScene, ray: &RayTraceRay, distance: f64) -> bool {
+	for object in scene.get_objects() {
+		if let Some(aabb) = object.get_aabb() {
+			if !aabb.is_hit(ray) {
+				continue;
+			}
+		}
+
+		if let Some(hit) = object.next_hit(ray) {
+			let dist = hit.get_distance();
+			if dist > 0.0 && dist < distance {
+				return true;
+			}
+		}
+	}
+
+	false
+}
@@ -0,0 +1,222 @@
+use std::f64;
+
+use vecmath::Vector2;
+use vecmath::Vector3;
+use vecmath::{vec3_add, vec3_dot, vec3_normalized, vec3_scale, vec3_sub};
+
+use color::RayTraceColor;
+use math_util::compute_tangent_frame;
+
+// Reflectance of dielectrics at normal incidence
+const DIELECTRIC_SPECULAR: f64 = 0.04;
+// Perfectly smooth surfaces have a singular distribution
+const MIN_ALPHA: f64 = 0.002;
+// Normals which are interpolated can face away from the viewer
+const MIN_COS: f64 = 1e-4;
+
+// Metallic-roughness BRDF with a GGX (Trowbridge-Reitz) distribution of the microfacets, the height correlated
+// Smith masking-shadowing and the Fresnel term of Schlick for the specular lobe and a Lambertian diffuse lobe
+// weighted by the light which is not reflected. All directions are normalized, in world space and point away from
+// the surface.
+#[derive(Debug, Clone)]
+pub struct RayTraceMicrofacetBrdf {
+	base_color: RayTraceColor,
+	metallic: f64,
+	roughness: f64
+}
+
+#[allow(dead_code)]
+impl RayTraceMicrofacetBrdf {
+	// The roughness is perceptual, the width of the distribution is its square
+	pub fn new(base_color: RayTraceColor, metallic: f64, roughness: f64) -> Self {
+		Self {
+			base_color: base_color,
+			metallic: metallic.max(0.0).min(1.0),
+			roughness: roughness.max(0.0).min(1.0)
+		}
+	}
+
+	pub fn get_base_color(&self) -> &RayTraceColor {
+		&self.base_color
+	}
+
+	pub fn get_metallic(&self) -> f64 {
+		self.metallic
+	}
+
+	pub fn get_roughness(&self) -> f64 {
+		self.roughness
+	}
+
+	pub fn get_alpha(&self) -> f64 {
+		(self.roughness * self.roughness).max(MIN_ALPHA)
+	}
+
+	// Surfaces this smooth reflect like mirrors
+	pub fn is_mirror(&self) -> bool {
+		self.roughness * self.roughness <= MIN_ALPHA
+	}
+
+	// Color reflected at normal incidence, metals tint their reflections
+	pub fn get_specular_color(&self) -> [f64; 3] {
+		let (r, g, b, _) = self.base_color.get();
+		let mix = |c: f32| DIELECTRIC_SPECULAR + (c as f64 - DIELECTRIC_SPECULAR) * self.metallic;
+		[mix(r), mix(g), mix(b)]
+	}
+
+	// Albedo of the diffuse lobe, metals have none
+	pub fn get_diffuse_color(&self) -> [f64; 3] {
+		let (r, g, b, _) = self.base_color.get();
+		let scale = 1.0 - self.metallic;
+		[r as f64 * scale, g as f64 * scale, b as f64 * scale]
+	}
+
+	// Diffuse and specular light reflected from the light direction towards the viewer, multiplied by the cosine of
+	// the light direction so the results only have to be multiplied by the incoming light
+	pub fn evaluate_lobes(&self, normal: Vector3<f64>, view: Vector3<f64>, light: Vector3<f64>)
+			-> (RayTraceColor, RayTraceColor) {
+		let n_dot_l = vec3_dot(normal, light);
+		if n_dot_l <= 0.0 {
+			return (RayTraceColor::black(), RayTraceColor::black());
+		}
+
+		let n_dot_v = vec3_dot(normal, view).max(MIN_COS);
+		let half = vec3_normalized(vec3_add(view, light));
+		let (n_dot_h, v_dot_h) = (vec3_dot(normal, half).max(0.0), vec3_dot(view, half).max(0.0));
+
+		let alpha = self.get_alpha();
+		let fresnel = schlick_fresnel(self.get_specular_color(), v_dot_h);
+		let specular = ggx_distribution(n_dot_h, alpha) * smith_visibility(n_dot_v, n_dot_l, alpha) * n_dot_l;
+		let diffuse = self.get_diffuse_color();
+
+		let lobe = |i: usize| ((1.0 - fresnel[i]) * diffuse[i] / f64::consts::PI * n_dot_l, fresnel[i] * specular);
+		let (r, g, b) = (lobe(0), lobe(1), lobe(2));
+		(RayTraceColor::new_with(r.0 as f32, g.0 as f32, b.0 as f32, 1.0),
+			RayTraceColor::new_with(r.1 as f32, g.1 as f32, b.1 as f32, 1.0))
+	}
+
+	// Sum of both lobes
+	pub fn evaluate(&self, normal: Vector3<f64>, view: Vector3<f64>, light: Vector3<f64>) -> RayTraceColor {
+		let (mut diffuse, specular) = self.evaluate_lobes(normal, view, light);
+		diffuse += specular;
+		diffuse.set_a(1.0);
+		diffuse
+	}
+
+	// Probability density per solid angle with which `sample` chooses the light direction
+	pub fn pdf(&self, normal: Vector3<f64>, view: Vector3<f64>, light: Vector3<f64>) -> f64 {
+		let n_dot_l = vec3_dot(normal, light);
+		if n_dot_l <= 0.0 {
+			return 0.0;
+		}
+
+		let half = vec3_normalized(vec3_add(view, light));
+		let v_dot_h = vec3_dot(view, half);
+		let specular_pdf = if v_dot_h > 0.0 {
+			let n_dot_h = vec3_dot(normal, half).max(0.0);
+			ggx_distribution(n_dot_h, self.get_alpha()) * n_dot_h / (4.0 * v_dot_h)
+		} else {
+			0.0
+		};
+
+		let p = self.get_specular_probability();
+		p * specular_pdf + (1.0 - p) * n_dot_l / f64::consts::PI
+	}
+
+	// Chooses a light direction from three uniform random numbers, the specular lobe samples the half vectors of the
+	// GGX distribution and the diffuse lobe a cosine weighted hemisphere. Returns the direction, the evaluated BRDF
+	// divided by the density and the density, None if the direction is below the surface.
+	pub fn sample(&self, normal: Vector3<f64>, view: Vector3<f64>, u: Vector3<f64>)
+			-> Option<(Vector3<f64>, RayTraceColor, f64)> {
+		let frame = compute_tangent_frame(normal);
+		let to_world = |x: f64, y: f64, z: f64|
+			vec3_add(vec3_add(vec3_scale(frame[0], x), vec3_scale(frame[1], y)), vec3_scale(normal, z));
+		let phi = 2.0 * f64::consts::PI * u[2];
+
+		let light = if u[0] < self.get_specular_probability() {
+			let alpha2 = self.get_alpha() * self.get_alpha();
+			let cos_theta = ((1.0 - u[1]) / (1.0 + (alpha2 - 1.0) * u[1])).max(0.0).sqrt();
+			let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+			let half = to_world(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta);
+			vec3_sub(vec3_scale(half, 2.0 * vec3_dot(view, half)), view)
+		} else {
+			let r = u[1].sqrt();
+			to_world(r * phi.cos(), r * phi.sin(), (1.0 - u[1]).max(0.0).sqrt())
+		};
+
+		let pdf = self.pdf(normal, view, light);
+		if pdf <= 0.0 {
+			return None;
+		}
+
+		let weight = self.evaluate(normal, view, light) / pdf as f32;
+		Some((light, RayTraceColor::new_with(weight.get_r(), weight.get_g(), weight.get_b(), 1.0), pdf))
+	}
+
+	// Chooses a reflected direction from the half vectors of the GGX distribution for two uniform random numbers.
+	// Returns the direction and the weight of the light arriving from it, which is the masking-shadowing of the
+	// specular lobe tinted by the Fresnel term, None if the direction is below the surface.
+	pub fn sample_specular(&self, normal: Vector3<f64>, view: Vector3<f64>, u: Vector2<f64>)
+			-> Option<(Vector3<f64>, RayTraceColor)> {
+		let alpha = self.get_alpha();
+		let cos_theta = ((1.0 - u[0]) / (1.0 + (alpha * alpha - 1.0) * u[0])).max(0.0).sqrt();
+		let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+		let phi = 2.0 * f64::consts::PI * u[1];
+
+		let frame = compute_tangent_frame(normal);
+		let half = vec3_add(vec3_add(vec3_scale(frame[0], sin_theta * phi.cos()),
+			vec3_scale(frame[1], sin_theta * phi.sin())), vec3_scale(normal, cos_theta));
+		let v_dot_h = vec3_dot(view, half);
+		let light = vec3_sub(vec3_scale(half, 2.0 * v_dot_h), view);
+
+		let n_dot_l = vec3_dot(normal, light);
+		if n_dot_l <= 0.0 || v_dot_h <= 0.0 {
+			return None;
+		}
+
+		// The density D * n_dot_h / (4 * v_dot_h) cancels the distribution of the BRDF
+		let n_dot_v = vec3_dot(normal, view).max(MIN_COS);
+		let weight = (smith_visibility(n_dot_v, n_dot_l, alpha) * 4.0 * n_dot_l * v_dot_h / cos_theta.max(MIN_COS))
+			.min(1.0);
+		let fresnel = schlick_fresnel(self.get_specular_color(), v_dot_h);
+		Some((light, RayTraceColor::new_with((fresnel[0] * weight) as f32, (fresnel[1] * weight) as f32,
+			(fresnel[2] * weight) as f32, 1.0)))
+	}
+
+	// Part of the light arriving from the mirror direction which a smooth surface reflects
+	pub fn get_mirror_weight(&self, normal: Vector3<f64>, view: Vector3<f64>) -> RayTraceColor {
+		let fresnel = schlick_fresnel(self.get_specular_color(), vec3_dot(normal, view));
+		RayTraceColor::new_with(fresnel[0] as f32, fresnel[1] as f32, fresnel[2] as f32, 1.0)
+	}
+
+	// The lobes are chosen by their reflectance at normal incidence
+	fn get_specular_probability(&self) -> f64 {
+		let luminance = |c: [f64; 3]| 0.2126 * c[0] + 0.7152 * c[1] + 0.0722 * c[2];
+		let (specular, diffuse) = (luminance(self.get_specular_color()), luminance(self.get_diffuse_color()));
+		if specular + diffuse <= 0.0 {
+			1.0
+		} else {
+			(specular / (specular + diffuse)).max(0.1)
+		}
+	}
+}
+
+// Density of microfacets oriented along the half vector
+pub fn ggx_distribution(n_dot_h: f64, alpha: f64) -> f64 {
+	let alpha2 = alpha * alpha;
+	let d = n_dot_h * n_dot_h * (alpha2 - 1.0) + 1.0;
+	alpha2 / (f64::consts::PI * d * d)
+}
+
+// Height correlated Smith masking-shadowing divided by the denominator 4 * n_dot_v * n_dot_l of the BRDF
+pub fn smith_visibility(n_dot_v: f64, n_dot_l: f64, alpha: f64) -> f64 {
+	let alpha2 = alpha * alpha;
+	let v = n_dot_l * (n_dot_v * n_dot_v * (1.0 - alpha2) + alpha2).sqrt();
+	let l = n_dot_v * (n_dot_l * n_dot_l * (1.0 - alpha2) + alpha2).sqrt();
+	0.5 / (v + l)
+}
+
+pub fn schlick_fresnel(f0: [f64; 3], cos_theta: f64) -> [f64; 3] {
+	let t = (1.0 - cos_theta.max(0.0).min(1.0)).powi(5);
+	[f0[0] + (1.0 - f0[0]) * t, f0[1] + (1.0 - f0[1]) * t, f0[2] + (1.0 - f0[2]) * t]
+}
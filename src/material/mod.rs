@@ -1,11 +1,124 @@
+mod microfacet;
+mod mtl;
+mod noise;
+mod normal_map;
+mod pbr;
+mod procedural;
 mod simple;
 mod test;
+mod texture;
 
+pub use self::microfacet::RayTraceMicrofacetBrdf;
+pub use self::microfacet::ggx_distribution;
+pub use self::microfacet::schlick_fresnel;
+pub use self::microfacet::smith_visibility;
+pub use self::mtl::RayTraceMtlMaterial;
+pub use self::noise::RayTraceNoise;
+pub use self::noise::RayTraceNoiseBasis;
+pub use self::normal_map::RayTraceNormalMapKind;
+pub use self::normal_map::RayTraceNormalMapMaterial;
+pub use self::pbr::RayTracePbrMaterial;
+pub use self::procedural::RayTraceColorRamp;
+pub use self::procedural::RayTraceNoiseMaterial;
+pub use self::procedural::RayTraceNoisePattern;
+pub use self::procedural::RayTraceNoiseSpace;
 pub use self::simple::RayTraceSimpleMaterial;
 pub use self::test::RayTraceCheckerboardMaterial;
+pub use self::texture::RayTraceTexture;
+pub use self::texture::RayTraceTextureFilter;
+pub use self::texture::RayTraceTextureMaterial;
+pub use self::texture::RayTraceTextureMipmap;
+pub use self::texture::RayTraceTextureWrap;
 
+use vecmath::Vector2;
+use vecmath::Vector3;
+use vecmath::vec3_add;
+
+use color::RayTraceColor;
 use hit::RayTraceMaterialHit;
+use hit::RayTraceShadingContext;
+use ray::RayTraceRay;
+use scene_file::RayTraceSceneWriteError;
+use scene_file::RayTraceSceneWriter;
+
+use sink::image::RgbaImage;
 
 pub trait RayTraceMaterial: Send + Sync {
 	fn get_hit(&self, x: f64, y: f64) -> RayTraceMaterialHit;
-}
\ No newline at end of file
+
+	// Hit with everything known about the surface like the footprint of the pixel and the position in object space,
+	// materials which do not override this are point sampled at the texture coordinates
+	fn get_hit_context(&self, context: &RayTraceShadingContext) -> RayTraceMaterialHit {
+		let texcoord = context.get_texcoord();
+		self.get_hit(texcoord[0], texcoord[1])
+	}
+
+	// Emissive materials turn their objects into area lights, materials which do not override this emit no light
+	fn is_emissive(&self) -> bool {
+		false
+	}
+
+	// Materials which do not override this can not be written to a scene file
+	fn serialize(&self, writer: &mut RayTraceSceneWriter) -> Result<(), RayTraceSceneWriteError> {
+		Err(writer.unsupported("material"))
+	}
+}
+
+// Change of the texture coordinates from one pixel to the next in x and y direction of the screen
+#[derive(Clone, Debug)]
+pub struct RayTraceFootprint {
+	dx: Vector2<f64>,
+	dy: Vector2<f64>
+}
+
+#[allow(dead_code)]
+impl RayTraceFootprint {
+	pub fn new(dx: Vector2<f64>, dy: Vector2<f64>) -> Self {
+		Self {
+			dx: dx,
+			dy: dy
+		}
+	}
+
+	// Evaluates the texture coordinates of the surface where the neighbouring rays hit its tangent plane, the
+	// position and normal are given in the space of the ray
+	pub fn compute<F>(ray: &RayTraceRay, position: Vector3<f64>, normal: Vector3<f64>, texcoord: F) -> Option<Self>
+			where F: Fn(Vector3<f64>) -> Vector2<f64> {
+		ray.get_footprint(position, normal).map(|offsets| {
+			let center = texcoord(position);
+			let x = texcoord(vec3_add(position, offsets[0]));
+			let y = texcoord(vec3_add(position, offsets[1]));
+			Self::new([x[0] - center[0], x[1] - center[1]], [y[0] - center[0], y[1] - center[1]])
+		})
+	}
+
+	pub fn get_dx(&self) -> &Vector2<f64> {
+		&self.dx
+	}
+
+	pub fn get_dy(&self) -> &Vector2<f64> {
+		&self.dy
+	}
+
+	// Largest change of the texture coordinates per pixel along each texture axis
+	pub fn get_width(&self) -> Vector2<f64> {
+		[self.dx[0].abs().max(self.dy[0].abs()), self.dx[1].abs().max(self.dy[1].abs())]
+	}
+}
+
+// Looks up the nearest texel with repeated texture coordinates starting at the bottom left corner
+fn sample_image(image: &RgbaImage, x: f64, y: f64) -> RayTraceColor {
+	let (width, height) = image.dimensions();
+	if width == 0 || height == 0 {
+		return RayTraceColor::white();
+	}
+
+	let u = x - x.floor();
+	let v = 1.0 - (y - y.floor());
+	let px = ((u * width as f64) as u32).min(width - 1);
+	let py = ((v * height as f64) as u32).min(height - 1);
+
+	let texel = image.get_pixel(px, py).data;
+	RayTraceColor::new_with(texel[0] as f32 / 255.0, texel[1] as f32 / 255.0, texel[2] as f32 / 255.0,
+		texel[3] as f32 / 255.0)
+}
@@ -0,0 +1,134 @@
+use color::RayTraceColor;
+
+use hit::RayTraceMaterialHit;
+
+use material::RayTraceMaterial;
+use material::sample_image;
+use scene_file::RayTraceSceneWriteError;
+use scene_file::RayTraceSceneWriter;
+
+use sink::image::RgbaImage;
+
+// Material as described by the statements of a Wavefront .mtl file
+pub struct RayTraceMtlMaterial {
+	diffuse: RayTraceColor,
+	specular: RayTraceColor,
+	shininess: f32,
+	dissolve: f32,
+	optical_density: f32,
+	illumination: usize,
+	diffuse_map: Option<RgbaImage>
+}
+
+#[allow(dead_code)]
+impl RayTraceMtlMaterial {
+	pub fn new() -> Self {
+		Self {
+			diffuse: RayTraceColor::new_with(0.8, 0.8, 0.8, 1.0),
+			specular: RayTraceColor::black(),
+			shininess: 0.0,
+			dissolve: 1.0,
+			optical_density: 1.0,
+			illumination: 2,
+			diffuse_map: None
+		}
+	}
+
+	pub fn get_diffuse(&self) -> &RayTraceColor {
+		&self.diffuse
+	}
+
+	pub fn set_diffuse(&mut self, diffuse: RayTraceColor) {
+		self.diffuse = diffuse;
+	}
+
+	pub fn get_specular(&self) -> &RayTraceColor {
+		&self.specular
+	}
+
+	pub fn set_specular(&mut self, specular: RayTraceColor) {
+		self.specular = specular;
+	}
+
+	pub fn get_shininess(&self) -> f32 {
+		self.shininess
+	}
+
+	pub fn set_shininess(&mut self, shininess: f32) {
+		self.shininess = shininess;
+	}
+
+	pub fn get_dissolve(&self) -> f32 {
+		self.dissolve
+	}
+
+	pub fn set_dissolve(&mut self, dissolve: f32) {
+		self.dissolve = dissolve;
+	}
+
+	pub fn get_optical_density(&self) -> f32 {
+		self.optical_density
+	}
+
+	pub fn set_optical_density(&mut self, optical_density: f32) {
+		self.optical_density = optical_density;
+	}
+
+	pub fn get_illumination(&self) -> usize {
+		self.illumination
+	}
+
+	pub fn set_illumination(&mut self, illumination: usize) {
+		self.illumination = illumination;
+	}
+
+	pub fn get_diffuse_map(&self) -> Option<&RgbaImage> {
+		self.diffuse_map.as_ref()
+	}
+
+	pub fn set_diffuse_map(&mut self, diffuse_map: Option<RgbaImage>) {
+		self.diffuse_map = diffuse_map;
+	}
+
+	fn get_diffuse_color(&self, x: f64, y: f64) -> RayTraceColor {
+		let mut color = self.diffuse.clone();
+
+		if let Some(ref map) = self.diffuse_map {
+			color = &color * &sample_image(map, x, y);
+		}
+
+		color.set_a(color.get_a() * self.dissolve);
+		color
+	}
+}
+
+impl RayTraceMaterial for RayTraceMtlMaterial {
+	fn get_hit(&self, x: f64, y: f64) -> RayTraceMaterialHit {
+		let specular = (self.specular.get_r() + self.specular.get_g() + self.specular.get_b()) / 3.0;
+
+		// Illumination models 3 and above enable reflections based on the specular color or the optical density
+		let reflectance = if self.illumination >= 3 {
+			let f0 = (self.optical_density - 1.0) / (self.optical_density + 1.0);
+			specular.max(f0 * f0)
+		} else {
+			0.0
+		};
+
+		RayTraceMaterialHit::new_with(self.get_diffuse_color(x, y), reflectance, 1.0 - reflectance, specular,
+			self.shininess)
+	}
+
+	fn serialize(&self, writer: &mut RayTraceSceneWriter) -> Result<(), RayTraceSceneWriteError> {
+		writer.set_type("mtl");
+		try!(writer.set_color("diffuse", &self.diffuse));
+		try!(writer.set_color("specular", &self.specular));
+		try!(writer.set_f64("shininess", self.shininess as f64));
+		try!(writer.set_f64("dissolve", self.dissolve as f64));
+		try!(writer.set_f64("optical_density", self.optical_density as f64));
+		writer.set_usize("illumination", self.illumination);
+		if let Some(ref map) = self.diffuse_map {
+			try!(writer.set_image("diffuse_map", map));
+		}
+		Ok(())
+	}
+}
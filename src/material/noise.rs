@@ -0,0 +1,239 @@
+use std::f64;
+
+use vecmath::Vector3;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RayTraceNoiseBasis {
+	// Improved gradient noise by Ken Perlin
+	Perlin,
+	// Simplex noise by Ken Perlin with fewer directional artifacts
+	Simplex
+}
+
+// Gradient and cellular noise with a permutation table shuffled by a seed, the same seed always gives the same noise
+pub struct RayTraceNoise {
+	seed: u32,
+	basis: RayTraceNoiseBasis,
+	// Permutation of 0 to 255 repeated once to avoid wrapping the indices
+	perm: Vec<usize>
+}
+
+const GRADIENTS: [[f64; 3]; 12] = [
+	[1.0, 1.0, 0.0], [-1.0, 1.0, 0.0], [1.0, -1.0, 0.0], [-1.0, -1.0, 0.0],
+	[1.0, 0.0, 1.0], [-1.0, 0.0, 1.0], [1.0, 0.0, -1.0], [-1.0, 0.0, -1.0],
+	[0.0, 1.0, 1.0], [0.0, -1.0, 1.0], [0.0, 1.0, -1.0], [0.0, -1.0, -1.0]
+];
+
+#[allow(dead_code)]
+impl RayTraceNoise {
+	pub fn new() -> Self {
+		Self::new_with(0, RayTraceNoiseBasis::Perlin)
+	}
+
+	pub fn new_with(seed: u32, basis: RayTraceNoiseBasis) -> Self {
+		// Fisher-Yates shuffle driven by a xorshift generator
+		let mut state = seed.wrapping_mul(2654435761).wrapping_add(0x9e3779b9) | 1;
+		let mut perm: Vec<usize> = (0..256).collect();
+		for i in (1..256).rev() {
+			state ^= state << 13;
+			state ^= state >> 17;
+			state ^= state << 5;
+			perm.swap(i, state as usize % (i + 1));
+		}
+
+		for i in 0..256 {
+			let value = perm[i];
+			perm.push(value);
+		}
+
+		Self {
+			seed: seed,
+			basis: basis,
+			perm: perm
+		}
+	}
+
+	pub fn get_seed(&self) -> u32 {
+		self.seed
+	}
+
+	pub fn get_basis(&self) -> RayTraceNoiseBasis {
+		self.basis
+	}
+
+	// Noise of the configured basis in the range of about -1 to 1, 2D noise is evaluated with a z of 0
+	pub fn get(&self, p: Vector3<f64>) -> f64 {
+		match self.basis {
+			RayTraceNoiseBasis::Perlin => self.perlin(p),
+			RayTraceNoiseBasis::Simplex => self.simplex(p)
+		}
+	}
+
+	// Zero at the corners of the unit cubes
+	pub fn perlin(&self, p: Vector3<f64>) -> f64 {
+		let perm = &self.perm;
+		let (fx, fy, fz) = (p[0].floor(), p[1].floor(), p[2].floor());
+		let (xi, yi, zi) = ((fx as i64 & 255) as usize, (fy as i64 & 255) as usize, (fz as i64 & 255) as usize);
+		let (x, y, z) = (p[0] - fx, p[1] - fy, p[2] - fz);
+		let (u, v, w) = (fade(x), fade(y), fade(z));
+
+		let a = perm[xi] + yi;
+		let (aa, ab) = (perm[a] + zi, perm[a + 1] + zi);
+		let b = perm[xi + 1] + yi;
+		let (ba, bb) = (perm[b] + zi, perm[b + 1] + zi);
+
+		lerp(w,
+			lerp(v,
+				lerp(u, gradient(perm[aa], x, y, z), gradient(perm[ba], x - 1.0, y, z)),
+				lerp(u, gradient(perm[ab], x, y - 1.0, z), gradient(perm[bb], x - 1.0, y - 1.0, z))),
+			lerp(v,
+				lerp(u, gradient(perm[aa + 1], x, y, z - 1.0), gradient(perm[ba + 1], x - 1.0, y, z - 1.0)),
+				lerp(u, gradient(perm[ab + 1], x, y - 1.0, z - 1.0),
+					gradient(perm[bb + 1], x - 1.0, y - 1.0, z - 1.0))))
+	}
+
+	// Sums the contributions of the corners of the surrounding tetrahedron, see "Simplex noise demystified" by
+	// Stefan Gustavson
+	pub fn simplex(&self, p: Vector3<f64>) -> f64 {
+		const F3: f64 = 1.0 / 3.0;
+		const G3: f64 = 1.0 / 6.0;
+
+		// Skew the space to find the cube which contains the tetrahedron
+		let s = (p[0] + p[1] + p[2]) * F3;
+		let (i, j, k) = ((p[0] + s).floor(), (p[1] + s).floor(), (p[2] + s).floor());
+		let t = (i + j + k) * G3;
+		let p0 = [p[0] - (i - t), p[1] - (j - t), p[2] - (k - t)];
+
+		// Offsets of the second and third corner depend on the order of the coordinates
+		let (o1, o2) = if p0[0] >= p0[1] {
+			if p0[1] >= p0[2] {
+				([1.0, 0.0, 0.0], [1.0, 1.0, 0.0])
+			} else if p0[0] >= p0[2] {
+				([1.0, 0.0, 0.0], [1.0, 0.0, 1.0])
+			} else {
+				([0.0, 0.0, 1.0], [1.0, 0.0, 1.0])
+			}
+		} else {
+			if p0[1] < p0[2] {
+				([0.0, 0.0, 1.0], [0.0, 1.0, 1.0])
+			} else if p0[0] < p0[2] {
+				([0.0, 1.0, 0.0], [0.0, 1.0, 1.0])
+			} else {
+				([0.0, 1.0, 0.0], [1.0, 1.0, 0.0])
+			}
+		};
+
+		let corners = [[0.0, 0.0, 0.0], o1, o2, [1.0, 1.0, 1.0]];
+		let (ii, jj, kk) = ((i as i64 & 255) as usize, (j as i64 & 255) as usize, (k as i64 & 255) as usize);
+
+		let mut sum = 0.0;
+		for (n, corner) in corners.iter().enumerate() {
+			let g = G3 * n as f64;
+			let d = [p0[0] - corner[0] + g, p0[1] - corner[1] + g, p0[2] - corner[2] + g];
+			let weight = 0.6 - d[0] * d[0] - d[1] * d[1] - d[2] * d[2];
+			if weight > 0.0 {
+				let (ci, cj, ck) = (corner[0] as usize, corner[1] as usize, corner[2] as usize);
+				let hash = self.perm[ii + ci + self.perm[jj + cj + self.perm[kk + ck]]] % 12;
+				let grad = GRADIENTS[hash];
+				sum += weight.powi(4) * (grad[0] * d[0] + grad[1] * d[1] + grad[2] * d[2]);
+			}
+		}
+
+		32.0 * sum
+	}
+
+	// Distances to the nearest and second nearest of the points which are scattered one per unit cube, see "A
+	// Cellular Texture Basis Function" by Steven Worley
+	pub fn worley(&self, p: Vector3<f64>) -> [f64; 2] {
+		let (fx, fy, fz) = (p[0].floor(), p[1].floor(), p[2].floor());
+		let mut nearest = [f64::INFINITY, f64::INFINITY];
+
+		for dz in -1..2 {
+			for dy in -1..2 {
+				for dx in -1..2 {
+					let (cx, cy, cz) = (fx + dx as f64, fy + dy as f64, fz + dz as f64);
+					let hash = self.hash(cx as i64, cy as i64, cz as i64);
+					let feature = [
+						cx + (self.perm[hash] as f64 + 0.5) / 256.0,
+						cy + (self.perm[hash + 1] as f64 + 0.5) / 256.0,
+						cz + (self.perm[hash + 2] as f64 + 0.5) / 256.0
+					];
+
+					let d = [feature[0] - p[0], feature[1] - p[1], feature[2] - p[2]];
+					let distance = (d[0] * d[0] + d[1] * d[1] + d[2] * d[2]).sqrt();
+					if distance < nearest[0] {
+						nearest = [distance, nearest[0]];
+					} else if distance < nearest[1] {
+						nearest[1] = distance;
+					}
+				}
+			}
+		}
+
+		nearest
+	}
+
+	// Fractal brownian motion sums octaves of noise whose frequency grows by the lacunarity and whose amplitude
+	// shrinks by the gain, the last octave is faded in by the fraction of the octaves
+	pub fn fbm(&self, p: Vector3<f64>, octaves: f64, lacunarity: f64, gain: f64) -> f64 {
+		self.sum_octaves(p, octaves, lacunarity, gain, |n| n)
+	}
+
+	// Sum of the absolute values of the octaves which gives creases where the noise crosses zero
+	pub fn turbulence(&self, p: Vector3<f64>, octaves: f64, lacunarity: f64, gain: f64) -> f64 {
+		self.sum_octaves(p, octaves, lacunarity, gain, |n| n.abs())
+	}
+
+	fn sum_octaves<F>(&self, p: Vector3<f64>, octaves: f64, lacunarity: f64, gain: f64, map: F) -> f64
+			where F: Fn(f64) -> f64 {
+		let octaves = octaves.max(0.0);
+		let (mut sum, mut amplitude, mut frequency) = (0.0, 1.0, 1.0);
+
+		for _ in 0..(octaves.floor() as usize) {
+			sum += amplitude * map(self.get([p[0] * frequency, p[1] * frequency, p[2] * frequency]));
+			amplitude *= gain;
+			frequency *= lacunarity;
+		}
+
+		let rest = octaves - octaves.floor();
+		if rest > 0.0 {
+			sum += rest * amplitude * map(self.get([p[0] * frequency, p[1] * frequency, p[2] * frequency]));
+		}
+
+		sum
+	}
+
+	// Index into the permutation table for a lattice cell which leaves room for 3 consecutive entries
+	fn hash(&self, x: i64, y: i64, z: i64) -> usize {
+		let perm = &self.perm;
+		perm[perm[perm[(x & 255) as usize] + (y & 255) as usize] + (z & 255) as usize]
+	}
+}
+
+// Maximum absolute value of the sum of the given octaves of noise in the range of -1 to 1
+pub fn noise_amplitude(octaves: f64, gain: f64) -> f64 {
+	let octaves = octaves.max(0.0);
+	let (mut sum, mut amplitude) = (0.0, 1.0);
+	for _ in 0..(octaves.floor() as usize) {
+		sum += amplitude;
+		amplitude *= gain;
+	}
+
+	sum + (octaves - octaves.floor()) * amplitude
+}
+
+// Quintic curve 6t^5 - 15t^4 + 10t^3 with zero first and second derivatives at 0 and 1
+fn fade(t: f64) -> f64 {
+	t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(t: f64, a: f64, b: f64) -> f64 {
+	a + t * (b - a)
+}
+
+fn gradient(hash: usize, x: f64, y: f64, z: f64) -> f64 {
+	let h = hash & 15;
+	let u = if h < 8 { x } else { y };
+	let v = if h < 4 { y } else if h == 12 || h == 14 { x } else { z };
+	(if h & 1 == 0 { u } else { -u }) + (if h & 2 == 0 { v } else { -v })
+}
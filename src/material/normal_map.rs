@@ -0,0 +1,144 @@
+use vecmath::Vector3;
+use vecmath::{vec3_add, vec3_dot, vec3_normalized, vec3_scale, vec3_sub};
+
+use hit::RayTraceMaterialHit;
+use hit::RayTraceShadingContext;
+
+use material::RayTraceMaterial;
+use material::RayTraceTexture;
+use scene_file::RayTraceSceneWriteError;
+use scene_file::RayTraceSceneWriter;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RayTraceNormalMapKind {
+	// Directions in tangent space encoded as colors with the surface normal in blue and the bitangent in green
+	Normal,
+	// Heights in the red channel whose slopes tilt the normal
+	Bump
+}
+
+// Perturbs the shading normal of another material with a normal or bump map, the map is sampled with the texture
+// coordinates and oriented by the tangents of the object
+pub struct RayTraceNormalMapMaterial {
+	material: Box<RayTraceMaterial>,
+	map: RayTraceTexture,
+	kind: RayTraceNormalMapKind,
+	strength: f64,
+	// Maps made for DirectX point the green channel down
+	flip_green: bool
+}
+
+#[allow(dead_code)]
+impl RayTraceNormalMapMaterial {
+	// Normal maps should be linear textures
+	pub fn new(material: Box<RayTraceMaterial>, map: RayTraceTexture, kind: RayTraceNormalMapKind) -> Self {
+		Self {
+			material: material,
+			map: map,
+			kind: kind,
+			strength: 1.0,
+			flip_green: false
+		}
+	}
+
+	pub fn get_material(&self) -> &Box<RayTraceMaterial> {
+		&self.material
+	}
+
+	pub fn get_map(&self) -> &RayTraceTexture {
+		&self.map
+	}
+
+	pub fn get_map_mut(&mut self) -> &mut RayTraceTexture {
+		&mut self.map
+	}
+
+	pub fn get_kind(&self) -> RayTraceNormalMapKind {
+		self.kind
+	}
+
+	// Scales the tilt of normal maps and the heights of bump maps
+	pub fn get_strength(&self) -> f64 {
+		self.strength
+	}
+
+	pub fn set_strength(&mut self, strength: f64) {
+		self.strength = strength;
+	}
+
+	pub fn get_flip_green(&self) -> bool {
+		self.flip_green
+	}
+
+	pub fn set_flip_green(&mut self, flip_green: bool) {
+		self.flip_green = flip_green;
+	}
+
+	// Shading normal in world space after applying the map
+	pub fn get_normal(&self, context: &RayTraceShadingContext) -> Vector3<f64> {
+		let (n, t, b) = (*context.get_shading_normal(), *context.get_tangent(), *context.get_bitangent());
+		let texcoord = context.get_texcoord();
+
+		let (dt, db, dn) = match self.kind {
+			RayTraceNormalMapKind::Normal => {
+				let c = self.map.sample_filtered(texcoord[0], texcoord[1], context.get_footprint());
+				let green = c.get_g() as f64 * 2.0 - 1.0;
+				let green = if self.flip_green { -green } else { green };
+				((c.get_r() as f64 * 2.0 - 1.0) * self.strength, green * self.strength, c.get_b() as f64 * 2.0 - 1.0)
+			},
+			RayTraceNormalMapKind::Bump => {
+				// Central differences over one texel
+				let (width, height) = self.map.get_image().dimensions();
+				let scale = self.map.get_scale();
+				let du = 1.0 / (width.max(1) as f64 * scale[0].abs().max(1e-12));
+				let dv = 1.0 / (height.max(1) as f64 * scale[1].abs().max(1e-12));
+				let h = |x: f64, y: f64| self.map.sample(x, y).get_r() as f64;
+
+				let dhdu = (h(texcoord[0] + du, texcoord[1]) - h(texcoord[0] - du, texcoord[1])) / (2.0 * du);
+				let dhdv = (h(texcoord[0], texcoord[1] + dv) - h(texcoord[0], texcoord[1] - dv)) / (2.0 * dv);
+				(-dhdu * self.strength, -dhdv * self.strength, 1.0)
+			}
+		};
+
+		let normal = vec3_add(vec3_add(vec3_scale(t, dt), vec3_scale(b, db)), vec3_scale(n, dn.max(1e-6)));
+		let normal = vec3_normalized(normal);
+
+		// Normals pointing below the geometric surface would let light through from behind
+		let g = *context.get_geometric_normal();
+		let below = vec3_dot(normal, g);
+		if below < 0.0 {
+			vec3_normalized(vec3_sub(normal, vec3_scale(g, below * 1.01)))
+		} else {
+			normal
+		}
+	}
+}
+
+impl RayTraceMaterial for RayTraceNormalMapMaterial {
+	fn get_hit(&self, x: f64, y: f64) -> RayTraceMaterialHit {
+		self.material.get_hit(x, y)
+	}
+
+	fn get_hit_context(&self, context: &RayTraceShadingContext) -> RayTraceMaterialHit {
+		let mut hit = self.material.get_hit_context(context);
+		hit.set_normal(Some(self.get_normal(context)));
+		hit
+	}
+
+	fn is_emissive(&self) -> bool {
+		self.material.is_emissive()
+	}
+
+	fn serialize(&self, writer: &mut RayTraceSceneWriter) -> Result<(), RayTraceSceneWriteError> {
+		writer.set_type("normal_map");
+		try!(writer.set_material("material", &*self.material));
+		writer.set_str("kind", match self.kind {
+			RayTraceNormalMapKind::Normal => "normal",
+			RayTraceNormalMapKind::Bump => "bump"
+		});
+		try!(self.map.serialize(writer));
+		try!(writer.set_f64("strength", self.strength));
+		writer.set_bool("flip_green", self.flip_green);
+		Ok(())
+	}
+}
@@ -0,0 +1,115 @@
+use color::RayTraceColor;
+
+use hit::RayTraceMaterialHit;
+
+use material::RayTraceMaterial;
+use material::RayTraceMicrofacetBrdf;
+use material::sample_image;
+use scene_file::RayTraceSceneWriteError;
+use scene_file::RayTraceSceneWriter;
+
+use sink::image::RgbaImage;
+
+// Metallic-roughness material as used by glTF with a microfacet BRDF, the phong parameters approximate it for
+// shadings which do not support the BRDF
+pub struct RayTracePbrMaterial {
+	base_color: RayTraceColor,
+	metallic: f32,
+	roughness: f32,
+	base_color_map: Option<RgbaImage>,
+	emission: RayTraceColor
+}
+
+#[allow(dead_code)]
+impl RayTracePbrMaterial {
+	pub fn new(base_color: RayTraceColor, metallic: f32, roughness: f32) -> Self {
+		Self {
+			base_color: base_color,
+			metallic: metallic,
+			roughness: roughness,
+			base_color_map: None,
+			emission: RayTraceColor::black()
+		}
+	}
+
+	pub fn get_base_color(&self) -> &RayTraceColor {
+		&self.base_color
+	}
+
+	pub fn set_base_color(&mut self, base_color: RayTraceColor) {
+		self.base_color = base_color;
+	}
+
+	pub fn get_metallic(&self) -> f32 {
+		self.metallic
+	}
+
+	pub fn set_metallic(&mut self, metallic: f32) {
+		self.metallic = metallic;
+	}
+
+	pub fn get_roughness(&self) -> f32 {
+		self.roughness
+	}
+
+	pub fn set_roughness(&mut self, roughness: f32) {
+		self.roughness = roughness;
+	}
+
+	pub fn get_base_color_map(&self) -> Option<&RgbaImage> {
+		self.base_color_map.as_ref()
+	}
+
+	pub fn set_base_color_map(&mut self, base_color_map: Option<RgbaImage>) {
+		self.base_color_map = base_color_map;
+	}
+
+	// Light emitted by the surface, colors brighter than white are allowed
+	pub fn get_emission(&self) -> &RayTraceColor {
+		&self.emission
+	}
+
+	pub fn set_emission(&mut self, emission: RayTraceColor) {
+		self.emission = emission;
+	}
+}
+
+impl RayTraceMaterial for RayTracePbrMaterial {
+	fn get_hit(&self, x: f64, y: f64) -> RayTraceMaterialHit {
+		let color = match self.base_color_map {
+			Some(ref map) => &self.base_color * &sample_image(map, x, y),
+			None => self.base_color.clone()
+		};
+
+		let metallic = self.metallic.max(0.0).min(1.0);
+		let roughness = self.roughness.max(0.01).min(1.0);
+
+		// Phong exponent matching the width of the specular lobe, the reflections are weighted by the BRDF
+		let alpha = roughness * roughness;
+		let exponent = (2.0 / (alpha * alpha) - 2.0).max(1.0).min(1000.0);
+
+		let brdf = RayTraceMicrofacetBrdf::new(color.clone(), metallic as f64, roughness as f64);
+		let mut hit = RayTraceMaterialHit::new_with(color, 0.0, 1.0 - metallic, 1.0 - roughness, exponent);
+		hit.set_brdf(Some(brdf));
+		hit.set_emission(self.emission.clone());
+		hit
+	}
+
+	fn is_emissive(&self) -> bool {
+		self.emission.get_r() > 0.0 || self.emission.get_g() > 0.0 || self.emission.get_b() > 0.0
+	}
+
+	fn serialize(&self, writer: &mut RayTraceSceneWriter) -> Result<(), RayTraceSceneWriteError> {
+		writer.set_type("pbr");
+		try!(writer.set_color("base_color", &self.base_color));
+		try!(writer.set_f64("metallic", self.metallic as f64));
+		try!(writer.set_f64("roughness", self.roughness as f64));
+		if self.is_emissive() {
+			try!(writer.set_color("emission", &self.emission));
+		}
+		if let Some(ref map) = self.base_color_map {
+			try!(writer.set_image("base_color_map", map));
+		}
+		Ok(())
+	}
+}
@@ -0,0 +1,332 @@
+use std::cmp::Ordering;
+use std::f64::consts::PI;
+
+use vecmath::Vector3;
+
+use color::RayTraceColor;
+
+use hit::RayTraceMaterialHit;
+use hit::RayTraceShadingContext;
+
+use material::RayTraceFootprint;
+use material::RayTraceMaterial;
+use material::noise::noise_amplitude;
+use material::noise::RayTraceNoise;
+use material::noise::RayTraceNoiseBasis;
+use scene_file::RayTraceSceneWriteError;
+use scene_file::RayTraceSceneWriter;
+
+// Frequency and amplitude change from one octave to the next
+const LACUNARITY: f64 = 2.0;
+const GAIN: f64 = 0.5;
+
+// Colors at positions from 0 to 1 which are interpolated linearly, values outside use the first or last color
+#[derive(Clone, Debug)]
+pub struct RayTraceColorRamp {
+	stops: Vec<(f32, RayTraceColor)>
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RayTraceNoisePattern {
+	// Single octave of noise
+	Noise,
+	// Fractal brownian motion
+	Clouds,
+	Turbulence,
+	// Stripes along the x-axis which are bent by turbulence
+	Marble,
+	// Rings around the y-axis which are bent by noise
+	Wood,
+	// Borders of cellular noise roughened by noise
+	Granite,
+	// Distance to the nearest cellular feature point
+	Cells
+}
+
+// Where the pattern is evaluated, in object space patterns do not stretch with the texture coordinates of the object
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RayTraceNoiseSpace {
+	Texture,
+	Object
+}
+
+// Material with a procedural pattern whose value is mapped to a color by a ramp
+pub struct RayTraceNoiseMaterial {
+	noise: RayTraceNoise,
+	pattern: RayTraceNoisePattern,
+	ramp: RayTraceColorRamp,
+	space: RayTraceNoiseSpace,
+	// Size of a feature of the pattern
+	scale: f64,
+	octaves: f64,
+	// Strength of the noise which bends the stripes, rings and cells
+	distortion: f64,
+	reflectance: f32,
+	glossiness: Option<f32>
+}
+
+#[allow(dead_code)]
+impl RayTraceColorRamp {
+	pub fn new(mut stops: Vec<(f32, RayTraceColor)>) -> Self {
+		stops.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal));
+		Self {
+			stops: stops
+		}
+	}
+
+	pub fn new_with_colors(start: RayTraceColor, end: RayTraceColor) -> Self {
+		Self::new(vec![(0.0, start), (1.0, end)])
+	}
+
+	pub fn get_stops(&self) -> &Vec<(f32, RayTraceColor)> {
+		&self.stops
+	}
+
+	pub fn get_color(&self, position: f32) -> RayTraceColor {
+		if self.stops.is_empty() {
+			return RayTraceColor::white();
+		}
+
+		let first = &self.stops[0];
+		if position <= first.0 {
+			return first.1.clone();
+		}
+
+		for window in self.stops.windows(2) {
+			let (a, b) = (&window[0], &window[1]);
+			if position <= b.0 {
+				let length = b.0 - a.0;
+				let t = if length > 0.0 { (position - a.0) / length } else { 1.0 };
+				return a.1.mix(&b.1, t);
+			}
+		}
+
+		self.stops[self.stops.len() - 1].1.clone()
+	}
+
+	pub fn serialize(&self, writer: &mut RayTraceSceneWriter) -> Result<(), RayTraceSceneWriteError> {
+		let stops = &self.stops;
+		writer.set_child_list("ramp", stops.len(), |i, child| {
+			try!(child.set_f64("position", stops[i].0 as f64));
+			try!(child.set_color("color", &stops[i].1));
+			Ok(())
+		})
+	}
+}
+
+#[allow(dead_code)]
+impl RayTraceNoiseMaterial {
+	// Uses the colors and distortion which suit the pattern
+	pub fn new(pattern: RayTraceNoisePattern) -> Self {
+		let (ramp, distortion) = match pattern {
+			RayTraceNoisePattern::Marble => (RayTraceColorRamp::new(vec![
+				(0.0, RayTraceColor::new_with(0.95, 0.94, 0.9, 1.0)),
+				(0.7, RayTraceColor::new_with(0.8, 0.8, 0.78, 1.0)),
+				(1.0, RayTraceColor::new_with(0.25, 0.25, 0.3, 1.0))
+			]), 5.0),
+			RayTraceNoisePattern::Wood => (RayTraceColorRamp::new(vec![
+				(0.0, RayTraceColor::new_with(0.63, 0.41, 0.2, 1.0)),
+				(0.8, RayTraceColor::new_with(0.45, 0.27, 0.12, 1.0)),
+				(1.0, RayTraceColor::new_with(0.3, 0.17, 0.07, 1.0))
+			]), 0.5),
+			RayTraceNoisePattern::Granite => (RayTraceColorRamp::new(vec![
+				(0.0, RayTraceColor::new_with(0.15, 0.13, 0.13, 1.0)),
+				(0.5, RayTraceColor::new_with(0.6, 0.55, 0.52, 1.0)),
+				(1.0, RayTraceColor::new_with(0.85, 0.75, 0.72, 1.0))
+			]), 0.3),
+			RayTraceNoisePattern::Clouds => (RayTraceColorRamp::new_with_colors(
+				RayTraceColor::new_with(0.3, 0.5, 0.9, 1.0), RayTraceColor::white()), 1.0),
+			_ => (RayTraceColorRamp::new_with_colors(RayTraceColor::black(), RayTraceColor::white()), 1.0)
+		};
+
+		Self::new_with(pattern, ramp, distortion)
+	}
+
+	pub fn new_with(pattern: RayTraceNoisePattern, ramp: RayTraceColorRamp, distortion: f64) -> Self {
+		Self {
+			noise: RayTraceNoise::new(),
+			pattern: pattern,
+			ramp: ramp,
+			space: RayTraceNoiseSpace::Object,
+			scale: 1.0,
+			octaves: 6.0,
+			distortion: distortion,
+			reflectance: 0.0,
+			glossiness: None
+		}
+	}
+
+	pub fn get_pattern(&self) -> RayTraceNoisePattern {
+		self.pattern
+	}
+
+	pub fn get_noise(&self) -> &RayTraceNoise {
+		&self.noise
+	}
+
+	pub fn set_noise(&mut self, seed: u32, basis: RayTraceNoiseBasis) {
+		self.noise = RayTraceNoise::new_with(seed, basis);
+	}
+
+	pub fn get_ramp(&self) -> &RayTraceColorRamp {
+		&self.ramp
+	}
+
+	pub fn set_ramp(&mut self, ramp: RayTraceColorRamp) {
+		self.ramp = ramp;
+	}
+
+	pub fn get_space(&self) -> RayTraceNoiseSpace {
+		self.space
+	}
+
+	pub fn set_space(&mut self, space: RayTraceNoiseSpace) {
+		self.space = space;
+	}
+
+	pub fn get_scale(&self) -> f64 {
+		self.scale
+	}
+
+	pub fn set_scale(&mut self, scale: f64) {
+		self.scale = scale;
+	}
+
+	pub fn get_octaves(&self) -> f64 {
+		self.octaves
+	}
+
+	pub fn set_octaves(&mut self, octaves: f64) {
+		self.octaves = octaves;
+	}
+
+	pub fn get_distortion(&self) -> f64 {
+		self.distortion
+	}
+
+	pub fn set_distortion(&mut self, distortion: f64) {
+		self.distortion = distortion;
+	}
+
+	pub fn get_reflectance(&self) -> f32 {
+		self.reflectance
+	}
+
+	pub fn set_reflectance(&mut self, reflectance: f32) {
+		self.reflectance = reflectance;
+	}
+
+	// Phong exponent of glossy reflections, reflections are sharp if it is not set
+	pub fn get_glossiness(&self) -> Option<f32> {
+		self.glossiness
+	}
+
+	pub fn set_glossiness(&mut self, glossiness: Option<f32>) {
+		self.glossiness = glossiness;
+	}
+
+	// Value of the pattern in the range of 0 to 1 at a position which is not scaled yet
+	pub fn get_value(&self, position: Vector3<f64>) -> f64 {
+		self.get_value_with(position, self.octaves)
+	}
+
+	// Octaves which are left out average to zero, so the pattern keeps its contrast when the octaves are reduced
+	fn get_value_with(&self, position: Vector3<f64>, octaves: f64) -> f64 {
+		let p = [position[0] / self.scale, position[1] / self.scale, position[2] / self.scale];
+		let amplitude = noise_amplitude(self.octaves, GAIN).max(1e-6);
+		let noise = &self.noise;
+
+		let value = match self.pattern {
+			RayTraceNoisePattern::Noise => 0.5 + 0.5 * noise.get(p),
+			RayTraceNoisePattern::Clouds => 0.5 + 0.5 * noise.fbm(p, octaves, LACUNARITY, GAIN) / amplitude,
+			RayTraceNoisePattern::Turbulence => noise.turbulence(p, octaves, LACUNARITY, GAIN) / amplitude,
+			RayTraceNoisePattern::Marble => {
+				let turbulence = noise.turbulence(p, octaves, LACUNARITY, GAIN) / amplitude;
+				0.5 + 0.5 * ((p[0] + self.distortion * turbulence) * PI).sin()
+			},
+			RayTraceNoisePattern::Wood => {
+				let radius = (p[0] * p[0] + p[2] * p[2]).sqrt() +
+					self.distortion * noise.fbm(p, octaves, LACUNARITY, GAIN) / amplitude;
+				radius - radius.floor()
+			},
+			RayTraceNoisePattern::Granite => {
+				let cells = noise.worley(p);
+				cells[1] - cells[0] + self.distortion * noise.fbm(p, octaves, LACUNARITY, GAIN) / amplitude
+			},
+			RayTraceNoisePattern::Cells => noise.worley(p)[0]
+		};
+
+		value.max(0.0).min(1.0)
+	}
+
+	// Octaves which change faster than the pixels are left out, only the footprint in texture space is known
+	fn get_filtered_octaves(&self, footprint: Option<&RayTraceFootprint>) -> f64 {
+		match (footprint, self.space) {
+			(Some(footprint), RayTraceNoiseSpace::Texture) => {
+				let width = footprint.get_width();
+				let width = width[0].max(width[1]) / self.scale;
+				if width <= 0.0 {
+					self.octaves
+				} else {
+					// The octave with a frequency of 2^n changes twice per pixel
+					(-width.log2() - 1.0).max(0.0).min(self.octaves)
+				}
+			},
+			_ => self.octaves
+		}
+	}
+
+	fn get_hit_at(&self, position: Vector3<f64>, octaves: f64) -> RayTraceMaterialHit {
+		let color = self.ramp.get_color(self.get_value_with(position, octaves) as f32);
+		let mut hit = RayTraceMaterialHit::new_with(color, self.reflectance, 1.0 - self.reflectance, 1.0, 100.0);
+		hit.set_glossiness(self.glossiness);
+		hit
+	}
+}
+
+impl RayTraceMaterial for RayTraceNoiseMaterial {
+	fn get_hit(&self, x: f64, y: f64) -> RayTraceMaterialHit {
+		self.get_hit_at([x, y, 0.0], self.octaves)
+	}
+
+	fn get_hit_context(&self, context: &RayTraceShadingContext) -> RayTraceMaterialHit {
+		match self.space {
+			RayTraceNoiseSpace::Texture => {
+				let texcoord = context.get_texcoord();
+				self.get_hit_at([texcoord[0], texcoord[1], 0.0], self.get_filtered_octaves(context.get_footprint()))
+			},
+			RayTraceNoiseSpace::Object => self.get_hit_at(*context.get_object_position(), self.octaves)
+		}
+	}
+
+	fn serialize(&self, writer: &mut RayTraceSceneWriter) -> Result<(), RayTraceSceneWriteError> {
+		writer.set_type("noise");
+		writer.set_str("pattern", match self.pattern {
+			RayTraceNoisePattern::Noise => "noise",
+			RayTraceNoisePattern::Clouds => "clouds",
+			RayTraceNoisePattern::Turbulence => "turbulence",
+			RayTraceNoisePattern::Marble => "marble",
+			RayTraceNoisePattern::Wood => "wood",
+			RayTraceNoisePattern::Granite => "granite",
+			RayTraceNoisePattern::Cells => "cells"
+		});
+		writer.set_str("basis", match self.noise.get_basis() {
+			RayTraceNoiseBasis::Perlin => "perlin",
+			RayTraceNoiseBasis::Simplex => "simplex"
+		});
+		writer.set_usize("seed", self.noise.get_seed() as usize);
+		writer.set_str("space", match self.space {
+			RayTraceNoiseSpace::Texture => "texture",
+			RayTraceNoiseSpace::Object => "object"
+		});
+		try!(writer.set_f64("scale", self.scale));
+		try!(writer.set_f64("octaves", self.octaves));
+		try!(writer.set_f64("distortion", self.distortion));
+		try!(self.ramp.serialize(writer));
+		try!(writer.set_f64("reflectance", self.reflectance as f64));
+		if let Some(glossiness) = self.glossiness {
+			try!(writer.set_f64("glossiness", glossiness as f64));
+		}
+		Ok(())
+	}
+}
@@ -3,38 +3,87 @@ use color::RayTraceColor;
 use hit::RayTraceMaterialHit;
 
 use material::RayTraceMaterial;
+use scene_file::RayTraceSceneWriteError;
+use scene_file::RayTraceSceneWriter;
 
 pub struct RayTraceSimpleMaterial {
 	color: RayTraceColor,
-	reflectance: f32
+	reflectance: f32,
+	glossiness: Option<f32>,
+	emission: RayTraceColor
 }
 
 impl RayTraceSimpleMaterial {
 	pub fn new(color: RayTraceColor) -> Self {
 		Self {
 			color: color,
-			reflectance: 0.0
+			reflectance: 0.0,
+			glossiness: None,
+			emission: RayTraceColor::black()
 		}
 	}
 
 	pub fn new_with_color(r: f32, g: f32, b: f32, a: f32) -> Self {
 		Self {
 			color: RayTraceColor::new_with(r, g, b, a),
-			reflectance: 0.0
+			reflectance: 0.0,
+			glossiness: None,
+			emission: RayTraceColor::black()
 		}
 	}
 
 	pub fn new_with(color: RayTraceColor, reflectance: f32) -> Self {
 		Self {
 			color: color,
-			reflectance: reflectance
+			reflectance: reflectance,
+			glossiness: None,
+			emission: RayTraceColor::black()
 		}
 	}
+
+	// Light emitted by the surface, colors brighter than white are allowed
+	pub fn get_emission(&self) -> &RayTraceColor {
+		&self.emission
+	}
+
+	pub fn set_emission(&mut self, emission: RayTraceColor) {
+		self.emission = emission;
+	}
+
+	// Phong exponent of glossy reflections, reflections are sharp if it is not set
+	pub fn get_glossiness(&self) -> Option<f32> {
+		self.glossiness
+	}
+
+	pub fn set_glossiness(&mut self, glossiness: Option<f32>) {
+		self.glossiness = glossiness;
+	}
 }
 
 #[allow(unused_variables)]
 impl RayTraceMaterial for RayTraceSimpleMaterial {
 	fn get_hit(&self, x: f64, y: f64) -> RayTraceMaterialHit {
-		RayTraceMaterialHit::new_with(self.color.clone(), self.reflectance, 1.0 - self.reflectance, 1.0, 100.0)
+		let mut hit = RayTraceMaterialHit::new_with(self.color.clone(), self.reflectance, 1.0 - self.reflectance, 1.0,
+			100.0);
+		hit.set_glossiness(self.glossiness);
+		hit.set_emission(self.emission.clone());
+		hit
+	}
+
+	fn is_emissive(&self) -> bool {
+		self.emission.get_r() > 0.0 || self.emission.get_g() > 0.0 || self.emission.get_b() > 0.0
+	}
+
+	fn serialize(&self, writer: &mut RayTraceSceneWriter) -> Result<(), RayTraceSceneWriteError> {
+		writer.set_type("simple");
+		try!(writer.set_color("color", &self.color));
+		try!(writer.set_f64("reflectance", self.reflectance as f64));
+		if let Some(glossiness) = self.glossiness {
+			try!(writer.set_f64("glossiness", glossiness as f64));
+		}
+		if self.is_emissive() {
+			try!(writer.set_color("emission", &self.emission));
+		}
+		Ok(())
 	}
 }
\ No newline at end of file
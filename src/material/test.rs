@@ -1,13 +1,17 @@
 use color::RayTraceColor;
 
 use hit::RayTraceMaterialHit;
+use hit::RayTraceShadingContext;
 
 use material::RayTraceMaterial;
+use scene_file::RayTraceSceneWriteError;
+use scene_file::RayTraceSceneWriter;
 
 pub struct RayTraceCheckerboardMaterial {
 	colors: [RayTraceColor; 2],
 	scale: [f64; 2],
-	reflectance: f32
+	reflectance: f32,
+	glossiness: Option<f32>
 }
 
 impl RayTraceCheckerboardMaterial {
@@ -15,7 +19,8 @@ impl RayTraceCheckerboardMaterial {
 		Self {
 			colors: [RayTraceColor::black(), RayTraceColor::white()],
 			scale: [1.0, 1.0],
-			reflectance: 0.0
+			reflectance: 0.0,
+			glossiness: None
 		}
 	}
 
@@ -23,7 +28,8 @@ impl RayTraceCheckerboardMaterial {
 		Self {
 			colors: colors,
 			scale: [1.0, 1.0],
-			reflectance: 0.0
+			reflectance: 0.0,
+			glossiness: None
 		}
 	}
 
@@ -31,7 +37,8 @@ impl RayTraceCheckerboardMaterial {
 		Self {
 			colors: colors,
 			scale: scale,
-			reflectance: reflectance
+			reflectance: reflectance,
+			glossiness: None
 		}
 	}
 
@@ -39,7 +46,8 @@ impl RayTraceCheckerboardMaterial {
 		Self {
 			colors: [RayTraceColor::black(), RayTraceColor::white()],
 			scale: scale,
-			reflectance: reflectance
+			reflectance: reflectance,
+			glossiness: None
 		}
 	}
 
@@ -47,9 +55,19 @@ impl RayTraceCheckerboardMaterial {
 		Self {
 			colors: [RayTraceColor::black(), RayTraceColor::white()],
 			scale: [1.0, 1.0],
-			reflectance: reflectance
+			reflectance: reflectance,
+			glossiness: None
 		}
 	}
+
+	// Phong exponent of glossy reflections, reflections are sharp if it is not set
+	pub fn get_glossiness(&self) -> Option<f32> {
+		self.glossiness
+	}
+
+	pub fn set_glossiness(&mut self, glossiness: Option<f32>) {
+		self.glossiness = glossiness;
+	}
 }
 
 #[allow(unused_variables)]
@@ -58,7 +76,56 @@ impl RayTraceMaterial for RayTraceCheckerboardMaterial {
 		let x_scaled = (x / self.scale[0]) as i64 + (if x >= 0.0 {0} else {1});
 		let y_scaled = (y / self.scale[1]) as i64 + (if y >= 0.0 {0} else {1});
 
-		RayTraceMaterialHit::new_with(self.colors[(x_scaled + y_scaled) as usize & 0x01].clone(), self.reflectance,
-			1.0, 0.5, 100.0)
+		let mut hit = RayTraceMaterialHit::new_with(self.colors[(x_scaled + y_scaled) as usize & 0x01].clone(),
+			self.reflectance, 1.0, 0.5, 100.0);
+		hit.set_glossiness(self.glossiness);
+		hit
+	}
+
+	// Box filters the squares over the width of the footprint, see "Advanced RenderMan" by Apodaca and Gritz
+	fn get_hit_context(&self, context: &RayTraceShadingContext) -> RayTraceMaterialHit {
+		let (x, y) = (context.get_texcoord()[0], context.get_texcoord()[1]);
+		let width = match context.get_footprint() {
+			Some(footprint) => footprint.get_width(),
+			None => { return self.get_hit(x, y); }
+		};
+
+		// Half of the filter width measured in squares
+		let ds = width[0] / self.scale[0] / 2.0;
+		let dt = width[1] / self.scale[1] / 2.0;
+		if ds < 1e-6 || dt < 1e-6 {
+			return self.get_hit(x, y);
+		}
+
+		let area = if ds >= 1.0 || dt >= 1.0 {
+			0.5
+		} else {
+			let (s, t) = (x / self.scale[0], y / self.scale[1]);
+			let s_odd = (odd_integral(s + ds) - odd_integral(s - ds)) / (2.0 * ds);
+			let t_odd = (odd_integral(t + dt) - odd_integral(t - dt)) / (2.0 * dt);
+			s_odd + t_odd - 2.0 * s_odd * t_odd
+		};
+
+		let mut hit = RayTraceMaterialHit::new_with(self.colors[0].mix(&self.colors[1], area as f32),
+			self.reflectance, 1.0, 0.5, 100.0);
+		hit.set_glossiness(self.glossiness);
+		hit
+	}
+
+	fn serialize(&self, writer: &mut RayTraceSceneWriter) -> Result<(), RayTraceSceneWriteError> {
+		writer.set_type("checkerboard");
+		writer.set_color_list("colors", &self.colors);
+		writer.set_f64_list("scale", &self.scale);
+		try!(writer.set_f64("reflectance", self.reflectance as f64));
+		if let Some(glossiness) = self.glossiness {
+			try!(writer.set_f64("glossiness", glossiness as f64));
+		}
+		Ok(())
 	}
-}
\ No newline at end of file
+}
+
+// Integral from 0 to x of the function which is 1 on odd squares and 0 on even ones
+fn odd_integral(x: f64) -> f64 {
+	let half = x / 2.0;
+	half.floor() + 2.0 * (half - half.floor() - 0.5).max(0.0)
+}
@@ -0,0 +1,536 @@
+use std::io::Error as IOError;
+use std::io::ErrorKind;
+
+use color::RayTraceColor;
+
+use hit::RayTraceMaterialHit;
+use hit::RayTraceShadingContext;
+
+use material::RayTraceFootprint;
+use material::RayTraceMaterial;
+use scene_file::RayTraceSceneWriteError;
+use scene_file::RayTraceSceneWriter;
+
+use sink::image;
+use sink::image::RgbaImage;
+
+// Longest ratio of the axes of the elliptical footprint
+const MAX_ANISOTROPY: f64 = 8.0;
+// Falloff of the gaussian weights of the elliptical footprint
+const EWA_ALPHA: f64 = 2.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RayTraceTextureFilter {
+	Nearest,
+	Bilinear,
+	// Catmull-Rom spline through the 4x4 nearest texels
+	Bicubic
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RayTraceTextureWrap {
+	Repeat,
+	Clamp,
+	Mirror
+}
+
+// Prefiltering of textures which are minified by the footprint of the pixel
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RayTraceTextureMipmap {
+	None,
+	// Blend of the two levels matching the longer side of the footprint
+	Trilinear,
+	// Elliptical weighted average along the footprint, see "Physically Based Rendering" by Pharr et al.
+	Anisotropic
+}
+
+// Image sampled with texture coordinates starting at the bottom left corner, the coordinates are scaled and then
+// offset before sampling
+pub struct RayTraceTexture {
+	image: RgbaImage,
+	// Linear colors of the texels row by row starting at the top, every level has half the size of the previous one
+	levels: Vec<MipLevel>,
+	srgb: bool,
+	filter: RayTraceTextureFilter,
+	wrap: RayTraceTextureWrap,
+	mipmap: RayTraceTextureMipmap,
+	scale: [f64; 2],
+	offset: [f64; 2]
+}
+
+struct MipLevel {
+	width: u32,
+	height: u32,
+	texels: Vec<[f32; 4]>
+}
+
+// Material whose color is taken from a texture
+pub struct RayTraceTextureMaterial {
+	texture: RayTraceTexture,
+	reflectance: f32,
+	glossiness: Option<f32>
+}
+
+#[allow(dead_code)]
+impl RayTraceTexture {
+	// Color textures are stored in sRGB and are converted to linear colors
+	pub fn new(image: RgbaImage) -> Self {
+		Self::new_with(image, true)
+	}
+
+	// Data textures like normal maps are used without conversion
+	pub fn new_linear(image: RgbaImage) -> Self {
+		Self::new_with(image, false)
+	}
+
+	pub fn new_with(image: RgbaImage, srgb: bool) -> Self {
+		let texels = image.pixels().map(|p| {
+			let d = p.data;
+			if srgb {
+				[srgb_to_linear(d[0]), srgb_to_linear(d[1]), srgb_to_linear(d[2]), d[3] as f32 / 255.0]
+			} else {
+				[d[0] as f32 / 255.0, d[1] as f32 / 255.0, d[2] as f32 / 255.0, d[3] as f32 / 255.0]
+			}
+		}).collect();
+
+		let (width, height) = image.dimensions();
+		let mut levels = vec![MipLevel::new(width, height, texels)];
+		loop {
+			let next = match levels.last() {
+				Some(last) if last.width > 1 || last.height > 1 => last.downsample(),
+				_ => { break; }
+			};
+			levels.push(next);
+		}
+
+		Self {
+			image: image,
+			levels: levels,
+			srgb: srgb,
+			filter: RayTraceTextureFilter::Bilinear,
+			wrap: RayTraceTextureWrap::Repeat,
+			mipmap: RayTraceTextureMipmap::Trilinear,
+			scale: [1.0, 1.0],
+			offset: [0.0, 0.0]
+		}
+	}
+
+	// Loads a PNG or JPEG file as color texture
+	pub fn load(file_name: &str) -> Result<Self, IOError> {
+		match image::open(file_name) {
+			Ok(image) => Ok(Self::new(image.to_rgba())),
+			Err(err) => Err(IOError::new(ErrorKind::InvalidData, format!("Could not load {}: {:?}", file_name, err)))
+		}
+	}
+
+	pub fn get_image(&self) -> &RgbaImage {
+		&self.image
+	}
+
+	pub fn is_srgb(&self) -> bool {
+		self.srgb
+	}
+
+	pub fn get_filter(&self) -> RayTraceTextureFilter {
+		self.filter
+	}
+
+	pub fn set_filter(&mut self, filter: RayTraceTextureFilter) {
+		self.filter = filter;
+	}
+
+	pub fn get_wrap(&self) -> RayTraceTextureWrap {
+		self.wrap
+	}
+
+	pub fn set_wrap(&mut self, wrap: RayTraceTextureWrap) {
+		self.wrap = wrap;
+	}
+
+	pub fn get_mipmap(&self) -> RayTraceTextureMipmap {
+		self.mipmap
+	}
+
+	pub fn set_mipmap(&mut self, mipmap: RayTraceTextureMipmap) {
+		self.mipmap = mipmap;
+	}
+
+	// Number of prefiltered levels including the full size image
+	pub fn get_level_count(&self) -> usize {
+		self.levels.len()
+	}
+
+	pub fn get_scale(&self) -> [f64; 2] {
+		self.scale
+	}
+
+	pub fn set_scale(&mut self, scale: [f64; 2]) {
+		self.scale = scale;
+	}
+
+	pub fn get_offset(&self) -> [f64; 2] {
+		self.offset
+	}
+
+	pub fn set_offset(&mut self, offset: [f64; 2]) {
+		self.offset = offset;
+	}
+
+	pub fn sample(&self, x: f64, y: f64) -> RayTraceColor {
+		let (width, height) = self.image.dimensions();
+		if width == 0 || height == 0 {
+			return RayTraceColor::white();
+		}
+
+		let (u, v) = self.get_texture_position(x, y);
+		to_color(self.sample_level(0, u, v))
+	}
+
+	// Averages the texels covered by the footprint of the pixel, without a footprint this is the same as sample
+	pub fn sample_filtered(&self, x: f64, y: f64, footprint: Option<&RayTraceFootprint>) -> RayTraceColor {
+		let (width, height) = self.image.dimensions();
+		let footprint = match (footprint, self.mipmap) {
+			(Some(footprint), RayTraceTextureMipmap::Trilinear) |
+			(Some(footprint), RayTraceTextureMipmap::Anisotropic) if width > 0 && height > 0 => footprint,
+			_ => { return self.sample(x, y); }
+		};
+
+		// Footprint in texels of the full size image, rows are counted from the top
+		let (dx, dy) = (footprint.get_dx(), footprint.get_dy());
+		let axes = [
+			[dx[0] * self.scale[0] * width as f64, -dx[1] * self.scale[1] * height as f64],
+			[dy[0] * self.scale[0] * width as f64, -dy[1] * self.scale[1] * height as f64]
+		];
+		let (u, v) = self.get_texture_position(x, y);
+
+		let c = match self.mipmap {
+			RayTraceTextureMipmap::Anisotropic => self.sample_ewa(u, v, axes),
+			_ => {
+				let length = vec2_length(axes[0]).max(vec2_length(axes[1]));
+				let level = self.get_level(length);
+				let lower = level.floor() as usize;
+				if lower + 1 >= self.levels.len() {
+					self.sample_level(lower, u, v)
+				} else {
+					let t = (level - lower as f64) as f32;
+					lerp(self.sample_level(lower, u, v), self.sample_level(lower + 1, u, v), t)
+				}
+			}
+		};
+
+		to_color(c)
+	}
+
+	// Writes the settings of the texture into the object of the material
+	pub fn serialize(&self, writer: &mut RayTraceSceneWriter) -> Result<(), RayTraceSceneWriteError> {
+		try!(writer.set_image("file", &self.image));
+		writer.set_bool("srgb", self.srgb);
+		writer.set_str("filter", match self.filter {
+			RayTraceTextureFilter::Nearest => "nearest",
+			RayTraceTextureFilter::Bilinear => "bilinear",
+			RayTraceTextureFilter::Bicubic => "bicubic"
+		});
+		writer.set_str("wrap", match self.wrap {
+			RayTraceTextureWrap::Repeat => "repeat",
+			RayTraceTextureWrap::Clamp => "clamp",
+			RayTraceTextureWrap::Mirror => "mirror"
+		});
+		writer.set_str("mipmap", match self.mipmap {
+			RayTraceTextureMipmap::None => "none",
+			RayTraceTextureMipmap::Trilinear => "trilinear",
+			RayTraceTextureMipmap::Anisotropic => "anisotropic"
+		});
+		writer.set_f64_list("scale", &self.scale);
+		writer.set_f64_list("offset", &self.offset);
+		Ok(())
+	}
+
+	// Position in the range of 0 to 1 on the image from the left and the top
+	fn get_texture_position(&self, x: f64, y: f64) -> (f64, f64) {
+		(x * self.scale[0] + self.offset[0], 1.0 - (y * self.scale[1] + self.offset[1]))
+	}
+
+	// Fractional level whose texels have the given size in texels of the full size image
+	fn get_level(&self, size: f64) -> f64 {
+		if size <= 1.0 {
+			0.0
+		} else {
+			size.log2().min((self.levels.len() - 1) as f64)
+		}
+	}
+
+	fn sample_level(&self, level: usize, u: f64, v: f64) -> [f32; 4] {
+		let mip = &self.levels[level];
+
+		// Texel centers are at half coordinates
+		let u = u * mip.width as f64 - 0.5;
+		let v = v * mip.height as f64 - 0.5;
+
+		match self.filter {
+			RayTraceTextureFilter::Nearest => self.get_texel(mip, u.round() as i64, v.round() as i64),
+			RayTraceTextureFilter::Bilinear => {
+				let (x0, y0) = (u.floor(), v.floor());
+				let (fx, fy) = ((u - x0) as f32, (v - y0) as f32);
+				let (x0, y0) = (x0 as i64, y0 as i64);
+
+				let top = lerp(self.get_texel(mip, x0, y0), self.get_texel(mip, x0 + 1, y0), fx);
+				let bottom = lerp(self.get_texel(mip, x0, y0 + 1), self.get_texel(mip, x0 + 1, y0 + 1), fx);
+				lerp(top, bottom, fy)
+			},
+			RayTraceTextureFilter::Bicubic => {
+				let (x0, y0) = (u.floor(), v.floor());
+				let wx = catmull_rom_weights((u - x0) as f32);
+				let wy = catmull_rom_weights((v - y0) as f32);
+				let (x0, y0) = (x0 as i64, y0 as i64);
+
+				let mut c = [0.0; 4];
+				for j in 0..4 {
+					for i in 0..4 {
+						let t = self.get_texel(mip, x0 + i as i64 - 1, y0 + j as i64 - 1);
+						let w = wx[i] * wy[j];
+						for k in 0..4 {
+							c[k] += t[k] * w;
+						}
+					}
+				}
+
+				// The spline overshoots at hard edges
+				[c[0].max(0.0), c[1].max(0.0), c[2].max(0.0), c[3].max(0.0).min(1.0)]
+			}
+		}
+	}
+
+	// The axes of the footprint are the two conjugate radii of the ellipse, both in texels of the full size image
+	fn sample_ewa(&self, u: f64, v: f64, axes: [[f64; 2]; 2]) -> [f32; 4] {
+		let (mut major, mut minor) = if vec2_length(axes[0]) >= vec2_length(axes[1]) {
+			(axes[0], axes[1])
+		} else {
+			(axes[1], axes[0])
+		};
+
+		// Very eccentric ellipses are made rounder to bound the number of texels which are read
+		let (major_length, minor_length) = (vec2_length(major), vec2_length(minor));
+		if minor_length * MAX_ANISOTROPY < major_length {
+			if minor_length > 0.0 {
+				let scale = major_length / (minor_length * MAX_ANISOTROPY);
+				minor = [minor[0] * scale, minor[1] * scale];
+			} else {
+				// Degenerated footprint, the minor axis is perpendicular to the major axis
+				let scale = 1.0 / MAX_ANISOTROPY;
+				minor = [-major[1] * scale, major[0] * scale];
+			}
+		}
+		if major_length == 0.0 {
+			major = [1.0, 0.0];
+			minor = [0.0, 1.0];
+		}
+
+		let level = self.get_level(vec2_length(minor));
+		let lower = level.floor() as usize;
+		let c = self.sample_ewa_level(lower, u, v, major, minor);
+		if lower + 1 >= self.levels.len() {
+			c
+		} else {
+			lerp(c, self.sample_ewa_level(lower + 1, u, v, major, minor), (level - lower as f64) as f32)
+		}
+	}
+
+	fn sample_ewa_level(&self, level: usize, u: f64, v: f64, major: [f64; 2], minor: [f64; 2]) -> [f32; 4] {
+		let mip = &self.levels[level];
+		let sx = mip.width as f64 / self.levels[0].width as f64;
+		let sy = mip.height as f64 / self.levels[0].height as f64;
+
+		// Axes in texels of the level
+		let s = u * mip.width as f64 - 0.5;
+		let t = v * mip.height as f64 - 0.5;
+		let (ds0, dt0) = (major[0] * sx, major[1] * sy);
+		let (ds1, dt1) = (minor[0] * sx, minor[1] * sy);
+
+		// Implicit equation A s^2 + B s t + C t^2 = 1 of the ellipse, widened by a texel to always cover a texel
+		let a = dt0 * dt0 + dt1 * dt1 + 1.0;
+		let b = -2.0 * (ds0 * dt0 + ds1 * dt1);
+		let c = ds0 * ds0 + ds1 * ds1 + 1.0;
+		let f = a * c - b * b / 4.0;
+		let (a, b, c) = (a / f, b / f, c / f);
+
+		let det = -b * b + 4.0 * a * c;
+		let (s_size, t_size) = (2.0 * (det * c).sqrt() / det, 2.0 * (a * det).sqrt() / det);
+		let (s0, s1) = ((s - s_size).ceil() as i64, (s + s_size).floor() as i64);
+		let (t0, t1) = ((t - t_size).ceil() as i64, (t + t_size).floor() as i64);
+
+		let mut sum = [0.0; 4];
+		let mut weight_sum = 0.0;
+		for y in t0..(t1 + 1) {
+			let tt = y as f64 - t;
+			for x in s0..(s1 + 1) {
+				let ss = x as f64 - s;
+				let r2 = a * ss * ss + b * ss * tt + c * tt * tt;
+				if r2 < 1.0 {
+					let weight = ((-EWA_ALPHA * r2).exp() - (-EWA_ALPHA).exp()) as f32;
+					let texel = self.get_texel(mip, x, y);
+					for k in 0..4 {
+						sum[k] += texel[k] * weight;
+					}
+					weight_sum += weight;
+				}
+			}
+		}
+
+		if weight_sum <= 0.0 {
+			return self.get_texel(mip, s.round() as i64, t.round() as i64);
+		}
+
+		[sum[0] / weight_sum, sum[1] / weight_sum, sum[2] / weight_sum, sum[3] / weight_sum]
+	}
+
+	fn get_texel(&self, mip: &MipLevel, x: i64, y: i64) -> [f32; 4] {
+		let x = wrap_index(x, mip.width as i64, self.wrap);
+		let y = wrap_index(y, mip.height as i64, self.wrap);
+		mip.texels[(y * mip.width as i64 + x) as usize]
+	}
+}
+
+impl MipLevel {
+	fn new(width: u32, height: u32, texels: Vec<[f32; 4]>) -> Self {
+		Self {
+			width: width,
+			height: height,
+			texels: texels
+		}
+	}
+
+	// Averages blocks of 2x2 texels, the last row or column of odd sizes is used twice
+	fn downsample(&self) -> Self {
+		let width = (self.width / 2).max(1);
+		let height = (self.height / 2).max(1);
+
+		let mut texels = Vec::with_capacity((width * height) as usize);
+		for y in 0..height {
+			for x in 0..width {
+				let mut c = [0.0; 4];
+				for &(i, j) in [(0, 0), (1, 0), (0, 1), (1, 1)].iter() {
+					let sx = (2 * x + i).min(self.width - 1);
+					let sy = (2 * y + j).min(self.height - 1);
+					let t = self.texels[(sy * self.width + sx) as usize];
+					for k in 0..4 {
+						c[k] += t[k] * 0.25;
+					}
+				}
+				texels.push(c);
+			}
+		}
+
+		Self::new(width, height, texels)
+	}
+}
+
+#[allow(dead_code)]
+impl RayTraceTextureMaterial {
+	pub fn new(texture: RayTraceTexture) -> Self {
+		Self::new_with(texture, 0.0)
+	}
+
+	pub fn new_with(texture: RayTraceTexture, reflectance: f32) -> Self {
+		Self {
+			texture: texture,
+			reflectance: reflectance,
+			glossiness: None
+		}
+	}
+
+	pub fn get_texture(&self) -> &RayTraceTexture {
+		&self.texture
+	}
+
+	pub fn get_texture_mut(&mut self) -> &mut RayTraceTexture {
+		&mut self.texture
+	}
+
+	pub fn get_reflectance(&self) -> f32 {
+		self.reflectance
+	}
+
+	pub fn set_reflectance(&mut self, reflectance: f32) {
+		self.reflectance = reflectance;
+	}
+
+	// Phong exponent of glossy reflections, reflections are sharp if it is not set
+	pub fn get_glossiness(&self) -> Option<f32> {
+		self.glossiness
+	}
+
+	pub fn set_glossiness(&mut self, glossiness: Option<f32>) {
+		self.glossiness = glossiness;
+	}
+
+	fn get_hit_with(&self, color: RayTraceColor) -> RayTraceMaterialHit {
+		let mut hit = RayTraceMaterialHit::new_with(color, self.reflectance, 1.0 - self.reflectance, 1.0, 100.0);
+		hit.set_glossiness(self.glossiness);
+		hit
+	}
+}
+
+impl RayTraceMaterial for RayTraceTextureMaterial {
+	fn get_hit(&self, x: f64, y: f64) -> RayTraceMaterialHit {
+		self.get_hit_with(self.texture.sample(x, y))
+	}
+
+	fn get_hit_context(&self, context: &RayTraceShadingContext) -> RayTraceMaterialHit {
+		let texcoord = context.get_texcoord();
+		self.get_hit_with(self.texture.sample_filtered(texcoord[0], texcoord[1], context.get_footprint()))
+	}
+
+	fn serialize(&self, writer: &mut RayTraceSceneWriter) -> Result<(), RayTraceSceneWriteError> {
+		writer.set_type("texture");
+		try!(self.texture.serialize(writer));
+		try!(writer.set_f64("reflectance", self.reflectance as f64));
+		if let Some(glossiness) = self.glossiness {
+			try!(writer.set_f64("glossiness", glossiness as f64));
+		}
+		Ok(())
+	}
+}
+
+fn wrap_index(index: i64, size: i64, wrap: RayTraceTextureWrap) -> i64 {
+	match wrap {
+		RayTraceTextureWrap::Repeat => ((index % size) + size) % size,
+		RayTraceTextureWrap::Clamp => index.max(0).min(size - 1),
+		RayTraceTextureWrap::Mirror => {
+			let period = ((index % (2 * size)) + 2 * size) % (2 * size);
+			if period < size { period } else { 2 * size - 1 - period }
+		}
+	}
+}
+
+fn srgb_to_linear(value: u8) -> f32 {
+	let c = value as f32 / 255.0;
+	if c <= 0.04045 {
+		c / 12.92
+	} else {
+		((c + 0.055) / 1.055).powf(2.4)
+	}
+}
+
+fn to_color(c: [f32; 4]) -> RayTraceColor {
+	RayTraceColor::new_with(c[0], c[1], c[2], c[3])
+}
+
+fn vec2_length(v: [f64; 2]) -> f64 {
+	(v[0] * v[0] + v[1] * v[1]).sqrt()
+}
+
+fn lerp(a: [f32; 4], b: [f32; 4], t: f32) -> [f32; 4] {
+	[a[0] + (b[0] - a[0]) * t, a[1] + (b[1] - a[1]) * t, a[2] + (b[2] - a[2]) * t, a[3] + (b[3] - a[3]) * t]
+}
+
+// Weights of the texels at -1, 0, 1 and 2 relative to the sample position
+fn catmull_rom_weights(t: f32) -> [f32; 4] {
+	let t2 = t * t;
+	let t3 = t2 * t;
+	[
+		0.5 * (-t3 + 2.0 * t2 - t),
+		0.5 * (3.0 * t3 - 5.0 * t2 + 2.0),
+		0.5 * (-3.0 * t3 + 4.0 * t2 + t),
+		0.5 * (t3 - t2)
+	]
+}
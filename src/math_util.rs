@@ -1,9 +1,13 @@
-use vecmath::{Matrix3, Vector3};
-use vecmath::{vec3_sub, vec3_scale, vec3_dot};
-use vecmath::{mat3_id, row_mat3_mul};
+use vecmath::{Matrix3, Matrix4, Vector2, Vector3};
+use vecmath::{vec3_add, vec3_sub, vec3_scale, vec3_dot, vec3_normalized};
+use vecmath::{mat3_id, row_mat3_mul, row_mat3_transform};
+use vecmath::{mat4_id, mat4_inv, row_mat4_mul, row_mat4_transform};
 use vecmath::{vec4_sub, vec4_scale};
 
+use aabb::AABB;
+use anim::RayTraceAnimation;
 use ray::RayTraceRay;
+use ray::RayTraceRayDifferentials;
 
 pub const PI: f64 = 3.14159265359;
 pub const TWO_PI: f64 = 2.0 * PI;
@@ -137,8 +141,480 @@ pub fn compute_plane_hit(ray: &RayTraceRay, center: Vector3<f64>, vec1: Vector3<
 	return Some((mat[0][3] / mat[0][0], mat[1][3] / mat[1][1], mat[2][3] / mat[2][2]));
 }
 
+// The neighbouring rays are reflected where they hit the tangent plane at the hit position
 pub fn compute_reflected_ray(n: Vector3<f64>, ray: &RayTraceRay, distance: f64) -> RayTraceRay {
-	let d = ray.get_direction().clone();
-	let r = vec3_sub(d, vec3_scale(n, 2.0 * vec3_dot(d, n)));
-	return RayTraceRay::new(ray.get_position_on_ray(distance - 1e-10), r);
+	let reflect = |d: Vector3<f64>| vec3_sub(d, vec3_scale(n, 2.0 * vec3_dot(d, n)));
+	let position = ray.get_position_on_ray(distance - 1e-10);
+	let mut reflected_ray = RayTraceRay::new(position, reflect(*ray.get_direction()));
+
+	if let (Some(d), Some(footprint)) = (ray.get_differentials(), ray.get_footprint(position, n)) {
+		let x = RayTraceRay::new(vec3_add(position, footprint[0]), reflect(*d.get_x_ray().get_direction()));
+		let y = RayTraceRay::new(vec3_add(position, footprint[1]), reflect(*d.get_y_ray().get_direction()));
+		reflected_ray.set_differentials(Some(RayTraceRayDifferentials::new(&x, &y)));
+	}
+
+	reflected_ray
+}
+
+// Direction distributed like the phong lobe cos(angle)^exponent around the normalized axis for two uniform random
+// numbers, the lobe is normalized so samples need no weighting
+pub fn sample_phong_lobe(axis: Vector3<f64>, exponent: f64, u: Vector2<f64>) -> Vector3<f64> {
+	let cos_theta = u[0].powf(1.0 / (exponent + 1.0));
+	let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+	let phi = TWO_PI * u[1];
+
+	let frame = compute_tangent_frame(axis);
+	vec3_add(vec3_add(vec3_scale(frame[0], sin_theta * phi.cos()), vec3_scale(frame[1], sin_theta * phi.sin())),
+		vec3_scale(axis, cos_theta))
+}
+
+// Any orthonormal tangent and bitangent for a normalized normal, see "Building an Orthonormal Basis, Revisited"
+// by Duff et al.
+pub fn compute_tangent_frame(n: Vector3<f64>) -> [Vector3<f64>; 2] {
+	let sign = if n[2] >= 0.0 { 1.0 } else { -1.0 };
+	let a = -1.0 / (sign + n[2]);
+	let b = n[0] * n[1] * a;
+	[
+		[1.0 + sign * n[0] * n[0] * a, sign * b, -sign * n[0]],
+		[b, sign + n[1] * n[1] * a, -n[1]]
+	]
+}
+
+// Directions in which the texture coordinates of a triangle grow, None if the texture coordinates are degenerated
+pub fn compute_triangle_tangents(vertices: &[Vector3<f64>; 3], texcoords: &[Vector2<f64>; 3])
+		-> Option<[Vector3<f64>; 2]> {
+	let (e1, e2) = (vec3_sub(vertices[1], vertices[0]), vec3_sub(vertices[2], vertices[0]));
+	let (du1, dv1) = (texcoords[1][0] - texcoords[0][0], texcoords[1][1] - texcoords[0][1]);
+	let (du2, dv2) = (texcoords[2][0] - texcoords[0][0], texcoords[2][1] - texcoords[0][1]);
+
+	let det = du1 * dv2 - du2 * dv1;
+	if det.abs() < 1e-20 {
+		return None;
+	}
+
+	let r = 1.0 / det;
+	Some([
+		vec3_scale(vec3_sub(vec3_scale(e1, dv2), vec3_scale(e2, dv1)), r),
+		vec3_scale(vec3_sub(vec3_scale(e2, du1), vec3_scale(e1, du2)), r)
+	])
+}
+
+// Splits a simple polygon into triangles by ear clipping, the triangles keep the winding of the polygon
+pub fn triangulate_polygon(points: &[Vector2<f64>]) -> Vec<[usize; 3]> {
+	let mut triangles = Vec::new();
+	if points.len() < 3 {
+		return triangles;
+	}
+
+	let mut area = 0.0;
+	for i in 0..points.len() {
+		let (p, q) = (points[i], points[(i + 1) % points.len()]);
+		area += p[0] * q[1] - q[0] * p[1];
+	}
+
+	// Clip the ears on a counter clockwise ordered list of indices
+	let ccw = area >= 0.0;
+	let mut indices: Vec<usize> = if ccw { (0..points.len()).collect() } else { (0..points.len()).rev().collect() };
+
+	while indices.len() > 3 {
+		let len = indices.len();
+		let mut ear = None;
+
+		for i in 0..len {
+			let (a, b, c) = (indices[(i + len - 1) % len], indices[i], indices[(i + 1) % len]);
+			if cross_2d(points[a], points[b], points[c]) <= THRESHOLD {
+				continue; // Reflex or degenerated corner
+			}
+
+			let contains_point = indices.iter()
+				.filter(|j| **j != a && **j != b && **j != c)
+				.any(|j| is_point_in_triangle(points[*j], points[a], points[b], points[c]));
+			if !contains_point {
+				ear = Some(i);
+				break;
+			}
+		}
+
+		// Degenerated polygons get clipped anyway to guarantee progress
+		let i = ear.unwrap_or(0);
+		let (a, b, c) = (indices[(i + len - 1) % len], indices[i], indices[(i + 1) % len]);
+		triangles.push(if ccw { [a, b, c] } else { [c, b, a] });
+		indices.remove(i);
+	}
+
+	let (a, b, c) = (indices[0], indices[1], indices[2]);
+	triangles.push(if ccw { [a, b, c] } else { [c, b, a] });
+
+	triangles
+}
+
+fn cross_2d(a: Vector2<f64>, b: Vector2<f64>, c: Vector2<f64>) -> f64 {
+	(b[0] - a[0]) * (c[1] - a[1]) - (b[1] - a[1]) * (c[0] - a[0])
+}
+
+fn is_point_in_triangle(p: Vector2<f64>, a: Vector2<f64>, b: Vector2<f64>, c: Vector2<f64>) -> bool {
+	cross_2d(a, b, p) >= 0.0 && cross_2d(b, c, p) >= 0.0 && cross_2d(c, a, p) >= 0.0
+}
+
+// Barycentric coordinates of a point projected onto the plane of a triangle
+pub fn compute_barycentric(vertices: &[Vector3<f64>; 3], position: Vector3<f64>) -> Vector3<f64> {
+	let e0 = vec3_sub(vertices[1], vertices[0]);
+	let e1 = vec3_sub(vertices[2], vertices[0]);
+	let p = vec3_sub(position, vertices[0]);
+
+	let (d00, d01, d11) = (vec3_dot(e0, e0), vec3_dot(e0, e1), vec3_dot(e1, e1));
+	let (d20, d21) = (vec3_dot(p, e0), vec3_dot(p, e1));
+	let denom = d00 * d11 - d01 * d01;
+	if denom.abs() < 1e-20 {
+		return [1.0, 0.0, 0.0];
+	}
+
+	let v = (d11 * d20 - d01 * d21) / denom;
+	let w = (d00 * d21 - d01 * d20) / denom;
+	[1.0 - v - w, v, w]
+}
+
+// Watertight ray-triangle intersection by Woop et al. returning the distance and barycentric coordinates
+pub fn compute_triangle_hit(ray: &RayTraceRay, vertices: &[Vector3<f64>; 3], cull_back_faces: bool)
+		-> Option<(f64, Vector3<f64>)> {
+	let ray_pos = ray.get_position();
+	let ray_dir = ray.get_direction();
+
+	// Use the dominant ray direction as z-axis and keep the winding of the triangle
+	let kz = if ray_dir[0].abs() > ray_dir[1].abs() {
+		if ray_dir[0].abs() > ray_dir[2].abs() { 0 } else { 2 }
+	} else {
+		if ray_dir[1].abs() > ray_dir[2].abs() { 1 } else { 2 }
+	};
+	if ray_dir[kz] == 0.0 {
+		return None;
+	}
+
+	let mut kx = (kz + 1) % 3;
+	let mut ky = (kx + 1) % 3;
+	if ray_dir[kz] < 0.0 {
+		let tmp = kx;
+		kx = ky;
+		ky = tmp;
+	}
+
+	let shear = [ray_dir[kx] / ray_dir[kz], ray_dir[ky] / ray_dir[kz], 1.0 / ray_dir[kz]];
+
+	let a = vec3_sub(vertices[0], *ray_pos);
+	let b = vec3_sub(vertices[1], *ray_pos);
+	let c = vec3_sub(vertices[2], *ray_pos);
+
+	let (ax, ay) = (a[kx] - shear[0] * a[kz], a[ky] - shear[1] * a[kz]);
+	let (bx, by) = (b[kx] - shear[0] * b[kz], b[ky] - shear[1] * b[kz]);
+	let (cx, cy) = (c[kx] - shear[0] * c[kz], c[ky] - shear[1] * c[kz]);
+
+	// Scaled barycentric coordinates from the edge functions
+	let u = cx * by - cy * bx;
+	let v = ax * cy - ay * cx;
+	let w = bx * ay - by * ax;
+
+	// Front faces (counter clockwise) have only non negative edge functions
+	if cull_back_faces {
+		if u < 0.0 || v < 0.0 || w < 0.0 {
+			return None;
+		}
+	} else if (u < 0.0 || v < 0.0 || w < 0.0) && (u > 0.0 || v > 0.0 || w > 0.0) {
+		return None;
+	}
+
+	let det = u + v + w;
+	if det == 0.0 {
+		return None;
+	}
+
+	let t = (u * a[kz] + v * b[kz] + w * c[kz]) * shear[2] / det;
+	if t < THRESHOLD {
+		return None;
+	}
+
+	Some((t, [u / det, v / det, w / det]))
+}
+
+pub fn translate_mat4(translation: Vector3<f64>) -> Matrix4<f64> {
+	[
+		[1.0, 0.0, 0.0, translation[0]],
+		[0.0, 1.0, 0.0, translation[1]],
+		[0.0, 0.0, 1.0, translation[2]],
+		[0.0, 0.0, 0.0, 1.0]
+	]
+}
+
+pub fn scale_mat4(scale: Vector3<f64>) -> Matrix4<f64> {
+	[
+		[scale[0], 0.0, 0.0, 0.0],
+		[0.0, scale[1], 0.0, 0.0],
+		[0.0, 0.0, scale[2], 0.0],
+		[0.0, 0.0, 0.0, 1.0]
+	]
+}
+
+pub fn mat3_to_mat4(mat: Matrix3<f64>) -> Matrix4<f64> {
+	[
+		[mat[0][0], mat[0][1], mat[0][2], 0.0],
+		[mat[1][0], mat[1][1], mat[1][2], 0.0],
+		[mat[2][0], mat[2][1], mat[2][2], 0.0],
+		[0.0, 0.0, 0.0, 1.0]
+	]
+}
+
+pub fn transform_position(mat: &Matrix4<f64>, position: Vector3<f64>) -> Vector3<f64> {
+	let v = row_mat4_transform(*mat, [position[0], position[1], position[2], 1.0]);
+	[v[0], v[1], v[2]]
+}
+
+pub fn transform_direction(mat: &Matrix4<f64>, direction: Vector3<f64>) -> Vector3<f64> {
+	let v = row_mat4_transform(*mat, [direction[0], direction[1], direction[2], 0.0]);
+	[v[0], v[1], v[2]]
+}
+
+pub trait RayTraceTransformable {
+	fn get_transform(&self) -> &RayTraceTransform;
+	fn get_transform_mut(&mut self) -> &mut RayTraceTransform;
+}
+
+// Maps object space into world space as matrix * translation * rotation * scale * (-pivot)
+pub struct RayTraceTransform {
+	translation: Vector3<f64>,
+	rotation: Vector3<f64>,
+	scale: Vector3<f64>,
+	pivot: Vector3<f64>,
+	matrix: Matrix4<f64>,
+	anim_translation: Option<Box<RayTraceAnimation<Vector3<f64>>>>,
+	anim_rotation: Option<Box<RayTraceAnimation<Vector3<f64>>>>,
+	anim_scale: Option<Box<RayTraceAnimation<Vector3<f64>>>>,
+	anim_pivot: Option<Box<RayTraceAnimation<Vector3<f64>>>>,
+	anim_matrix: Option<Box<RayTraceAnimation<Matrix4<f64>>>>,
+	world_matrix: Matrix4<f64>,
+	inverse_matrix: Matrix4<f64>,
+	normal_matrix: Matrix3<f64>
+}
+
+#[allow(dead_code)]
+impl RayTraceTransform {
+	pub fn new() -> Self {
+		Self::new_with([0.0, 0.0, 0.0], [0.0, 0.0, 0.0], [1.0, 1.0, 1.0])
+	}
+
+	pub fn new_with(translation: Vector3<f64>, rotation: Vector3<f64>, scale: Vector3<f64>) -> Self {
+		let mut transform = Self {
+			translation: translation,
+			rotation: rotation,
+			scale: scale,
+			pivot: [0.0, 0.0, 0.0],
+			matrix: mat4_id(),
+			anim_translation: None,
+			anim_rotation: None,
+			anim_scale: None,
+			anim_pivot: None,
+			anim_matrix: None,
+			world_matrix: mat4_id(),
+			inverse_matrix: mat4_id(),
+			normal_matrix: mat3_id()
+		};
+
+		transform.update();
+		transform
+	}
+
+	pub fn init(&mut self, frame: usize) {
+		if let Some(ref anim) = self.anim_translation {
+			self.translation = anim.next_frame(frame);
+		}
+		if let Some(ref anim) = self.anim_rotation {
+			self.rotation = anim.next_frame(frame);
+		}
+		if let Some(ref anim) = self.anim_scale {
+			self.scale = anim.next_frame(frame);
+		}
+		if let Some(ref anim) = self.anim_pivot {
+			self.pivot = anim.next_frame(frame);
+		}
+		if let Some(ref anim) = self.anim_matrix {
+			self.matrix = anim.next_frame(frame);
+		}
+
+		self.update();
+	}
+
+	fn update(&mut self) {
+		let local = row_mat4_mul(
+			row_mat4_mul(translate_mat4(self.translation), mat3_to_mat4(rotate_xyz(self.rotation))),
+			row_mat4_mul(scale_mat4(self.scale), translate_mat4(vec3_scale(self.pivot, -1.0))));
+
+		self.world_matrix = row_mat4_mul(self.matrix, local);
+		self.inverse_matrix = mat4_inv(self.world_matrix);
+
+		// Normals are transformed with the inverse transpose of the linear part
+		let inv = self.inverse_matrix;
+		self.normal_matrix = [
+			[inv[0][0], inv[1][0], inv[2][0]],
+			[inv[0][1], inv[1][1], inv[2][1]],
+			[inv[0][2], inv[1][2], inv[2][2]]
+		];
+	}
+
+	pub fn get_translation(&self) -> &Vector3<f64> {
+		&self.translation
+	}
+
+	pub fn set_translation(&mut self, translation: Vector3<f64>) {
+		self.translation = translation;
+		self.update();
+	}
+
+	pub fn get_rotation(&self) -> &Vector3<f64> {
+		&self.rotation
+	}
+
+	pub fn set_rotation(&mut self, rotation: Vector3<f64>) {
+		self.rotation = rotation;
+		self.update();
+	}
+
+	pub fn get_scale(&self) -> &Vector3<f64> {
+		&self.scale
+	}
+
+	pub fn set_scale(&mut self, scale: Vector3<f64>) {
+		self.scale = scale;
+		self.update();
+	}
+
+	pub fn get_pivot(&self) -> &Vector3<f64> {
+		&self.pivot
+	}
+
+	pub fn set_pivot(&mut self, pivot: Vector3<f64>) {
+		self.pivot = pivot;
+		self.update();
+	}
+
+	pub fn get_matrix(&self) -> &Matrix4<f64> {
+		&self.matrix
+	}
+
+	pub fn set_matrix(&mut self, matrix: Matrix4<f64>) {
+		self.matrix = matrix;
+		self.update();
+	}
+
+	pub fn set_anim_translation_opt(&mut self, anim: Option<Box<RayTraceAnimation<Vector3<f64>>>>) {
+		self.anim_translation = anim;
+	}
+
+	pub fn set_anim_translation(&mut self, anim: Box<RayTraceAnimation<Vector3<f64>>>) {
+		self.anim_translation = Some(anim);
+	}
+
+	pub fn set_anim_rotation_opt(&mut self, anim: Option<Box<RayTraceAnimation<Vector3<f64>>>>) {
+		self.anim_rotation = anim;
+	}
+
+	pub fn set_anim_rotation(&mut self, anim: Box<RayTraceAnimation<Vector3<f64>>>) {
+		self.anim_rotation = Some(anim);
+	}
+
+	pub fn set_anim_scale_opt(&mut self, anim: Option<Box<RayTraceAnimation<Vector3<f64>>>>) {
+		self.anim_scale = anim;
+	}
+
+	pub fn set_anim_scale(&mut self, anim: Box<RayTraceAnimation<Vector3<f64>>>) {
+		self.anim_scale = Some(anim);
+	}
+
+	pub fn set_anim_pivot_opt(&mut self, anim: Option<Box<RayTraceAnimation<Vector3<f64>>>>) {
+		self.anim_pivot = anim;
+	}
+
+	pub fn set_anim_pivot(&mut self, anim: Box<RayTraceAnimation<Vector3<f64>>>) {
+		self.anim_pivot = Some(anim);
+	}
+
+	pub fn set_anim_matrix_opt(&mut self, anim: Option<Box<RayTraceAnimation<Matrix4<f64>>>>) {
+		self.anim_matrix = anim;
+	}
+
+	pub fn set_anim_matrix(&mut self, anim: Box<RayTraceAnimation<Matrix4<f64>>>) {
+		self.anim_matrix = Some(anim);
+	}
+
+	pub fn get_anim_translation(&self) -> Option<&Box<RayTraceAnimation<Vector3<f64>>>> {
+		self.anim_translation.as_ref()
+	}
+
+	pub fn get_anim_rotation(&self) -> Option<&Box<RayTraceAnimation<Vector3<f64>>>> {
+		self.anim_rotation.as_ref()
+	}
+
+	pub fn get_anim_scale(&self) -> Option<&Box<RayTraceAnimation<Vector3<f64>>>> {
+		self.anim_scale.as_ref()
+	}
+
+	pub fn get_anim_pivot(&self) -> Option<&Box<RayTraceAnimation<Vector3<f64>>>> {
+		self.anim_pivot.as_ref()
+	}
+
+	pub fn get_anim_matrix(&self) -> Option<&Box<RayTraceAnimation<Matrix4<f64>>>> {
+		self.anim_matrix.as_ref()
+	}
+
+	pub fn get_world_matrix(&self) -> &Matrix4<f64> {
+		&self.world_matrix
+	}
+
+	pub fn get_inverse_matrix(&self) -> &Matrix4<f64> {
+		&self.inverse_matrix
+	}
+
+	pub fn get_normal_matrix(&self) -> &Matrix3<f64> {
+		&self.normal_matrix
+	}
+
+	pub fn get_position(&self) -> Vector3<f64> {
+		transform_position(&self.world_matrix, [0.0, 0.0, 0.0])
+	}
+
+	pub fn transform_position(&self, position: Vector3<f64>) -> Vector3<f64> {
+		transform_position(&self.world_matrix, position)
+	}
+
+	pub fn transform_direction(&self, direction: Vector3<f64>) -> Vector3<f64> {
+		transform_direction(&self.world_matrix, direction)
+	}
+
+	pub fn transform_normal(&self, normal: Vector3<f64>) -> Vector3<f64> {
+		vec3_normalized(row_mat3_transform(self.normal_matrix, normal))
+	}
+
+	pub fn inverse_transform_position(&self, position: Vector3<f64>) -> Vector3<f64> {
+		transform_position(&self.inverse_matrix, position)
+	}
+
+	pub fn inverse_transform_direction(&self, direction: Vector3<f64>) -> Vector3<f64> {
+		transform_direction(&self.inverse_matrix, direction)
+	}
+
+	// The direction is not normalized to keep the ray distances equal in both spaces
+	pub fn inverse_transform_ray(&self, ray: &RayTraceRay) -> RayTraceRay {
+		ray.transform_with(|p| self.inverse_transform_position(p), |d| self.inverse_transform_direction(d))
+	}
+
+	// Encloses the eight transformed corners of the object space bounds
+	pub fn transform_aabb(&self, start: Vector3<f64>, end: Vector3<f64>) -> AABB {
+		let mut aabb = AABB::new(self.transform_position(start), self.transform_position(start));
+		for i in 1..8 {
+			aabb.expand(self.transform_position([
+				if i & 0x01 != 0 { end[0] } else { start[0] },
+				if i & 0x02 != 0 { end[1] } else { start[1] },
+				if i & 0x04 != 0 { end[2] } else { start[2] }
+			]));
+		}
+
+		aabb
+	}
 }
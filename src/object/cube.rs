@@ -1,19 +1,22 @@
 use std::f64;
 
 use vecmath::Vector3;
-use vecmath::{vec3_add, vec3_sub, vec3_scale, vec3_neg};
-use vecmath::row_mat3_transform;
 
 use aabb::AABB;
 use anim::RayTraceAnimation;
 use hit::RayTraceRayHit;
+use hit::RayTraceShadingContext;
+use light::lights::RayTraceAreaLightShape;
+use material::RayTraceFootprint;
 use material::RayTraceMaterial;
 use object::RayTraceObject;
 use object::RayTraceHitable;
 use ray::RayTraceRay;
+use scene_file::RayTraceSceneWriteError;
+use scene_file::RayTraceSceneWriter;
 
-use math_util::compute_plane_hit;
-use math_util::rotate_xyz;
+use math_util::RayTraceTransform;
+use math_util::RayTraceTransformable;
 
 enum CubeMaterial {
 	OnePerCube(Box<RayTraceMaterial>),
@@ -24,10 +27,7 @@ enum CubeMaterial {
 pub struct RayTraceObjectCube {
 	material: Box<CubeMaterial>,
 	size: Vector3<f64>,
-	center: Vector3<f64>,
-	rotation: Vector3<f64>,
-	anim_pos: Option<Box<RayTraceAnimation<Vector3<f64>>>>,
-	anim_rot: Option<Box<RayTraceAnimation<Vector3<f64>>>>,
+	transform: RayTraceTransform,
 	anim_size: Option<Box<RayTraceAnimation<Vector3<f64>>>>,
 	data: Option<WorkingData>
 }
@@ -37,11 +37,8 @@ impl RayTraceObjectCube {
 	pub fn new(center: Vector3<f64>, size: Vector3<f64>, material: Box<RayTraceMaterial>) -> Self {
 		Self {
 			material: box CubeMaterial::OnePerCube(material),
-			center: center,
 			size: size,
-			rotation: [0.0, 0.0, 0.0],
-			anim_rot: None,
-			anim_pos: None,
+			transform: RayTraceTransform::new_with(center, [0.0, 0.0, 0.0], [1.0, 1.0, 1.0]),
 			anim_size: None,
 			data: None
 		}
@@ -50,44 +47,17 @@ impl RayTraceObjectCube {
 	pub fn new_with(center: Vector3<f64>, size: Vector3<f64>, materials: [Box<RayTraceMaterial>; 6]) -> Self {
 		Self {
 			material: box CubeMaterial::OnePerSide(materials),
-			center: center,
 			size: size,
-			rotation: [0.0, 0.0, 0.0],
-			anim_rot: None,
-			anim_pos: None,
+			transform: RayTraceTransform::new_with(center, [0.0, 0.0, 0.0], [1.0, 1.0, 1.0]),
 			anim_size: None,
 			data: None
 		}
 	}
 
-	pub fn set_rotation(&mut self, rotation: Vector3<f64>) {
-		self.rotation = rotation;
-	}
-
-	pub fn set_position(&mut self, position: Vector3<f64>) {
-		self.center = position;
-	}
-
 	pub fn set_size(&mut self, size: Vector3<f64>) {
 		self.size = size;
 	}
 
-	pub fn set_anim_pos_opt(&mut self, anim: Option<Box<RayTraceAnimation<Vector3<f64>>>>) {
-		self.anim_pos = anim;
-	}
-
-	pub fn set_anim_pos(&mut self, anim: Box<RayTraceAnimation<Vector3<f64>>>) {
-		self.anim_pos = Some(anim);
-	}
-
-	pub fn set_anim_rot_opt(&mut self, anim: Option<Box<RayTraceAnimation<Vector3<f64>>>>) {
-		self.anim_rot = anim;
-	}
-
-	pub fn set_anim_rot(&mut self, anim: Box<RayTraceAnimation<Vector3<f64>>>) {
-		self.anim_rot = Some(anim);
-	}
-
 	pub fn set_anim_size_opt(&mut self, anim: Option<Box<RayTraceAnimation<Vector3<f64>>>>) {
 		self.anim_size = anim;
 	}
@@ -106,57 +76,70 @@ impl RayTraceObjectCube {
 			}
 		}
 	}
+
+	#[deprecated(note = "Use the transform instead")]
+	pub fn get_position(&self) -> &Vector3<f64> {
+		self.transform.get_translation()
+	}
+
+	#[deprecated(note = "Use the transform instead")]
+	pub fn set_position(&mut self, position: Vector3<f64>) {
+		self.transform.set_translation(position);
+	}
+
+	#[deprecated(note = "Use the transform instead")]
+	pub fn set_rotation(&mut self, rotation: Vector3<f64>) {
+		self.transform.set_rotation(rotation);
+	}
+
+	#[deprecated(note = "Use the transform instead")]
+	pub fn set_anim_pos_opt(&mut self, anim: Option<Box<RayTraceAnimation<Vector3<f64>>>>) {
+		self.transform.set_anim_translation_opt(anim);
+	}
+
+	#[deprecated(note = "Use the transform instead")]
+	pub fn set_anim_pos(&mut self, anim: Box<RayTraceAnimation<Vector3<f64>>>) {
+		self.transform.set_anim_translation(anim);
+	}
+
+	#[deprecated(note = "Use the transform instead")]
+	pub fn set_anim_rot_opt(&mut self, anim: Option<Box<RayTraceAnimation<Vector3<f64>>>>) {
+		self.transform.set_anim_rotation_opt(anim);
+	}
+
+	#[deprecated(note = "Use the transform instead")]
+	pub fn set_anim_rot(&mut self, anim: Box<RayTraceAnimation<Vector3<f64>>>) {
+		self.transform.set_anim_rotation(anim);
+	}
 }
 
-fn gen_aabb(center: Vector3<f64>, size: Vector3<f64>) -> AABB {
-	let dir = vec3_scale(size, 1.42 * 0.5);
-	return AABB::new(vec3_sub(center, dir), vec3_add(center, dir));
+impl RayTraceTransformable for RayTraceObjectCube {
+	fn get_transform(&self) -> &RayTraceTransform {
+		&self.transform
+	}
+
+	fn get_transform_mut(&mut self) -> &mut RayTraceTransform {
+		&mut self.transform
+	}
 }
 
 struct WorkingData {
-	plane_vec: [Vector3<f64>; 3],
-	plane_center: [Vector3<f64>; 6],
 	aabb: AABB
 }
 
+const THRESHOLD: f64 = 1e-10;
+
 #[allow(unused_variables)]
 impl RayTraceObject for RayTraceObjectCube {
 	fn init(&mut self, frame: usize) {
-		if let Some(ref anim_pos) = self.anim_pos {
-			self.center = anim_pos.next_frame(frame);
-		}
-		if let Some(ref anim_rot) = self.anim_rot {
-			self.rotation = anim_rot.next_frame(frame);
-		}
+		self.transform.init(frame);
 		if let Some(ref anim_size) = self.anim_size {
 			self.size = anim_size.next_frame(frame);
 		}
 
-		let plane_vec1 = [1.0, 0.0, 0.0];
-		let plane_vec2 = [0.0, 1.0, 0.0];
-		let plane_vec3 = [0.0, 0.0, 1.0];
-
-		let rot = rotate_xyz(self.rotation);
-
-		let vec1 = row_mat3_transform(rot, plane_vec1);
-		let vec2 = row_mat3_transform(rot, plane_vec2);
-		let vec3 = row_mat3_transform(rot, plane_vec3);
-
-		let vec1_scaled = vec3_scale(vec1, 0.5 * self.size[0]);
-		let vec2_scaled = vec3_scale(vec2, 0.5 * self.size[1]);
-		let vec3_scaled = vec3_scale(vec3, 0.5 * self.size[2]);
-
+		let half = [0.5 * self.size[0], 0.5 * self.size[1], 0.5 * self.size[2]];
 		self.data = Some(WorkingData {
-			plane_vec: [vec1, vec2, vec3],
-			plane_center: [
-				vec3_add(self.center, vec1_scaled),
-				vec3_sub(self.center, vec1_scaled),
-				vec3_add(self.center, vec2_scaled),
-				vec3_sub(self.center, vec2_scaled),
-				vec3_add(self.center, vec3_scaled),
-				vec3_sub(self.center, vec3_scaled),
-			],
-			aabb: gen_aabb(self.center, self.size)
+			aabb: self.transform.transform_aabb([-half[0], -half[1], -half[2]], half)
 		});
 	}
 
@@ -167,81 +150,124 @@ impl RayTraceObject for RayTraceObjectCube {
 			panic!("Qube was not initialized!");
 		}
 	}
+
+	// Every emissive side adds two triangles
+	fn get_area_light(&self) -> Option<RayTraceAreaLightShape> {
+		let mut triangles = Vec::new();
+		for side in (0..6).filter(|&s| self.get_material(s).is_emissive()) {
+			let axis = side / 2;
+			let (v1, v2) = match axis {
+				0 => (1, 2),
+				1 => (0, 2),
+				_ => (0, 1)
+			};
+
+			let sign = if side % 2 == 0 { 1.0 } else { -1.0 };
+
+			let corner = |a: f64, b: f64| {
+				let mut p = [0.0, 0.0, 0.0];
+				p[axis] = sign * 0.5 * self.size[axis];
+				p[v1] = a * 0.5 * self.size[v1];
+				p[v2] = b * 0.5 * self.size[v2];
+				self.transform.transform_position(p)
+			};
+			triangles.push([corner(-1.0, -1.0), corner(1.0, -1.0), corner(1.0, 1.0)]);
+			triangles.push([corner(-1.0, -1.0), corner(1.0, 1.0), corner(-1.0, 1.0)]);
+		}
+
+		if triangles.is_empty() {
+			None
+		} else {
+			Some(RayTraceAreaLightShape::Triangles(triangles))
+		}
+	}
+
+	fn serialize(&self, writer: &mut RayTraceSceneWriter) -> Result<(), RayTraceSceneWriteError> {
+		writer.set_type("cube");
+		try!(writer.set_vec3("size", self.size));
+		if let Some(ref anim) = self.anim_size {
+			writer.set_anim_vec3("size_animation", &**anim);
+		}
+		match self.material {
+			box CubeMaterial::OnePerCube(ref material) => { try!(writer.set_material("material", &**material)); },
+			box CubeMaterial::OnePerSide(ref materials) => { try!(writer.set_material_list("materials", materials)); }
+		}
+		writer.set_transform(&self.transform);
+		Ok(())
+	}
 }
 
 impl RayTraceHitable for RayTraceObjectCube {
 	fn next_hit(&self, ray: &RayTraceRay) -> Option<RayTraceRayHit> {
-		if let Some(ref data) = self.data {
-			let mut hit_distance = f64::MAX;
-			let mut hit_ret = None;
+		if self.data.is_some() {
+			// Intersect the axis aligned box around the origin in object space
+			let object_ray = self.transform.inverse_transform_ray(ray);
+			let origin = object_ray.get_position();
+			let dir = object_ray.get_direction();
 
-			if let Some(hit) = get_plane_hit(ray, data.plane_center[0], &self.size,
-					data.plane_vec[0], data.plane_vec, 1, 2, self.get_material(0)) {
-				hit_distance = hit.get_distance();
-				hit_ret = Some(hit);
-			}
-			if let Some(hit) = get_plane_hit(ray, data.plane_center[1], &self.size,
-					vec3_neg(data.plane_vec[0]), data.plane_vec, 1, 2, self.get_material(1)) {
-				if hit.get_distance() < hit_distance {
-					hit_distance = hit.get_distance();
-					hit_ret = Some(hit);
+			let mut near = (f64::MIN, 0);
+			let mut far = (f64::MAX, 0);
+
+			for i in 0..3 {
+				let half = 0.5 * self.size[i];
+
+				if dir[i].abs() < THRESHOLD {
+					if origin[i] < -half || origin[i] > half {
+						return None;
+					}
+
+					continue;
 				}
-			}
 
-			if let Some(hit) = get_plane_hit(ray, data.plane_center[2], &self.size,
-					data.plane_vec[1], data.plane_vec, 0, 2, self.get_material(2)) {
-				if hit.get_distance() < hit_distance {
-					hit_distance = hit.get_distance();
-					hit_ret = Some(hit);
+				// Sides are ordered as +x, -x, +y, -y, +z, -z
+				let t_pos = (half - origin[i]) / dir[i];
+				let t_neg = (-half - origin[i]) / dir[i];
+				let (t0, t1) = if t_neg < t_pos { ((t_neg, 2 * i + 1), (t_pos, 2 * i)) } else {
+					((t_pos, 2 * i), (t_neg, 2 * i + 1))
+				};
+
+				if t0.0 > near.0 {
+					near = t0;
 				}
-			}
-			if let Some(hit) = get_plane_hit(ray, data.plane_center[3], &self.size,
-					vec3_neg(data.plane_vec[1]), data.plane_vec, 0, 2, self.get_material(3)) {
-				if hit.get_distance() < hit_distance {
-					hit_distance = hit.get_distance();
-					hit_ret = Some(hit);
+				if t1.0 < far.0 {
+					far = t1;
 				}
 			}
 
-			if let Some(hit) = get_plane_hit(ray, data.plane_center[4], &self.size,
-					data.plane_vec[2], data.plane_vec, 0, 1, self.get_material(4)) {
-				if hit.get_distance() < hit_distance {
-					hit_distance = hit.get_distance();
-					hit_ret = Some(hit);
-				}
-			}
-			if let Some(hit) = get_plane_hit(ray, data.plane_center[5], &self.size,
-					vec3_neg(data.plane_vec[2]), data.plane_vec, 0, 1, self.get_material(5)) {
-				if hit.get_distance() < hit_distance {
-					hit_ret = Some(hit);
-				}
+			if near.0 > far.0 || far.0 == f64::MAX {
+				return None;
 			}
 
-			return hit_ret;
+			// Rays starting inside of the cube hit the side they are leaving through
+			let (dist, side) = if near.0 > 0.0 { near } else if far.0 > 0.0 { far } else { return None; };
+			let axis = side / 2;
+
+			let mut normal = [0.0, 0.0, 0.0];
+			normal[axis] = if side % 2 == 0 { 1.0 } else { -1.0 };
+
+			let hit_point = object_ray.get_position_on_ray(dist);
+			let (v1, v2) = match axis {
+				0 => (1, 2),
+				1 => (0, 2),
+				_ => (0, 1)
+			};
+
+			let surface_normal = self.transform.transform_normal(normal);
+			let (mut tangent, mut bitangent) = ([0.0, 0.0, 0.0], [0.0, 0.0, 0.0]);
+			tangent[v1] = 1.0;
+			bitangent[v2] = 1.0;
+
+			let mut context = RayTraceShadingContext::new(ray, dist, hit_point, surface_normal,
+				[hit_point[v1], hit_point[v2]]);
+			context.set_tangents(self.transform.transform_direction(tangent),
+				self.transform.transform_direction(bitangent));
+			context.set_footprint(RayTraceFootprint::compute(&object_ray, hit_point, normal, |p| [p[v1], p[v2]]));
+			context.set_primitive(side);
+
+			return Some(RayTraceRayHit::new(dist, *context.get_position(), surface_normal,
+				self.get_material(side).get_hit_context(&context)));
 		} else {
 			panic!("Qube was not initialized!");
 		}
 	}
 }
-
-fn get_plane_hit(ray: &RayTraceRay, center: Vector3<f64>, size: &Vector3<f64>, normal_vec: Vector3<f64>,
-		vec: [Vector3<f64>; 3], v1: usize, v2: usize, material: &Box<RayTraceMaterial>) -> Option<RayTraceRayHit> {
-	if let Some((dist, vec1, vec2)) = compute_plane_hit(ray, center, vec[v1], vec[v2]) {
-		if dist <= 0.0 {
-			return None;
-		}
-
-		if vec1.abs() > size[v1] * 0.5 {
-			return None;
-		}
-
-		if vec2.abs() > size[v2] * 0.5 {
-			return None;
-		}
-
-		return Some(RayTraceRayHit::new(dist, ray.get_position_on_ray(dist),
-				normal_vec, material.get_hit(vec1, vec2)));
-	} else {
-		return None;
-	}
-}
\ No newline at end of file
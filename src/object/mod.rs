@@ -2,21 +2,40 @@ mod cube;
 mod plane;
 mod sphere;
 pub mod model;
+pub mod voxel;
 
 pub use self::cube::RayTraceObjectCube;
 pub use self::plane::RayTraceObjectPlane;
 pub use self::sphere::RayTraceObjectSphere;
 pub use self::model::RayTraceObjectModel;
+pub use self::voxel::RayTraceObjectVoxelGrid;
 
 use aabb::AABB;
 use hit::RayTraceRayHit;
+use light::lights::RayTraceAreaLightShape;
 use ray::RayTraceRay;
+use scene::RayTraceAny;
+use scene_file::RayTraceSceneWriteError;
+use scene_file::RayTraceSceneWriter;
 
-pub trait RayTraceObject: Sync + Send + RayTraceHitable {
+/// Object of a scene which is initialized for every frame before rays are traced.
+pub trait RayTraceObject: Sync + Send + RayTraceHitable + RayTraceAny {
+	/// Applies the animations for the frame and prepares the data for the hit tests.
 	fn init(&mut self, frame: usize);
+	/// Bounding box in world space or nothing for unbounded objects like planes.
 	fn get_aabb(&self) -> Option<&AABB>;
+	/// Emissive surface in world space which the scene uses as area light, objects which do not override this do
+	/// not light other objects.
+	fn get_area_light(&self) -> Option<RayTraceAreaLightShape> {
+		None
+	}
+	/// Writes the object into a scene file, objects which do not override this can not be saved.
+	fn serialize(&self, writer: &mut RayTraceSceneWriter) -> Result<(), RayTraceSceneWriteError> {
+		Err(writer.unsupported("object"))
+	}
 }
 
 pub trait RayTraceHitable {
+	/// Returns the nearest hit in front of the ray position.
 	fn next_hit(&self, ray: &RayTraceRay) -> Option<RayTraceRayHit>;
 }
\ No newline at end of file
@@ -0,0 +1,431 @@
+use std::fs::File;
+use std::io::Read;
+use std::io::Write;
+use std::io::Error as IOError;
+use std::io::ErrorKind;
+
+use vecmath::Vector2;
+use vecmath::Vector3;
+
+use aabb::AABB;
+use color::RayTraceColor;
+use material::RayTraceMaterial;
+use object::RayTraceObjectModel;
+use object::model::RayTraceModelGroup;
+use object::model::WorkingData;
+use object::model::octree::RayTraceOctree;
+
+const CACHE_MAGIC: &'static [u8; 4] = b"RTMC";
+const CACHE_VERSION: u32 = 1;
+const CACHE_EXTENSION: &'static str = "rtcache";
+
+const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+// Little endian binary data of a cache file
+pub struct CacheWriter {
+	data: Vec<u8>
+}
+
+pub struct CacheReader<'a> {
+	data: &'a [u8],
+	position: usize
+}
+
+// Loads a model through a cache file next to the source file which is written if it is missing or outdated.
+// Materials are not stored so models which bring their own materials are loaded without the cache.
+pub fn cache_load_model<F>(file_name: &str, material: Box<RayTraceMaterial>, loader: F)
+		-> Result<RayTraceObjectModel, IOError>
+		where F: FnOnce(&str, Box<RayTraceMaterial>) -> Result<RayTraceObjectModel, IOError> {
+	let hash = try!(hash_file(file_name));
+	let cache_file = format!("{}.{}", file_name, CACHE_EXTENSION);
+
+	let material = match cache_load(&cache_file, material, hash) {
+		Ok(Ok(model)) => {
+			info!("Loaded {} from cache {}", file_name, cache_file);
+			return Ok(model);
+		},
+		Ok(Err(material)) => material,
+		Err(err) => { return Err(err); }
+	};
+
+	let mut model = try!(loader(file_name, material));
+	if !model.get_materials().is_empty() {
+		warn!("Model {} has materials of its own and is not cached", file_name);
+		return Ok(model);
+	}
+
+	cache_build(&mut model);
+	if let Err(err) = cache_save(&model, &cache_file, hash, true) {
+		warn!("Could not write cache {}: {}", cache_file, err);
+	}
+
+	Ok(model)
+}
+
+// Builds the octree of a model to store it in a cache, it is reused on initialization
+pub fn cache_build(model: &mut RayTraceObjectModel) {
+	if model.data.is_none() {
+		let data = model.create_working_data();
+		model.data = Some(data);
+	}
+}
+
+// Writes the geometry of a model and optionally its octree if the model has been initialized
+pub fn cache_save(model: &RayTraceObjectModel, file_name: &str, source_hash: u64, with_tree: bool)
+		-> Result<(), IOError> {
+	let data = try!(cache_encode(model, source_hash, with_tree));
+	let mut file = try!(File::create(file_name));
+	file.write_all(&data)
+}
+
+// Cache data of a model which is written to a file or kept in memory
+pub fn cache_encode(model: &RayTraceObjectModel, source_hash: u64, with_tree: bool) -> Result<Vec<u8>, IOError> {
+	if model.vertices.len() > u32::max_value() as usize || model.faces.len() > u32::max_value() as usize {
+		return Err(IOError::new(ErrorKind::InvalidInput, "Model is too large for the cache"));
+	}
+
+	let mut writer = CacheWriter::new();
+
+	writer.write_bytes(CACHE_MAGIC);
+	writer.write_u32(CACHE_VERSION);
+	writer.write_u64(source_hash);
+
+	writer.write_u32(model.vertices.len() as u32);
+	for v in model.vertices.iter() {
+		writer.write_vec3(v);
+	}
+	writer.write_u32(model.vertex_normals.len() as u32);
+	for n in model.vertex_normals.iter() {
+		writer.write_vec3(n);
+	}
+	writer.write_u32(model.texture_normals.len() as u32);
+	for t in model.texture_normals.iter() {
+		writer.write_f64(t[0]);
+		writer.write_f64(t[1]);
+	}
+	writer.write_u32(model.vertex_colors.len() as u32);
+	for c in model.vertex_colors.iter() {
+		for value in [c.get_r(), c.get_g(), c.get_b(), c.get_a()].iter() {
+			writer.write_u32(value.to_bits());
+		}
+	}
+
+	writer.write_u32(model.faces.len() as u32);
+	for face in model.faces.iter() {
+		for v in face.iter() {
+			writer.write_u32(v[0] as u32);
+			writer.write_u32(v[1] as u32);
+			writer.write_u32(v[2] as u32);
+		}
+	}
+	writer.write_u32_list(&model.face_materials.iter().map(|m| *m as u32).collect());
+	writer.write_u32_list(&model.smoothing_groups);
+
+	for groups in [&model.objects, &model.groups].iter() {
+		writer.write_u32(groups.len() as u32);
+		for group in groups.iter() {
+			writer.write_str(&group.name);
+			writer.write_u32_list(&group.faces.iter().map(|f| *f as u32).collect());
+		}
+	}
+
+	match model.data.as_ref().and_then(|d| d.tree.as_ref().map(|t| (t, d.object_aabb.as_ref()))) {
+		Some((tree, Some(aabb))) if with_tree => {
+			writer.write_u8(1);
+			writer.write_vec3(aabb.get_start());
+			writer.write_vec3(aabb.get_end());
+			tree.write_cache(&mut writer);
+		},
+		_ => { writer.write_u8(0); }
+	}
+
+	Ok(writer.data)
+}
+
+// Gives the material back if the cache is missing or was written by another version or for another source
+pub fn cache_load(file_name: &str, material: Box<RayTraceMaterial>, source_hash: u64)
+		-> Result<Result<RayTraceObjectModel, Box<RayTraceMaterial>>, IOError> {
+	let mut content = Vec::new();
+	match File::open(file_name) {
+		Ok(mut file) => { try!(file.read_to_end(&mut content)); },
+		Err(ref err) if err.kind() == ErrorKind::NotFound => { return Ok(Err(material)); },
+		Err(err) => { return Err(err); }
+	}
+
+	Ok(cache_decode(&content, file_name, material, source_hash))
+}
+
+// Creates a model from cache data, the name is used for messages only
+pub fn cache_decode(content: &[u8], name: &str, material: Box<RayTraceMaterial>, source_hash: u64)
+		-> Result<RayTraceObjectModel, Box<RayTraceMaterial>> {
+	let mut reader = CacheReader::new(content);
+	let valid = match (reader.read_bytes(4), reader.read_u32(), reader.read_u64()) {
+		(Ok(magic), Ok(version), Ok(hash)) => {
+			magic == CACHE_MAGIC && version == CACHE_VERSION && hash == source_hash
+		},
+		_ => false
+	};
+	if !valid {
+		info!("Cache {} is outdated", name);
+		return Err(material);
+	}
+
+	match read_model(&mut reader) {
+		Ok(cached) => {
+			let mut model = RayTraceObjectModel::new(material, cached.vertices, cached.vertex_normals,
+				cached.texture_normals, cached.faces);
+			model.vertex_colors = cached.vertex_colors;
+			model.face_materials = cached.face_materials;
+			model.smoothing_groups = cached.smoothing_groups;
+			model.objects = cached.objects;
+			model.groups = cached.groups;
+			model.data = cached.data;
+
+			// Caches written without the octree may lack the generated normals
+			model.generate_missing_normals();
+
+			Ok(model)
+		},
+		Err(err) => {
+			warn!("Ignored invalid cache {}: {}", name, err);
+			Err(material)
+		}
+	}
+}
+
+// FNV-1a hash of the file content to detect changes of the source
+pub fn hash_file(file_name: &str) -> Result<u64, IOError> {
+	let mut content = Vec::new();
+	let mut file = try!(File::open(file_name));
+	try!(file.read_to_end(&mut content));
+
+	let mut hash = FNV_OFFSET;
+	for byte in content.iter() {
+		hash ^= *byte as u64;
+		hash = hash.wrapping_mul(FNV_PRIME);
+	}
+
+	Ok(hash)
+}
+
+// Model data read from a cache before the model is created
+struct CachedModel {
+	vertices: Vec<Vector3<f64>>,
+	vertex_normals: Vec<Vector3<f64>>,
+	texture_normals: Vec<Vector2<f64>>,
+	vertex_colors: Vec<RayTraceColor>,
+	faces: Vec<[Vector3<usize>; 3]>,
+	face_materials: Vec<usize>,
+	smoothing_groups: Vec<u32>,
+	objects: Vec<RayTraceModelGroup>,
+	groups: Vec<RayTraceModelGroup>,
+	data: Option<WorkingData>
+}
+
+fn read_model(reader: &mut CacheReader) -> Result<CachedModel, IOError> {
+	let mut vertices = Vec::new();
+	for _ in 0..try!(reader.read_count(24)) {
+		vertices.push(try!(reader.read_vec3()));
+	}
+	let mut vertex_normals = Vec::new();
+	for _ in 0..try!(reader.read_count(24)) {
+		vertex_normals.push(try!(reader.read_vec3()));
+	}
+	let mut texture_normals = Vec::new();
+	for _ in 0..try!(reader.read_count(16)) {
+		texture_normals.push([try!(reader.read_f64()), try!(reader.read_f64())]);
+	}
+	let mut vertex_colors = Vec::new();
+	for _ in 0..try!(reader.read_count(16)) {
+		let mut c = [0.0; 4];
+		for value in c.iter_mut() {
+			*value = f32::from_bits(try!(reader.read_u32()));
+		}
+		vertex_colors.push(RayTraceColor::new_with(c[0], c[1], c[2], c[3]));
+	}
+
+	let mut faces = Vec::new();
+	for _ in 0..try!(reader.read_count(36)) {
+		let mut face = [[0; 3]; 3];
+		for v in face.iter_mut() {
+			for i in v.iter_mut() {
+				*i = try!(reader.read_u32()) as usize;
+			}
+		}
+
+		if face.iter().any(|v| v[0] == 0 || v[0] > vertices.len() || v[1] > vertex_normals.len()
+				|| v[2] > texture_normals.len()) {
+			return format_err("Face with invalid indices");
+		}
+		faces.push(face);
+	}
+
+	let face_materials = try!(reader.read_u32_list()).iter().map(|m| *m as usize).collect();
+	let smoothing_groups = try!(reader.read_u32_list());
+	let objects = try!(read_groups(reader));
+	let groups = try!(read_groups(reader));
+
+	let data = match try!(reader.read_u8()) {
+		0 => None,
+		_ => {
+			let aabb = AABB::new(try!(reader.read_vec3()), try!(reader.read_vec3()));
+			let triangles = faces.iter()
+				.map(|f| [vertices[f[0][0] - 1], vertices[f[1][0] - 1], vertices[f[2][0] - 1]])
+				.collect();
+
+			Some(WorkingData {
+				aabb: None,
+				object_aabb: Some(aabb),
+				tree: Some(try!(RayTraceOctree::read_cache(reader, triangles)))
+			})
+		}
+	};
+
+	Ok(CachedModel {
+		vertices: vertices,
+		vertex_normals: vertex_normals,
+		texture_normals: texture_normals,
+		vertex_colors: vertex_colors,
+		faces: faces,
+		face_materials: face_materials,
+		smoothing_groups: smoothing_groups,
+		objects: objects,
+		groups: groups,
+		data: data
+	})
+}
+
+fn read_groups(reader: &mut CacheReader) -> Result<Vec<RayTraceModelGroup>, IOError> {
+	let mut groups = Vec::new();
+	for _ in 0..try!(reader.read_count(8)) {
+		let name = try!(reader.read_str());
+		let faces = try!(reader.read_u32_list()).iter().map(|f| *f as usize).collect();
+		groups.push(RayTraceModelGroup::new_with(name, faces));
+	}
+
+	Ok(groups)
+}
+
+impl CacheWriter {
+	pub fn new() -> Self {
+		Self {
+			data: Vec::new()
+		}
+	}
+
+	pub fn write_bytes(&mut self, bytes: &[u8]) {
+		self.data.extend_from_slice(bytes);
+	}
+
+	pub fn write_u8(&mut self, value: u8) {
+		self.data.push(value);
+	}
+
+	pub fn write_u32(&mut self, value: u32) {
+		for i in 0..4 {
+			self.data.push((value >> (i * 8)) as u8);
+		}
+	}
+
+	pub fn write_u64(&mut self, value: u64) {
+		for i in 0..8 {
+			self.data.push((value >> (i * 8)) as u8);
+		}
+	}
+
+	pub fn write_f64(&mut self, value: f64) {
+		self.write_u64(value.to_bits());
+	}
+
+	pub fn write_vec3(&mut self, value: &Vector3<f64>) {
+		for v in value.iter() {
+			self.write_f64(*v);
+		}
+	}
+
+	pub fn write_str(&mut self, value: &str) {
+		self.write_u32(value.len() as u32);
+		self.write_bytes(value.as_bytes());
+	}
+
+	pub fn write_u32_list(&mut self, values: &Vec<u32>) {
+		self.write_u32(values.len() as u32);
+		for v in values.iter() {
+			self.write_u32(*v);
+		}
+	}
+}
+
+impl<'a> CacheReader<'a> {
+	pub fn new(data: &'a [u8]) -> Self {
+		Self {
+			data: data,
+			position: 0
+		}
+	}
+
+	pub fn read_bytes(&mut self, count: usize) -> Result<&'a [u8], IOError> {
+		if self.data.len() - self.position < count {
+			return format_err("Unexpected end of cache");
+		}
+
+		let bytes = &self.data[self.position..self.position + count];
+		self.position += count;
+		Ok(bytes)
+	}
+
+	pub fn read_u8(&mut self) -> Result<u8, IOError> {
+		Ok(try!(self.read_bytes(1))[0])
+	}
+
+	pub fn read_u32(&mut self) -> Result<u32, IOError> {
+		let bytes = try!(self.read_bytes(4));
+		Ok(bytes.iter().enumerate().fold(0, |value, (i, b)| value | (*b as u32) << (i * 8)))
+	}
+
+	pub fn read_u64(&mut self) -> Result<u64, IOError> {
+		let bytes = try!(self.read_bytes(8));
+		Ok(bytes.iter().enumerate().fold(0, |value, (i, b)| value | (*b as u64) << (i * 8)))
+	}
+
+	pub fn read_f64(&mut self) -> Result<f64, IOError> {
+		Ok(f64::from_bits(try!(self.read_u64())))
+	}
+
+	pub fn read_vec3(&mut self) -> Result<Vector3<f64>, IOError> {
+		Ok([try!(self.read_f64()), try!(self.read_f64()), try!(self.read_f64())])
+	}
+
+	pub fn read_str(&mut self) -> Result<String, IOError> {
+		let length = try!(self.read_count(1));
+		match String::from_utf8(try!(self.read_bytes(length)).to_vec()) {
+			Ok(value) => Ok(value),
+			Err(_) => format_err("Invalid string")
+		}
+	}
+
+	pub fn read_u32_list(&mut self) -> Result<Vec<u32>, IOError> {
+		let count = try!(self.read_count(4));
+		let mut values = Vec::with_capacity(count);
+		for _ in 0..count {
+			values.push(try!(self.read_u32()));
+		}
+
+		Ok(values)
+	}
+
+	// Reads the length of a list and checks that the remaining data can hold it
+	pub fn read_count(&mut self, element_size: usize) -> Result<usize, IOError> {
+		let count = try!(self.read_u32()) as usize;
+		if count * element_size > self.data.len() - self.position {
+			return format_err("Unexpected end of cache");
+		}
+
+		Ok(count)
+	}
+}
+
+fn format_err<T>(message: &str) -> Result<T, IOError> {
+	Err(IOError::new(ErrorKind::InvalidData, message.to_string()))
+}
@@ -0,0 +1,75 @@
+use std::collections::HashMap;
+use std::mem;
+
+use vecmath::{vec2_add, vec2_scale, vec3_add, vec3_len, vec3_normalized, vec3_scale};
+
+use object::RayTraceObjectModel;
+
+// Splits every face into 4 faces at the midpoints of its edges, faces sharing an edge share its midpoint
+pub fn subdivide_model(model: &mut RayTraceObjectModel) {
+	let old_faces = mem::replace(&mut model.faces, Vec::new());
+	let with_colors = !model.vertex_colors.is_empty() && model.vertex_colors.len() == model.vertices.len();
+
+	let mut vertex_midpoints = HashMap::new();
+	let mut normal_midpoints = HashMap::new();
+	let mut texcoord_midpoints = HashMap::new();
+
+	for face in old_faces.iter() {
+		let mut mids = [[0, 0, 0]; 3];
+		for i in 0..3 {
+			let (a, b) = (face[i], face[(i + 1) % 3]);
+
+			let count = model.vertices.len();
+			mids[i][0] = midpoint(&mut vertex_midpoints, a[0], b[0], &mut model.vertices,
+				|x, y| vec3_scale(vec3_add(*x, *y), 0.5));
+			if with_colors && model.vertices.len() > count {
+				let color = model.vertex_colors[a[0] - 1].mix(&model.vertex_colors[b[0] - 1], 0.5);
+				model.vertex_colors.push(color);
+			}
+
+			mids[i][1] = midpoint(&mut normal_midpoints, a[1], b[1], &mut model.vertex_normals, |x, y| {
+				let sum = vec3_add(*x, *y);
+				if vec3_len(sum) > 0.0 { vec3_normalized(sum) } else { *x }
+			});
+			mids[i][2] = midpoint(&mut texcoord_midpoints, a[2], b[2], &mut model.texture_normals,
+				|x, y| vec2_scale(vec2_add(*x, *y), 0.5));
+		}
+
+		model.faces.push([face[0], mids[0], mids[2]]);
+		model.faces.push([mids[0], face[1], mids[1]]);
+		model.faces.push([mids[2], mids[1], face[2]]);
+		model.faces.push([mids[0], mids[1], mids[2]]);
+	}
+
+	// Face data is repeated for the 4 faces which replace a face
+	if model.face_materials.len() == old_faces.len() {
+		model.face_materials = model.face_materials.iter().flat_map(|m| vec![*m; 4]).collect();
+	}
+	if model.smoothing_groups.len() == old_faces.len() {
+		model.smoothing_groups = model.smoothing_groups.iter().flat_map(|g| vec![*g; 4]).collect();
+	}
+	for group in model.objects.iter_mut().chain(model.groups.iter_mut()) {
+		group.faces = group.faces.iter().flat_map(|&f| (0..4).map(move |i| f * 4 + i)).collect();
+	}
+}
+
+// Indices start with 1, the index 0 means that there is no value so the midpoint has none either
+fn midpoint<T, F>(midpoints: &mut HashMap<(usize, usize), usize>, a: usize, b: usize, values: &mut Vec<T>, mix: F)
+		-> usize where F: Fn(&T, &T) -> T {
+	if a == 0 || b == 0 {
+		return 0;
+	}
+	if a == b {
+		return a;
+	}
+
+	let key = (a.min(b), a.max(b));
+	if let Some(index) = midpoints.get(&key).cloned() {
+		return index;
+	}
+
+	let value = mix(&values[a - 1], &values[b - 1]);
+	values.push(value);
+	midpoints.insert(key, values.len());
+	values.len()
+}
@@ -0,0 +1,102 @@
+use std::f64;
+
+use vecmath::Vector2;
+use vecmath::Vector3;
+use vecmath::{vec2_sub, vec2_len, vec2_normalized};
+
+use material::RayTraceMaterial;
+use object::RayTraceObjectModel;
+
+use math_util::triangulate_polygon;
+
+// Extrudes the closed polygon in the xy-plane along the z-axis, centered around the origin
+pub fn extrude_create(polygon: &Vec<Vector2<f64>>, depth: f64, material: Box<RayTraceMaterial>)
+		-> RayTraceObjectModel {
+	let mut vertices = Vec::<Vector3<f64>>::new();
+	let mut vertex_normals = Vec::<Vector3<f64>>::new();
+	let mut texture_normals = Vec::<Vector2<f64>>::new();
+	let mut faces = Vec::<[Vector3<usize>; 3]>::new();
+
+	if polygon.len() < 3 {
+		return RayTraceObjectModel::new(material, vertices, vertex_normals, texture_normals, faces);
+	}
+
+	// Use a counter clockwise polygon to get outward facing normals
+	let mut area = 0.0;
+	for i in 0..polygon.len() {
+		let (p, q) = (polygon[i], polygon[(i + 1) % polygon.len()]);
+		area += p[0] * q[1] - q[0] * p[1];
+	}
+
+	let mut points = polygon.clone();
+	if area < 0.0 {
+		points.reverse();
+	}
+
+	let (z_back, z_front) = (-0.5 * depth, 0.5 * depth);
+	let perimeter = (0..points.len())
+		.map(|i| vec2_len(vec2_sub(points[(i + 1) % points.len()], points[i])))
+		.fold(0.0, |sum, len| sum + len);
+
+	// Side walls with one flat shaded quad per edge
+	let mut distance = 0.0;
+	for i in 0..points.len() {
+		let (p0, p1) = (points[i], points[(i + 1) % points.len()]);
+		let edge = vec2_sub(p1, p0);
+		let edge_len = vec2_len(edge);
+		if edge_len == 0.0 {
+			continue;
+		}
+
+		let normal = vec2_normalized([edge[1], -edge[0]]);
+		let (u0, u1) = (distance / perimeter, (distance + edge_len) / perimeter);
+		distance += edge_len;
+
+		let a = vertices.len() + 1;
+		let (b, c, d) = (a + 1, a + 2, a + 3);
+
+		vertices.push([p0[0], p0[1], z_back]);
+		vertices.push([p1[0], p1[1], z_back]);
+		vertices.push([p1[0], p1[1], z_front]);
+		vertices.push([p0[0], p0[1], z_front]);
+		texture_normals.push([u0, 0.0]);
+		texture_normals.push([u1, 0.0]);
+		texture_normals.push([u1, 1.0]);
+		texture_normals.push([u0, 1.0]);
+		for _ in 0..4 {
+			vertex_normals.push([normal[0], normal[1], 0.0]);
+		}
+
+		faces.push([[a, a, a], [b, b, b], [c, c, c]]);
+		faces.push([[a, a, a], [c, c, c], [d, d, d]]);
+	}
+
+	// Caps use the polygon bounds for the texture coordinates
+	let (mut min, mut max) = ([f64::MAX, f64::MAX], [f64::MIN, f64::MIN]);
+	for p in points.iter() {
+		min = [min[0].min(p[0]), min[1].min(p[1])];
+		max = [max[0].max(p[0]), max[1].max(p[1])];
+	}
+	let size = [(max[0] - min[0]).max(1e-10), (max[1] - min[1]).max(1e-10)];
+
+	let triangles = triangulate_polygon(&points);
+	for &(z, normal) in [(z_front, 1.0), (z_back, -1.0)].iter() {
+		let base = vertices.len() + 1;
+		for p in points.iter() {
+			vertices.push([p[0], p[1], z]);
+			vertex_normals.push([0.0, 0.0, normal]);
+			texture_normals.push([(p[0] - min[0]) / size[0], (p[1] - min[1]) / size[1]]);
+		}
+
+		for t in triangles.iter() {
+			let (a, b, c) = (base + t[0], base + t[1], base + t[2]);
+			if normal > 0.0 {
+				faces.push([[a, a, a], [b, b, b], [c, c, c]]);
+			} else {
+				faces.push([[c, c, c], [b, b, b], [a, a, a]]);
+			}
+		}
+	}
+
+	RayTraceObjectModel::new(material, vertices, vertex_normals, texture_normals, faces)
+}
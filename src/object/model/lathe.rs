@@ -0,0 +1,145 @@
+use vecmath::Vector2;
+use vecmath::Vector3;
+use vecmath::{vec2_add, vec2_sub, vec2_scale, vec2_len, vec2_normalized};
+
+use material::RayTraceMaterial;
+use object::RayTraceObjectModel;
+
+use math_util::TWO_PI;
+
+pub enum RayTraceModelProfile {
+	// Points given as (radius, height) from bottom to top
+	Polyline(Vec<Vector2<f64>>),
+	// Catmull-Rom spline through the control points sampled with the given count per segment
+	Spline(Vec<Vector2<f64>>, usize)
+}
+
+// Profile corners sharper than this angle (cos) are not smoothed
+const CREASE_COS: f64 = 0.866;
+const THRESHOLD: f64 = 1e-10;
+
+impl RayTraceModelProfile {
+	pub fn get_points(&self) -> Vec<Vector2<f64>> {
+		match *self {
+			RayTraceModelProfile::Polyline(ref points) => points.clone(),
+			RayTraceModelProfile::Spline(ref points, samples) => {
+				if points.len() < 3 || samples < 2 {
+					return points.clone();
+				}
+
+				let mut result = Vec::with_capacity((points.len() - 1) * samples + 1);
+				for i in 0..points.len() - 1 {
+					let p0 = if i == 0 { points[0] } else { points[i - 1] };
+					let p1 = points[i];
+					let p2 = points[i + 1];
+					let p3 = if i + 2 < points.len() { points[i + 2] } else { points[i + 1] };
+
+					for s in 0..samples {
+						result.push(catmull_rom(p0, p1, p2, p3, s as f64 / samples as f64));
+					}
+				}
+
+				result.push(points[points.len() - 1]);
+				result
+			}
+		}
+	}
+}
+
+// Revolves the profile around the y-axis
+pub fn lathe_create(profile: &RayTraceModelProfile, segments: usize, material: Box<RayTraceMaterial>)
+		-> RayTraceObjectModel {
+	let points = profile.get_points();
+	let segments = segments.max(3);
+
+	let mut vertices = Vec::<Vector3<f64>>::new();
+	let mut vertex_normals = Vec::<Vector3<f64>>::new();
+	let mut texture_normals = Vec::<Vector2<f64>>::new();
+	let mut faces = Vec::<[Vector3<usize>; 3]>::new();
+
+	if points.len() < 2 {
+		return RayTraceObjectModel::new(material, vertices, vertex_normals, texture_normals, faces);
+	}
+
+	// Each profile segment gets its own vertices to allow sharp corners
+	let mut length = 0.0;
+	let mut profile_length = Vec::with_capacity(points.len());
+	let mut segment_normals = Vec::with_capacity(points.len() - 1);
+	for i in 0..points.len() - 1 {
+		let tangent = vec2_sub(points[i + 1], points[i]);
+		profile_length.push(length);
+		length += vec2_len(tangent);
+		segment_normals.push(if vec2_len(tangent) < THRESHOLD { [0.0, 0.0] } else {
+			vec2_normalized([tangent[1], -tangent[0]])
+		});
+	}
+	profile_length.push(length);
+
+	for s in 0..points.len() - 1 {
+		let start_normal = get_profile_normal(&segment_normals, s, true);
+		let end_normal = get_profile_normal(&segment_normals, s, false);
+		let profile = [
+			(points[s], start_normal, profile_length[s]),
+			(points[s + 1], end_normal, profile_length[s + 1])
+		];
+
+		let base = vertices.len();
+		for i in 0..segments + 1 {
+			let angle = TWO_PI * i as f64 / segments as f64;
+			let (sin, cos) = (angle.sin(), angle.cos());
+
+			for &(point, normal, distance) in profile.iter() {
+				vertices.push([point[0] * sin, point[1], point[0] * cos]);
+				vertex_normals.push([normal[0] * sin, normal[1], normal[0] * cos]);
+				let v = if length > 0.0 { distance / length } else { 0.0 };
+				texture_normals.push([i as f64 / segments as f64, v]);
+			}
+		}
+
+		let on_axis = [points[s][0].abs() < THRESHOLD, points[s + 1][0].abs() < THRESHOLD];
+		for i in 0..segments {
+			// Indices in the model are starting with 1
+			let a = base + i * 2 + 1;
+			let (b, c, d) = (a + 2, a + 3, a + 1);
+
+			if !on_axis[0] {
+				faces.push([[a, a, a], [b, b, b], [c, c, c]]);
+			}
+			if !on_axis[1] {
+				faces.push([[a, a, a], [c, c, c], [d, d, d]]);
+			}
+		}
+	}
+
+	RayTraceObjectModel::new(material, vertices, vertex_normals, texture_normals, faces)
+}
+
+fn get_profile_normal(normals: &Vec<Vector2<f64>>, segment: usize, start: bool) -> Vector2<f64> {
+	let normal = normals[segment];
+	let neighbour = if start {
+		if segment == 0 { return normal; }
+		normals[segment - 1]
+	} else {
+		if segment + 1 == normals.len() { return normal; }
+		normals[segment + 1]
+	};
+
+	if normal[0] * neighbour[0] + normal[1] * neighbour[1] < CREASE_COS {
+		normal
+	} else {
+		vec2_normalized(vec2_add(normal, neighbour))
+	}
+}
+
+fn catmull_rom(p0: Vector2<f64>, p1: Vector2<f64>, p2: Vector2<f64>, p3: Vector2<f64>, t: f64) -> Vector2<f64> {
+	let t2 = t * t;
+	let t3 = t2 * t;
+
+	let a = vec2_scale(p1, 2.0);
+	let b = vec2_scale(vec2_sub(p2, p0), t);
+	let c = vec2_scale(vec2_add(vec2_sub(vec2_scale(p0, 2.0), vec2_scale(p1, 5.0)),
+		vec2_sub(vec2_scale(p2, 4.0), p3)), t2);
+	let d = vec2_scale(vec2_add(vec2_sub(vec2_scale(p1, 3.0), p0), vec2_sub(p3, vec2_scale(p2, 3.0))), t3);
+
+	vec2_scale(vec2_add(vec2_add(a, b), vec2_add(c, d)), 0.5)
+}
@@ -1,49 +1,99 @@
+mod cache;
+mod displacement;
+mod extrude;
+mod lathe;
+mod mtl_loader;
+mod normals;
 mod obj_loader;
 mod octree;
+mod ply_loader;
+mod stl_loader;
+mod tangents;
 
 use self::octree::RayTraceOctree;
 use self::octree::RayTraceOctreeItem;
-
+use self::normals::generate_normals;
+use self::displacement::subdivide_model;
+use self::tangents::generate_tangents;
+
+pub use self::cache::cache_build;
+pub use self::cache::cache_decode;
+pub use self::cache::cache_encode;
+pub use self::cache::cache_load;
+pub use self::cache::cache_load_model;
+pub use self::cache::cache_save;
+pub use self::cache::hash_file;
+pub use self::extrude::extrude_create;
+pub use self::lathe::lathe_create;
+pub use self::lathe::RayTraceModelProfile;
+pub use self::mtl_loader::mtl_load;
+pub use self::mtl_loader::mtl_load_with;
+pub use self::normals::RayTraceModelNormalWeighting;
 pub use self::obj_loader::obj_load;
+pub use self::obj_loader::obj_load_with;
+pub use self::ply_loader::ply_load;
+pub use self::stl_loader::stl_load;
 
 use std::f64;
-use std::mem;
 use std::collections::BinaryHeap;
 
 use vecmath::Vector3;
 use vecmath::Vector2;
-use vecmath::{vec3_add, vec3_mul, vec3_sub, vec3_normalized};
-use vecmath::row_mat3_transform;
+use vecmath::Vector4;
+use vecmath::{vec3_add, vec3_sub, vec3_cross, vec3_normalized, vec3_scale};
 
 use aabb::AABB;
 use anim::RayTraceAnimation;
+use color::RayTraceColor;
 use hit::RayTraceHitHeapEntry;
 use hit::RayTraceRayHit;
+use hit::RayTraceShadingContext;
+use light::lights::RayTraceAreaLightShape;
+use material::RayTraceFootprint;
 use material::RayTraceMaterial;
+use material::RayTraceTexture;
 use object::RayTraceObject;
 use object::RayTraceHitable;
 use ray::RayTraceRay;
+use scene_file::RayTraceSceneWriteError;
+use scene_file::RayTraceSceneWriter;
 
-use math_util::rotate_xyz;
-use math_util::compute_plane_hit;
+use math_util::RAD_TO_DEG;
+use math_util::compute_barycentric;
+use math_util::compute_triangle_hit;
+use math_util::triangulate_polygon;
+use math_util::RayTraceTransform;
+use math_util::RayTraceTransformable;
 
 pub struct RayTraceObjectModel {
 	material: Box<RayTraceMaterial>,
+	materials: Vec<Box<RayTraceMaterial>>,
+	face_materials: Vec<usize>,
+	objects: Vec<RayTraceModelGroup>,
+	groups: Vec<RayTraceModelGroup>,
 	interpolation: RayTraceModelNormalInterpolation,
-	scale: Vector3<f64>,
-	position: Vector3<f64>,
-	rotation: Vector3<f64>,
-	offset: Vector3<f64>,
-	anim_pos: Option<Box<RayTraceAnimation<Vector3<f64>>>>,
-	anim_rot: Option<Box<RayTraceAnimation<Vector3<f64>>>>,
-	anim_scale: Option<Box<RayTraceAnimation<Vector3<f64>>>>,
+	normal_weighting: RayTraceModelNormalWeighting,
+	crease_angle: f64,
+	smoothing_groups: Vec<u32>,
+	back_face_culling: bool,
+	transform: RayTraceTransform,
 	vertices: Vec<Vector3<f64>>,
 	vertex_normals: Vec<Vector3<f64>>,
 	texture_normals: Vec<Vector2<f64>>,
+	vertex_colors: Vec<RayTraceColor>,
 	faces: Vec<[Vector3<usize>; 3]>,
+	// Generated on initialization, see generate_tangents
+	tangents: Vec<Vector4<f64>>,
+	face_tangents: Vec<[usize; 3]>,
 	data: Option<WorkingData>
 }
 
+// Named set of faces like the objects and groups of an .obj file
+pub struct RayTraceModelGroup {
+	name: String,
+	faces: Vec<usize>
+}
+
 pub enum RayTraceModelNormalInterpolation {
 	Average,
 	Linear
@@ -51,51 +101,80 @@ pub enum RayTraceModelNormalInterpolation {
 
 struct WorkingData {
 	aabb: Option<AABB>,
-	tree: Option<RayTraceOctree>,
-	vertex_normals: Vec<Vector3<f64>>
+	object_aabb: Option<AABB>,
+	tree: Option<RayTraceOctree>
 }
 
 const AABB_MIN_DIST: Vector3<f64> = [0.001, 0.001, 0.001];
+const DEFAULT_CREASE_ANGLE: f64 = f64::consts::FRAC_PI_3;
 
 impl RayTraceObjectModel {
-	pub fn set_rotation(&mut self, rotation: Vector3<f64>) {
-		self.rotation = rotation;
+	pub fn new(material: Box<RayTraceMaterial>, vertices: Vec<Vector3<f64>>, vertex_normals: Vec<Vector3<f64>>,
+			texture_normals: Vec<Vector2<f64>>, faces: Vec<[Vector3<usize>; 3]>) -> Self {
+		Self {
+			material: material,
+			materials: Vec::new(),
+			face_materials: Vec::new(),
+			objects: Vec::new(),
+			groups: Vec::new(),
+			interpolation: RayTraceModelNormalInterpolation::Average,
+			normal_weighting: RayTraceModelNormalWeighting::Angle,
+			crease_angle: DEFAULT_CREASE_ANGLE,
+			smoothing_groups: Vec::new(),
+			back_face_culling: false,
+			transform: RayTraceTransform::new(),
+			vertices: vertices,
+			vertex_normals: vertex_normals,
+			texture_normals: texture_normals,
+			vertex_colors: Vec::new(),
+			faces: faces,
+			tangents: Vec::new(),
+			face_tangents: Vec::new(),
+			data: None
+		}
 	}
 
-	pub fn set_position(&mut self, position: Vector3<f64>) {
-		self.position = position;
+	// Face materials are indices into the materials starting with 1, the value 0 selects the default material
+	pub fn set_materials(&mut self, materials: Vec<Box<RayTraceMaterial>>, face_materials: Vec<usize>) {
+		self.materials = materials;
+		self.face_materials = face_materials;
 	}
 
-	pub fn set_scale(&mut self, scale: Vector3<f64>) {
-		self.scale = scale;
+	pub fn get_materials(&self) -> &Vec<Box<RayTraceMaterial>> {
+		&self.materials
 	}
 
-	pub fn set_offset(&mut self, offset: Vector3<f64>) {
-		self.offset = offset;
+	pub fn get_face_materials(&self) -> &Vec<usize> {
+		&self.face_materials
 	}
 
-	pub fn set_anim_pos_opt(&mut self, anim: Option<Box<RayTraceAnimation<Vector3<f64>>>>) {
-		self.anim_pos = anim;
+	// Colors for each vertex which are multiplied with the color of the material
+	pub fn set_vertex_colors(&mut self, vertex_colors: Vec<RayTraceColor>) {
+		self.vertex_colors = vertex_colors;
 	}
 
-	pub fn set_anim_pos(&mut self, anim: Box<RayTraceAnimation<Vector3<f64>>>) {
-		self.anim_pos = Some(anim);
+	pub fn get_vertex_colors(&self) -> &Vec<RayTraceColor> {
+		&self.vertex_colors
 	}
 
-	pub fn set_anim_rot_opt(&mut self, anim: Option<Box<RayTraceAnimation<Vector3<f64>>>>) {
-		self.anim_rot = anim;
+	pub fn set_objects(&mut self, objects: Vec<RayTraceModelGroup>) {
+		self.objects = objects;
 	}
 
-	pub fn set_anim_rot(&mut self, anim: Box<RayTraceAnimation<Vector3<f64>>>) {
-		self.anim_rot = Some(anim);
+	pub fn get_objects(&self) -> &Vec<RayTraceModelGroup> {
+		&self.objects
 	}
 
-	pub fn set_anim_scale_opt(&mut self, anim: Option<Box<RayTraceAnimation<Vector3<f64>>>>) {
-		self.anim_scale = anim;
+	pub fn set_groups(&mut self, groups: Vec<RayTraceModelGroup>) {
+		self.groups = groups;
 	}
 
-	pub fn set_anim_scale(&mut self, anim: Box<RayTraceAnimation<Vector3<f64>>>) {
-		self.anim_scale = Some(anim);
+	pub fn get_groups(&self) -> &Vec<RayTraceModelGroup> {
+		&self.groups
+	}
+
+	pub fn get_group(&self, name: &str) -> Option<&RayTraceModelGroup> {
+		self.groups.iter().find(|g| g.name == name)
 	}
 
 	pub fn set_interpolation(&mut self, interpolation: RayTraceModelNormalInterpolation) {
@@ -106,75 +185,290 @@ impl RayTraceObjectModel {
 		&self.interpolation
 	}
 
-	fn transform_data(&self, data: &mut WorkingData) {
-		// Reset stored data
-		data.aabb = None;
-		data.vertex_normals.clear();
+	pub fn set_normal_weighting(&mut self, normal_weighting: RayTraceModelNormalWeighting) {
+		self.normal_weighting = normal_weighting;
+	}
 
-		let rot_matrix = rotate_xyz(self.rotation);
+	pub fn get_normal_weighting(&self) -> &RayTraceModelNormalWeighting {
+		&self.normal_weighting
+	}
 
-		let mut vertices = Vec::with_capacity(self.vertices.len());
-		for vert in self.vertices.iter() {
-			let vec = vec3_add(row_mat3_transform(rot_matrix, vec3_mul(vec3_sub(*vert, self.offset), self.scale)),
-				self.position);
-			vertices.push(vec);
+	// Maximum angle in radians between faces whose shared vertices get a smooth normal
+	pub fn set_crease_angle(&mut self, crease_angle: f64) {
+		self.crease_angle = crease_angle;
+	}
+
+	pub fn get_crease_angle(&self) -> f64 {
+		self.crease_angle
+	}
+
+	// Smoothing group for each face, faces in group 0 are shaded flat
+	pub fn set_smoothing_groups(&mut self, smoothing_groups: Vec<u32>) {
+		self.smoothing_groups = smoothing_groups;
+	}
+
+	pub fn get_smoothing_groups(&self) -> &Vec<u32> {
+		&self.smoothing_groups
+	}
+
+	// Computes the normals of all faces without normal data, this is done automatically on initialization
+	pub fn generate_normals(&mut self) {
+		let count = self.vertex_normals.len();
+		generate_normals(&self.vertices, &mut self.vertex_normals, &mut self.faces, &self.smoothing_groups,
+			&self.normal_weighting, self.crease_angle);
+
+		if self.vertex_normals.len() > count {
+			info!("Generated {} vertex normals", self.vertex_normals.len() - count);
+		}
+	}
+
+	// Computes tangents for normal mapping which match those of MikkTSpace, this is done automatically on
+	// initialization
+	pub fn generate_tangents(&mut self) {
+		let (tangents, face_tangents) = generate_tangents(&self.vertices, &self.vertex_normals, &self.texture_normals,
+			&self.faces);
+		self.tangents = tangents;
+		self.face_tangents = face_tangents;
+	}
+
+	// Subdivides every face into 4^subdivisions faces and moves the vertices along their normals by the height in
+	// the red channel of the map times the scale. The normals are generated again afterwards and vertices without
+	// texture coordinates keep their position.
+	pub fn displace(&mut self, map: &RayTraceTexture, scale: f64, subdivisions: usize) {
+		for _ in 0..subdivisions {
+			subdivide_model(self);
+		}
+
+		let mut offsets: Vec<Option<Vector3<f64>>> = vec![None; self.vertices.len()];
+		for face in self.faces.iter() {
+			let points = [self.vertices[face[0][0] - 1], self.vertices[face[1][0] - 1], self.vertices[face[2][0] - 1]];
+			let face_normal = vec3_cross(vec3_sub(points[1], points[0]), vec3_sub(points[2], points[0]));
+
+			for corner in face.iter() {
+				if corner[2] == 0 || offsets[corner[0] - 1].is_some() {
+					continue;
+				}
+
+				// Vertices on seams of the texture coordinates use the first corner to stay connected
+				let normal = if corner[1] == 0 { face_normal } else { self.vertex_normals[corner[1] - 1] };
+				let texcoord = self.texture_normals[corner[2] - 1];
+				let height = map.sample(texcoord[0], texcoord[1]).get_r() as f64;
+				offsets[corner[0] - 1] = Some(vec3_scale(vec3_normalized(normal), height * scale));
+			}
+		}
+
+		for (vertex, offset) in self.vertices.iter_mut().zip(offsets.iter()) {
+			if let Some(offset) = *offset {
+				*vertex = vec3_add(*vertex, offset);
+			}
+		}
 
-			match data.aabb {
+		self.vertex_normals.clear();
+		for face in self.faces.iter_mut() {
+			for corner in face.iter_mut() {
+				corner[1] = 0;
+			}
+		}
+		self.tangents.clear();
+		self.face_tangents.clear();
+		self.data = None;
+	}
+
+	// Ignores triangles whose vertices are ordered clockwise as seen from the ray
+	pub fn set_back_face_culling(&mut self, back_face_culling: bool) {
+		self.back_face_culling = back_face_culling;
+	}
+
+	pub fn get_back_face_culling(&self) -> bool {
+		self.back_face_culling
+	}
+
+	fn generate_missing_normals(&mut self) {
+		if self.faces.iter().any(|f| f.iter().any(|v| v[1] == 0)) {
+			self.generate_normals();
+		}
+	}
+
+	fn create_working_data(&mut self) -> WorkingData {
+		self.generate_missing_normals();
+
+		let mut data = WorkingData {
+			aabb: None,
+			object_aabb: None,
+			tree: None
+		};
+
+		self.build_tree(&mut data);
+		data
+	}
+
+	// Tangent and bitangent in object space at the barycentric position on the face
+	fn get_face_tangents(&self, face: usize, barycentric: Vector3<f64>, normal: Vector3<f64>)
+			-> Option<[Vector3<f64>; 2]> {
+		let indices = match self.face_tangents.get(face) {
+			Some(indices) if indices.iter().all(|i| *i != 0) => indices,
+			_ => { return None; }
+		};
+
+		let mut tangent = [0.0, 0.0, 0.0];
+		for i in 0..3 {
+			let t = self.tangents[indices[i] - 1];
+			tangent = vec3_add(tangent, vec3_scale([t[0], t[1], t[2]], barycentric[i]));
+		}
+
+		let sign = self.tangents[indices[0] - 1][3];
+		Some([tangent, vec3_scale(vec3_cross(normal, tangent), sign)])
+	}
+
+	fn get_face_material(&self, face: usize) -> &Box<RayTraceMaterial> {
+		match self.face_materials.get(face) {
+			Some(&index) if index > 0 && index <= self.materials.len() => &self.materials[index - 1],
+			_ => &self.material
+		}
+	}
+
+	fn build_tree(&self, data: &mut WorkingData) {
+		// The tree is built once in object space and reused for every frame
+		let mut object_aabb: Option<AABB> = None;
+		for vert in self.vertices.iter() {
+			match object_aabb {
 				Some(ref mut aabb) => {
-					aabb.expand(vec3_sub(vec, AABB_MIN_DIST));
-					aabb.expand(vec3_add(vec, AABB_MIN_DIST));
+					aabb.expand(vec3_sub(*vert, AABB_MIN_DIST));
+					aabb.expand(vec3_add(*vert, AABB_MIN_DIST));
 				},
 				None => {
-					data.aabb = Some(AABB::new(vec3_sub(vec, AABB_MIN_DIST), vec3_add(vec, AABB_MIN_DIST)));
+					object_aabb = Some(AABB::new(vec3_sub(*vert, AABB_MIN_DIST), vec3_add(*vert, AABB_MIN_DIST)));
 				}
 			}
 		}
 
-		for norm in self.vertex_normals.iter() {
-			data.vertex_normals.push(vec3_normalized(row_mat3_transform(rot_matrix, *norm)));
-		}
+		let mut tree = match object_aabb {
+			Some(ref aabb) => RayTraceOctree::new(aabb.clone()),
+			None => {
+				return; // Empty model
+			}
+		};
 
-		let mut tree = RayTraceOctree::new(data.aabb.as_ref().unwrap().clone());
 		for (id, face) in self.faces.iter().enumerate() {
-			let v1 = vertices[face[0][0] - 1];
-			let v2 = vertices[face[1][0] - 1];
-			let v3 = vertices[face[2][0] - 1];
+			let v1 = self.vertices[face[0][0] - 1];
+			let v2 = self.vertices[face[1][0] - 1];
+			let v3 = self.vertices[face[2][0] - 1];
 
 			if tree.add([v1, v2, v3]) != id {
 				panic!("Wrong index in Octree!");
 			}
 		}
 
+		data.object_aabb = object_aabb;
 		data.tree = Some(tree);
 	}
+
+	#[deprecated(note = "Use the transform instead")]
+	pub fn get_position(&self) -> &Vector3<f64> {
+		self.transform.get_translation()
+	}
+
+	#[deprecated(note = "Use the transform instead")]
+	pub fn set_position(&mut self, position: Vector3<f64>) {
+		self.transform.set_translation(position);
+	}
+
+	#[deprecated(note = "Use the transform instead")]
+	pub fn set_rotation(&mut self, rotation: Vector3<f64>) {
+		self.transform.set_rotation(rotation);
+	}
+
+	#[deprecated(note = "Use the transform instead")]
+	pub fn set_scale(&mut self, scale: Vector3<f64>) {
+		self.transform.set_scale(scale);
+	}
+
+	#[deprecated(note = "Use the transform instead")]
+	pub fn set_offset(&mut self, offset: Vector3<f64>) {
+		self.transform.set_pivot(offset);
+	}
+
+	#[deprecated(note = "Use the transform instead")]
+	pub fn set_anim_pos_opt(&mut self, anim: Option<Box<RayTraceAnimation<Vector3<f64>>>>) {
+		self.transform.set_anim_translation_opt(anim);
+	}
+
+	#[deprecated(note = "Use the transform instead")]
+	pub fn set_anim_pos(&mut self, anim: Box<RayTraceAnimation<Vector3<f64>>>) {
+		self.transform.set_anim_translation(anim);
+	}
+
+	#[deprecated(note = "Use the transform instead")]
+	pub fn set_anim_rot_opt(&mut self, anim: Option<Box<RayTraceAnimation<Vector3<f64>>>>) {
+		self.transform.set_anim_rotation_opt(anim);
+	}
+
+	#[deprecated(note = "Use the transform instead")]
+	pub fn set_anim_rot(&mut self, anim: Box<RayTraceAnimation<Vector3<f64>>>) {
+		self.transform.set_anim_rotation(anim);
+	}
+
+	#[deprecated(note = "Use the transform instead")]
+	pub fn set_anim_scale_opt(&mut self, anim: Option<Box<RayTraceAnimation<Vector3<f64>>>>) {
+		self.transform.set_anim_scale_opt(anim);
+	}
+
+	#[deprecated(note = "Use the transform instead")]
+	pub fn set_anim_scale(&mut self, anim: Box<RayTraceAnimation<Vector3<f64>>>) {
+		self.transform.set_anim_scale(anim);
+	}
 }
 
-impl RayTraceObject for RayTraceObjectModel {
-	fn init(&mut self, frame: usize) {
-		if let Some(ref anim_pos) = self.anim_pos {
-			self.position = anim_pos.next_frame(frame);
-		}
-		if let Some(ref anim_rot) = self.anim_rot {
-			self.rotation = anim_rot.next_frame(frame);
-		}
-		if let Some(ref anim_scale) = self.anim_scale {
-			self.scale = anim_scale.next_frame(frame);
+impl RayTraceModelGroup {
+	pub fn new(name: String) -> Self {
+		Self {
+			name: name,
+			faces: Vec::new()
 		}
+	}
 
-		let mut working_data = None;
-		if self.data.is_some() {
-			mem::swap(&mut working_data, &mut self.data);
+	pub fn new_with(name: String, faces: Vec<usize>) -> Self {
+		Self {
+			name: name,
+			faces: faces
 		}
+	}
 
-		let mut data = if working_data.is_some() { working_data.unwrap() } else {
-			WorkingData {
-				aabb: None,
-				tree: None,
-				vertex_normals: Vec::new()
-			}
+	pub fn get_name(&self) -> &str {
+		&self.name
+	}
+
+	pub fn get_faces(&self) -> &Vec<usize> {
+		&self.faces
+	}
+}
+
+impl RayTraceTransformable for RayTraceObjectModel {
+	fn get_transform(&self) -> &RayTraceTransform {
+		&self.transform
+	}
+
+	fn get_transform_mut(&mut self) -> &mut RayTraceTransform {
+		&mut self.transform
+	}
+}
+
+impl RayTraceObject for RayTraceObjectModel {
+	fn init(&mut self, frame: usize) {
+		self.transform.init(frame);
+
+		let mut data = match self.data.take() {
+			Some(data) => data,
+			None => self.create_working_data()
 		};
+		if self.face_tangents.len() != self.faces.len() {
+			self.generate_tangents();
+		}
 
-		self.transform_data(&mut data);
+		data.aabb = match data.object_aabb {
+			Some(ref aabb) => Some(self.transform.transform_aabb(*aabb.get_start(), *aabb.get_end())),
+			None => None
+		};
 		self.data = Some(data);
 	}
 
@@ -185,6 +479,65 @@ impl RayTraceObject for RayTraceObjectModel {
 			panic!("Model was not initialized!");
 		}
 	}
+
+	// The faces with emissive materials
+	fn get_area_light(&self) -> Option<RayTraceAreaLightShape> {
+		let triangles: Vec<[Vector3<f64>; 3]> = self.faces.iter().enumerate()
+			.filter(|&(i, _)| self.get_face_material(i).is_emissive())
+			.map(|(_, face)| [
+				self.transform.transform_position(self.vertices[face[0][0] - 1]),
+				self.transform.transform_position(self.vertices[face[1][0] - 1]),
+				self.transform.transform_position(self.vertices[face[2][0] - 1])
+			])
+			.collect();
+
+		if triangles.is_empty() {
+			None
+		} else {
+			Some(RayTraceAreaLightShape::Triangles(triangles))
+		}
+	}
+
+	fn serialize(&self, writer: &mut RayTraceSceneWriter) -> Result<(), RayTraceSceneWriteError> {
+		// Models are written inline as their source files are not known
+		writer.set_type("mesh");
+		writer.set_vec3_list("vertices", &self.vertices);
+		writer.set_vec3_list("normals", &self.vertex_normals);
+		writer.set_vec2_list("texcoords", &self.texture_normals);
+
+		let faces: Vec<Vec<usize>> = self.faces.iter()
+			.map(|f| f.iter().flat_map(|v| v.iter().cloned()).collect())
+			.collect();
+		writer.set_usize_rows("faces", &faces);
+
+		if !self.vertex_colors.is_empty() {
+			writer.set_color_list("vertex_colors", &self.vertex_colors);
+		}
+		if !self.smoothing_groups.is_empty() {
+			let groups: Vec<usize> = self.smoothing_groups.iter().map(|g| *g as usize).collect();
+			writer.set_usize_list("smoothing_groups", &groups);
+		}
+
+		try!(writer.set_f64("crease_angle", self.crease_angle * RAD_TO_DEG));
+		writer.set_str("normal_weighting", match self.normal_weighting {
+			RayTraceModelNormalWeighting::Angle => "angle",
+			RayTraceModelNormalWeighting::Area => "area"
+		});
+		writer.set_str("interpolation", match self.interpolation {
+			RayTraceModelNormalInterpolation::Average => "average",
+			RayTraceModelNormalInterpolation::Linear => "linear"
+		});
+		writer.set_bool("back_face_culling", self.back_face_culling);
+
+		try!(writer.set_material("material", &*self.material));
+		if !self.materials.is_empty() {
+			try!(writer.set_material_list("materials", &self.materials));
+			writer.set_usize_list("face_materials", &self.face_materials);
+		}
+
+		writer.set_transform(&self.transform);
+		Ok(())
+	}
 }
 
 impl RayTraceHitable for RayTraceObjectModel {
@@ -193,7 +546,15 @@ impl RayTraceHitable for RayTraceObjectModel {
 			// Collect all ray hits
 			let mut ray_hits = BinaryHeap::<RayTraceHitHeapEntry<RayTraceRayHit>>::new();
 
-			for hit in data.tree.as_ref().unwrap().get_hits(ray) {
+			let tree = match data.tree {
+				Some(ref tree) => tree,
+				None => { return None; }
+			};
+
+			let world_ray = ray;
+			let ray = &self.transform.inverse_transform_ray(world_ray);
+
+			for hit in tree.get_hits(ray) {
 				let face;
 
 				match hit {
@@ -209,13 +570,8 @@ impl RayTraceHitable for RayTraceObjectModel {
 					}
 				}
 
-				let vectors = face.get_vectors();
-				if let Some((dist, vec1, vec2)) = compute_plane_hit(ray, *face.get_position(), vectors[0], vectors[1]) {
-					if vec1 < 0.0 || vec1 > 1.0 || vec2 < 0.0 || vec2 > 1.0 || vec1 + vec2 > 1.0 {
-						continue; // Missed triangle
-					}
-
-					let normals = face.get_normals(&self.faces, &data.vertex_normals, &self.texture_normals);
+				if let Some((dist, f)) = compute_triangle_hit(ray, face.get_vertices(), self.back_face_culling) {
+					let normals = face.get_normals(&self.faces, &self.vertex_normals, &self.texture_normals);
 
 					let surface_normal;
 					let texture_normal;
@@ -232,14 +588,6 @@ impl RayTraceHitable for RayTraceObjectModel {
 							];
 						},
 						RayTraceModelNormalInterpolation::Linear => {
-							let mut f = [1.0 - vec1 - vec2, vec1, vec2];
-							let sum = f[0] + f[1] + f[2];
-							if sum != 0.0 {
-								f[0] /= sum;
-								f[1] /= sum;
-								f[2] /= sum;
-							}
-
 							surface_normal = [
 								(normals[0].0[0] * f[0] + normals[1].0[0] * f[1] + normals[2].0[0] * f[2]),
 								(normals[0].0[1] * f[0] + normals[1].0[1] * f[1] + normals[2].0[1] * f[2]),
@@ -255,10 +603,52 @@ impl RayTraceHitable for RayTraceObjectModel {
 						}*/
 					}
 
-					let material_hit = self.material.get_hit(texture_normal[0], texture_normal[1]);
+					// Texture coordinates only change within a face if they are interpolated
+					let position = ray.get_position_on_ray(dist);
+					let footprint = match self.interpolation {
+						RayTraceModelNormalInterpolation::Linear => {
+							let vertices = face.get_vertices();
+							RayTraceFootprint::compute(ray, position, *face.get_normal(), |p| {
+								let b = compute_barycentric(vertices, p);
+								[normals[0].1[0] * b[0] + normals[1].1[0] * b[1] + normals[2].1[0] * b[2],
+									normals[0].1[1] * b[0] + normals[1].1[1] * b[1] + normals[2].1[1] * b[2]]
+							})
+						},
+						RayTraceModelNormalInterpolation::Average => None
+					};
+
+					let tangents = self.get_face_tangents(face.get_id(), f, surface_normal);
+					let surface_normal = self.transform.transform_normal(surface_normal);
+					let geometric_normal = self.transform.transform_normal(*face.get_normal());
+					let mut context = RayTraceShadingContext::new(world_ray, dist, position, surface_normal,
+						texture_normal);
+					context.set_geometric_normal(geometric_normal);
+					if let Some(tangents) = tangents {
+						context.set_tangents(self.transform.transform_direction(tangents[0]),
+							self.transform.transform_direction(tangents[1]));
+					}
+					context.set_footprint(footprint);
+					context.set_primitive(face.get_id());
+
+					let material = self.get_face_material(face.get_id());
+					let mut material_hit = material.get_hit_context(&context);
+
+					if self.vertex_colors.len() == self.vertices.len() {
+						let indices = self.faces[face.get_id()];
+						let mut color = RayTraceColor::new();
+						for i in 0..3 {
+							color += &self.vertex_colors[indices[i][0] - 1] * f[i] as f32;
+						}
+
+						let color = material_hit.get_color() * &color;
+						material_hit.set_color(color);
+					}
+
+					let mut ray_hit = RayTraceRayHit::new(dist, *context.get_position(), surface_normal, material_hit);
+					ray_hit.set_geometric_normal(geometric_normal);
+					ray_hit.set_triangle(face.get_id(), f);
 
-					ray_hits.push(RayTraceHitHeapEntry::new(dist,
-							RayTraceRayHit::new(dist, ray.get_position_on_ray(dist), surface_normal, material_hit)));
+					ray_hits.push(RayTraceHitHeapEntry::new(dist, ray_hit));
 				}
 			}
 
@@ -272,4 +662,58 @@ impl RayTraceHitable for RayTraceObjectModel {
 			panic!("Model was not initialized!");
 		}
 	}
-}
\ No newline at end of file
+}
+
+// Splits a polygon into triangles by projecting it onto the plane of its dominant normal axis
+fn triangulate_face(face: &Vec<Vector3<usize>>, vertices: &Vec<Vector3<f64>>) -> Vec<[Vector3<usize>; 3]> {
+	if face.len() == 3 {
+		return vec![[face[0], face[1], face[2]]];
+	}
+
+	// Fall back to a triangle fan if the indices are invalid, the validation reports them later on
+	if face.iter().any(|v| v[0] > vertices.len()) {
+		return (1..face.len() - 1).map(|i| [face[0], face[i], face[i + 1]]).collect();
+	}
+
+	// Newell's method gives a robust normal for non planar polygons
+	let mut normal = [0.0, 0.0, 0.0];
+	for i in 0..face.len() {
+		let (p, q) = (vertices[face[i][0] - 1], vertices[face[(i + 1) % face.len()][0] - 1]);
+		normal = vec3_add(normal, vec3_cross(p, q));
+	}
+
+	let axis = if normal[0].abs() > normal[1].abs() {
+		if normal[0].abs() > normal[2].abs() { 0 } else { 2 }
+	} else {
+		if normal[1].abs() > normal[2].abs() { 1 } else { 2 }
+	};
+	let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+
+	let points: Vec<Vector2<f64>> = face.iter()
+		.map(|f| vertices[f[0] - 1])
+		.map(|p| [p[u], p[v]])
+		.collect();
+
+	triangulate_polygon(&points).iter()
+		.map(|t| [face[t[0]], face[t[1]], face[t[2]]])
+		.collect()
+}
+
+fn validate_model(faces: &Vec<[Vector3<usize>; 3]>, vert: usize, vert_norm: usize, text_norm: usize)
+		-> Option<(usize, &str)> {
+	for (i, face) in faces.iter().enumerate() {
+		for v in face.iter() {
+			if v[0] > vert || v[0] == 0 {
+				return Some((i, "vertex"));
+			}
+			if v[1] > vert_norm {
+				return Some((i, "normal"));
+			}
+			if v[2] > text_norm {
+				return Some((i, "texture"));
+			}
+		}
+	}
+
+	return None;
+}
@@ -0,0 +1,163 @@
+use std::fs::File;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Error as IOError;
+use std::io::ErrorKind;
+use std::path::Path;
+
+use color::RayTraceColor;
+use material::RayTraceMtlMaterial;
+
+use sink::image;
+use sink::image::RgbaImage;
+
+// Loads all materials of a Wavefront .mtl file with their names
+pub fn mtl_load(file_name: &str) -> Result<Vec<(String, RayTraceMtlMaterial)>, IOError> {
+	mtl_load_with(file_name, |texture_file| image::open(texture_file).map(|texture| texture.to_rgba()))
+}
+
+// Reads the textures with the given function, e.g. to reuse textures which were loaded before
+pub fn mtl_load_with<F>(file_name: &str, mut load_texture: F) -> Result<Vec<(String, RayTraceMtlMaterial)>, IOError>
+		where F: FnMut(&str) -> Result<RgbaImage, image::ImageError> {
+	let mut materials = Vec::<(String, RayTraceMtlMaterial)>::new();
+
+	let file = try!(File::open(file_name));
+	let reader = BufReader::new(file);
+	let directory = Path::new(file_name).parent().unwrap_or(Path::new(""));
+
+	let mut line_number = 0;
+
+	for line in reader.lines() {
+		let line = try!(line);
+		line_number += 1;
+
+		let line = match line.find('#') {
+			Some(index) => &line[..index],
+			None => &line[..]
+		};
+
+		let data: Vec<&str> = line.split_whitespace().collect();
+		if data.len() == 0 {
+			continue;
+		}
+
+		if data[0] == "newmtl" {
+			if data.len() < 2 {
+				return format_err("Missing material name", line_number);
+			}
+
+			materials.push((data[1..].join(" "), RayTraceMtlMaterial::new()));
+			continue;
+		}
+
+		let material = match materials.last_mut() {
+			Some(&mut (_, ref mut material)) => material,
+			None => {
+				return format_err("Material statement before the first newmtl", line_number);
+			}
+		};
+
+		match data[0] {
+			"Kd" => {
+				let (r, g, b) = try!(parse_color(&data, line_number));
+				let a = material.get_diffuse().get_a();
+				material.set_diffuse(RayTraceColor::new_with(r, g, b, a));
+			},
+			"Ks" => {
+				let (r, g, b) = try!(parse_color(&data, line_number));
+				material.set_specular(RayTraceColor::new_with(r, g, b, 1.0));
+			},
+			"Ns" => {
+				material.set_shininess(try!(parse_value(&data, line_number)));
+			},
+			"d" => {
+				material.set_dissolve(try!(parse_value(&data, line_number)));
+			},
+			"Tr" => {
+				material.set_dissolve(1.0 - try!(parse_value(&data, line_number)));
+			},
+			"Ni" => {
+				material.set_optical_density(try!(parse_value(&data, line_number)));
+			},
+			"illum" => {
+				match data.get(1).and_then(|v| v.parse::<usize>().ok()) {
+					Some(illumination) => { material.set_illumination(illumination); },
+					None => { return format_err("Invalid illumination model", line_number); }
+				}
+			},
+			"map_Kd" => {
+				let texture_file = try!(parse_texture_file(&data, line_number));
+				let texture_file = directory.join(texture_file.replace("\\", "/"));
+				let texture_file = texture_file.to_string_lossy();
+				match load_texture(&texture_file) {
+					Ok(texture) => { material.set_diffuse_map(Some(texture)); },
+					Err(err) => {
+						warn!("Could not load texture {}: {:?}", texture_file, err);
+					}
+				}
+			},
+			_ => {
+				info!("Ignored line {}: {}", line_number, line);
+			}
+		}
+	}
+
+	info!("Loaded {} materials from {}", materials.len(), file_name);
+
+	Ok(materials)
+}
+
+fn parse_color(data: &Vec<&str>, line: usize) -> Result<(f32, f32, f32), IOError> {
+	if data.len() < 2 || data.len() > 4 {
+		return format_err("Invalid color", line);
+	}
+
+	// A single value is used for all channels
+	let r = try!(parse_value(data, line));
+	let g = if data.len() > 2 { data[2].parse::<f32>().ok() } else { Some(r) };
+	let b = if data.len() > 3 { data[3].parse::<f32>().ok() } else { g };
+
+	match g.and_then(|g| b.and_then(|b| Some((r, g, b)))) {
+		Some(color) => Ok(color),
+		None => format_err("Invalid color value", line)
+	}
+}
+
+// Skips the options like -s or -o which precede the file name, the rest of the statement is the file name which
+// may contain spaces
+fn parse_texture_file(data: &Vec<&str>, line: usize) -> Result<String, IOError> {
+	let mut i = 1;
+	while i < data.len() && data[i].starts_with('-') {
+		let values = match data[i] {
+			"-blendu" | "-blendv" | "-bm" | "-boost" | "-cc" | "-clamp" | "-imfchan" | "-texres" | "-type" => 1,
+			"-mm" => 2,
+			// Up to three coordinates of which only the first one is required
+			"-o" | "-s" | "-t" => {
+				1 + data[i + 1..].iter().skip(1).take(2).take_while(|v| v.parse::<f32>().is_ok()).count()
+			},
+			option => { return format_err(&format!("Unknown texture option {}", option), line); }
+		};
+
+		if i + values >= data.len() {
+			return format_err(&format!("Missing value of texture option {}", data[i]), line);
+		}
+		i += values + 1;
+	}
+
+	if i >= data.len() {
+		return format_err("Missing texture file", line);
+	}
+
+	Ok(data[i..].join(" "))
+}
+
+fn parse_value(data: &Vec<&str>, line: usize) -> Result<f32, IOError> {
+	match data.get(1).and_then(|v| v.parse::<f32>().ok()) {
+		Some(value) => Ok(value),
+		None => format_err("Invalid value", line)
+	}
+}
+
+fn format_err<T>(message: &str, line: usize) -> Result<T, IOError> {
+	Err(IOError::new(ErrorKind::InvalidData, format!("Error on line {}: {}", line, message)))
+}
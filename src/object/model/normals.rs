@@ -0,0 +1,105 @@
+use vecmath::Vector3;
+use vecmath::{vec3_sub, vec3_add, vec3_cross, vec3_dot, vec3_len, vec3_scale};
+
+pub enum RayTraceModelNormalWeighting {
+	// Faces contribute with the angle of their corner at the vertex
+	Angle,
+	// Faces contribute with their area
+	Area
+}
+
+// Computes normals for all face corners without a normal and stores their indices in the faces.
+// Faces are only smoothed with neighbours of the same smoothing group whose normals differ by less than the
+// crease angle, the smoothing group 0 is shaded flat.
+pub fn generate_normals(vertices: &Vec<Vector3<f64>>, normals: &mut Vec<Vector3<f64>>,
+		faces: &mut Vec<[Vector3<usize>; 3]>, smoothing_groups: &Vec<u32>, weighting: &RayTraceModelNormalWeighting,
+		crease_angle: f64) {
+	// The length of the cross product is twice the area of the face
+	let face_normals: Vec<Vector3<f64>> = faces.iter()
+		.map(|f| {
+			let (a, b, c) = (vertices[f[0][0] - 1], vertices[f[1][0] - 1], vertices[f[2][0] - 1]);
+			vec3_cross(vec3_sub(b, a), vec3_sub(c, a))
+		})
+		.collect();
+
+	let mut vertex_faces = vec![Vec::<(usize, usize)>::new(); vertices.len()];
+	for (i, face) in faces.iter().enumerate() {
+		for corner in 0..3 {
+			vertex_faces[face[corner][0] - 1].push((i, corner));
+		}
+	}
+
+	// Models without smoothing groups are smoothed everywhere
+	let get_group = |face: usize| smoothing_groups.get(face).cloned().unwrap_or(1);
+	let cos_crease = crease_angle.cos();
+
+	for vertex in 0..vertices.len() {
+		// Corners sharing the same normal reuse it
+		let first = normals.len();
+
+		for &(face, corner) in vertex_faces[vertex].iter() {
+			if faces[face][corner][1] != 0 {
+				continue;
+			}
+
+			let length = vec3_len(face_normals[face]);
+			if length == 0.0 {
+				continue; // Degenerated faces keep the fallback to the face normal
+			}
+
+			let own = vec3_scale(face_normals[face], 1.0 / length);
+			let group = get_group(face);
+
+			let mut normal = own;
+			if group != 0 {
+				let mut sum = [0.0, 0.0, 0.0];
+				for &(other, other_corner) in vertex_faces[vertex].iter() {
+					let other_length = vec3_len(face_normals[other]);
+					if other_length == 0.0 || get_group(other) != group {
+						continue;
+					}
+
+					let other_normal = vec3_scale(face_normals[other], 1.0 / other_length);
+					if other != face && vec3_dot(own, other_normal) < cos_crease {
+						continue;
+					}
+
+					sum = vec3_add(sum, match *weighting {
+						RayTraceModelNormalWeighting::Angle => {
+							vec3_scale(other_normal, corner_angle(vertices, &faces[other], other_corner))
+						},
+						RayTraceModelNormalWeighting::Area => face_normals[other]
+					});
+				}
+
+				let sum_length = vec3_len(sum);
+				if sum_length > 0.0 {
+					normal = vec3_scale(sum, 1.0 / sum_length);
+				}
+			}
+
+			let index = match normals[first..].iter().position(|n| *n == normal) {
+				Some(index) => first + index,
+				None => {
+					normals.push(normal);
+					normals.len() - 1
+				}
+			};
+
+			faces[face][corner][1] = index + 1;
+		}
+	}
+}
+
+fn corner_angle(vertices: &Vec<Vector3<f64>>, face: &[Vector3<usize>; 3], corner: usize) -> f64 {
+	let p = vertices[face[corner][0] - 1];
+	let e1 = vec3_sub(vertices[face[(corner + 1) % 3][0] - 1], p);
+	let e2 = vec3_sub(vertices[face[(corner + 2) % 3][0] - 1], p);
+
+	let length = vec3_len(e1) * vec3_len(e2);
+	if length == 0.0 {
+		return 0.0;
+	}
+
+	(vec3_dot(e1, e2) / length).max(-1.0).min(1.0).acos()
+}
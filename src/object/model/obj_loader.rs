@@ -1,132 +1,192 @@
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::BufRead;
 use std::io::BufReader;
 use std::io::Error as IOError;
 use std::io::ErrorKind;
+use std::path::Path;
 
 use vecmath::Vector2;
 use vecmath::Vector3;
 
 use object::RayTraceObjectModel;
-use object::model::RayTraceModelNormalInterpolation;
+use object::model::RayTraceModelGroup;
+use object::model::mtl_load;
+use object::model::triangulate_face;
+use object::model::validate_model;
 use material::RayTraceMaterial;
+use material::RayTraceMtlMaterial;
 
 pub fn obj_load(file_name: &str, material: Box<RayTraceMaterial>) -> Result<RayTraceObjectModel, IOError> {
+	obj_load_with(file_name, material, mtl_load)
+}
+
+// Reads the material libraries with the given function, e.g. to reuse their textures
+pub fn obj_load_with<F>(file_name: &str, material: Box<RayTraceMaterial>, mut load_library: F)
+		-> Result<RayTraceObjectModel, IOError>
+		where F: FnMut(&str) -> Result<Vec<(String, RayTraceMtlMaterial)>, IOError> {
 	let mut vertices = Vec::<Vector3<f64>>::new();
 	let mut vertex_normals = Vec::<Vector3<f64>>::new();
 	let mut texture_normals = Vec::<Vector2<f64>>::new();
 	let mut faces = Vec::<[Vector3<usize>; 3]>::new();
 
+	// Materials of the model are referenced starting with 1, the value 0 selects the default material
+	let mut materials = Vec::<Box<RayTraceMaterial>>::new();
+	let mut material_names = HashMap::<String, usize>::new();
+	let mut face_materials = Vec::<usize>::new();
+	let mut current_material = 0;
+
+	let mut objects = Vec::<RayTraceModelGroup>::new();
+	let mut groups = Vec::<RayTraceModelGroup>::new();
+	let mut current_groups = Vec::<usize>::new();
+
+	// Smoothing groups are only used if the file defines them, otherwise all faces are smoothed
+	let mut smoothing_groups = Vec::<u32>::new();
+	let mut current_smoothing_group = 0;
+	let mut has_smoothing_groups = false;
+
 	let file = try!(File::open(file_name));
 	let reader = BufReader::new(file);
+	let directory = Path::new(file_name).parent().unwrap_or(Path::new(""));
 
 	let mut line_number = 0;
-	let mut current_face = Vec::with_capacity(3);
+	let mut current_face = Vec::with_capacity(4);
+	let mut statement = String::new();
 
 	for line in reader.lines() {
 		let line = try!(line);
 		line_number += 1;
 
-		let data: Vec<&str> = line.trim().split(' ').collect();
+		let line = match line.find('#') {
+			Some(index) => &line[..index],
+			None => &line[..]
+		};
+
+		// Lines ending with a backslash are continued on the next line
+		let line = line.trim_right();
+		if line.ends_with('\\') {
+			statement.push_str(&line[..line.len() - 1]);
+			statement.push(' ');
+			continue;
+		}
+
+		statement.push_str(line);
+		let data: Vec<String> = statement.split_whitespace().map(|s| s.to_string()).collect();
+		statement.clear();
+
 		if data.len() == 0 {
 			continue;
 		}
 
-		match data[0].trim() {
+		match &data[0][..] {
 			"v" => { // vertex
-				match data.len() {
-					4|5 => {
-						let (x, y, z) = (data[1].parse::<f64>(), data[2].parse::<f64>(), data[3].parse::<f64>());
-						match x.ok().and_then(|x| y.ok().and_then(|y| z.ok().and_then(|z| Some((x, y, z))))) {
-							Some((x, y, z)) => { vertices.push([x, y, z]); },
-							None => { return format_err("Invalid vertex coordinate", line_number); }
-						}
-					},
-					_ => {
-						return format_err("Invalid vertex", line_number);
-					}
+				// Additional values like the weight or vertex colors are ignored
+				match parse_floats(&data[1..]) {
+					Some(ref v) if v.len() >= 3 => { vertices.push([v[0], v[1], v[2]]); },
+					_ => { return format_err("Invalid vertex", line_number); }
 				}
 			},
 			"vn" => { // vertex normal
-				match data.len() {
-					4 => {
-						let (x, y, z) = (data[1].parse::<f64>(), data[2].parse::<f64>(), data[3].parse::<f64>());
-						match x.ok().and_then(|x| y.ok().and_then(|y| z.ok().and_then(|z| Some((x, y, z))))) {
-							Some((x, y, z)) => { vertex_normals.push([x, y, z]); },
-							None => { return format_err("Invalid vertex normal coordinate", line_number); }
-						}
-					},
-					_ => {
-						return format_err("Invalid vertex normal", line_number);
-					}
+				match parse_floats(&data[1..]) {
+					Some(ref v) if v.len() == 3 => { vertex_normals.push([v[0], v[1], v[2]]); },
+					_ => { return format_err("Invalid vertex normal", line_number); }
 				}
 			},
 			"vt" => { // vertex texture
-				match data.len() {
-					3|4 => {
-						let (u, v) = (data[1].parse::<f64>(), data[2].parse::<f64>());
-						match u.ok().and_then(|u| v.ok().and_then(|v| Some((u, v)))) {
-							Some((u, v)) => { texture_normals.push([u, v]); },
-							None => { return format_err("Invalid vertex texture coordinate", line_number); }
-						}
+				match parse_floats(&data[1..]) {
+					Some(ref v) if v.len() >= 1 && v.len() <= 3 => {
+						texture_normals.push([v[0], if v.len() > 1 { v[1] } else { 0.0 }]);
 					},
-					_ => {
-						return format_err("Invalid vertex texture", line_number);
-					}
+					_ => { return format_err("Invalid vertex texture", line_number); }
 				}
 			},
 			"f" => { // face
-				match data.len() {
-					4 => {
-						current_face.clear();
-
-						for v in 1..4 {
-							let data: Vec<&str> = data[v].trim().split('/').collect();
-							match data.len() {
-								1 => {
-									match data[0].parse::<usize>() {
-										Ok(v) => {
-											current_face.push([v, 0, 0]);
-										},
-										Err(_) => {
-											return format_err(
-												&format!("Invalid face data at element {}", v), line_number);
-										}
-									}
-								},
-								3 => {
-									let vert = data[0].parse::<usize>();
-									let text = data[1].parse::<usize>();
-									let norm = data[2].parse::<usize>();
-
-									match vert {
-										Ok(v) => {
-											let n: usize = norm.or_else::<(),_>(|_| Ok(0_usize)).unwrap();
-											let t: usize = text.or_else::<(),_>(|_| Ok(0_usize)).unwrap();
-											current_face.push([v, n, t]);
-										},
-										Err(_) => {
-											return format_err(
-												&format!("Invalid face data at element {}", v), line_number);
-										}
-									}
-								},
-								_ => {
-									return format_err(&format!("Invalid face data at element {}", v), line_number);
-								}
-							}
+				if data.len() < 4 {
+					return format_err("Invalid face", line_number);
+				}
+
+				current_face.clear();
+				for (i, element) in data[1..].iter().enumerate() {
+					match parse_face_element(element, vertices.len(), vertex_normals.len(), texture_normals.len()) {
+						Some(v) => { current_face.push(v); },
+						None => {
+							return format_err(&format!("Invalid face data at element {}", i + 1), line_number);
 						}
+					}
+				}
 
-						faces.push([current_face[0], current_face[1], current_face[2]]);
-					},
-					len if len > 4 => {
-						return format_err("Only faces with 3 vertices are supported", line_number);
+				for triangle in triangulate_face(&current_face, &vertices) {
+					for group in current_groups.iter() {
+						groups[*group].faces.push(faces.len());
+					}
+					if let Some(object) = objects.last_mut() {
+						object.faces.push(faces.len());
+					}
+
+					faces.push(triangle);
+					face_materials.push(current_material);
+					smoothing_groups.push(current_smoothing_group);
+				}
+			},
+			"o" => { // object
+				objects.push(RayTraceModelGroup::new(data[1..].join(" ")));
+			},
+			"g" => { // groups
+				current_groups.clear();
+				for name in data[1..].iter() {
+					match groups.iter().position(|g| g.get_name() == name) {
+						Some(index) => { current_groups.push(index); },
+						None => {
+							current_groups.push(groups.len());
+							groups.push(RayTraceModelGroup::new(name.clone()));
+						}
+					}
+				}
+			},
+			"s" => { // smoothing group
+				current_smoothing_group = match data.get(1).map(|s| &s[..]) {
+					Some("off") => 0,
+					Some(group) => {
+						match group.parse::<u32>() {
+							Ok(group) => group,
+							Err(_) => { return format_err("Invalid smoothing group", line_number); }
+						}
 					},
-					_ => {
-						return format_err("Invalid face", line_number);
+					None => { return format_err("Missing smoothing group", line_number); }
+				};
+				has_smoothing_groups = true;
+			},
+			"mtllib" => { // material libraries
+				for name in data[1..].iter() {
+					let mtl_file = directory.join(name.replace("\\", "/"));
+					let mtl_file = mtl_file.to_string_lossy();
+
+					match load_library(&mtl_file) {
+						Ok(loaded) => {
+							for (name, material) in loaded {
+								materials.push(Box::new(material));
+								material_names.insert(name, materials.len());
+							}
+						},
+						Err(ref err) if err.kind() == ErrorKind::NotFound => {
+							warn!("Material library {} was not found", mtl_file);
+						},
+						Err(err) => {
+							return Err(IOError::new(err.kind(), format!("Error in {}: {}", mtl_file, err)));
+						}
 					}
 				}
 			},
+			"usemtl" => { // material
+				let name = data[1..].join(" ");
+				current_material = match material_names.get(&name) {
+					Some(index) => *index,
+					None => {
+						warn!("Unknown material {} on line {}", name, line_number);
+						0
+					}
+				};
+			},
 			_ => {
 				info!("Ignored line {}: {}", line_number, line);
 			}
@@ -141,45 +201,68 @@ pub fn obj_load(file_name: &str, material: Box<RayTraceMaterial>) -> Result<RayT
 			&format!("Face {} is not valid since some {} data is missing", face, t), 0);
 	}
 
-	Ok(
-		RayTraceObjectModel {
-			material: material,
-			interpolation: RayTraceModelNormalInterpolation::Average,
-			scale: [1.0, 1.0, 1.0],
-			position: [0.0, 0.0, 0.0],
-			rotation: [0.0, 0.0, 0.0],
-			offset: [0.0, 0.0, 0.0],
-			anim_pos: None,
-			anim_rot: None,
-			anim_scale: None,
-			vertices: vertices,
-			vertex_normals: vertex_normals,
-			texture_normals: texture_normals,
-			faces: faces,
-			data: None
-		}
-	)
+	let mut model = RayTraceObjectModel::new(material, vertices, vertex_normals, texture_normals, faces);
+	model.set_materials(materials, face_materials);
+	model.set_objects(objects);
+	model.set_groups(groups);
+	if has_smoothing_groups {
+		model.set_smoothing_groups(smoothing_groups);
+	}
+
+	Ok(model)
 }
 
 fn format_err<T>(message: &str, line: usize) -> Result<T, IOError> {
 	Err(IOError::new(ErrorKind::InvalidData, format!("Error on line {}: {}", line, message)))
 }
 
-fn validate_model(faces: &Vec<[Vector3<usize>; 3]>, vert: usize, vert_norm: usize, text_norm: usize)
-		-> Option<(usize, &str)> {
-	for (i, face) in faces.iter().enumerate() {
-		for v in face.iter() {
-			if v[0] > vert || v[0] == 0 {
-				return Some((i, "vertex"));
-			}
-			if v[1] > vert_norm {
-				return Some((i, "normal"));
-			}
-			if v[2] > text_norm {
-				return Some((i, "texture"));
-			}
+fn parse_floats(data: &[String]) -> Option<Vec<f64>> {
+	let mut values = Vec::with_capacity(data.len());
+	for value in data.iter() {
+		match value.parse::<f64>() {
+			Ok(value) => { values.push(value); },
+			Err(_) => { return None; }
 		}
 	}
 
-	return None;
-}
\ No newline at end of file
+	Some(values)
+}
+
+// Parses the forms v, v/t, v//n and v/t/n where negative indices are relative to the current end of the list
+fn parse_face_element(element: &str, vert: usize, vert_norm: usize, text_norm: usize) -> Option<Vector3<usize>> {
+	let data: Vec<&str> = element.split('/').collect();
+	if data.len() > 3 {
+		return None;
+	}
+
+	let v = match resolve_index(data[0], vert) {
+		Some(v) if v > 0 => v,
+		_ => { return None; }
+	};
+	let t = if data.len() > 1 && data[1].len() > 0 {
+		match resolve_index(data[1], text_norm) {
+			Some(t) => t,
+			None => { return None; }
+		}
+	} else {
+		0
+	};
+	let n = if data.len() > 2 && data[2].len() > 0 {
+		match resolve_index(data[2], vert_norm) {
+			Some(n) => n,
+			None => { return None; }
+		}
+	} else {
+		0
+	};
+
+	Some([v, n, t])
+}
+
+fn resolve_index(index: &str, count: usize) -> Option<usize> {
+	match index.parse::<isize>() {
+		Ok(i) if i > 0 => Some(i as usize),
+		Ok(i) if i < 0 && (-i) as usize <= count => Some(count + 1 - (-i) as usize),
+		_ => None
+	}
+}
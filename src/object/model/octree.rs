@@ -1,4 +1,6 @@
 use std::collections::BinaryHeap;
+use std::io::Error as IOError;
+use std::io::ErrorKind;
 use std::mem;
 
 use vecmath::Vector2;
@@ -9,6 +11,9 @@ use aabb::AABB;
 use hit::RayTraceHitHeapEntry;
 use ray::RayTraceRay;
 
+use object::model::cache::CacheReader;
+use object::model::cache::CacheWriter;
+
 pub struct RayTraceOctree {
 	root: Node,
 	quads: usize,
@@ -27,6 +32,7 @@ enum NodeContent {
 
 pub struct Face {
 	id: usize,
+	vertices: [Vector3<f64>; 3],
 	normal: Vector3<f64>,
 	position: Vector3<f64>,
 	vec: [Vector3<f64>; 2]
@@ -34,6 +40,7 @@ pub struct Face {
 
 const SPLIT_THRESHOLD: usize = 30;
 const ELEMENTS_PER_QUAD_THRESHOLD: usize = 10;
+const MAX_CACHE_DEPTH: usize = 64;
 
 impl<'a> RayTraceOctree {
 	pub fn new(aabb: AABB) -> Self {
@@ -49,17 +56,7 @@ impl<'a> RayTraceOctree {
 
 	pub fn add(&mut self, v: [Vector3<f64>; 3]) -> usize {
 		let index = self.faces.len();
-
-		let pos = v[0];
-		let vec1 = vec3_sub(v[1], v[0]);
-		let vec2 = vec3_sub(v[2], v[0]);
-		let normal = vec3_normalized(vec3_cross(vec1, vec2));
-		let face = Face {
-				id: index,
-				normal: normal,
-				position: pos,
-				vec: [vec1, vec2]
-			};
+		let face = Face::new(index, v);
 
 		let mut stack = Vec::new();
 		stack.push(&mut self.root as *mut Node);
@@ -130,6 +127,23 @@ impl<'a> RayTraceOctree {
 		}
 	}
 
+	// Writes the nodes of the tree, the faces are restored from the vertices of the model
+	pub fn write_cache(&self, writer: &mut CacheWriter) {
+		writer.write_u32(self.quads as u32);
+		write_node(&self.root, writer);
+	}
+
+	pub fn read_cache(reader: &mut CacheReader, triangles: Vec<[Vector3<f64>; 3]>) -> Result<Self, IOError> {
+		let quads = try!(reader.read_u32()) as usize;
+		let root = try!(read_node(reader, triangles.len(), 0));
+
+		Ok(Self {
+			root: root,
+			quads: quads,
+			faces: triangles.into_iter().enumerate().map(|(i, v)| Face::new(i, v)).collect()
+		})
+	}
+
 	fn split_container(&mut self, elements: &Box<Vec<usize>>, aabb: &AABB, current_face: &Face) -> NodeContent {
 		self.quads += 8;
 
@@ -199,6 +213,57 @@ impl<'a> RayTraceOctree {
 	}
 }
 
+fn write_node(node: &Node, writer: &mut CacheWriter) {
+	writer.write_vec3(node.aabb.get_start());
+	writer.write_vec3(node.aabb.get_end());
+
+	match node.content {
+		NodeContent::Container(ref children) => {
+			writer.write_u8(1);
+			for child in children.iter() {
+				write_node(child, writer);
+			}
+		},
+		NodeContent::Elements(ref elements) => {
+			writer.write_u8(0);
+			writer.write_u32_list(&elements.iter().map(|e| *e as u32).collect());
+		}
+	}
+}
+
+fn read_node(reader: &mut CacheReader, face_count: usize, depth: usize) -> Result<Node, IOError> {
+	if depth > MAX_CACHE_DEPTH {
+		return Err(IOError::new(ErrorKind::InvalidData, "Octree is too deep"));
+	}
+
+	let aabb = AABB::new(try!(reader.read_vec3()), try!(reader.read_vec3()));
+	let content = match try!(reader.read_u8()) {
+		0 => {
+			let elements: Vec<usize> = try!(reader.read_u32_list()).iter().map(|e| *e as usize).collect();
+			if elements.iter().any(|e| *e >= face_count) {
+				return Err(IOError::new(ErrorKind::InvalidData, "Octree with invalid face"));
+			}
+
+			NodeContent::Elements(box elements)
+		},
+		_ => {
+			let mut children = Vec::with_capacity(8);
+			for _ in 0..8 {
+				children.push(try!(read_node(reader, face_count, depth + 1)));
+			}
+
+			let mut children = children.into_iter();
+			let mut next = || children.next().unwrap();
+			NodeContent::Container(box [next(), next(), next(), next(), next(), next(), next(), next()])
+		}
+	};
+
+	Ok(Node {
+		aabb: aabb,
+		content: content
+	})
+}
+
 struct OctreeIterator<'a> {
 	tree: &'a RayTraceOctree,
 	ray: &'a RayTraceRay,
@@ -264,12 +329,25 @@ unsafe impl<'a> Send for RayTraceOctree { }
 unsafe impl<'a> Sync for RayTraceOctree { }
 
 impl Face {
+	fn new(id: usize, v: [Vector3<f64>; 3]) -> Self {
+		let vec1 = vec3_sub(v[1], v[0]);
+		let vec2 = vec3_sub(v[2], v[0]);
+
+		Self {
+			id: id,
+			vertices: v,
+			normal: vec3_normalized(vec3_cross(vec1, vec2)),
+			position: v[0],
+			vec: [vec1, vec2]
+		}
+	}
+
 	pub fn get_normals(&self, faces: &Vec<[Vector3<usize>; 3]>, normals: &Vec<Vector3<f64>>,
 			texture_normals: &Vec<Vector2<f64>>) -> [(Vector3<f64>, Vector2<f64>); 3] {
 		let face = faces[self.id];
 
 		let n = [face[0][1], face[1][1], face[2][1]];
-		let t = [face[0][2], face[0][2], face[0][2]];
+		let t = [face[0][2], face[1][2], face[2][2]];
 		let face_normal = vec3_normalized(vec3_cross(self.vec[0], self.vec[1]));
 
 		[
@@ -288,11 +366,15 @@ impl Face {
 		]
 	}
 
-	pub fn get_position(&self) -> &Vector3<f64> {
-		&self.position
+	pub fn get_id(&self) -> usize {
+		self.id
+	}
+
+	pub fn get_vertices(&self) -> &[Vector3<f64>; 3] {
+		&self.vertices
 	}
 
-	pub fn get_vectors(&self) -> &[Vector3<f64>; 2] {
-		&self.vec
+	pub fn get_normal(&self) -> &Vector3<f64> {
+		&self.normal
 	}
 }
@@ -0,0 +1,422 @@
+use std::fs::File;
+use std::io::Read;
+use std::io::Error as IOError;
+use std::io::ErrorKind;
+use std::str;
+
+use vecmath::Vector2;
+use vecmath::Vector3;
+
+use color::RayTraceColor;
+use object::RayTraceObjectModel;
+use object::model::triangulate_face;
+use object::model::validate_model;
+use material::RayTraceMaterial;
+
+#[derive(Clone, Copy, PartialEq)]
+enum PlyFormat {
+	Ascii,
+	BinaryLittleEndian,
+	BinaryBigEndian
+}
+
+#[derive(Clone, Copy)]
+enum PlyType {
+	Int8,
+	UInt8,
+	Int16,
+	UInt16,
+	Int32,
+	UInt32,
+	Float32,
+	Float64
+}
+
+struct PlyProperty {
+	name: String,
+	kind: PlyType,
+	// Type of the element count for list properties
+	list: Option<PlyType>
+}
+
+struct PlyElement {
+	name: String,
+	count: usize,
+	properties: Vec<PlyProperty>
+}
+
+// Reads the values of the body either from whitespace separated text or from binary data
+struct PlyReader<'a> {
+	format: PlyFormat,
+	data: &'a [u8],
+	position: usize,
+	line: usize
+}
+
+pub fn ply_load(file_name: &str, material: Box<RayTraceMaterial>) -> Result<RayTraceObjectModel, IOError> {
+	let mut vertices = Vec::<Vector3<f64>>::new();
+	let mut vertex_normals = Vec::<Vector3<f64>>::new();
+	let mut texture_normals = Vec::<Vector2<f64>>::new();
+	let mut vertex_colors = Vec::<RayTraceColor>::new();
+	let mut faces = Vec::<[Vector3<usize>; 3]>::new();
+
+	let mut content = Vec::new();
+	let mut file = try!(File::open(file_name));
+	try!(file.read_to_end(&mut content));
+
+	let (elements, mut reader) = try!(read_header(&content));
+
+	let mut current_face = Vec::with_capacity(4);
+	let mut has_normals = false;
+	let mut has_textures = false;
+
+	for element in elements.iter() {
+		match &element.name[..] {
+			"vertex" => {
+				let find = |names: &[&str]| element.properties.iter().position(|p| names.contains(&&p.name[..]));
+				let position = [find(&["x"]), find(&["y"]), find(&["z"])];
+				let normal = [find(&["nx"]), find(&["ny"]), find(&["nz"])];
+				let texture = [
+					find(&["u", "s", "texture_u", "texture_s"]),
+					find(&["v", "t", "texture_v", "texture_t"])
+				];
+				let color = [
+					find(&["red", "r"]),
+					find(&["green", "g"]),
+					find(&["blue", "b"]),
+					find(&["alpha", "a"])
+				];
+
+				if position.iter().any(|p| p.is_none()) {
+					return format_err("Vertex element is missing a coordinate", reader.line);
+				}
+
+				has_normals = normal.iter().all(|n| n.is_some());
+				has_textures = texture.iter().all(|t| t.is_some());
+				let has_colors = color[0..3].iter().all(|c| c.is_some());
+
+				for _ in 0..element.count {
+					let mut values = Vec::with_capacity(element.properties.len());
+					for property in element.properties.iter() {
+						values.push(try!(reader.read_property(property)));
+					}
+
+					let get = |index: Option<usize>| index.map_or(0.0, |i| values[i]);
+					vertices.push([get(position[0]), get(position[1]), get(position[2])]);
+
+					if has_normals {
+						vertex_normals.push([get(normal[0]), get(normal[1]), get(normal[2])]);
+					}
+					if has_textures {
+						texture_normals.push([get(texture[0]), get(texture[1])]);
+					}
+					if has_colors {
+						// Integer colors are stored in the range of their type
+						let scale = |index: Option<usize>| match index.map(|i| element.properties[i].kind) {
+							Some(PlyType::Float32) | Some(PlyType::Float64) => 1.0,
+							Some(PlyType::UInt16) => 1.0 / 65535.0,
+							Some(_) => 1.0 / 255.0,
+							None => 1.0
+						};
+
+						vertex_colors.push(RayTraceColor::new_with(
+							(get(color[0]) * scale(color[0])) as f32,
+							(get(color[1]) * scale(color[1])) as f32,
+							(get(color[2]) * scale(color[2])) as f32,
+							if color[3].is_some() { (get(color[3]) * scale(color[3])) as f32 } else { 1.0 }));
+					}
+
+					reader.next_line();
+				}
+			},
+			"face" => {
+				let indices = element.properties.iter()
+					.position(|p| p.list.is_some() && (p.name == "vertex_indices" || p.name == "vertex_index"));
+				let indices = match indices {
+					Some(indices) => indices,
+					None => {
+						return format_err("Face element is missing the vertex indices", reader.line);
+					}
+				};
+
+				for _ in 0..element.count {
+					current_face.clear();
+
+					for (i, property) in element.properties.iter().enumerate() {
+						if i != indices {
+							try!(reader.read_property(property));
+							continue;
+						}
+
+						let count = match property.list {
+							Some(list) => try!(reader.read_value(list)) as usize,
+							None => 0
+						};
+						for _ in 0..count {
+							// Indices in the file start at 0 while the model starts with 1
+							let index = try!(reader.read_value(property.kind));
+							if index < 0.0 || index.fract() != 0.0 || index >= vertices.len() as f64 {
+								return format_err("Invalid vertex index", reader.line);
+							}
+							let v = index as usize + 1;
+							current_face.push([v, if has_normals { v } else { 0 }, if has_textures { v } else { 0 }]);
+						}
+					}
+
+					if current_face.len() < 3 {
+						return format_err("Invalid face", reader.line);
+					}
+
+					faces.extend(triangulate_face(&current_face, &vertices));
+					reader.next_line();
+				}
+			},
+			_ => {
+				info!("Ignored element {} with {} entries", element.name, element.count);
+
+				for _ in 0..element.count {
+					for property in element.properties.iter() {
+						try!(reader.read_property(property));
+					}
+
+					reader.next_line();
+				}
+			}
+		}
+	}
+
+	info!("Loaded {} vertices, {} vertex normals, {} texture normals and {} vertex colors", vertices.len(),
+		vertex_normals.len(), texture_normals.len(), vertex_colors.len());
+
+	if let Some((face, t)) = validate_model(&faces, vertices.len(), vertex_normals.len(), texture_normals.len()) {
+		return format_err(
+			&format!("Face {} is not valid since some {} data is missing", face, t), 0);
+	}
+
+	let mut model = RayTraceObjectModel::new(material, vertices, vertex_normals, texture_normals, faces);
+	model.set_vertex_colors(vertex_colors);
+
+	Ok(model)
+}
+
+fn read_header(content: &[u8]) -> Result<(Vec<PlyElement>, PlyReader), IOError> {
+	let mut format = None;
+	let mut elements = Vec::<PlyElement>::new();
+
+	let mut position = 0;
+	let mut line_number = 0;
+
+	loop {
+		let end = match content[position..].iter().position(|c| *c == b'\n') {
+			Some(end) => position + end,
+			None => {
+				return format_err("Missing end of header", line_number);
+			}
+		};
+
+		let line = match str::from_utf8(&content[position..end]) {
+			Ok(line) => line.trim(),
+			Err(_) => {
+				return format_err("Invalid header", line_number + 1);
+			}
+		};
+
+		position = end + 1;
+		line_number += 1;
+
+		let data: Vec<&str> = line.split_whitespace().collect();
+		if line_number == 1 {
+			if data != ["ply"] {
+				return format_err("Not a ply file", line_number);
+			}
+
+			continue;
+		}
+
+		if data.len() == 0 {
+			continue;
+		}
+
+		match data[0] {
+			"format" => {
+				format = match data.get(1) {
+					Some(&"ascii") => Some(PlyFormat::Ascii),
+					Some(&"binary_little_endian") => Some(PlyFormat::BinaryLittleEndian),
+					Some(&"binary_big_endian") => Some(PlyFormat::BinaryBigEndian),
+					_ => {
+						return format_err("Unsupported format", line_number);
+					}
+				};
+			},
+			"element" => {
+				match (data.get(1), data.get(2).and_then(|c| c.parse::<usize>().ok())) {
+					(Some(name), Some(count)) if data.len() == 3 => {
+						elements.push(PlyElement {
+							name: name.to_string(),
+							count: count,
+							properties: Vec::new()
+						});
+					},
+					_ => {
+						return format_err("Invalid element", line_number);
+					}
+				}
+			},
+			"property" => {
+				let property = match data.len() {
+					3 => parse_type(data[1]).map(|kind| PlyProperty {
+						name: data[2].to_string(),
+						kind: kind,
+						list: None
+					}),
+					5 if data[1] == "list" => {
+						parse_type(data[2]).and_then(|list| parse_type(data[3]).map(|kind| PlyProperty {
+							name: data[4].to_string(),
+							kind: kind,
+							list: Some(list)
+						}))
+					},
+					_ => None
+				};
+
+				match (property, elements.last_mut()) {
+					(Some(property), Some(element)) => { element.properties.push(property); },
+					_ => {
+						return format_err("Invalid property", line_number);
+					}
+				}
+			},
+			"end_header" => {
+				break;
+			},
+			"comment" | "obj_info" => { },
+			_ => {
+				info!("Ignored line {}: {}", line_number, line);
+			}
+		}
+	}
+
+	match format {
+		Some(format) => {
+			Ok((elements, PlyReader {
+				format: format,
+				data: content,
+				position: position,
+				line: line_number + 1
+			}))
+		},
+		None => format_err("Missing format", line_number)
+	}
+}
+
+fn parse_type(name: &str) -> Option<PlyType> {
+	match name {
+		"char" | "int8" => Some(PlyType::Int8),
+		"uchar" | "uint8" => Some(PlyType::UInt8),
+		"short" | "int16" => Some(PlyType::Int16),
+		"ushort" | "uint16" => Some(PlyType::UInt16),
+		"int" | "int32" => Some(PlyType::Int32),
+		"uint" | "uint32" => Some(PlyType::UInt32),
+		"float" | "float32" => Some(PlyType::Float32),
+		"double" | "float64" => Some(PlyType::Float64),
+		_ => None
+	}
+}
+
+impl<'a> PlyReader<'a> {
+	// Reads a property value or skips the entries of a list returning its length
+	fn read_property(&mut self, property: &PlyProperty) -> Result<f64, IOError> {
+		match property.list {
+			None => self.read_value(property.kind),
+			Some(list) => {
+				let count = try!(self.read_value(list));
+				for _ in 0..count as usize {
+					try!(self.read_value(property.kind));
+				}
+
+				Ok(count)
+			}
+		}
+	}
+
+	fn read_value(&mut self, kind: PlyType) -> Result<f64, IOError> {
+		if self.format == PlyFormat::Ascii {
+			return self.read_text();
+		}
+
+		let size = match kind {
+			PlyType::Int8 | PlyType::UInt8 => 1,
+			PlyType::Int16 | PlyType::UInt16 => 2,
+			PlyType::Int32 | PlyType::UInt32 | PlyType::Float32 => 4,
+			PlyType::Float64 => 8
+		};
+
+		if self.position + size > self.data.len() {
+			return format_err("Unexpected end of file", self.line);
+		}
+
+		let mut bits: u64 = 0;
+		for i in 0..size {
+			let byte = if self.format == PlyFormat::BinaryLittleEndian {
+				self.data[self.position + size - 1 - i]
+			} else {
+				self.data[self.position + i]
+			};
+
+			bits = (bits << 8) | byte as u64;
+		}
+		self.position += size;
+
+		Ok(match kind {
+			PlyType::Int8 => bits as u8 as i8 as f64,
+			PlyType::UInt8 => bits as u8 as f64,
+			PlyType::Int16 => bits as u16 as i16 as f64,
+			PlyType::UInt16 => bits as u16 as f64,
+			PlyType::Int32 => bits as u32 as i32 as f64,
+			PlyType::UInt32 => bits as u32 as f64,
+			PlyType::Float32 => f32::from_bits(bits as u32) as f64,
+			PlyType::Float64 => f64::from_bits(bits)
+		})
+	}
+
+	fn read_text(&mut self) -> Result<f64, IOError> {
+		// Each element is stored on its own line in ascii files
+		while self.position < self.data.len() && self.data[self.position] != b'\n'
+				&& (self.data[self.position] as char).is_whitespace() {
+			self.position += 1;
+		}
+
+		let start = self.position;
+		while self.position < self.data.len() && !(self.data[self.position] as char).is_whitespace() {
+			self.position += 1;
+		}
+
+		if start == self.position {
+			return format_err("Missing value", self.line);
+		}
+
+		match str::from_utf8(&self.data[start..self.position]).ok().and_then(|v| v.parse::<f64>().ok()) {
+			Some(value) => Ok(value),
+			None => format_err("Invalid value", self.line)
+		}
+	}
+
+	fn next_line(&mut self) {
+		if self.format != PlyFormat::Ascii {
+			return;
+		}
+
+		while self.position < self.data.len() {
+			self.position += 1;
+			if self.data[self.position - 1] == b'\n' {
+				break;
+			}
+		}
+
+		self.line += 1;
+	}
+}
+
+fn format_err<T>(message: &str, line: usize) -> Result<T, IOError> {
+	Err(IOError::new(ErrorKind::InvalidData, format!("Error on line {}: {}", line, message)))
+}
@@ -0,0 +1,158 @@
+use std::fs::File;
+use std::io::Read;
+use std::io::Error as IOError;
+use std::io::ErrorKind;
+use std::str;
+
+use vecmath::Vector2;
+use vecmath::Vector3;
+
+use object::RayTraceObjectModel;
+use object::model::validate_model;
+use material::RayTraceMaterial;
+
+const HEADER_SIZE: usize = 80;
+const TRIANGLE_SIZE: usize = 50;
+
+// Facet normals are ignored since the winding of the vertices defines the orientation of a face
+pub fn stl_load(file_name: &str, material: Box<RayTraceMaterial>) -> Result<RayTraceObjectModel, IOError> {
+	let mut content = Vec::new();
+	let mut file = try!(File::open(file_name));
+	try!(file.read_to_end(&mut content));
+
+	// Binary files may start with "solid" as well so the size is checked first
+	let vertices = if is_binary(&content) {
+		try!(read_binary(&content))
+	} else {
+		try!(read_ascii(&content))
+	};
+
+	let faces: Vec<[Vector3<usize>; 3]> = (0..vertices.len() / 3)
+		.map(|i| [[i * 3 + 1, 0, 0], [i * 3 + 2, 0, 0], [i * 3 + 3, 0, 0]])
+		.collect();
+
+	info!("Loaded {} vertices", vertices.len());
+
+	if let Some((face, t)) = validate_model(&faces, vertices.len(), 0, 0) {
+		return format_err(
+			&format!("Face {} is not valid since some {} data is missing", face, t), 0);
+	}
+
+	Ok(RayTraceObjectModel::new(material, vertices, Vec::<Vector3<f64>>::new(), Vec::<Vector2<f64>>::new(), faces))
+}
+
+fn is_binary(content: &[u8]) -> bool {
+	if content.len() < HEADER_SIZE + 4 {
+		return false;
+	}
+
+	// Some exporters pad the file after the triangles, the count of ascii files is made of text and far too large
+	let count = read_u32(content, HEADER_SIZE) as u64;
+	content.len() as u64 >= (HEADER_SIZE + 4) as u64 + count * TRIANGLE_SIZE as u64
+}
+
+fn read_binary(content: &[u8]) -> Result<Vec<Vector3<f64>>, IOError> {
+	let count = read_u32(content, HEADER_SIZE) as usize;
+	let mut vertices = Vec::with_capacity(count * 3);
+
+	for i in 0..count {
+		// Skip the facet normal and read the three vertices
+		let offset = HEADER_SIZE + 4 + i * TRIANGLE_SIZE + 12;
+		for v in 0..3 {
+			let position = offset + v * 12;
+			vertices.push([
+				read_f32(content, position) as f64,
+				read_f32(content, position + 4) as f64,
+				read_f32(content, position + 8) as f64
+			]);
+		}
+	}
+
+	Ok(vertices)
+}
+
+fn read_ascii(content: &[u8]) -> Result<Vec<Vector3<f64>>, IOError> {
+	let mut vertices = Vec::<Vector3<f64>>::new();
+
+	let content = match str::from_utf8(content) {
+		Ok(content) => content,
+		Err(_) => {
+			return format_err("Invalid text", 0);
+		}
+	};
+
+	let mut facet_vertices = 0;
+	let mut in_facet = false;
+
+	for (line_number, line) in content.lines().enumerate() {
+		let line_number = line_number + 1;
+
+		let data: Vec<&str> = line.split_whitespace().collect();
+		if data.len() == 0 {
+			continue;
+		}
+
+		match data[0] {
+			"solid" => {
+				if in_facet {
+					return format_err("Unexpected solid", line_number);
+				}
+			},
+			"facet" => {
+				if in_facet {
+					return format_err("Unexpected facet", line_number);
+				}
+
+				in_facet = true;
+				facet_vertices = 0;
+			},
+			"vertex" => {
+				if !in_facet || facet_vertices >= 3 {
+					return format_err("Unexpected vertex", line_number);
+				}
+
+				let (x, y, z) = match data.len() {
+					4 => (data[1].parse::<f64>(), data[2].parse::<f64>(), data[3].parse::<f64>()),
+					_ => { return format_err("Invalid vertex", line_number); }
+				};
+				match x.ok().and_then(|x| y.ok().and_then(|y| z.ok().and_then(|z| Some((x, y, z))))) {
+					Some((x, y, z)) => { vertices.push([x, y, z]); },
+					None => { return format_err("Invalid vertex coordinate", line_number); }
+				}
+
+				facet_vertices += 1;
+			},
+			"endfacet" => {
+				if !in_facet || facet_vertices != 3 {
+					return format_err("Only facets with 3 vertices are supported", line_number);
+				}
+
+				in_facet = false;
+			},
+			"outer" | "endloop" | "endsolid" => { },
+			_ => {
+				info!("Ignored line {}: {}", line_number, line);
+			}
+		}
+	}
+
+	if in_facet {
+		return format_err("Unexpected end of file", content.lines().count());
+	}
+
+	Ok(vertices)
+}
+
+fn read_u32(content: &[u8], position: usize) -> u32 {
+	// Binary files are stored in little endian byte order
+	(content[position] as u32) | (content[position + 1] as u32) << 8 | (content[position + 2] as u32) << 16
+		| (content[position + 3] as u32) << 24
+}
+
+fn read_f32(content: &[u8], position: usize) -> f32 {
+	f32::from_bits(read_u32(content, position))
+}
+
+fn format_err<T>(message: &str, line: usize) -> Result<T, IOError> {
+	Err(IOError::new(ErrorKind::InvalidData, format!("Error on line {}: {}", line, message)))
+}
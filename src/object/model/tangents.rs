@@ -0,0 +1,86 @@
+use std::collections::HashMap;
+
+use vecmath::Vector2;
+use vecmath::Vector3;
+use vecmath::Vector4;
+use vecmath::{vec3_add, vec3_cross, vec3_dot, vec3_len, vec3_normalized, vec3_scale, vec3_sub};
+
+use math_util::compute_triangle_tangents;
+
+// Computes a tangent for every face corner with texture coordinates in the way of MikkTSpace: the tangents of the
+// faces are projected onto the vertex normal and summed weighted by the angle of the corner, corners are shared if
+// they have the same vertex, normal, texture coordinates and handedness. The fourth component is the sign of the
+// bitangent, which is the cross product of normal and tangent. The indices of the tangents start with 1, corners
+// without tangent get the index 0.
+pub fn generate_tangents(vertices: &Vec<Vector3<f64>>, normals: &Vec<Vector3<f64>>, texcoords: &Vec<Vector2<f64>>,
+		faces: &Vec<[Vector3<usize>; 3]>) -> (Vec<Vector4<f64>>, Vec<[usize; 3]>) {
+	let mut sums: Vec<(Vector3<f64>, Vector3<f64>, f64)> = Vec::new();
+	let mut shared = HashMap::<(usize, usize, usize, bool), usize>::new();
+	let mut face_tangents = Vec::with_capacity(faces.len());
+
+	for face in faces.iter() {
+		let corners = [face[0], face[1], face[2]];
+		let points = [vertices[corners[0][0] - 1], vertices[corners[1][0] - 1], vertices[corners[2][0] - 1]];
+		let face_normal = vec3_cross(vec3_sub(points[1], points[0]), vec3_sub(points[2], points[0]));
+
+		let tangents = if corners.iter().any(|c| c[2] == 0) || vec3_len(face_normal) == 0.0 {
+			None
+		} else {
+			let uvs = [texcoords[corners[0][2] - 1], texcoords[corners[1][2] - 1], texcoords[corners[2][2] - 1]];
+			compute_triangle_tangents(&points, &uvs)
+		};
+
+		let tangents = match tangents {
+			Some(t) => t,
+			None => {
+				face_tangents.push([0, 0, 0]);
+				continue;
+			}
+		};
+
+		// Mirrored texture coordinates flip the bitangent
+		let mirrored = vec3_dot(vec3_cross(face_normal, tangents[0]), tangents[1]) < 0.0;
+		let mut indices = [0; 3];
+
+		for i in 0..3 {
+			let corner = corners[i];
+			let normal = vec3_normalized(if corner[1] == 0 { face_normal } else { normals[corner[1] - 1] });
+
+			let (prev, next) = (points[(i + 2) % 3], points[(i + 1) % 3]);
+			let (a, b) = (vec3_sub(next, points[i]), vec3_sub(prev, points[i]));
+			let angle = (vec3_dot(a, b) / (vec3_len(a) * vec3_len(b))).max(-1.0).min(1.0).acos();
+
+			let key = (corner[0], corner[1], corner[2], mirrored);
+			let index = match shared.get(&key) {
+				Some(index) => *index,
+				None => {
+					sums.push(([0.0, 0.0, 0.0], normal, if mirrored { -1.0 } else { 1.0 }));
+					shared.insert(key, sums.len());
+					sums.len()
+				}
+			};
+
+			let tangent = project(tangents[0], normal);
+			if vec3_len(tangent) > 0.0 {
+				let sum = &mut sums[index - 1].0;
+				*sum = vec3_add(*sum, vec3_scale(vec3_normalized(tangent), angle));
+			}
+			indices[i] = index;
+		}
+
+		face_tangents.push(indices);
+	}
+
+	let tangents = sums.into_iter().map(|(sum, normal, sign)| {
+		let tangent = project(sum, normal);
+		let tangent = if vec3_len(tangent) > 0.0 { vec3_normalized(tangent) } else { [0.0, 0.0, 0.0] };
+		[tangent[0], tangent[1], tangent[2], sign]
+	}).collect();
+
+	(tangents, face_tangents)
+}
+
+// Removes the part along the normal
+fn project(v: Vector3<f64>, normal: Vector3<f64>) -> Vector3<f64> {
+	vec3_sub(v, vec3_scale(normal, vec3_dot(v, normal)))
+}
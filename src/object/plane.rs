@@ -1,25 +1,24 @@
-use vecmath::row_mat3_transform;
-use vecmath::vec3_dot;
 use vecmath::Vector3;
 
 use aabb::AABB;
 use anim::RayTraceAnimation;
 use hit::RayTraceRayHit;
+use hit::RayTraceShadingContext;
+use material::RayTraceFootprint;
 use material::RayTraceMaterial;
 use object::RayTraceObject;
 use object::RayTraceHitable;
 use ray::RayTraceRay;
+use scene_file::RayTraceSceneWriteError;
+use scene_file::RayTraceSceneWriter;
 
-use math_util::compute_plane_hit;
-use math_util::rotate_xyz;
+use math_util::RayTraceTransform;
+use math_util::RayTraceTransformable;
 
 #[allow(dead_code)]
 pub struct RayTraceObjectPlane {
-	center: Vector3<f64>,
-	rotation: Vector3<f64>,
+	transform: RayTraceTransform,
 	material: Box<RayTraceMaterial>,
-	anim_pos: Option<Box<RayTraceAnimation<Vector3<f64>>>>,
-	anim_rot: Option<Box<RayTraceAnimation<Vector3<f64>>>>,
 	data: Option<WorkingData>
 }
 
@@ -27,42 +26,59 @@ pub struct RayTraceObjectPlane {
 impl RayTraceObjectPlane {
 	pub fn new(center: Vector3<f64>, rotation: Vector3<f64>, material: Box<RayTraceMaterial>) -> Self {
 		Self {
-			center: center,
-			rotation: rotation,
+			transform: RayTraceTransform::new_with(center, rotation, [1.0, 1.0, 1.0]),
 			material: material,
-			anim_rot: None,
-			anim_pos: None,
 			data: None
 		}
 	}
 
-	pub fn set_rotation(&mut self, rotation: Vector3<f64>) {
-		self.rotation = rotation;
+	#[deprecated(note = "Use the transform instead")]
+	pub fn get_position(&self) -> &Vector3<f64> {
+		self.transform.get_translation()
 	}
 
+	#[deprecated(note = "Use the transform instead")]
 	pub fn set_position(&mut self, position: Vector3<f64>) {
-		self.center = position;
+		self.transform.set_translation(position);
 	}
 
+	#[deprecated(note = "Use the transform instead")]
+	pub fn set_rotation(&mut self, rotation: Vector3<f64>) {
+		self.transform.set_rotation(rotation);
+	}
+
+	#[deprecated(note = "Use the transform instead")]
 	pub fn set_anim_pos_opt(&mut self, anim: Option<Box<RayTraceAnimation<Vector3<f64>>>>) {
-		self.anim_pos = anim;
+		self.transform.set_anim_translation_opt(anim);
 	}
 
+	#[deprecated(note = "Use the transform instead")]
 	pub fn set_anim_pos(&mut self, anim: Box<RayTraceAnimation<Vector3<f64>>>) {
-		self.anim_pos = Some(anim);
+		self.transform.set_anim_translation(anim);
 	}
 
+	#[deprecated(note = "Use the transform instead")]
 	pub fn set_anim_rot_opt(&mut self, anim: Option<Box<RayTraceAnimation<Vector3<f64>>>>) {
-		self.anim_rot = anim;
+		self.transform.set_anim_rotation_opt(anim);
 	}
 
+	#[deprecated(note = "Use the transform instead")]
 	pub fn set_anim_rot(&mut self, anim: Box<RayTraceAnimation<Vector3<f64>>>) {
-		self.anim_rot = Some(anim);
+		self.transform.set_anim_rotation(anim);
+	}
+}
+
+impl RayTraceTransformable for RayTraceObjectPlane {
+	fn get_transform(&self) -> &RayTraceTransform {
+		&self.transform
+	}
+
+	fn get_transform_mut(&mut self) -> &mut RayTraceTransform {
+		&mut self.transform
 	}
 }
 
 struct WorkingData {
-	plane_vec: [Vector3<f64>; 2],
 	plane_normal: Vector3<f64>
 }
 
@@ -71,54 +87,55 @@ const THRESHOLD: f64 = 1e-10;
 #[allow(unused_variables)]
 impl RayTraceObject for RayTraceObjectPlane {
 	fn init(&mut self, frame: usize) {
-		if let Some(ref anim_pos) = self.anim_pos {
-			self.center = anim_pos.next_frame(frame);
-		}
-		if let Some(ref anim_rot) = self.anim_rot {
-			self.rotation = anim_rot.next_frame(frame);
-		}
-
-		let plane_vec1 = [1.0, 0.0, 0.0];
-		let plane_vec2 = [0.0, 1.0, 0.0];
-		let plane_vec3 = [0.0, 0.0, 1.0];
-
-		let rot = rotate_xyz(self.rotation);
-
-		let vec1 = row_mat3_transform(rot, plane_vec1);
-		let vec2 = row_mat3_transform(rot, plane_vec2);
-		let vec3 = row_mat3_transform(rot, plane_vec3);
+		self.transform.init(frame);
 
 		self.data = Some(WorkingData {
-			plane_vec: [vec1, vec3],
-			plane_normal: vec2
+			plane_normal: self.transform.transform_normal([0.0, 1.0, 0.0])
 		});
 	}
 
 	fn get_aabb(&self) -> Option<&AABB> {
 		return None;
 	}
+
+	fn serialize(&self, writer: &mut RayTraceSceneWriter) -> Result<(), RayTraceSceneWriteError> {
+		writer.set_type("plane");
+		try!(writer.set_material("material", &*self.material));
+		writer.set_transform(&self.transform);
+		Ok(())
+	}
 }
 
 impl RayTraceHitable for RayTraceObjectPlane {
 	fn next_hit(&self, ray: &RayTraceRay) -> Option<RayTraceRayHit> {
 		if let Some(ref data) = self.data {
-			if vec3_dot(data.plane_normal, ray.get_direction().clone()).abs() <= THRESHOLD {
+			// The plane spans the x- and z-axis in object space
+			let object_ray = self.transform.inverse_transform_ray(ray);
+			let origin = object_ray.get_position();
+			let dir = object_ray.get_direction();
+
+			if dir[1].abs() <= THRESHOLD {
 				return None;
 			}
 
-			if let Some((dist, vec1, vec2)) = compute_plane_hit(ray, self.center, data.plane_vec[0],
-					data.plane_vec[1]) {
-				if dist <= 0.0 {
-					return None;
-				}
-
-				return Some(RayTraceRayHit::new(dist, ray.get_position_on_ray(dist), data.plane_normal,
-						self.material.get_hit(vec1, vec2)));
-			} else {
+			let dist = -origin[1] / dir[1];
+			if dist <= 0.0 {
 				return None;
 			}
+
+			let (vec1, vec2) = (origin[0] + dist * dir[0], origin[2] + dist * dir[2]);
+			let footprint = RayTraceFootprint::compute(&object_ray, [vec1, 0.0, vec2], [0.0, 1.0, 0.0],
+				|p| [p[0], p[2]]);
+			let mut context = RayTraceShadingContext::new(ray, dist, [vec1, 0.0, vec2], data.plane_normal,
+				[vec1, vec2]);
+			context.set_tangents(self.transform.transform_direction([1.0, 0.0, 0.0]),
+				self.transform.transform_direction([0.0, 0.0, 1.0]));
+			context.set_footprint(footprint);
+
+			return Some(RayTraceRayHit::new(dist, *context.get_position(), data.plane_normal,
+					self.material.get_hit_context(&context)));
 		} else {
 			panic!("Plane was not initialized!");
 		}
 	}
-}
\ No newline at end of file
+}
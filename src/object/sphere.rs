@@ -1,30 +1,33 @@
 use std::f64;
 
+use vecmath::Vector2;
 use vecmath::Vector3;
-use vecmath::Matrix3;
-use vecmath::{vec3_add, vec3_sub, vec3_neg, vec3_dot, vec3_square_len, vec3_normalized_sub};
-use vecmath::row_mat3_transform;
+use vecmath::{vec3_dot, vec3_len, vec3_square_len, vec3_normalized};
 
 use aabb::AABB;
 use anim::RayTraceAnimation;
 use hit::RayTraceRayHit;
+use hit::RayTraceShadingContext;
+use light::lights::RayTraceAreaLightShape;
+use material::RayTraceFootprint;
 use material::RayTraceMaterial;
 use object::RayTraceObject;
 use object::RayTraceHitable;
 use ray::RayTraceRay;
+use scene_file::RayTraceSceneWriteError;
+use scene_file::RayTraceSceneWriter;
 
 use math_util::PI;
 use math_util::HALF_PI;
-use math_util::rotate_xyz;
+use math_util::TWO_PI;
+use math_util::RayTraceTransform;
+use math_util::RayTraceTransformable;
 
 #[allow(dead_code)]
 pub struct RayTraceObjectSphere {
 	material: Box<RayTraceMaterial>,
 	size: f64,
-	center: Vector3<f64>,
-	rotation: Vector3<f64>,
-	anim_pos: Option<Box<RayTraceAnimation<Vector3<f64>>>>,
-	anim_rot: Option<Box<RayTraceAnimation<Vector3<f64>>>>,
+	transform: RayTraceTransform,
 	anim_size: Option<Box<RayTraceAnimation<f64>>>,
 	data: Option<WorkingData>
 }
@@ -34,75 +37,86 @@ impl RayTraceObjectSphere {
 	pub fn new(center: Vector3<f64>, size: f64, material: Box<RayTraceMaterial>) -> Self {
 		Self {
 			material: material,
-			center: center,
 			size: size,
-			rotation: [0.0, 0.0, 0.0],
-			anim_rot: None,
-			anim_pos: None,
+			transform: RayTraceTransform::new_with(center, [0.0, 0.0, 0.0], [1.0, 1.0, 1.0]),
 			anim_size: None,
 			data: None
 		}
 	}
 
-	pub fn set_rotation(&mut self, rotation: Vector3<f64>) {
-		self.rotation = rotation;
+	pub fn set_size(&mut self, size: f64) {
+		self.size = size;
+	}
+
+	pub fn set_anim_size_opt(&mut self, anim: Option<Box<RayTraceAnimation<f64>>>) {
+		self.anim_size = anim;
+	}
+
+	pub fn set_anim_size(&mut self, anim: Box<RayTraceAnimation<f64>>) {
+		self.anim_size = Some(anim);
+	}
+
+	#[deprecated(note = "Use the transform instead")]
+	pub fn get_position(&self) -> &Vector3<f64> {
+		self.transform.get_translation()
 	}
 
+	#[deprecated(note = "Use the transform instead")]
 	pub fn set_position(&mut self, position: Vector3<f64>) {
-		self.center = position;
+		self.transform.set_translation(position);
 	}
 
-	pub fn set_size(&mut self, size: f64) {
-		self.size = size;
+	#[deprecated(note = "Use the transform instead")]
+	pub fn set_rotation(&mut self, rotation: Vector3<f64>) {
+		self.transform.set_rotation(rotation);
 	}
 
+	#[deprecated(note = "Use the transform instead")]
 	pub fn set_anim_pos_opt(&mut self, anim: Option<Box<RayTraceAnimation<Vector3<f64>>>>) {
-		self.anim_pos = anim;
+		self.transform.set_anim_translation_opt(anim);
 	}
 
+	#[deprecated(note = "Use the transform instead")]
 	pub fn set_anim_pos(&mut self, anim: Box<RayTraceAnimation<Vector3<f64>>>) {
-		self.anim_pos = Some(anim);
+		self.transform.set_anim_translation(anim);
 	}
 
+	#[deprecated(note = "Use the transform instead")]
 	pub fn set_anim_rot_opt(&mut self, anim: Option<Box<RayTraceAnimation<Vector3<f64>>>>) {
-		self.anim_rot = anim;
+		self.transform.set_anim_rotation_opt(anim);
 	}
 
+	#[deprecated(note = "Use the transform instead")]
 	pub fn set_anim_rot(&mut self, anim: Box<RayTraceAnimation<Vector3<f64>>>) {
-		self.anim_rot = Some(anim);
+		self.transform.set_anim_rotation(anim);
 	}
+}
 
-	pub fn set_anim_size_opt(&mut self, anim: Option<Box<RayTraceAnimation<f64>>>) {
-		self.anim_size = anim;
+impl RayTraceTransformable for RayTraceObjectSphere {
+	fn get_transform(&self) -> &RayTraceTransform {
+		&self.transform
 	}
 
-	pub fn set_anim_size(&mut self, anim: Box<RayTraceAnimation<f64>>) {
-		self.anim_size = Some(anim);
+	fn get_transform_mut(&mut self) -> &mut RayTraceTransform {
+		&mut self.transform
 	}
 }
 
 struct WorkingData {
-	aabb: AABB,
-	rot_matrix: Matrix3<f64>
+	aabb: AABB
 }
 
 #[allow(unused_variables)]
 impl RayTraceObject for RayTraceObjectSphere {
 	fn init(&mut self, frame: usize) {
-		if let Some(ref anim_pos) = self.anim_pos {
-			self.center = anim_pos.next_frame(frame);
-		}
-		if let Some(ref anim_rot) = self.anim_rot {
-			self.rotation = anim_rot.next_frame(frame);
-		}
+		self.transform.init(frame);
 		if let Some(ref anim_size) = self.anim_size {
 			self.size = anim_size.next_frame(frame);
 		}
 
-		let size_vec = [self.size, self.size, self.size];
+		let radius = self.size / 2.0;
 		self.data = Some(WorkingData {
-				aabb: AABB::new(vec3_sub(self.center, size_vec), vec3_add(self.center, size_vec)),
-				rot_matrix: rotate_xyz(vec3_neg(self.rotation))
+				aabb: self.transform.transform_aabb([-radius, -radius, -radius], [radius, radius, radius])
 			});
 	}
 
@@ -113,19 +127,55 @@ impl RayTraceObject for RayTraceObjectSphere {
 			panic!("Sphere was not initialized!");
 		}
 	}
+
+	// Only uniformly scaled spheres can be sampled by their solid angle, other ones do not light the scene
+	fn get_area_light(&self) -> Option<RayTraceAreaLightShape> {
+		if !self.material.is_emissive() {
+			return None;
+		}
+
+		let radius = self.size / 2.0;
+		let axes = [
+			self.transform.transform_direction([radius, 0.0, 0.0]),
+			self.transform.transform_direction([0.0, radius, 0.0]),
+			self.transform.transform_direction([0.0, 0.0, radius])
+		];
+		let world_radius = vec3_len(axes[0]);
+		let tolerance = 1e-9 * world_radius.max(1.0);
+		let uniform = (1..3).all(|i| (vec3_len(axes[i]) - world_radius).abs() < tolerance)
+			&& [(0, 1), (0, 2), (1, 2)].iter()
+				.all(|&(a, b)| vec3_dot(axes[a], axes[b]).abs() < tolerance * world_radius);
+		if !uniform {
+			warn!("Emissive sphere is not scaled uniformly and does not light other objects");
+			return None;
+		}
+
+		Some(RayTraceAreaLightShape::Sphere(self.transform.transform_position([0.0, 0.0, 0.0]), world_radius))
+	}
+
+	fn serialize(&self, writer: &mut RayTraceSceneWriter) -> Result<(), RayTraceSceneWriteError> {
+		writer.set_type("sphere");
+		try!(writer.set_f64("size", self.size));
+		if let Some(ref anim) = self.anim_size {
+			writer.set_anim_f64("size_animation", &**anim);
+		}
+		try!(writer.set_material("material", &*self.material));
+		writer.set_transform(&self.transform);
+		Ok(())
+	}
 }
 
 impl RayTraceHitable for RayTraceObjectSphere {
 	fn next_hit(&self, ray: &RayTraceRay) -> Option<RayTraceRayHit> {
-		if let Some(ref data) = self.data {
-			let l = ray.get_direction().clone();
-			let o = ray.get_position().clone();
-
-			let dist = vec3_sub(o, self.center);
+		if self.data.is_some() {
+			// Intersect with a sphere around the origin in object space to allow ellipsoids
+			let object_ray = self.transform.inverse_transform_ray(ray);
+			let l = object_ray.get_direction().clone();
+			let o = object_ray.get_position().clone();
 
 			let a = vec3_square_len(l);
-			let b = vec3_dot(l, dist) * 2.0;
-			let c = vec3_square_len(dist) - self.size * self.size / 4.0;
+			let b = vec3_dot(l, o) * 2.0;
+			let c = vec3_square_len(o) - self.size * self.size / 4.0;
 
 			let disc = b * b - 4.0 * a * c;
 			if disc < 0.0 {
@@ -137,20 +187,56 @@ impl RayTraceHitable for RayTraceObjectSphere {
 			let t = if t1 < 0.0 { if t2 < 0.0 { return None; } else { t2 } } else { t1 };
 
 			let hit_point = ray.get_position_on_ray(t);
-			let surface_normal = vec3_normalized_sub(hit_point, self.center);
-			let tex_normal = row_mat3_transform(data.rot_matrix, surface_normal);
-
-			let mut angle_t = if tex_normal[0] != 0.0 {(tex_normal[2] / tex_normal[0]).atan()} else { -HALF_PI };
-			angle_t -= HALF_PI;
-			if tex_normal[0] > 0.0 {
-				angle_t += PI;
-			}
-
-			let angle_p = tex_normal[1].acos();
-
-			return Some(RayTraceRayHit::new(t, hit_point, surface_normal, self.material.get_hit(angle_t, angle_p)));
+			let local_hit = object_ray.get_position_on_ray(t);
+			let tex_normal = vec3_normalized(local_hit);
+			let surface_normal = self.transform.transform_normal(tex_normal);
+
+			let texcoord = get_texcoord(tex_normal);
+			let footprint = RayTraceFootprint::compute(&object_ray, local_hit, tex_normal,
+					|p| get_texcoord(vec3_normalized(p)))
+				.map(|f| RayTraceFootprint::new([wrap_angle(f.get_dx()[0]), f.get_dx()[1]],
+					[wrap_angle(f.get_dy()[0]), f.get_dy()[1]]));
+
+			let tangents = get_tangents(tex_normal);
+			let mut context = RayTraceShadingContext::new(ray, t, local_hit, surface_normal, texcoord);
+			context.set_tangents(self.transform.transform_direction(tangents[0]),
+				self.transform.transform_direction(tangents[1]));
+			context.set_footprint(footprint);
+
+			return Some(RayTraceRayHit::new(t, hit_point, surface_normal, self.material.get_hit_context(&context)));
 		} else {
 			panic!("Sphere was not initialized!");
 		}
 	}
 }
+
+// Angle around the y-axis and angle from the top of the sphere for a normal
+fn get_texcoord(normal: Vector3<f64>) -> Vector2<f64> {
+	let mut angle_t = if normal[0] != 0.0 { (normal[2] / normal[0]).atan() } else { -HALF_PI };
+	angle_t -= HALF_PI;
+	if normal[0] > 0.0 {
+		angle_t += PI;
+	}
+
+	[angle_t, normal[1].acos()]
+}
+
+// Directions of growing texture coordinates, the tangent vanishes at the poles
+fn get_tangents(normal: Vector3<f64>) -> [Vector3<f64>; 2] {
+	let radius = (normal[0] * normal[0] + normal[2] * normal[2]).sqrt().max(1e-12);
+	[
+		[-normal[2], 0.0, normal[0]],
+		[normal[0] * normal[1] / radius, -radius, normal[2] * normal[1] / radius]
+	]
+}
+
+// Differences of the angle around the y-axis are taken the short way across the seam
+fn wrap_angle(angle: f64) -> f64 {
+	if angle > PI {
+		angle - TWO_PI
+	} else if angle < -PI {
+		angle + TWO_PI
+	} else {
+		angle
+	}
+}
@@ -0,0 +1,409 @@
+mod vox_loader;
+
+pub use self::vox_loader::vox_load;
+
+use std::f64;
+use std::collections::HashMap;
+
+use vecmath::Vector3;
+use vecmath::{vec3_add, vec3_scale, vec3_mul, vec3_sub};
+
+use aabb::AABB;
+use anim::RayTraceAnimation;
+use hit::RayTraceRayHit;
+use hit::RayTraceShadingContext;
+use material::RayTraceFootprint;
+use material::RayTraceMaterial;
+use object::RayTraceObject;
+use object::RayTraceHitable;
+use ray::RayTraceRay;
+use scene_file::RayTraceSceneWriteError;
+use scene_file::RayTraceSceneWriter;
+
+use math_util::RayTraceTransform;
+use math_util::RayTraceTransformable;
+
+// Voxel values index into the palette starting with 1, the value 0 marks an empty voxel
+pub const VOXEL_EMPTY: u16 = 0;
+
+pub enum RayTraceVoxelStorage {
+	Dense(Vec<u16>),
+	Sparse(HashMap<[usize; 3], u16>)
+}
+
+#[allow(dead_code)]
+pub struct RayTraceObjectVoxelGrid {
+	palette: Vec<Box<RayTraceMaterial>>,
+	storage: RayTraceVoxelStorage,
+	dimensions: [usize; 3],
+	voxel_size: Vector3<f64>,
+	transform: RayTraceTransform,
+	data: Option<WorkingData>
+}
+
+struct WorkingData {
+	aabb: AABB
+}
+
+const THRESHOLD: f64 = 1e-10;
+
+#[allow(dead_code)]
+impl RayTraceObjectVoxelGrid {
+	pub fn new(dimensions: [usize; 3], voxel_size: Vector3<f64>, palette: Vec<Box<RayTraceMaterial>>) -> Self {
+		let count = match get_voxel_count(dimensions) {
+			Some(count) => count,
+			None => { panic!("Voxel grid is too large!"); }
+		};
+		Self::new_with(dimensions, voxel_size, palette, RayTraceVoxelStorage::Dense(vec![VOXEL_EMPTY; count]))
+	}
+
+	pub fn new_sparse(dimensions: [usize; 3], voxel_size: Vector3<f64>, palette: Vec<Box<RayTraceMaterial>>)
+			-> Self {
+		Self::new_with(dimensions, voxel_size, palette, RayTraceVoxelStorage::Sparse(HashMap::new()))
+	}
+
+	pub fn new_with(dimensions: [usize; 3], voxel_size: Vector3<f64>, palette: Vec<Box<RayTraceMaterial>>,
+			storage: RayTraceVoxelStorage) -> Self {
+		if let RayTraceVoxelStorage::Dense(ref voxels) = storage {
+			if get_voxel_count(dimensions) != Some(voxels.len()) {
+				panic!("Dense voxel storage does not match the grid dimensions!");
+			}
+		}
+
+		Self {
+			palette: palette,
+			storage: storage,
+			dimensions: dimensions,
+			voxel_size: voxel_size,
+			transform: RayTraceTransform::new(),
+			data: None
+		}
+	}
+
+	pub fn get_dimensions(&self) -> &[usize; 3] {
+		&self.dimensions
+	}
+
+	pub fn get_voxel(&self, x: usize, y: usize, z: usize) -> u16 {
+		if x >= self.dimensions[0] || y >= self.dimensions[1] || z >= self.dimensions[2] {
+			return VOXEL_EMPTY;
+		}
+
+		match self.storage {
+			RayTraceVoxelStorage::Dense(ref voxels) => {
+				voxels[self.index_of(x, y, z)]
+			},
+			RayTraceVoxelStorage::Sparse(ref voxels) => {
+				match voxels.get(&[x, y, z]) {
+					Some(value) => *value,
+					None => VOXEL_EMPTY
+				}
+			}
+		}
+	}
+
+	pub fn set_voxel(&mut self, x: usize, y: usize, z: usize, value: u16) {
+		if x >= self.dimensions[0] || y >= self.dimensions[1] || z >= self.dimensions[2] {
+			panic!("Voxel ({}, {}, {}) is outside of the grid!", x, y, z);
+		}
+
+		let index = self.index_of(x, y, z);
+		match self.storage {
+			RayTraceVoxelStorage::Dense(ref mut voxels) => {
+				voxels[index] = value;
+			},
+			RayTraceVoxelStorage::Sparse(ref mut voxels) => {
+				if value == VOXEL_EMPTY {
+					voxels.remove(&[x, y, z]);
+				} else {
+					voxels.insert([x, y, z], value);
+				}
+			}
+		}
+	}
+
+	pub fn fill(&mut self, start: [usize; 3], end: [usize; 3], value: u16) {
+		for z in start[2]..end[2].min(self.dimensions[2]) {
+			for y in start[1]..end[1].min(self.dimensions[1]) {
+				for x in start[0]..end[0].min(self.dimensions[0]) {
+					self.set_voxel(x, y, z, value);
+				}
+			}
+		}
+	}
+
+	pub fn get_palette(&self) -> &Vec<Box<RayTraceMaterial>> {
+		&self.palette
+	}
+
+	pub fn set_palette(&mut self, palette: Vec<Box<RayTraceMaterial>>) {
+		self.palette = palette;
+	}
+
+	pub fn set_voxel_size(&mut self, voxel_size: Vector3<f64>) {
+		self.voxel_size = voxel_size;
+	}
+
+	fn index_of(&self, x: usize, y: usize, z: usize) -> usize {
+		(z * self.dimensions[1] + y) * self.dimensions[0] + x
+	}
+
+	fn get_extent(&self) -> Vector3<f64> {
+		vec3_mul(self.voxel_size, [self.dimensions[0] as f64, self.dimensions[1] as f64, self.dimensions[2] as f64])
+	}
+
+	#[deprecated(note = "Use the transform instead")]
+	pub fn get_position(&self) -> &Vector3<f64> {
+		self.transform.get_translation()
+	}
+
+	#[deprecated(note = "Use the transform instead")]
+	pub fn set_position(&mut self, position: Vector3<f64>) {
+		self.transform.set_translation(position);
+	}
+
+	#[deprecated(note = "Use the transform instead")]
+	pub fn set_rotation(&mut self, rotation: Vector3<f64>) {
+		self.transform.set_rotation(rotation);
+	}
+
+	#[deprecated(note = "Use the transform instead")]
+	pub fn set_anim_pos_opt(&mut self, anim: Option<Box<RayTraceAnimation<Vector3<f64>>>>) {
+		self.transform.set_anim_translation_opt(anim);
+	}
+
+	#[deprecated(note = "Use the transform instead")]
+	pub fn set_anim_pos(&mut self, anim: Box<RayTraceAnimation<Vector3<f64>>>) {
+		self.transform.set_anim_translation(anim);
+	}
+
+	#[deprecated(note = "Use the transform instead")]
+	pub fn set_anim_rot_opt(&mut self, anim: Option<Box<RayTraceAnimation<Vector3<f64>>>>) {
+		self.transform.set_anim_rotation_opt(anim);
+	}
+
+	#[deprecated(note = "Use the transform instead")]
+	pub fn set_anim_rot(&mut self, anim: Box<RayTraceAnimation<Vector3<f64>>>) {
+		self.transform.set_anim_rotation(anim);
+	}
+}
+
+impl RayTraceTransformable for RayTraceObjectVoxelGrid {
+	fn get_transform(&self) -> &RayTraceTransform {
+		&self.transform
+	}
+
+	fn get_transform_mut(&mut self) -> &mut RayTraceTransform {
+		&mut self.transform
+	}
+}
+
+#[allow(unused_variables)]
+impl RayTraceObject for RayTraceObjectVoxelGrid {
+	fn init(&mut self, frame: usize) {
+		self.transform.init(frame);
+
+		// The grid is centered around the origin in object space
+		let half = vec3_scale(self.get_extent(), 0.5);
+		self.data = Some(WorkingData {
+			aabb: self.transform.transform_aabb(vec3_scale(half, -1.0), half)
+		});
+	}
+
+	fn get_aabb(&self) -> Option<&AABB> {
+		if let Some(ref data) = self.data {
+			return Some(&data.aabb);
+		} else {
+			panic!("Voxel grid was not initialized!");
+		}
+	}
+
+	fn serialize(&self, writer: &mut RayTraceSceneWriter) -> Result<(), RayTraceSceneWriteError> {
+		writer.set_type("voxels");
+		writer.set_usize_list("dimensions", &self.dimensions);
+		try!(writer.set_vec3("voxel_size", self.voxel_size));
+		try!(writer.set_material_list("palette", &self.palette));
+
+		// Filled voxels are listed as [x, y, z, value]
+		let mut voxels = Vec::new();
+		match self.storage {
+			RayTraceVoxelStorage::Dense(_) => {
+				for z in 0..self.dimensions[2] {
+					for y in 0..self.dimensions[1] {
+						for x in 0..self.dimensions[0] {
+							let value = self.get_voxel(x, y, z);
+							if value != VOXEL_EMPTY {
+								voxels.push(vec![x, y, z, value as usize]);
+							}
+						}
+					}
+				}
+				writer.set_bool("sparse", false);
+			},
+			RayTraceVoxelStorage::Sparse(ref map) => {
+				voxels = map.iter().map(|(p, v)| vec![p[0], p[1], p[2], *v as usize]).collect();
+				voxels.sort_by_key(|v| (v[2], v[1], v[0]));
+				writer.set_bool("sparse", true);
+			}
+		}
+		writer.set_usize_rows("voxels", &voxels);
+
+		writer.set_transform(&self.transform);
+		Ok(())
+	}
+}
+
+impl RayTraceHitable for RayTraceObjectVoxelGrid {
+	fn next_hit(&self, ray: &RayTraceRay) -> Option<RayTraceRayHit> {
+		if self.data.is_some() {
+			// Transform the ray into the grid space where the grid spans from the origin to its extent
+			let extent = self.get_extent();
+			let object_ray = self.transform.inverse_transform_ray(ray);
+			let origin = vec3_add(*object_ray.get_position(), vec3_scale(extent, 0.5));
+			let dir = *object_ray.get_direction();
+
+			let mut t_enter = 0.0_f64;
+			let mut t_exit = f64::MAX;
+			let mut enter_axis = None;
+
+			for i in 0..3 {
+				if dir[i].abs() < THRESHOLD {
+					if origin[i] < 0.0 || origin[i] > extent[i] {
+						return None;
+					}
+
+					continue;
+				}
+
+				let t0 = (0.0 - origin[i]) / dir[i];
+				let t1 = (extent[i] - origin[i]) / dir[i];
+				let (t_near, t_far) = if t0 < t1 { (t0, t1) } else { (t1, t0) };
+
+				if t_near > t_enter {
+					t_enter = t_near;
+					enter_axis = Some(i);
+				}
+				if t_far < t_exit {
+					t_exit = t_far;
+				}
+				if t_enter > t_exit {
+					return None;
+				}
+			}
+
+			// Amanatides-Woo traversal starting at the voxel the ray enters first
+			let start = vec3_add(origin, vec3_scale(dir, t_enter));
+			let mut cell = [0_isize; 3];
+			let mut step = [0_isize; 3];
+			let mut t_max = [f64::MAX; 3];
+			let mut t_delta = [f64::MAX; 3];
+
+			for i in 0..3 {
+				let size = self.voxel_size[i];
+				cell[i] = ((start[i] / size).floor() as isize).max(0).min(self.dimensions[i] as isize - 1);
+
+				if dir[i] > THRESHOLD {
+					step[i] = 1;
+					t_max[i] = ((cell[i] + 1) as f64 * size - origin[i]) / dir[i];
+					t_delta[i] = size / dir[i];
+				} else if dir[i] < -THRESHOLD {
+					step[i] = -1;
+					t_max[i] = (cell[i] as f64 * size - origin[i]) / dir[i];
+					t_delta[i] = -size / dir[i];
+				}
+			}
+
+			let mut t = t_enter;
+			let mut axis = enter_axis;
+
+			loop {
+				let value = self.get_voxel(cell[0] as usize, cell[1] as usize, cell[2] as usize);
+
+				// A ray starting inside of a filled voxel does not hit its faces
+				if value != VOXEL_EMPTY && axis.is_some() {
+					if let Some(material) = self.palette.get(value as usize - 1) {
+						let hit_axis = axis.unwrap();
+
+						let mut local_normal = [0.0, 0.0, 0.0];
+						local_normal[hit_axis] = if dir[hit_axis] > 0.0 { -1.0 } else { 1.0 };
+
+						let local_hit = vec3_add(origin, vec3_scale(dir, t));
+						let (u, v) = get_face_coordinates(local_hit, self.voxel_size, cell, hit_axis);
+
+						// The face coordinates change linearly so the clamping to the face is left out
+						let object_hit = vec3_sub(local_hit, vec3_scale(extent, 0.5));
+						let size = self.voxel_size;
+						let footprint = RayTraceFootprint::compute(&object_ray, object_hit, local_normal, |p| {
+							let scaled = [p[0] / size[0], p[1] / size[1], p[2] / size[2]];
+							match hit_axis {
+								0 => [scaled[2], scaled[1]],
+								1 => [scaled[0], scaled[2]],
+								_ => [scaled[0], scaled[1]]
+							}
+						});
+
+						let (a, b) = match hit_axis {
+							0 => (2, 1),
+							1 => (0, 2),
+							_ => (0, 1)
+						};
+						let (mut tangent, mut bitangent) = ([0.0, 0.0, 0.0], [0.0, 0.0, 0.0]);
+						tangent[a] = 1.0;
+						bitangent[b] = 1.0;
+
+						let surface_normal = self.transform.transform_normal(local_normal);
+						let mut context = RayTraceShadingContext::new(ray, t, object_hit, surface_normal, [u, v]);
+						context.set_tangents(self.transform.transform_direction(tangent),
+							self.transform.transform_direction(bitangent));
+						context.set_footprint(footprint);
+						context.set_primitive(self.index_of(cell[0] as usize, cell[1] as usize, cell[2] as usize));
+
+						return Some(RayTraceRayHit::new(t, *context.get_position(), surface_normal,
+							material.get_hit_context(&context)));
+					}
+				}
+
+				let next_axis = if t_max[0] < t_max[1] {
+					if t_max[0] < t_max[2] { 0 } else { 2 }
+				} else {
+					if t_max[1] < t_max[2] { 1 } else { 2 }
+				};
+
+				if t_max[next_axis] > t_exit {
+					return None;
+				}
+
+				t = t_max[next_axis];
+				cell[next_axis] += step[next_axis];
+				if cell[next_axis] < 0 || cell[next_axis] >= self.dimensions[next_axis] as isize {
+					return None;
+				}
+
+				t_max[next_axis] += t_delta[next_axis];
+				axis = Some(next_axis);
+			}
+		} else {
+			panic!("Voxel grid was not initialized!");
+		}
+	}
+}
+
+// Number of voxels in a grid, None if it does not fit into memory
+fn get_voxel_count(dimensions: [usize; 3]) -> Option<usize> {
+	dimensions[0].checked_mul(dimensions[1]).and_then(|count| count.checked_mul(dimensions[2]))
+}
+
+fn get_face_coordinates(position: Vector3<f64>, voxel_size: Vector3<f64>, cell: [isize; 3], axis: usize)
+		-> (f64, f64) {
+	let fract = [
+		(position[0] / voxel_size[0] - cell[0] as f64).max(0.0).min(1.0),
+		(position[1] / voxel_size[1] - cell[1] as f64).max(0.0).min(1.0),
+		(position[2] / voxel_size[2] - cell[2] as f64).max(0.0).min(1.0)
+	];
+
+	match axis {
+		0 => (fract[2], fract[1]),
+		1 => (fract[0], fract[2]),
+		_ => (fract[0], fract[1])
+	}
+}
@@ -0,0 +1,214 @@
+use std::fs::File;
+use std::io::Read;
+use std::io::Error as IOError;
+use std::io::ErrorKind;
+
+use color::RayTraceColor;
+use material::RayTraceMaterial;
+use material::RayTraceSimpleMaterial;
+use object::RayTraceObjectVoxelGrid;
+
+const VOX_MAGIC: &'static [u8] = b"VOX ";
+const VOX_VERSION: u32 = 150;
+// Largest model which MagicaVoxel can create in each dimension
+const VOX_MAX_SIZE: usize = 256;
+
+// Grids filling less than this fraction of their volume are stored sparse
+const SPARSE_THRESHOLD: f64 = 0.125;
+
+pub fn vox_load(file_name: &str) -> Result<RayTraceObjectVoxelGrid, IOError> {
+	let mut data = Vec::new();
+	let mut file = try!(File::open(file_name));
+	try!(file.read_to_end(&mut data));
+
+	let mut reader = VoxReader { data: &data, offset: 0 };
+
+	if try!(reader.read_bytes(4)) != VOX_MAGIC {
+		return format_err("Not a MagicaVoxel file", 0);
+	}
+
+	let version = try!(reader.read_u32());
+	if version > VOX_VERSION {
+		warn!("Unknown MagicaVoxel version {}, trying to load anyway", version);
+	}
+
+	let (main_id, _, _) = try!(reader.read_chunk_header());
+	if main_id != b"MAIN" {
+		return format_err("Missing MAIN chunk", reader.offset);
+	}
+
+	let mut size = None;
+	let mut voxels = None;
+	let mut colors = None;
+	let mut models = 0;
+
+	while reader.offset < data.len() {
+		let chunk_offset = reader.offset;
+		let (id, content_size, _) = try!(reader.read_chunk_header());
+		let content_end = match reader.offset.checked_add(content_size) {
+			Some(end) if end <= data.len() => end,
+			_ => { return format_err("Chunk exceeds the file size", chunk_offset); }
+		};
+
+		if id == b"SIZE" {
+			models += 1;
+			if size.is_none() {
+				let model_size = [
+					try!(reader.read_u32()) as usize,
+					try!(reader.read_u32()) as usize,
+					try!(reader.read_u32()) as usize
+				];
+				if model_size.iter().any(|s| *s == 0 || *s > VOX_MAX_SIZE) {
+					return format_err(&format!("Model size must be between 1 and {}", VOX_MAX_SIZE), chunk_offset);
+				}
+				size = Some(model_size);
+			}
+		} else if id == b"XYZI" {
+			if voxels.is_none() {
+				let count = try!(reader.read_u32()) as usize;
+				if count.checked_mul(4).map_or(true, |bytes| bytes > content_end - reader.offset) {
+					return format_err("Voxel count exceeds the chunk size", chunk_offset);
+				}
+
+				let mut list = Vec::with_capacity(count);
+				for _ in 0..count {
+					let voxel = try!(reader.read_bytes(4));
+					list.push([voxel[0], voxel[1], voxel[2], voxel[3]]);
+				}
+				voxels = Some(list);
+			}
+		} else if id == b"RGBA" {
+			let mut palette = Vec::with_capacity(255);
+			for _ in 0..255 {
+				let color = try!(reader.read_bytes(4));
+				palette.push([color[0], color[1], color[2], color[3]]);
+			}
+			colors = Some(palette);
+		} else {
+			info!("Ignored chunk {} at {}", String::from_utf8_lossy(id), chunk_offset);
+		}
+
+		if reader.offset > content_end {
+			return format_err("Chunk content exceeds the chunk size", chunk_offset);
+		}
+
+		// Children of the MAIN chunk are read in the same loop, other chunks are skipped
+		reader.offset = content_end;
+	}
+
+	if models > 1 {
+		warn!("File {} contains {} models, only the first one is loaded", file_name, models);
+	}
+
+	let size = match size {
+		Some(size) => size,
+		None => { return format_err("Missing SIZE chunk", reader.offset); }
+	};
+	let voxels = match voxels {
+		Some(voxels) => voxels,
+		None => { return format_err("Missing XYZI chunk", reader.offset); }
+	};
+	let colors = match colors {
+		Some(colors) => colors,
+		None => default_palette()
+	};
+
+	let palette = colors.iter()
+		.map(|c| {
+			let color = RayTraceColor::new_with(c[0] as f32 / 255.0, c[1] as f32 / 255.0, c[2] as f32 / 255.0,
+				c[3] as f32 / 255.0);
+			Box::new(RayTraceSimpleMaterial::new(color)) as Box<RayTraceMaterial>
+		})
+		.collect();
+
+	// MagicaVoxel uses a z-up coordinate system which is converted into the y-up system of the ray tracer
+	let dimensions = [size[0], size[2], size[1]];
+	let volume = (dimensions[0] * dimensions[1] * dimensions[2]) as f64;
+	let voxel_size = [1.0, 1.0, 1.0];
+
+	let mut grid = if (voxels.len() as f64) < volume * SPARSE_THRESHOLD {
+		RayTraceObjectVoxelGrid::new_sparse(dimensions, voxel_size, palette)
+	} else {
+		RayTraceObjectVoxelGrid::new(dimensions, voxel_size, palette)
+	};
+
+	for (i, voxel) in voxels.iter().enumerate() {
+		let (x, y, z) = (voxel[0] as usize, voxel[1] as usize, voxel[2] as usize);
+		if x >= size[0] || y >= size[1] || z >= size[2] {
+			return format_err(&format!("Voxel {} is outside of the model", i), 0);
+		}
+
+		grid.set_voxel(x, z, size[1] - 1 - y, voxel[3] as u16);
+	}
+
+	info!("Loaded {} voxels in a grid of {}x{}x{}", voxels.len(), dimensions[0], dimensions[1], dimensions[2]);
+
+	Ok(grid)
+}
+
+struct VoxReader<'a> {
+	data: &'a [u8],
+	offset: usize
+}
+
+impl<'a> VoxReader<'a> {
+	fn read_bytes(&mut self, count: usize) -> Result<&'a [u8], IOError> {
+		if self.offset + count > self.data.len() {
+			return Err(IOError::new(ErrorKind::UnexpectedEof, "Unexpected end of file"));
+		}
+
+		let bytes = &self.data[self.offset..self.offset + count];
+		self.offset += count;
+		Ok(bytes)
+	}
+
+	fn read_u32(&mut self) -> Result<u32, IOError> {
+		let b = try!(self.read_bytes(4));
+		Ok((b[0] as u32) | ((b[1] as u32) << 8) | ((b[2] as u32) << 16) | ((b[3] as u32) << 24))
+	}
+
+	fn read_chunk_header(&mut self) -> Result<(&'a [u8], usize, usize), IOError> {
+		let id = try!(self.read_bytes(4));
+		let content_size = try!(self.read_u32()) as usize;
+		let children_size = try!(self.read_u32()) as usize;
+		Ok((id, content_size, children_size))
+	}
+}
+
+fn format_err<T>(message: &str, offset: usize) -> Result<T, IOError> {
+	Err(IOError::new(ErrorKind::InvalidData, format!("Error at byte {}: {}", offset, message)))
+}
+
+// Rebuilds the MagicaVoxel default palette (color cube followed by blue, green, red and gray ramps)
+fn default_palette() -> Vec<[u8; 4]> {
+	let levels = [0xff_u8, 0xcc, 0x99, 0x66, 0x33, 0x00];
+	let ramp = [0xee_u8, 0xdd, 0xbb, 0xaa, 0x88, 0x77, 0x55, 0x44, 0x22, 0x11];
+	let mut palette = Vec::with_capacity(255);
+
+	for r in levels.iter() {
+		for g in levels.iter() {
+			for b in levels.iter() {
+				if *r == 0 && *g == 0 && *b == 0 {
+					continue;
+				}
+
+				palette.push([*r, *g, *b, 0xff]);
+			}
+		}
+	}
+
+	for v in ramp.iter() {
+		palette.push([0x00, 0x00, *v, 0xff]);
+	}
+	for v in ramp.iter() {
+		palette.push([0x00, *v, 0x00, 0xff]);
+	}
+	for v in ramp.iter() {
+		palette.push([*v, 0x00, 0x00, 0xff]);
+	}
+	for v in ramp.iter() {
+		palette.push([*v, *v, *v, 0xff]);
+	}
+
+	palette
+}
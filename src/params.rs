@@ -4,10 +4,17 @@ use std::mem::swap;
 use color::RayTraceColor;
 use light::RayTraceShading;
 use sample::RayTraceSampleFilter;
+use scene_file::RayTraceSceneWriteError;
+use scene_file::RayTraceSceneWriter;
 
 pub trait RayTraceSampling {
 	fn apply(&self, x: f64, y: f64) -> (f64, f64);
 	fn get_ray_count(&self) -> usize;
+
+	// Samplings which do not override this can not be written to a scene file
+	fn serialize(&self, writer: &mut RayTraceSceneWriter) -> Result<(), RayTraceSceneWriteError> {
+		Err(writer.unsupported("sampling"))
+	}
 }
 
 #[allow(dead_code)]
@@ -46,6 +53,10 @@ pub struct RayTraceParams {
 	filter: Option<Box<RayTraceSampleFilter + Sync>>,
 	shading: Option<Box<RayTraceShading + Sync>>,
 	max_depth: usize,
+	// Shadow rays towards each area light per hit
+	light_samples: usize,
+	// Reflected rays of glossy surfaces hit by camera rays
+	reflection_samples: usize,
 	background_color: RayTraceColor,
 	indirect_color: RayTraceColor,
 	ambient_light: RayTraceColor
@@ -58,6 +69,8 @@ impl RayTraceParams {
 			sampling: None,
 			filter: None,
 			max_depth: 3,
+			light_samples: 1,
+			reflection_samples: 1,
 			background_color: RayTraceColor::transparent(),
 			indirect_color: RayTraceColor::white(),
 			ambient_light: RayTraceColor::white(),
@@ -95,6 +108,22 @@ impl RayTraceParams {
 		self.max_depth
 	}
 
+	pub fn set_light_samples(&mut self, light_samples: usize) {
+		self.light_samples = light_samples;
+	}
+
+	pub fn get_light_samples(&self) -> usize {
+		self.light_samples
+	}
+
+	pub fn set_reflection_samples(&mut self, reflection_samples: usize) {
+		self.reflection_samples = reflection_samples;
+	}
+
+	pub fn get_reflection_samples(&self) -> usize {
+		self.reflection_samples
+	}
+
 	pub fn set_background_color(&mut self, color: RayTraceColor) {
 		self.background_color = color;
 	}
@@ -168,4 +197,11 @@ impl RayTraceSampling for RayTraceRandomSampling {
 		(x + rng.gen_range(-1.0, 1.0) * self.size,
 		y + rng.gen_range(-1.0, 1.0) * self.size)
 	}
+
+	fn serialize(&self, writer: &mut RayTraceSceneWriter) -> Result<(), RayTraceSceneWriteError> {
+		writer.set_type("random");
+		try!(writer.set_f64("size", self.size));
+		writer.set_usize("rays", self.ray_count);
+		Ok(())
+	}
 }
@@ -1,29 +1,48 @@
 use vecmath::Vector3;
 use vecmath::vec3_add;
+use vecmath::vec3_dot;
 use vecmath::vec3_scale;
 use vecmath::vec3_sub;
 use vecmath::vec3_normalized;
 
+const THRESHOLD: f64 = 1e-10;
+
+/// Half line starting at a position which is used to find hits with objects.
 #[allow(dead_code)]
 pub struct RayTraceRay {
 	position: Vector3<f64>,
-	direction: Vector3<f64>
+	direction: Vector3<f64>,
+	differentials: Option<RayTraceRayDifferentials>
+}
+
+/// Rays through the neighbouring pixels in x and y direction of the screen which are used to estimate the area
+/// covered by a ray on a surface.
+#[derive(Clone, Debug)]
+pub struct RayTraceRayDifferentials {
+	x_position: Vector3<f64>,
+	x_direction: Vector3<f64>,
+	y_position: Vector3<f64>,
+	y_direction: Vector3<f64>
 }
 
 #[allow(dead_code)]
 impl RayTraceRay {
+	/// Creates a ray with the given direction which is not normalized.
 	pub fn new(position: Vector3<f64>, direction: Vector3<f64>) -> RayTraceRay {
 		RayTraceRay {
 			position: position,
-			direction: direction
+			direction: direction,
+			differentials: None
 		}
 	}
 
+	/// Creates a ray with a normalized direction pointing from the position to the target.
 	pub fn new_to(position: Vector3<f64>, to: Vector3<f64>) -> Self {
 		let direction = vec3_sub(to, position);
 		Self {
 			position: position,
-			direction: vec3_normalized(direction)
+			direction: vec3_normalized(direction),
+			differentials: None
 		}
 	}
 
@@ -35,7 +54,86 @@ impl RayTraceRay {
 		&self.direction
 	}
 
+	/// Returns the point at the given distance in units of the direction length.
 	pub fn get_position_on_ray(&self, distance: f64) -> Vector3<f64> {
 		vec3_add(self.position, vec3_scale(self.direction, distance))
 	}
+
+	pub fn get_differentials(&self) -> Option<&RayTraceRayDifferentials> {
+		self.differentials.as_ref()
+	}
+
+	pub fn set_differentials(&mut self, differentials: Option<RayTraceRayDifferentials>) {
+		self.differentials = differentials;
+	}
+
+	/// Moves the neighbouring rays closer by the factor, e.g. if a pixel is covered by several rays.
+	pub fn scale_differentials(&mut self, scale: f64) {
+		if let Some(ref mut d) = self.differentials {
+			d.x_position = vec3_add(self.position, vec3_scale(vec3_sub(d.x_position, self.position), scale));
+			d.x_direction = vec3_add(self.direction, vec3_scale(vec3_sub(d.x_direction, self.direction), scale));
+			d.y_position = vec3_add(self.position, vec3_scale(vec3_sub(d.y_position, self.position), scale));
+			d.y_direction = vec3_add(self.direction, vec3_scale(vec3_sub(d.y_direction, self.direction), scale));
+		}
+	}
+
+	/// Applies the functions to the positions and directions of the ray and its differentials.
+	pub fn transform_with<P, D>(&self, position: P, direction: D) -> RayTraceRay
+			where P: Fn(Vector3<f64>) -> Vector3<f64>, D: Fn(Vector3<f64>) -> Vector3<f64> {
+		RayTraceRay {
+			position: position(self.position),
+			direction: direction(self.direction),
+			differentials: self.differentials.as_ref().map(|d| RayTraceRayDifferentials {
+				x_position: position(d.x_position),
+				x_direction: direction(d.x_direction),
+				y_position: position(d.y_position),
+				y_direction: direction(d.y_direction)
+			})
+		}
+	}
+
+	/// Returns the offsets from the position to the points where the neighbouring rays hit the plane with the
+	/// given normal through the position.
+	pub fn get_footprint(&self, position: Vector3<f64>, normal: Vector3<f64>) -> Option<[Vector3<f64>; 2]> {
+		let d = match self.differentials {
+			Some(ref d) => d,
+			None => { return None; }
+		};
+
+		let offset = |origin: Vector3<f64>, direction: Vector3<f64>| {
+			let cos = vec3_dot(normal, direction);
+			if cos.abs() < THRESHOLD {
+				return None;
+			}
+
+			let t = vec3_dot(normal, vec3_sub(position, origin)) / cos;
+			Some(vec3_sub(vec3_add(origin, vec3_scale(direction, t)), position))
+		};
+
+		match (offset(d.x_position, d.x_direction), offset(d.y_position, d.y_direction)) {
+			(Some(x), Some(y)) => Some([x, y]),
+			_ => None
+		}
+	}
+}
+
+#[allow(dead_code)]
+impl RayTraceRayDifferentials {
+	/// Creates the differentials from the rays through the neighbouring pixels.
+	pub fn new(x: &RayTraceRay, y: &RayTraceRay) -> Self {
+		Self {
+			x_position: x.position,
+			x_direction: x.direction,
+			y_position: y.position,
+			y_direction: y.direction
+		}
+	}
+
+	pub fn get_x_ray(&self) -> RayTraceRay {
+		RayTraceRay::new(self.x_position, self.x_direction)
+	}
+
+	pub fn get_y_ray(&self) -> RayTraceRay {
+		RayTraceRay::new(self.y_position, self.y_direction)
+	}
 }
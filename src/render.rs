@@ -1,8 +1,13 @@
 use std::collections::BinaryHeap;
+use std::f32;
 use std::f64;
 use std::io::Error as IOError;
 use std::sync::{Arc};
 
+use rand::{Rng, thread_rng};
+
+use vecmath::{vec3_dot, vec3_neg, vec3_normalized, vec3_scale, vec3_sub};
+
 use time;
 
 use scoped_threadpool::Pool;
@@ -22,12 +27,119 @@ use scene::RayTraceScene;
 use source::RayTraceSource;
 use source::RayTraceSourceSet;
 use math_util::compute_reflected_ray;
+use math_util::sample_phong_lobe;
+
+const PROGRESS_STEPS: usize = 100;
+const DEFAULT_THREAD_COUNT: u32 = 8;
+
+pub struct RayTracer {
+	frame_hook: Option<Box<FnMut(usize, &mut RayTraceScene, &mut Box<RayTraceCamera>)>>,
+	progress_hook: Option<Box<FnMut(&RayTraceProgress)>>,
+	thread_count: u32,
+	frame_range: Option<(usize, usize)>
+}
+
+// State of a running render which is passed to the progress hook
+pub struct RayTraceProgress {
+	frame: usize,
+	frame_index: usize,
+	frame_count: usize,
+	rows_done: usize,
+	row_count: usize,
+	elapsed: f64
+}
+
+#[allow(dead_code)]
+impl RayTraceProgress {
+	pub fn get_frame(&self) -> usize {
+		self.frame
+	}
+
+	// Position of the frame in the rendered range starting at 0
+	pub fn get_frame_index(&self) -> usize {
+		self.frame_index
+	}
+
+	pub fn get_frame_count(&self) -> usize {
+		self.frame_count
+	}
+
+	pub fn get_rows_done(&self) -> usize {
+		self.rows_done
+	}
+
+	pub fn get_row_count(&self) -> usize {
+		self.row_count
+	}
+
+	// Seconds since the render was started
+	pub fn get_elapsed(&self) -> f64 {
+		self.elapsed
+	}
+
+	// Fraction of all frames which is done in the range 0 to 1
+	pub fn get_fraction(&self) -> f64 {
+		if self.frame_count == 0 || self.row_count == 0 {
+			return 1.0;
+		}
+
+		(self.frame_index as f64 + self.rows_done as f64 / self.row_count as f64) / self.frame_count as f64
+	}
+
+	// Estimated seconds until the render is done
+	pub fn get_remaining(&self) -> Option<f64> {
+		let fraction = self.get_fraction();
+		if fraction <= 0.0 {
+			return None;
+		}
 
-pub struct RayTracer { }
+		Some(self.elapsed / fraction * (1.0 - fraction))
+	}
+}
 
 impl RayTracer {
 	pub fn new() -> Self {
-		Self { }
+		Self {
+			frame_hook: None,
+			progress_hook: None,
+			thread_count: DEFAULT_THREAD_COUNT,
+			frame_range: None
+		}
+	}
+
+	// Called before each frame is initialized, this is the only place where the scene may be changed while rendering
+	pub fn set_frame_hook(&mut self, hook: Box<FnMut(usize, &mut RayTraceScene, &mut Box<RayTraceCamera>)>) {
+		self.frame_hook = Some(hook);
+	}
+
+	pub fn clear_frame_hook(&mut self) {
+		self.frame_hook = None;
+	}
+
+	// Called from the rendering thread whenever a band of rows is done
+	pub fn set_progress_hook(&mut self, hook: Box<FnMut(&RayTraceProgress)>) {
+		self.progress_hook = Some(hook);
+	}
+
+	pub fn clear_progress_hook(&mut self) {
+		self.progress_hook = None;
+	}
+
+	pub fn set_thread_count(&mut self, thread_count: u32) {
+		self.thread_count = if thread_count == 0 { 1 } else { thread_count };
+	}
+
+	pub fn get_thread_count(&self) -> u32 {
+		self.thread_count
+	}
+
+	// Renders only the frames from start up to (excluding) end, the sink receives the original frame numbers
+	pub fn set_frame_range(&mut self, start: usize, end: usize) {
+		self.frame_range = Some((start, end));
+	}
+
+	pub fn clear_frame_range(&mut self) {
+		self.frame_range = None;
 	}
 
 	pub fn render(&mut self, source: &mut RayTraceSource, sink: &mut Box<RayTraceSink>) -> Result<(), IOError> {
@@ -35,7 +147,13 @@ impl RayTracer {
 		let RayTraceSourceSet {ref mut scene, ref mut camera, ref mut params, ref out_params} = *w_guard;
 		let mut arc_acc = Arc::new(RayTraceSampleAccumulator::new(params.unwrap_filter()));
 
-		try!(sink.init(out_params.get_width(), out_params.get_height(), out_params.get_frames()));
+		let (first_frame, end_frame) = match self.frame_range {
+			Some((start, end)) => (start, end.min(out_params.get_frames())),
+			None => (0, out_params.get_frames())
+		};
+		let frame_count = if end_frame > first_frame { end_frame - first_frame } else { 0 };
+
+		try!(sink.init(out_params.get_width(), out_params.get_height(), frame_count));
 		Arc::get_mut(&mut arc_acc).unwrap().init(out_params.get_width(), out_params.get_height());
 
 		let mut arc_params: Arc<&mut RayTraceParams> = Arc::new(params);
@@ -43,20 +161,32 @@ impl RayTracer {
 		let mut arc_scene: Arc<&mut RayTraceScene> = Arc::new(scene);
 		//let mut arc_tree: Arc<RayTraceOctree<usize>>;
 
-		let mut thread_pool = Pool::new(8);
+		let mut thread_pool = Pool::new(self.thread_count);
+		let render_start = time::precise_time_s();
+
+		// Rows are rendered in bands to report the progress between them
+		let height = out_params.get_height();
+		let band_size = (height / PROGRESS_STEPS).max(1);
 
-		for frame in 0..out_params.get_frames() {
+		for frame in first_frame..end_frame {
 			info!("Initializing frame {} ...", frame + 1);
 			let start = time::now();
 
 			{
-				Arc::get_mut(&mut arc_camera).unwrap().init(frame);
-				Arc::get_mut(&mut arc_scene).unwrap().init(frame);
+				// The worker threads of the previous frame are done so the scene is not shared at this point
+				let camera: &mut Box<RayTraceCamera> = Arc::get_mut(&mut arc_camera).unwrap();
+				let scene: &mut RayTraceScene = Arc::get_mut(&mut arc_scene).unwrap();
+				if let Some(ref mut hook) = self.frame_hook {
+					hook(frame, scene, camera);
+				}
+
+				camera.init(frame);
+				scene.init(frame);
 				/*let scene = Arc::get_mut(&mut arc_scene).unwrap();
 				scene.init(frame);
 
 				let mut tree = RayTraceOctree::new();
-				for (i, object) in scene.get_objects().iter().enumerate() {
+				for (i, object) in scene.get_objects().enumerate() {
 					tree.add(i, object.get_aabb());
 				}
 				arc_tree = Arc::new(tree);*/
@@ -66,21 +196,39 @@ impl RayTracer {
 
 			info!("Rendering frame {} ...", frame + 1);
 			let start = time::now();
-			thread_pool.scoped(|scoped| {
-				for y in 0..out_params.get_height() {
-					for x in 0..out_params.get_width() {
-						let scoped_camera: Arc<&Box<RayTraceCamera>> = Arc::new(&arc_camera);
-						let scoped_scene: Arc<&RayTraceScene> = Arc::new(&arc_scene);
-						let scoped_params: Arc<&RayTraceParams> = Arc::new(&arc_params);
-						let scoped_acc = arc_acc.clone();
-						//let scoped_tree = arc_tree.clone();
-
-						scoped.execute(move || {
-							compute_samples(scoped_camera, scoped_scene, scoped_params, x, y, scoped_acc/*, scoped_tree*/);
-						});
+			let mut band_start = 0;
+			while band_start < height {
+				let band_end = (band_start + band_size).min(height);
+				thread_pool.scoped(|scoped| {
+					for y in band_start..band_end {
+						for x in 0..out_params.get_width() {
+							let scoped_camera: Arc<&Box<RayTraceCamera>> = Arc::new(&arc_camera);
+							let scoped_scene: Arc<&RayTraceScene> = Arc::new(&arc_scene);
+							let scoped_params: Arc<&RayTraceParams> = Arc::new(&arc_params);
+							let scoped_acc = arc_acc.clone();
+							//let scoped_tree = arc_tree.clone();
+
+							scoped.execute(move || {
+								compute_samples(scoped_camera, scoped_scene, scoped_params, x, y,
+									scoped_acc/*, scoped_tree*/);
+							});
+						}
 					}
+				});
+
+				if let Some(ref mut hook) = self.progress_hook {
+					hook(&RayTraceProgress {
+						frame: frame,
+						frame_index: frame - first_frame,
+						frame_count: frame_count,
+						rows_done: band_end,
+						row_count: height,
+						elapsed: time::precise_time_s() - render_start
+					});
 				}
-			});
+
+				band_start = band_end;
+			}
 
 			info!("Rendered frame {} in {}", frame + 1, (time::now() - start));
 
@@ -107,7 +255,7 @@ fn compute_samples(camera: Arc<&Box<RayTraceCamera>>, scene: Arc<&RayTraceScene>
 			let p_x = x as f64 + 0.5_f64;
 			let p_y = y as f64 + 0.5_f64;
 
-			let ray = camera.make_ray(p_x, p_y);
+			let ray = camera.make_ray_differential(p_x, p_y);
 			let color = compute_color_for_ray(&ray, *camera, *scene, *params/*, &*tree.as_ref()*/, 0);
 
 			acc.add_sample(x, y, RayTraceSample { x: p_x, y: p_y, color: color });
@@ -115,9 +263,13 @@ fn compute_samples(camera: Arc<&Box<RayTraceCamera>>, scene: Arc<&RayTraceScene>
 		&Some(ref sampling) => {
 			let ray_count = sampling.get_ray_count();
 
+			// The rays of a pixel share its area
+			let footprint_scale = 1.0 / (ray_count as f64).sqrt();
+
 			for _ in 0..ray_count {
 				let (p_x, p_y) = sampling.apply(x as f64, y as f64);
-				let ray = camera.make_ray(p_x, p_y);
+				let mut ray = camera.make_ray_differential(p_x, p_y);
+				ray.scale_differentials(footprint_scale);
 				let color = compute_color_for_ray(&ray, *camera, *scene, *params/*, &*tree.as_ref()*/, 0);
 				acc.add_sample(x, y, RayTraceSample { x: p_x, y: p_y, color: color });
 			}
@@ -135,7 +287,7 @@ fn compute_color_for_ray(ray: &RayTraceRay, camera: &Box<RayTraceCamera>, scene:
 	// Collect all ray hits
 	let mut ray_hits = BinaryHeap::<RayTraceHitHeapEntry<RayTraceRayHit>>::new();
 
-	for object in scene.get_objects().iter() {
+	for object in scene.get_objects() {
 		if let Some(aabb) = object.get_aabb() {
 			if !aabb.is_hit(ray) {
 				continue;
@@ -168,14 +320,71 @@ fn compute_color_for_ray(ray: &RayTraceRay, camera: &Box<RayTraceCamera>, scene:
 				overlay_color = RayTraceColor::transparent();
 			}
 
-			let reflectance = hit.get_surface_material().get_reflectance();
-			if reflectance != 0.0 {
-				let reflected_ray = compute_reflected_ray(hit.get_surface_normal().clone(), ray, hit.get_distance());
-				let reflected_color = compute_color_for_ray(&reflected_ray, camera, scene, params/*, tree*/, depth + 1);
-				material_color = mix_color(&material_color, &reflected_color, reflectance);
+			// Physically based surfaces add the reflected light weighted by their BRDF
+			let material = hit.get_surface_material();
+			if material.get_brdf().is_some() {
+				let reflected_color = compute_reflected_color(ray, &hit, camera, scene, params, depth);
+				material_color.add(reflected_color.get_r(), reflected_color.get_g(), reflected_color.get_b(), 0.0);
+			} else if material.get_reflectance() != 0.0 {
+				let reflected_color = compute_reflected_color(ray, &hit, camera, scene, params, depth);
+				material_color = mix_color(&material_color, &reflected_color, material.get_reflectance());
 			}
 
+			// Emitted light is not shaded
+			let emission = material.get_emission();
+			material_color.add(emission.get_r(), emission.get_g(), emission.get_b(), 0.0);
+
 			return mix_color(&material_color, &overlay_color, overlay_color.get_a());
 		}
 	}
 }
+
+// Mirrors reflect a single ray while glossy surfaces average rays sampled from their lobe, which is the GGX lobe of
+// physically based materials or the phong lobe with the glossiness as exponent. Reflected rays of reflected rays are
+// not split again.
+fn compute_reflected_color(ray: &RayTraceRay, hit: &RayTraceRayHit, camera: &Box<RayTraceCamera>,
+		scene: &RayTraceScene, params: &RayTraceParams, depth: usize) -> RayTraceColor {
+	let material = hit.get_surface_material();
+	let normal = *hit.get_surface_normal();
+	let view = vec3_normalized(vec3_neg(*ray.get_direction()));
+	if material.is_mirror() {
+		let reflected_ray = compute_reflected_ray(normal, ray, hit.get_distance());
+		let reflected_color = compute_color_for_ray(&reflected_ray, camera, scene, params/*, tree*/, depth + 1);
+		return match material.get_brdf() {
+			Some(brdf) => reflected_color * brdf.get_mirror_weight(normal, view),
+			None => reflected_color
+		};
+	}
+
+	let samples = if depth == 0 { params.get_reflection_samples().max(1) } else { 1 };
+	let position = ray.get_position_on_ray(hit.get_distance() - 1e-10);
+	let mirror = vec3_sub(vec3_scale(normal, 2.0 * vec3_dot(view, normal)), view);
+
+	let mut rng = thread_rng();
+	let mut color = RayTraceColor::new_with(0.0, 0.0, 0.0, 0.0);
+	for _ in 0..samples {
+		let u = [rng.gen::<f64>(), rng.gen::<f64>()];
+		let (direction, weight) = match material.get_brdf() {
+			Some(brdf) => match brdf.sample_specular(normal, view, u) {
+				Some(sample) => sample,
+				None => { continue; }
+			},
+			None => {
+				let exponent = material.get_glossiness().unwrap_or(f32::INFINITY);
+				(sample_phong_lobe(mirror, exponent as f64, u), RayTraceColor::white())
+			}
+		};
+
+		// Directions of the lobe below the surface are absorbed
+		if vec3_dot(direction, normal) <= 0.0 {
+			continue;
+		}
+
+		// The footprint of glossy rays is not tracked since the lobe blurs far more than a pixel
+		let reflected_ray = RayTraceRay::new(position, direction);
+		let reflected_color = compute_color_for_ray(&reflected_ray, camera, scene, params/*, tree*/, depth + 1);
+		color += reflected_color * weight;
+	}
+
+	color / samples as f32
+}
@@ -1,6 +1,8 @@
 use color::RayTraceColor;
 use sample::RayTraceSample;
 use sample::RayTraceSampleFilter;
+use scene_file::RayTraceSceneWriteError;
+use scene_file::RayTraceSceneWriter;
 
 use sample::index_of;
 
@@ -28,6 +30,11 @@ impl RayTraceSampleFilter for RayTraceAverageFilter {
 			average / sample_count
 		}
 	}
+
+	fn serialize(&self, writer: &mut RayTraceSceneWriter) -> Result<(), RayTraceSceneWriteError> {
+		writer.set_type("average");
+		Ok(())
+	}
 }
 
 unsafe impl Sync for RayTraceAverageFilter { }
\ No newline at end of file
@@ -1,6 +1,8 @@
 use color::RayTraceColor;
 use sample::RayTraceSample;
 use sample::RayTraceSampleFilter;
+use scene_file::RayTraceSceneWriteError;
+use scene_file::RayTraceSceneWriter;
 
 use sample::index_of;
 use sample::valid_index;
@@ -55,6 +57,12 @@ impl RayTraceSampleFilter for RayTraceBoxFilter {
 			color / factor
 		}
 	}
+
+	fn serialize(&self, writer: &mut RayTraceSceneWriter) -> Result<(), RayTraceSceneWriteError> {
+		writer.set_type("box");
+		try!(writer.set_f64("size", self.size));
+		Ok(())
+	}
 }
 
 unsafe impl Sync for RayTraceBoxFilter { }
\ No newline at end of file
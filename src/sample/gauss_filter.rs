@@ -1,6 +1,8 @@
 use color::RayTraceColor;
 use sample::RayTraceSample;
 use sample::RayTraceSampleFilter;
+use scene_file::RayTraceSceneWriteError;
+use scene_file::RayTraceSceneWriter;
 
 use sample::index_of;
 use sample::valid_index;
@@ -64,6 +66,12 @@ impl RayTraceSampleFilter for RayTraceGaussFilter {
 			color / factor
 		}
 	}
+
+	fn serialize(&self, writer: &mut RayTraceSceneWriter) -> Result<(), RayTraceSceneWriteError> {
+		writer.set_type("gauss");
+		try!(writer.set_f64("size", self.size));
+		Ok(())
+	}
 }
 
 unsafe impl Sync for RayTraceGaussFilter { }
\ No newline at end of file
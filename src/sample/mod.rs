@@ -11,6 +11,8 @@ use std::io::Error as IOError;
 use std::mem::swap;
 
 use color::RayTraceColor;
+use scene_file::RayTraceSceneWriteError;
+use scene_file::RayTraceSceneWriter;
 use sink::RayTraceSink;
 
 pub struct RayTraceSample {
@@ -22,6 +24,11 @@ pub struct RayTraceSample {
 pub trait RayTraceSampleFilter {
 	fn filter(&self, x: usize, y: usize, width: usize, height: usize, samples: &Box<[&Vec<RayTraceSample>]>)
 		-> RayTraceColor;
+
+	// Filters which do not override this can not be written to a scene file
+	fn serialize(&self, writer: &mut RayTraceSceneWriter) -> Result<(), RayTraceSceneWriteError> {
+		Err(writer.unsupported("filter"))
+	}
 }
 
 pub struct RayTraceSampleAccumulator {
@@ -1,65 +1,532 @@
-use std::mem;
-
-use nonsync::Unsafe;
-use nonsync::UnsafeRef;
+use std::any::Any;
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
 
+use aabb::AABB;
 use object::RayTraceObject;
 use light::RayTraceLight;
+use light::lights::RayTraceAreaLight;
+
+static NEXT_SCENE_ID: AtomicUsize = ATOMIC_USIZE_INIT;
 
 #[allow(dead_code)]
 pub struct RayTraceScene {
-	objects: Vec<Unsafe<Box<RayTraceObject>>>,
-	lights: Vec<Unsafe<Box<RayTraceLight>>>
+	id: usize,
+	frame: Option<usize>,
+	// Removed entries keep their slot so that the handles of other entries stay valid
+	objects: Vec<Option<RayTraceSceneEntry<RayTraceObject>>>,
+	lights: Vec<Option<RayTraceSceneEntry<RayTraceLight>>>,
+	// Visible emissive objects, collected on initialization and whenever objects are added, removed, replaced or
+	// hidden after it
+	area_lights: Vec<RayTraceAreaLight>
+}
+
+struct RayTraceSceneEntry<B: ?Sized> {
+	value: Box<B>,
+	name: Option<String>,
+	tags: Vec<String>,
+	visible: bool
+}
+
+/// Typed reference to an object of a scene which is used to change the object between frames.
+pub struct RayTraceObjectHandle<T> {
+	scene: usize,
+	index: usize,
+	marker: PhantomData<T>
+}
+
+/// Typed reference to a light of a scene which is used to change the light between frames.
+pub struct RayTraceLightHandle<T> {
+	scene: usize,
+	index: usize,
+	marker: PhantomData<T>
+}
+
+/// Description of an object or light returned by the queries of a scene.
+pub struct RayTraceSceneEntryInfo {
+	index: usize,
+	name: Option<String>,
+	tags: Vec<String>,
+	visible: bool,
+	aabb: Option<AABB>
+}
+
+/// Allows to get the concrete type of objects and lights back, this is implemented for all types.
+pub trait RayTraceAny {
+	fn as_any(&self) -> &Any;
+	fn as_any_mut(&mut self) -> &mut Any;
+	fn into_any(self: Box<Self>) -> Box<Any>;
+}
+
+impl<T: Any> RayTraceAny for T {
+	fn as_any(&self) -> &Any {
+		self
+	}
+
+	fn as_any_mut(&mut self) -> &mut Any {
+		self
+	}
+
+	fn into_any(self: Box<Self>) -> Box<Any> {
+		self
+	}
 }
 
 #[allow(dead_code, unused_variables)]
 impl RayTraceScene {
 	pub fn new() -> Self {
 		Self {
+			id: NEXT_SCENE_ID.fetch_add(1, Ordering::Relaxed),
+			frame: None,
 			objects: Vec::new(),
-			lights: Vec::new()
+			lights: Vec::new(),
+			area_lights: Vec::new()
 		}
 	}
 
 	pub fn init(&mut self, frame: usize) {
-		for obj in self.objects.iter_mut() {
-			obj.init(frame);
+		for obj in self.objects.iter_mut().filter_map(|o| o.as_mut()) {
+			obj.value.init(frame);
+		}
+
+		for light in self.lights.iter_mut().filter_map(|l| l.as_mut()) {
+			light.value.init(frame);
+		}
+
+		self.frame = Some(frame);
+		self.update_area_lights();
+	}
+
+	// Visible objects which are used for rendering
+	pub fn get_objects<'a>(&'a self) -> impl Iterator<Item = &'a Box<RayTraceObject>> + 'a {
+		self.objects.iter()
+			.filter_map(|o| o.as_ref())
+			.filter(|o| o.visible)
+			.map(|o| &o.value)
+	}
+
+	// Area lights of emissive objects with the objects, objects changed through `get_object_mut` are picked up by
+	// the next initialization
+	pub fn get_area_lights<'a>(&'a self)
+			-> impl Iterator<Item = (&'a RayTraceAreaLight, &'a Box<RayTraceObject>)> + 'a {
+		self.area_lights.iter()
+			.filter_map(move |l| match self.objects.get(l.get_object()) {
+				Some(&Some(ref o)) if o.visible => Some((l, &o.value)),
+				_ => None
+			})
+	}
+
+	pub fn add_object<T: RayTraceObject + 'static>(&mut self, object: Box<T>) -> RayTraceObjectHandle<T> {
+		self.add_object_entry(object, None)
+	}
+
+	pub fn add_named_object<T: RayTraceObject + 'static>(&mut self, name: &str, object: Box<T>)
+			-> RayTraceObjectHandle<T> {
+		self.add_object_entry(object, Some(name.to_string()))
+	}
+
+	pub fn get_object<T: RayTraceObject + 'static>(&self, handle: &RayTraceObjectHandle<T>) -> &T {
+		let entry = get_entry(&self.objects, self.id, handle.scene, handle.index);
+		downcast_ref(&*entry.value).expect("Object has a different type!")
+	}
+
+	pub fn get_object_mut<T: RayTraceObject + 'static>(&mut self, handle: &RayTraceObjectHandle<T>) -> &mut T {
+		let entry = get_entry_mut(&mut self.objects, self.id, handle.scene, handle.index);
+		downcast_mut(&mut *entry.value).expect("Object has a different type!")
+	}
+
+	pub fn contains_object<T>(&self, handle: &RayTraceObjectHandle<T>) -> bool {
+		handle.scene == self.id && self.objects.get(handle.index).map_or(false, |o| o.is_some())
+	}
+
+	pub fn remove_object<T: RayTraceObject + 'static>(&mut self, handle: RayTraceObjectHandle<T>) -> Box<T> {
+		get_entry(&self.objects, self.id, handle.scene, handle.index);
+
+		let entry = self.objects[handle.index].take().unwrap();
+		self.update_area_lights();
+		match RayTraceAny::into_any(entry.value).downcast::<T>() {
+			Ok(object) => object,
+			Err(_) => { panic!("Object has a different type!"); }
+		}
+	}
+
+	// Swaps the object while keeping its name, tags and visibility
+	pub fn replace_object<T: RayTraceObject + 'static, U: RayTraceObject + 'static>(&mut self,
+			handle: RayTraceObjectHandle<T>, mut object: Box<U>) -> (RayTraceObjectHandle<U>, Box<T>) {
+		if let Some(frame) = self.frame {
+			object.init(frame);
+		}
+
+		let entry = get_entry_mut(&mut self.objects, self.id, handle.scene, handle.index);
+		let old: Box<RayTraceObject> = ::std::mem::replace(&mut entry.value, object);
+		let old = match RayTraceAny::into_any(old).downcast::<T>() {
+			Ok(old) => old,
+			Err(_) => { panic!("Object has a different type!"); }
+		};
+		self.update_area_lights();
+
+		(RayTraceObjectHandle::new(handle.scene, handle.index), old)
+	}
+
+	pub fn set_object_name<T>(&mut self, handle: &RayTraceObjectHandle<T>, name: Option<String>) {
+		get_entry_mut(&mut self.objects, self.id, handle.scene, handle.index).name = name;
+	}
+
+	pub fn get_object_name<T>(&self, handle: &RayTraceObjectHandle<T>) -> Option<&str> {
+		get_entry(&self.objects, self.id, handle.scene, handle.index).name.as_ref().map(|n| &n[..])
+	}
+
+	pub fn add_object_tag<T>(&mut self, handle: &RayTraceObjectHandle<T>, tag: &str) {
+		add_tag(get_entry_mut(&mut self.objects, self.id, handle.scene, handle.index), tag);
+	}
+
+	pub fn remove_object_tag<T>(&mut self, handle: &RayTraceObjectHandle<T>, tag: &str) {
+		get_entry_mut(&mut self.objects, self.id, handle.scene, handle.index).tags.retain(|t| t != tag);
+	}
+
+	pub fn get_object_tags<T>(&self, handle: &RayTraceObjectHandle<T>) -> &Vec<String> {
+		&get_entry(&self.objects, self.id, handle.scene, handle.index).tags
+	}
+
+	// Hidden objects are neither rendered nor do they cast shadows
+	pub fn set_object_visible<T>(&mut self, handle: &RayTraceObjectHandle<T>, visible: bool) {
+		get_entry_mut(&mut self.objects, self.id, handle.scene, handle.index).visible = visible;
+		self.update_area_lights();
+	}
+
+	pub fn is_object_visible<T>(&self, handle: &RayTraceObjectHandle<T>) -> bool {
+		get_entry(&self.objects, self.id, handle.scene, handle.index).visible
+	}
+
+	// Returns the handle of the object at the index if it has the requested type
+	pub fn get_object_handle<T: RayTraceObject + 'static>(&self, index: usize) -> Option<RayTraceObjectHandle<T>> {
+		match self.objects.get(index) {
+			Some(&Some(ref entry)) if downcast_ref::<T, _>(&*entry.value).is_some() => {
+				Some(RayTraceObjectHandle::new(self.id, index))
+			},
+			_ => None
 		}
+	}
+
+	pub fn find_object<T: RayTraceObject + 'static>(&self, name: &str) -> Option<RayTraceObjectHandle<T>> {
+		find_entries(&self.objects, |e| e.name.as_ref().map_or(false, |n| n == name)).iter()
+			.filter_map(|i| self.get_object_handle(*i))
+			.next()
+	}
+
+	pub fn find_objects_by_tag<T: RayTraceObject + 'static>(&self, tag: &str) -> Vec<RayTraceObjectHandle<T>> {
+		find_entries(&self.objects, |e| e.tags.iter().any(|t| t == tag)).iter()
+			.filter_map(|i| self.get_object_handle(*i))
+			.collect()
+	}
+
+	// Lists all objects with their bounding boxes which are known after the scene was initialized
+	pub fn query_objects(&self) -> Vec<RayTraceSceneEntryInfo> {
+		let initialized = self.frame.is_some();
 
-		for light in self.lights.iter_mut() {
+		self.objects.iter().enumerate()
+			.filter_map(|(i, o)| o.as_ref().map(|o| (i, o)))
+			.map(|(i, o)| {
+				let aabb = if initialized { o.value.get_aabb().cloned() } else { None };
+				RayTraceSceneEntryInfo::new(i, o, aabb)
+			})
+			.collect()
+	}
+
+	// Object at an index of the queries, hidden objects are returned as well
+	pub fn get_object_at(&self, index: usize) -> Option<&Box<RayTraceObject>> {
+		self.objects.get(index).and_then(|o| o.as_ref()).map(|o| &o.value)
+	}
+
+	// Enabled lights which are used for rendering
+	pub fn get_lights<'a>(&'a self) -> impl Iterator<Item = &'a Box<RayTraceLight>> + 'a {
+		self.lights.iter()
+			.filter_map(|l| l.as_ref())
+			.filter(|l| l.visible)
+			.map(|l| &l.value)
+	}
+
+	pub fn add_light<T: RayTraceLight + 'static>(&mut self, light: Box<T>) -> RayTraceLightHandle<T> {
+		self.add_light_entry(light, None)
+	}
+
+	pub fn add_named_light<T: RayTraceLight + 'static>(&mut self, name: &str, light: Box<T>)
+			-> RayTraceLightHandle<T> {
+		self.add_light_entry(light, Some(name.to_string()))
+	}
+
+	pub fn get_light<T: RayTraceLight + 'static>(&self, handle: &RayTraceLightHandle<T>) -> &T {
+		let entry = get_entry(&self.lights, self.id, handle.scene, handle.index);
+		downcast_ref(&*entry.value).expect("Light has a different type!")
+	}
+
+	pub fn get_light_mut<T: RayTraceLight + 'static>(&mut self, handle: &RayTraceLightHandle<T>) -> &mut T {
+		let entry = get_entry_mut(&mut self.lights, self.id, handle.scene, handle.index);
+		downcast_mut(&mut *entry.value).expect("Light has a different type!")
+	}
+
+	pub fn contains_light<T>(&self, handle: &RayTraceLightHandle<T>) -> bool {
+		handle.scene == self.id && self.lights.get(handle.index).map_or(false, |l| l.is_some())
+	}
+
+	pub fn remove_light<T: RayTraceLight + 'static>(&mut self, handle: RayTraceLightHandle<T>) -> Box<T> {
+		get_entry(&self.lights, self.id, handle.scene, handle.index);
+
+		let entry = self.lights[handle.index].take().unwrap();
+		match RayTraceAny::into_any(entry.value).downcast::<T>() {
+			Ok(light) => light,
+			Err(_) => { panic!("Light has a different type!"); }
+		}
+	}
+
+	// Swaps the light while keeping its name, tags and visibility
+	pub fn replace_light<T: RayTraceLight + 'static, U: RayTraceLight + 'static>(&mut self,
+			handle: RayTraceLightHandle<T>, mut light: Box<U>) -> (RayTraceLightHandle<U>, Box<T>) {
+		if let Some(frame) = self.frame {
 			light.init(frame);
 		}
+
+		let entry = get_entry_mut(&mut self.lights, self.id, handle.scene, handle.index);
+		let old: Box<RayTraceLight> = ::std::mem::replace(&mut entry.value, light);
+		let old = match RayTraceAny::into_any(old).downcast::<T>() {
+			Ok(old) => old,
+			Err(_) => { panic!("Light has a different type!"); }
+		};
+
+		(RayTraceLightHandle::new(handle.scene, handle.index), old)
 	}
 
-	pub fn get_objects(&self) -> &Vec<Unsafe<Box<RayTraceObject>>> {
-		&self.objects
+	pub fn set_light_name<T>(&mut self, handle: &RayTraceLightHandle<T>, name: Option<String>) {
+		get_entry_mut(&mut self.lights, self.id, handle.scene, handle.index).name = name;
 	}
 
-	pub fn add_object<T: RayTraceObject + 'static>(&mut self, object: Box<T>) -> UnsafeRef<Box<T>> {
-		// Totally safe from here ...
-		let cell = Unsafe::<Box<RayTraceObject>>::new(object);
-		let cell_ref = cell.get_ref();
+	pub fn get_light_name<T>(&self, handle: &RayTraceLightHandle<T>) -> Option<&str> {
+		get_entry(&self.lights, self.id, handle.scene, handle.index).name.as_ref().map(|n| &n[..])
+	}
+
+	pub fn add_light_tag<T>(&mut self, handle: &RayTraceLightHandle<T>, tag: &str) {
+		add_tag(get_entry_mut(&mut self.lights, self.id, handle.scene, handle.index), tag);
+	}
+
+	pub fn remove_light_tag<T>(&mut self, handle: &RayTraceLightHandle<T>, tag: &str) {
+		get_entry_mut(&mut self.lights, self.id, handle.scene, handle.index).tags.retain(|t| t != tag);
+	}
+
+	pub fn get_light_tags<T>(&self, handle: &RayTraceLightHandle<T>) -> &Vec<String> {
+		&get_entry(&self.lights, self.id, handle.scene, handle.index).tags
+	}
+
+	// Hidden lights are switched off
+	pub fn set_light_visible<T>(&mut self, handle: &RayTraceLightHandle<T>, visible: bool) {
+		get_entry_mut(&mut self.lights, self.id, handle.scene, handle.index).visible = visible;
+	}
 
-		self.objects.push(cell);
+	pub fn is_light_visible<T>(&self, handle: &RayTraceLightHandle<T>) -> bool {
+		get_entry(&self.lights, self.id, handle.scene, handle.index).visible
+	}
 
-		unsafe {
-			mem::transmute(cell_ref)
+	pub fn get_light_handle<T: RayTraceLight + 'static>(&self, index: usize) -> Option<RayTraceLightHandle<T>> {
+		match self.lights.get(index) {
+			Some(&Some(ref entry)) if downcast_ref::<T, _>(&*entry.value).is_some() => {
+				Some(RayTraceLightHandle::new(self.id, index))
+			},
+			_ => None
 		}
 	}
 
-	pub fn get_lights(&self) -> &Vec<Unsafe<Box<RayTraceLight>>> {
-		&self.lights
+	pub fn find_light<T: RayTraceLight + 'static>(&self, name: &str) -> Option<RayTraceLightHandle<T>> {
+		find_entries(&self.lights, |e| e.name.as_ref().map_or(false, |n| n == name)).iter()
+			.filter_map(|i| self.get_light_handle(*i))
+			.next()
 	}
 
-	pub fn add_light<T: RayTraceLight + 'static>(&mut self, light: Box<RayTraceLight>) -> UnsafeRef<Box<T>> {
-				// Totally safe from here ...
-		let cell = Unsafe::<Box<RayTraceLight>>::new(light);
-		let cell_ref = cell.get_ref();
+	pub fn find_lights_by_tag<T: RayTraceLight + 'static>(&self, tag: &str) -> Vec<RayTraceLightHandle<T>> {
+		find_entries(&self.lights, |e| e.tags.iter().any(|t| t == tag)).iter()
+			.filter_map(|i| self.get_light_handle(*i))
+			.collect()
+	}
 
-		self.lights.push(cell);
+	// Lists all lights, they have no bounding box
+	pub fn query_lights(&self) -> Vec<RayTraceSceneEntryInfo> {
+		self.lights.iter().enumerate()
+			.filter_map(|(i, l)| l.as_ref().map(|l| RayTraceSceneEntryInfo::new(i, l, None)))
+			.collect()
+	}
 
-		unsafe {
-			mem::transmute(cell_ref)
+	pub fn get_light_at(&self, index: usize) -> Option<&Box<RayTraceLight>> {
+		self.lights.get(index).and_then(|l| l.as_ref()).map(|l| &l.value)
+	}
+
+	fn add_object_entry<T: RayTraceObject + 'static>(&mut self, mut object: Box<T>, name: Option<String>)
+			-> RayTraceObjectHandle<T> {
+		// Objects added after the initialization are brought to the current frame
+		if let Some(frame) = self.frame {
+			object.init(frame);
 		}
+
+		self.objects.push(Some(RayTraceSceneEntry::new(object, name)));
+		self.update_area_lights();
+		RayTraceObjectHandle::new(self.id, self.objects.len() - 1)
 	}
-}
\ No newline at end of file
+
+	fn add_light_entry<T: RayTraceLight + 'static>(&mut self, mut light: Box<T>, name: Option<String>)
+			-> RayTraceLightHandle<T> {
+		if let Some(frame) = self.frame {
+			light.init(frame);
+		}
+
+		self.lights.push(Some(RayTraceSceneEntry::new(light, name)));
+		RayTraceLightHandle::new(self.id, self.lights.len() - 1)
+	}
+
+	// The shapes of the lights are only known for initialized objects
+	fn update_area_lights(&mut self) {
+		if self.frame.is_none() {
+			return;
+		}
+
+		self.area_lights = self.objects.iter().enumerate()
+			.filter_map(|(i, o)| o.as_ref().map(|o| (i, o)))
+			.filter(|&(_, o)| o.visible)
+			.filter_map(|(i, o)| o.value.get_area_light().map(|shape| RayTraceAreaLight::new(i, shape)))
+			.collect();
+	}
+}
+
+impl<B: ?Sized> RayTraceSceneEntry<B> {
+	fn new(value: Box<B>, name: Option<String>) -> Self {
+		Self {
+			value: value,
+			name: name,
+			tags: Vec::new(),
+			visible: true
+		}
+	}
+}
+
+fn get_entry<B: ?Sized>(entries: &Vec<Option<RayTraceSceneEntry<B>>>, scene: usize, handle_scene: usize,
+		index: usize) -> &RayTraceSceneEntry<B> {
+	if scene != handle_scene {
+		panic!("Handle belongs to another scene!");
+	}
+
+	match entries[index] {
+		Some(ref entry) => entry,
+		None => { panic!("Entry was removed from the scene!"); }
+	}
+}
+
+fn get_entry_mut<B: ?Sized>(entries: &mut Vec<Option<RayTraceSceneEntry<B>>>, scene: usize, handle_scene: usize,
+		index: usize) -> &mut RayTraceSceneEntry<B> {
+	if scene != handle_scene {
+		panic!("Handle belongs to another scene!");
+	}
+
+	match entries[index] {
+		Some(ref mut entry) => entry,
+		None => { panic!("Entry was removed from the scene!"); }
+	}
+}
+
+fn find_entries<B: ?Sized, F>(entries: &Vec<Option<RayTraceSceneEntry<B>>>, filter: F) -> Vec<usize>
+		where F: Fn(&RayTraceSceneEntry<B>) -> bool {
+	entries.iter().enumerate()
+		.filter(|&(_, e)| e.as_ref().map_or(false, |e| filter(e)))
+		.map(|(i, _)| i)
+		.collect()
+}
+
+fn add_tag<B: ?Sized>(entry: &mut RayTraceSceneEntry<B>, tag: &str) {
+	if !entry.tags.iter().any(|t| t == tag) {
+		entry.tags.push(tag.to_string());
+	}
+}
+
+// The call goes through the trait object since boxes implement RayTraceAny too
+fn downcast_ref<T: Any, B: ?Sized + RayTraceAny>(value: &B) -> Option<&T> {
+	value.as_any().downcast_ref::<T>()
+}
+
+fn downcast_mut<T: Any, B: ?Sized + RayTraceAny>(value: &mut B) -> Option<&mut T> {
+	value.as_any_mut().downcast_mut::<T>()
+}
+
+#[allow(dead_code)]
+impl RayTraceSceneEntryInfo {
+	fn new<B: ?Sized>(index: usize, entry: &RayTraceSceneEntry<B>, aabb: Option<AABB>) -> Self {
+		Self {
+			index: index,
+			name: entry.name.clone(),
+			tags: entry.tags.clone(),
+			visible: entry.visible,
+			aabb: aabb
+		}
+	}
+
+	// Index to get a typed handle from the scene
+	pub fn get_index(&self) -> usize {
+		self.index
+	}
+
+	pub fn get_name(&self) -> Option<&str> {
+		self.name.as_ref().map(|n| &n[..])
+	}
+
+	pub fn get_tags(&self) -> &Vec<String> {
+		&self.tags
+	}
+
+	pub fn is_visible(&self) -> bool {
+		self.visible
+	}
+
+	pub fn get_aabb(&self) -> Option<&AABB> {
+		self.aabb.as_ref()
+	}
+}
+
+#[allow(dead_code)]
+impl<T> RayTraceObjectHandle<T> {
+	fn new(scene: usize, index: usize) -> Self {
+		Self {
+			scene: scene,
+			index: index,
+			marker: PhantomData
+		}
+	}
+
+	pub fn get_index(&self) -> usize {
+		self.index
+	}
+}
+
+impl<T> Clone for RayTraceObjectHandle<T> {
+	fn clone(&self) -> Self {
+		Self::new(self.scene, self.index)
+	}
+}
+
+impl<T> Copy for RayTraceObjectHandle<T> { }
+
+#[allow(dead_code)]
+impl<T> RayTraceLightHandle<T> {
+	fn new(scene: usize, index: usize) -> Self {
+		Self {
+			scene: scene,
+			index: index,
+			marker: PhantomData
+		}
+	}
+
+	pub fn get_index(&self) -> usize {
+		self.index
+	}
+}
+
+impl<T> Clone for RayTraceLightHandle<T> {
+	fn clone(&self) -> Self {
+		Self::new(self.scene, self.index)
+	}
+}
+
+impl<T> Copy for RayTraceLightHandle<T> { }
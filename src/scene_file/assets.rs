@@ -0,0 +1,200 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::Error as IOError;
+use std::io::ErrorKind;
+use std::time::SystemTime;
+
+use material::RayTraceMaterial;
+use material::RayTraceMtlMaterial;
+use object::RayTraceObjectModel;
+use object::model::{cache_build, cache_decode, cache_encode, mtl_load_with};
+
+use sink::image;
+use sink::image::RgbaImage;
+
+// Models and textures of scene files which are kept between loads as long as their files do not change
+pub struct RayTraceSceneAssets {
+	models: HashMap<String, CachedAsset<CachedModel>>,
+	images: HashMap<String, CachedAsset<RgbaImage>>,
+	// Scene file and assets of the last load with their modification times
+	files: Vec<(String, Option<SystemTime>)>,
+	// Material libraries which were read for the model which is loaded
+	libraries: Vec<String>
+}
+
+struct CachedAsset<T> {
+	modified: SystemTime,
+	value: T
+}
+
+struct CachedModel {
+	// Geometry and octree of the model without its materials
+	data: Vec<u8>,
+	// Material libraries of the model in the order they were read with their modification times
+	libraries: Vec<(String, Option<SystemTime>)>
+}
+
+#[allow(dead_code)]
+impl RayTraceSceneAssets {
+	pub fn new() -> Self {
+		Self {
+			models: HashMap::new(),
+			images: HashMap::new(),
+			files: Vec::new(),
+			libraries: Vec::new()
+		}
+	}
+
+	// Files which were read by the last load
+	pub fn get_files(&self) -> Vec<&str> {
+		self.files.iter().map(|f| &f.0[..]).collect()
+	}
+
+	// Files which were changed, created or deleted since the last load
+	pub fn get_changed_files(&self) -> Vec<&str> {
+		self.files.iter()
+			.filter(|f| modified_time(&f.0) != f.1)
+			.map(|f| &f.0[..])
+			.collect()
+	}
+
+	pub fn has_changes(&self) -> bool {
+		self.files.iter().any(|f| modified_time(&f.0) != f.1)
+	}
+
+	// Called before a scene file is read
+	pub fn begin(&mut self, file_name: &str) {
+		self.files.clear();
+		self.watch(file_name);
+	}
+
+	// Drops the assets which are not used by the scene anymore
+	pub fn finish(&mut self) {
+		let files: Vec<String> = self.files.iter().map(|f| f.0.clone()).collect();
+		self.models.retain(|name, _| files.contains(name));
+		self.images.retain(|name, _| files.contains(name));
+	}
+
+	// Adds a file to the watched files and returns its modification time
+	pub fn watch(&mut self, file_name: &str) -> Option<SystemTime> {
+		let modified = modified_time(file_name);
+		if !self.files.iter().any(|f| f.0 == file_name) {
+			self.files.push((file_name.to_string(), modified));
+		}
+
+		modified
+	}
+
+	// Models are kept with their octree while their materials are created again from the material libraries, which
+	// the loader has to read with load_library, so the textures of the libraries are reused as well
+	pub fn load_model<F>(&mut self, file_name: &str, material: Box<RayTraceMaterial>, loader: F)
+			-> Result<RayTraceObjectModel, IOError>
+			where F: FnOnce(&str, Box<RayTraceMaterial>, &mut RayTraceSceneAssets)
+				-> Result<RayTraceObjectModel, IOError> {
+		let modified = self.watch(file_name);
+
+		// Changed libraries may define other materials, so the faces have to be assigned again
+		let cached = match (self.models.remove(file_name), modified) {
+			(Some(cached), Some(modified)) if cached.modified == modified
+					&& cached.value.libraries.iter().all(|l| modified_time(&l.0) == l.1) => Some(cached),
+			_ => None
+		};
+
+		let material = match cached {
+			Some(cached) => {
+				match cache_decode(&cached.value.data, file_name, material, 0) {
+					Ok(mut model) => {
+						let face_materials = model.get_face_materials().clone();
+						let materials = try!(self.load_materials(&cached.value.libraries));
+						model.set_materials(materials, face_materials);
+						self.models.insert(file_name.to_string(), cached);
+
+						info!("Reused model {}", file_name);
+						return Ok(model);
+					},
+					Err(material) => material
+				}
+			},
+			None => material
+		};
+
+		self.libraries.clear();
+		let mut model = try!(loader(file_name, material, self));
+		let libraries = self.libraries.drain(..).map(|l| {
+			let modified = modified_time(&l);
+			(l, modified)
+		}).collect();
+
+		if let Some(modified) = modified {
+			cache_build(&mut model);
+			match cache_encode(&model, 0, true) {
+				Ok(data) => {
+					self.models.insert(file_name.to_string(), CachedAsset::new(modified, CachedModel {
+						data: data,
+						libraries: libraries
+					}));
+				},
+				Err(err) => { warn!("Could not keep model {}: {}", file_name, err); }
+			}
+		}
+
+		Ok(model)
+	}
+
+	// Reads the materials of a .mtl file whose textures are kept like other images
+	pub fn load_library(&mut self, file_name: &str) -> Result<Vec<(String, RayTraceMtlMaterial)>, IOError> {
+		self.watch(file_name);
+		self.libraries.push(file_name.to_string());
+		mtl_load_with(file_name, |texture_file| self.load_image(texture_file))
+	}
+
+	pub fn load_image(&mut self, file_name: &str) -> Result<RgbaImage, image::ImageError> {
+		let modified = self.watch(file_name);
+
+		if let (Some(cached), Some(modified)) = (self.images.get(file_name), modified) {
+			if cached.modified == modified {
+				return Ok(cached.value.clone());
+			}
+		}
+
+		let texture = try!(image::open(file_name)).to_rgba();
+		if let Some(modified) = modified {
+			self.images.insert(file_name.to_string(), CachedAsset::new(modified, texture.clone()));
+		}
+
+		Ok(texture)
+	}
+
+	// Creates the materials of all libraries in the order the model read them, missing libraries are skipped like
+	// the model loaders do
+	fn load_materials(&mut self, libraries: &Vec<(String, Option<SystemTime>)>)
+			-> Result<Vec<Box<RayTraceMaterial>>, IOError> {
+		let mut materials = Vec::<Box<RayTraceMaterial>>::new();
+		for &(ref library, _) in libraries.iter() {
+			match self.load_library(library) {
+				Ok(loaded) => {
+					for (_, material) in loaded {
+						materials.push(Box::new(material));
+					}
+				},
+				Err(ref err) if err.kind() == ErrorKind::NotFound => { },
+				Err(err) => { return Err(err); }
+			}
+		}
+
+		Ok(materials)
+	}
+}
+
+impl<T> CachedAsset<T> {
+	fn new(modified: SystemTime, value: T) -> Self {
+		Self {
+			modified: modified,
+			value: value
+		}
+	}
+}
+
+fn modified_time(file_name: &str) -> Option<SystemTime> {
+	fs::metadata(file_name).and_then(|m| m.modified()).ok()
+}
@@ -0,0 +1,590 @@
+// Scene description files are JSON documents of the form
+//
+// {
+//   "output": { "width": 640, "height": 480, "frames": 1, "fps": 25 },
+//   "params": { "max_depth": 4, "background_color": [0, 0, 0], "sampling": { "type": "random", "rays": 4 },
+//     "filter": { "type": "gauss", "size": 1.5 }, "shading": { "type": "phong" } },
+//   "camera": { "type": "perspective", "transform": { "translation": [0, 1, 5] } },
+//   "materials": { "red": { "type": "simple", "color": [1, 0, 0] } },
+//   "objects": [ { "type": "sphere", "name": "ball", "material": "red", "size": 1 } ],
+//   "lights": [ { "type": "spot", "position": [0, 5, 0] } ],
+//   "sink": { "type": "png", "file": "out/frame" }
+// }
+//
+// Paths are relative to the scene file and rotations are given in degrees. Sources which are written back
+// have their models inline and all animations sampled for every frame.
+
+mod assets;
+mod objects;
+mod reader;
+mod writer;
+
+pub use self::assets::RayTraceSceneAssets;
+pub use self::writer::RayTraceSceneWriteError;
+pub use self::writer::RayTraceSceneWriter;
+
+use self::objects::SceneFileMaterials;
+use self::objects::read_lights;
+use self::objects::read_objects;
+use self::objects::read_transform;
+use self::reader::SceneFileReader;
+use self::writer::SceneWriterContext;
+
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+use std::io::Write;
+use std::mem;
+use std::path::Path;
+use std::path::PathBuf;
+
+use camera::RayTraceCamera;
+use camera::RayTracerCameraOrthographic;
+use camera::RayTracerCameraPerspective;
+use json::JsonValue;
+use json::json_parse;
+use json::json_write;
+use light::RayTraceDebugAxisShading;
+use light::RayTraceDebugNormalShading;
+use light::RayTraceDebugNormalType;
+use light::RayTracePhongShading;
+use light::RayTraceShading;
+use params::RayTraceOutputParams;
+use params::RayTraceParams;
+use params::RayTraceRandomSampling;
+use params::RayTraceSampling;
+use sample::RayTraceAverageFilter;
+use sample::RayTraceBoxFilter;
+use sample::RayTraceGaussFilter;
+use sample::RayTraceSampleFilter;
+use scene::RayTraceScene;
+use sink::JpegSink;
+use sink::PngSink;
+use sink::RayTraceSink;
+use sink::Y4mSink;
+use sink::y4m::Ratio;
+use source::RayTraceSource;
+
+use math_util::RayTraceTransformable;
+
+const CAMERA_TYPES: [&'static str; 2] = ["perspective", "orthographic"];
+const SAMPLING_TYPES: [&'static str; 1] = ["random"];
+const FILTER_TYPES: [&'static str; 3] = ["average", "box", "gauss"];
+const SHADING_TYPES: [&'static str; 3] = ["phong", "debug_axis", "debug_normal"];
+const SINK_TYPES: [&'static str; 3] = ["png", "jpeg", "y4m"];
+
+const DEFAULT_FPS: f64 = 25.0;
+
+// Everything needed to render a scene file
+pub struct RayTraceSceneFile {
+	source: RayTraceSource,
+	sink: Option<Box<RayTraceSink>>,
+	frame_rate: f64
+}
+
+// Values which replace the output settings of the scene file
+#[derive(Clone)]
+pub struct RayTraceSceneFileOptions {
+	size: Option<(usize, usize)>,
+	frames: Option<usize>
+}
+
+// Position 0 is used for errors which do not belong to a value of the document
+#[derive(Debug)]
+pub struct RayTraceSceneFileError {
+	kind: RayTraceSceneFileErrorKind,
+	file_name: String,
+	line: usize,
+	column: usize,
+	message: String
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RayTraceSceneFileErrorKind {
+	// The scene file or a model, texture or output file it refers to could not be read or created
+	Io,
+	// The scene file is no valid JSON document
+	Syntax,
+	// The document does not describe a valid scene
+	Semantic
+}
+
+#[allow(dead_code)]
+impl RayTraceSceneFile {
+	pub fn get_source_mut(&mut self) -> &mut RayTraceSource {
+		&mut self.source
+	}
+
+	pub fn get_frame_rate(&self) -> f64 {
+		self.frame_rate
+	}
+
+	pub fn has_sink(&self) -> bool {
+		self.sink.is_some()
+	}
+
+	pub fn into_parts(self) -> (RayTraceSource, Option<Box<RayTraceSink>>) {
+		(self.source, self.sink)
+	}
+}
+
+#[allow(dead_code)]
+impl RayTraceSceneFileOptions {
+	pub fn new() -> Self {
+		Self {
+			size: None,
+			frames: None
+		}
+	}
+
+	pub fn set_size(&mut self, width: usize, height: usize) {
+		self.size = Some((width, height));
+	}
+
+	pub fn set_frames(&mut self, frames: usize) {
+		self.frames = Some(frames);
+	}
+}
+
+#[allow(dead_code)]
+impl RayTraceSceneFileError {
+	pub fn new(file_name: &str, line: usize, column: usize, message: &str) -> Self {
+		Self::new_with(RayTraceSceneFileErrorKind::Semantic, file_name, line, column, message)
+	}
+
+	pub fn new_with(kind: RayTraceSceneFileErrorKind, file_name: &str, line: usize, column: usize, message: &str)
+			-> Self {
+		Self {
+			kind: kind,
+			file_name: file_name.to_string(),
+			line: line,
+			column: column,
+			message: message.to_string()
+		}
+	}
+
+	pub fn get_kind(&self) -> RayTraceSceneFileErrorKind {
+		self.kind
+	}
+
+	pub fn get_file_name(&self) -> &str {
+		&self.file_name
+	}
+
+	pub fn get_line(&self) -> usize {
+		self.line
+	}
+
+	pub fn get_column(&self) -> usize {
+		self.column
+	}
+
+	pub fn get_message(&self) -> &str {
+		&self.message
+	}
+}
+
+impl fmt::Display for RayTraceSceneFileError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		if self.line == 0 {
+			write!(f, "{}: {}", self.file_name, self.message)
+		} else {
+			write!(f, "{}:{}:{}: {}", self.file_name, self.line, self.column, self.message)
+		}
+	}
+}
+
+impl Error for RayTraceSceneFileError {
+	fn description(&self) -> &str {
+		&self.message
+	}
+}
+
+pub fn scene_file_load(file_name: &str) -> Result<RayTraceSceneFile, RayTraceSceneFileError> {
+	scene_file_load_with(file_name, &RayTraceSceneFileOptions::new())
+}
+
+pub fn scene_file_load_with(file_name: &str, options: &RayTraceSceneFileOptions)
+		-> Result<RayTraceSceneFile, RayTraceSceneFileError> {
+	scene_file_load_cached(file_name, options, &mut RayTraceSceneAssets::new())
+}
+
+// Reuses the models and textures of previous loads whose files did not change, the assets know the files
+// which were read to detect changes of the scene
+pub fn scene_file_load_cached(file_name: &str, options: &RayTraceSceneFileOptions, assets: &mut RayTraceSceneAssets)
+		-> Result<RayTraceSceneFile, RayTraceSceneFileError> {
+	assets.begin(file_name);
+	let reader = SceneFileReader::new(file_name, mem::replace(assets, RayTraceSceneAssets::new()));
+	let result = read_scene_file(&reader, options);
+
+	*assets = reader.into_assets();
+	if result.is_ok() {
+		assets.finish();
+	}
+
+	result
+}
+
+fn read_scene_file(reader: &SceneFileReader, options: &RayTraceSceneFileOptions)
+		-> Result<RayTraceSceneFile, RayTraceSceneFileError> {
+	let file_name = reader.get_file_name();
+	let mut text = String::new();
+	if let Err(err) = File::open(file_name).and_then(|mut file| file.read_to_string(&mut text)) {
+		return Err(RayTraceSceneFileError::new_with(RayTraceSceneFileErrorKind::Io, file_name, 0, 0,
+			&format!("Could not read file: {}", err)));
+	}
+
+	let document = match json_parse(&text) {
+		Ok(document) => document,
+		Err(err) => {
+			return Err(RayTraceSceneFileError::new_with(RayTraceSceneFileErrorKind::Syntax, file_name,
+				err.get_line(), err.get_column(), err.get_message()));
+		}
+	};
+
+	try!(reader.read_members(&document, &["output", "params", "camera", "materials", "objects", "lights", "sink"]));
+
+	let output = try!(reader.require(&document, "output"));
+	let (out_params, frame_rate) = try!(read_output(reader, output, options));
+	let params = match document.get("params") {
+		Some(params) => try!(read_params(reader, params)),
+		None => RayTraceParams::new()
+	};
+	let camera = try!(read_camera(reader, try!(reader.require(&document, "camera")), &out_params));
+
+	let materials = try!(SceneFileMaterials::read(reader, document.get("materials")));
+	let mut scene = RayTraceScene::new();
+	if let Some(objects) = document.get("objects") {
+		try!(read_objects(reader, objects, &materials, &mut scene, &out_params, frame_rate));
+	}
+	if let Some(lights) = document.get("lights") {
+		try!(read_lights(reader, lights, &mut scene));
+	}
+
+	let sink = match document.get("sink") {
+		Some(sink) => Some(try!(read_sink(reader, sink, frame_rate))),
+		None => None
+	};
+
+	Ok(RayTraceSceneFile {
+		source: RayTraceSource::new(scene, camera, out_params, params),
+		sink: sink,
+		frame_rate: frame_rate
+	})
+}
+
+// Textures of materials are stored as .png files in a directory named after the scene file
+pub fn scene_file_save(file_name: &str, source: &mut RayTraceSource) -> Result<(), RayTraceSceneWriteError> {
+	let path = Path::new(file_name);
+	let directory = path.parent().map_or(PathBuf::new(), |p| p.to_path_buf());
+	let stem = path.file_stem().map_or("scene".to_string(), |s| s.to_string_lossy().into_owned());
+
+	let source = source.get();
+	let frames = source.out_params.get_frames();
+	let mut context = SceneWriterContext::new(frames.max(1), directory, format!("{}_textures/", stem));
+
+	let document = {
+		let mut writer = RayTraceSceneWriter::new(&mut context, String::new());
+		try!(writer.set_child("output", |output| {
+			output.set_usize("width", source.out_params.get_width());
+			output.set_usize("height", source.out_params.get_height());
+			output.set_usize("frames", frames);
+			Ok(())
+		}));
+		try!(writer.set_child("params", |params| write_params(params, &source.params)));
+		try!(writer.set_child("camera", |camera| source.camera.serialize(camera)));
+
+		let scene = &source.scene;
+		let objects = scene.query_objects();
+		try!(writer.set_child_list("objects", objects.len(), |i, object| {
+			let info = &objects[i];
+			try!(scene.get_object_at(info.get_index()).unwrap().serialize(object));
+			write_entry_info(object, info.get_name(), info.get_tags(), info.is_visible());
+			Ok(())
+		}));
+
+		let lights = scene.query_lights();
+		try!(writer.set_child_list("lights", lights.len(), |i, light| {
+			let info = &lights[i];
+			try!(scene.get_light_at(info.get_index()).unwrap().serialize(light));
+			write_entry_info(light, info.get_name(), info.get_tags(), info.is_visible());
+			Ok(())
+		}));
+
+		writer.into_value()
+	};
+
+	if let Err(err) = File::create(file_name).and_then(|mut file| file.write_all(json_write(&document).as_bytes())) {
+		return Err(RayTraceSceneWriteError::new("", &format!("Could not write {}: {}", file_name, err)));
+	}
+
+	Ok(())
+}
+
+fn write_params(writer: &mut RayTraceSceneWriter, params: &RayTraceParams) -> Result<(), RayTraceSceneWriteError> {
+	writer.set_usize("max_depth", params.get_max_depth());
+	writer.set_usize("light_samples", params.get_light_samples());
+	writer.set_usize("reflection_samples", params.get_reflection_samples());
+	try!(writer.set_color("background_color", params.get_background_color()));
+	try!(writer.set_color("indirect_color", params.get_indirect_color()));
+	try!(writer.set_color("ambient_light", params.get_ambient_light()));
+
+	if let Some(ref sampling) = *params.get_sampling() {
+		try!(writer.set_child("sampling", |w| sampling.serialize(w)));
+	}
+	if let Some(ref filter) = *params.get_filter() {
+		try!(writer.set_child("filter", |w| filter.serialize(w)));
+	}
+	if let Some(ref shading) = *params.get_shading() {
+		try!(writer.set_child("shading", |w| shading.serialize(w)));
+	}
+
+	Ok(())
+}
+
+fn write_entry_info(writer: &mut RayTraceSceneWriter, name: Option<&str>, tags: &Vec<String>, visible: bool) {
+	if let Some(name) = name {
+		writer.set_str("name", name);
+	}
+	if !tags.is_empty() {
+		writer.set_str_list("tags", tags);
+	}
+	if !visible {
+		writer.set_bool("visible", false);
+	}
+}
+
+fn read_output(reader: &SceneFileReader, value: &JsonValue, options: &RayTraceSceneFileOptions)
+		-> Result<(RayTraceOutputParams, f64), RayTraceSceneFileError> {
+	try!(reader.read_members(value, &["width", "height", "frames", "fps"]));
+	let (width, height) = match options.size {
+		Some(size) => size,
+		None => (try!(reader.read_usize(try!(reader.require(value, "width")))),
+			try!(reader.read_usize(try!(reader.require(value, "height")))))
+	};
+	if width == 0 || height == 0 {
+		return reader.error(value, "The output size must not be zero");
+	}
+
+	let frames = match (options.frames, value.get("frames")) {
+		(Some(frames), _) => frames,
+		(None, Some(frames)) => try!(reader.read_usize(frames)),
+		(None, None) => 1
+	};
+	let fps = match value.get("fps") {
+		Some(fps) => try!(reader.read_positive(fps)),
+		None => DEFAULT_FPS
+	};
+
+	Ok((RayTraceOutputParams::new(width, height, frames), fps))
+}
+
+fn read_params(reader: &SceneFileReader, value: &JsonValue) -> Result<RayTraceParams, RayTraceSceneFileError> {
+	try!(reader.read_members(value, &["max_depth", "light_samples", "reflection_samples", "background_color",
+		"indirect_color", "ambient_light", "sampling", "filter", "shading"]));
+	let mut params = RayTraceParams::new();
+
+	if let Some(depth) = value.get("max_depth") {
+		params.set_max_depth(try!(reader.read_usize(depth)));
+	}
+	if let Some(samples) = value.get("light_samples") {
+		params.set_light_samples(try!(reader.read_usize(samples)));
+	}
+	if let Some(samples) = value.get("reflection_samples") {
+		params.set_reflection_samples(try!(reader.read_usize(samples)));
+	}
+	if let Some(color) = value.get("background_color") {
+		params.set_background_color(try!(reader.read_color(color)));
+	}
+	if let Some(color) = value.get("indirect_color") {
+		params.set_indirect_color(try!(reader.read_color(color)));
+	}
+	if let Some(color) = value.get("ambient_light") {
+		params.set_ambient_light(try!(reader.read_color(color)));
+	}
+	if let Some(sampling) = value.get("sampling") {
+		params.set_sampling(Some(try!(read_sampling(reader, sampling))));
+	}
+	if let Some(filter) = value.get("filter") {
+		params.set_filter(Some(try!(read_filter(reader, filter))));
+	}
+	if let Some(shading) = value.get("shading") {
+		params.set_shading(Some(try!(read_shading(reader, shading))));
+	}
+
+	Ok(params)
+}
+
+fn read_sampling(reader: &SceneFileReader, value: &JsonValue)
+		-> Result<Box<RayTraceSampling + Sync>, RayTraceSceneFileError> {
+	let kind = try!(reader.read_type(value));
+	match kind {
+		"random" => {
+			try!(reader.read_members(value, &["type", "size", "rays"]));
+			let size = match value.get("size") {
+				Some(size) => try!(reader.read_positive(size)),
+				None => 1.0
+			};
+			let rays = match value.get("rays") {
+				Some(rays) => try!(reader.read_usize(rays)),
+				None => 1
+			};
+			if rays == 0 {
+				return reader.error(value.get("rays").unwrap(), "At least one ray is needed per sample");
+			}
+
+			Ok(box RayTraceRandomSampling::new_with(size, rays))
+		},
+		_ => reader.unknown_type(value, kind, &SAMPLING_TYPES)
+	}
+}
+
+fn read_filter(reader: &SceneFileReader, value: &JsonValue)
+		-> Result<Box<RayTraceSampleFilter + Sync>, RayTraceSceneFileError> {
+	let kind = try!(reader.read_type(value));
+	match kind {
+		"average" => {
+			try!(reader.read_members(value, &["type"]));
+			Ok(box RayTraceAverageFilter::new())
+		},
+		"box" | "gauss" => {
+			try!(reader.read_members(value, &["type", "size"]));
+			let size = try!(reader.read_positive(try!(reader.require(value, "size"))));
+			if kind == "box" {
+				Ok(box RayTraceBoxFilter::new(size))
+			} else {
+				Ok(box RayTraceGaussFilter::new(size))
+			}
+		},
+		_ => reader.unknown_type(value, kind, &FILTER_TYPES)
+	}
+}
+
+fn read_shading(reader: &SceneFileReader, value: &JsonValue)
+		-> Result<Box<RayTraceShading + Sync>, RayTraceSceneFileError> {
+	let kind = try!(reader.read_type(value));
+	match kind {
+		"phong" => {
+			try!(reader.read_members(value, &["type", "ambient", "diffuse", "specular"]));
+			let mut shading = RayTracePhongShading::new();
+			if let Some(ambient) = value.get("ambient") {
+				shading.set_ambient(try!(reader.read_f32(ambient)));
+			}
+			if let Some(diffuse) = value.get("diffuse") {
+				shading.set_diffuse(try!(reader.read_f32(diffuse)));
+			}
+			if let Some(specular) = value.get("specular") {
+				shading.set_specular(try!(reader.read_f32(specular)));
+			}
+
+			Ok(box shading)
+		},
+		"debug_axis" => {
+			try!(reader.read_members(value, &["type", "axis", "scale", "offset"]));
+			let axis = try!(reader.read_vec3(try!(reader.require(value, "axis"))));
+			let scale = match value.get("scale") {
+				Some(scale) => try!(reader.read_f64(scale)),
+				None => 1.0
+			};
+			let offset = match value.get("offset") {
+				Some(offset) => try!(reader.read_f64(offset)),
+				None => 0.0
+			};
+
+			Ok(box RayTraceDebugAxisShading::new_with(axis, scale, offset))
+		},
+		"debug_normal" => {
+			try!(reader.read_members(value, &["type", "normal"]));
+			let normal = match value.get("normal") {
+				Some(normal) => match try!(reader.read_str(normal)) {
+					"xz" => RayTraceDebugNormalType::XZ,
+					"y" => RayTraceDebugNormalType::Y,
+					"both" => RayTraceDebugNormalType::Both,
+					_ => { return reader.error(normal, "Expected one of: xz, y, both"); }
+				},
+				None => RayTraceDebugNormalType::Both
+			};
+
+			Ok(box RayTraceDebugNormalShading::new(normal))
+		},
+		_ => reader.unknown_type(value, kind, &SHADING_TYPES)
+	}
+}
+
+// The camera size is either given by "width" and "height" or by "scale" and the aspect ratio of the output
+fn read_camera(reader: &SceneFileReader, value: &JsonValue, screen: &RayTraceOutputParams)
+		-> Result<Box<RayTraceCamera>, RayTraceSceneFileError> {
+	let kind = try!(reader.read_type(value));
+	match kind {
+		"perspective" => {
+			try!(reader.read_members(value, &["type", "transform", "scale", "width", "height", "distance"]));
+			let (width, height) = try!(read_camera_size(reader, value, screen));
+			let distance = match value.get("distance") {
+				Some(distance) => try!(reader.read_positive(distance)),
+				None => 1.0
+			};
+
+			let camera = RayTracerCameraPerspective::new_with(screen, width, height, distance);
+			read_camera_transform(reader, value, camera)
+		},
+		"orthographic" => {
+			try!(reader.read_members(value, &["type", "transform", "scale", "width", "height"]));
+			let (width, height) = try!(read_camera_size(reader, value, screen));
+
+			let camera = RayTracerCameraOrthographic::new_with(screen, width, height);
+			read_camera_transform(reader, value, camera)
+		},
+		_ => reader.unknown_type(value, kind, &CAMERA_TYPES)
+	}
+}
+
+fn read_camera_size(reader: &SceneFileReader, value: &JsonValue, screen: &RayTraceOutputParams)
+		-> Result<(f64, f64), RayTraceSceneFileError> {
+	let scale = match value.get("scale") {
+		Some(scale) => try!(reader.read_positive(scale)),
+		None => 1.0
+	};
+	let width = match value.get("width") {
+		Some(width) => try!(reader.read_positive(width)),
+		None => (screen.get_width() as f64) / (screen.get_height() as f64) * scale
+	};
+	let height = match value.get("height") {
+		Some(height) => try!(reader.read_positive(height)),
+		None => scale
+	};
+
+	Ok((width, height))
+}
+
+fn read_camera_transform<T>(reader: &SceneFileReader, value: &JsonValue, mut camera: T)
+		-> Result<Box<RayTraceCamera>, RayTraceSceneFileError>
+		where T: RayTraceCamera + RayTraceTransformable + 'static {
+	if let Some(transform) = value.get("transform") {
+		try!(read_transform(reader, transform, camera.get_transform_mut()));
+	}
+
+	Ok(box camera)
+}
+
+fn read_sink(reader: &SceneFileReader, value: &JsonValue, fps: f64)
+		-> Result<Box<RayTraceSink>, RayTraceSceneFileError> {
+	let kind = try!(reader.read_type(value));
+	try!(reader.read_members(value, &["type", "file"]));
+	let file = try!(reader.require(value, "file"));
+	let file_name = try!(reader.read_path(file));
+
+	match kind {
+		"png" => Ok(box PngSink::new(file_name)),
+		"jpeg" => Ok(box JpegSink::new(file_name)),
+		"y4m" => {
+			// The frame rate is stored with three decimals
+			let frame_rate = Ratio::new((fps * 1000.0).round() as usize, 1000);
+			match Y4mSink::new(file_name.clone(), frame_rate) {
+				Ok(sink) => Ok(box sink),
+				Err(err) => reader.io_error(file, &format!("Could not create {}: {}", file_name, err))
+			}
+		},
+		_ => reader.unknown_type(value, kind, &SINK_TYPES)
+	}
+}
@@ -0,0 +1,925 @@
+use std::collections::HashMap;
+use std::u32;
+
+use vecmath::Matrix4;
+use vecmath::Vector3;
+
+use anim::RayTraceAnimation;
+use anim::RayTraceAnimF64Linear;
+use anim::RayTraceAnimFrames;
+use anim::RayTraceAnimSequence;
+use anim::RayTraceAnimVec3Linear;
+use color::RayTraceColor;
+use gltf::gltf_load;
+use json::JsonValue;
+use light::RayTraceLight;
+use light::lights::RayTraceDirectedSpotLight;
+use light::lights::RayTraceSpotLight;
+use material::RayTraceCheckerboardMaterial;
+use material::RayTraceColorRamp;
+use material::RayTraceMaterial;
+use material::RayTraceMtlMaterial;
+use material::RayTraceNoiseBasis;
+use material::RayTraceNoiseMaterial;
+use material::RayTraceNoisePattern;
+use material::RayTraceNoiseSpace;
+use material::RayTraceNormalMapKind;
+use material::RayTraceNormalMapMaterial;
+use material::RayTracePbrMaterial;
+use material::RayTraceSimpleMaterial;
+use material::RayTraceTexture;
+use material::RayTraceTextureFilter;
+use material::RayTraceTextureMaterial;
+use material::RayTraceTextureMipmap;
+use material::RayTraceTextureWrap;
+use object::RayTraceObject;
+use object::RayTraceObjectCube;
+use object::RayTraceObjectModel;
+use object::RayTraceObjectPlane;
+use object::RayTraceObjectSphere;
+use object::RayTraceObjectVoxelGrid;
+use object::model::RayTraceModelNormalInterpolation;
+use object::model::RayTraceModelNormalWeighting;
+use object::model::{cache_load_model, obj_load_with, ply_load, stl_load};
+use object::voxel::vox_load;
+use params::RayTraceOutputParams;
+use scene::RayTraceScene;
+
+use sink::image;
+
+use math_util::DEG_TO_RAD;
+use math_util::rot_deg;
+use math_util::RayTraceTransform;
+use math_util::RayTraceTransformable;
+
+use scene_file::RayTraceSceneFileError;
+use scene_file::reader::SceneFileReader;
+
+const MATERIAL_TYPES: [&'static str; 7] = ["simple", "checkerboard", "pbr", "mtl", "texture", "noise",
+	"normal_map"];
+const OBJECT_TYPES: [&'static str; 10] = ["sphere", "cube", "plane", "mesh", "voxels", "obj", "ply", "stl", "vox",
+	"gltf"];
+const LIGHT_TYPES: [&'static str; 2] = ["spot", "directed_spot"];
+const ANIMATION_TYPES: [&'static str; 3] = ["linear", "frames", "sequence"];
+
+// Every octave is another evaluation of the noise for each shading
+const MAX_NOISE_OCTAVES: f64 = 16.0;
+
+// Materials can not be shared so named materials are created again for every object which uses them
+pub struct SceneFileMaterials<'a> {
+	definitions: HashMap<&'a str, &'a JsonValue>
+}
+
+impl<'a> SceneFileMaterials<'a> {
+	pub fn read(reader: &SceneFileReader, value: Option<&'a JsonValue>) -> Result<Self, RayTraceSceneFileError> {
+		let mut definitions = HashMap::new();
+		if let Some(value) = value {
+			let members = match value.as_object() {
+				Some(members) => members,
+				None => { return reader.error(value, "Expected an object of named materials"); }
+			};
+
+			for &(ref name, ref material) in members.iter() {
+				// Materials are built once to report errors even if they are not used
+				try!(create_material(reader, material));
+				definitions.insert(&name[..], material);
+			}
+		}
+
+		Ok(Self {
+			definitions: definitions
+		})
+	}
+
+	// A material is either the name of a defined material or an inline definition
+	pub fn create(&self, reader: &SceneFileReader, value: Option<&JsonValue>)
+			-> Result<Box<RayTraceMaterial>, RayTraceSceneFileError> {
+		match value {
+			None => Ok(box RayTraceSimpleMaterial::new(RayTraceColor::white())),
+			Some(value) => match value.as_str() {
+				Some(name) => match self.definitions.get(name) {
+					Some(material) => create_material(reader, material),
+					None => reader.error(value, &format!("Unknown material \"{}\"", name))
+				},
+				None => create_material(reader, value)
+			}
+		}
+	}
+}
+
+fn create_material(reader: &SceneFileReader, value: &JsonValue)
+		-> Result<Box<RayTraceMaterial>, RayTraceSceneFileError> {
+	let kind = try!(reader.read_type(value));
+	match kind {
+		"simple" => {
+			try!(reader.read_members(value, &["type", "color", "reflectance", "glossiness", "emission"]));
+			let color = try!(reader.read_color(try!(reader.require(value, "color"))));
+			let reflectance = match value.get("reflectance") {
+				Some(r) => try!(reader.read_f32(r)),
+				None => 0.0
+			};
+
+			let mut material = RayTraceSimpleMaterial::new_with(color, reflectance);
+			material.set_glossiness(try!(read_glossiness(reader, value)));
+			if let Some(emission) = value.get("emission") {
+				material.set_emission(try!(reader.read_color(emission)));
+			}
+
+			Ok(box material)
+		},
+		"checkerboard" => {
+			try!(reader.read_members(value, &["type", "colors", "scale", "reflectance", "glossiness"]));
+			let colors = match value.get("colors") {
+				Some(colors) => match colors.as_array() {
+					Some(c) if c.len() == 2 => [try!(reader.read_color(&c[0])), try!(reader.read_color(&c[1]))],
+					_ => { return reader.error(colors, "Expected an array of 2 colors"); }
+				},
+				None => [RayTraceColor::black(), RayTraceColor::white()]
+			};
+			let scale = match value.get("scale") {
+				Some(scale) => match scale.as_f64_vec() {
+					Some(ref s) if s.len() == 2 => [s[0], s[1]],
+					_ => { return reader.error(scale, "Expected an array of 2 numbers"); }
+				},
+				None => [1.0, 1.0]
+			};
+			let reflectance = match value.get("reflectance") {
+				Some(r) => try!(reader.read_f32(r)),
+				None => 0.0
+			};
+
+			let mut material = RayTraceCheckerboardMaterial::new_with(colors, scale, reflectance);
+			material.set_glossiness(try!(read_glossiness(reader, value)));
+
+			Ok(box material)
+		},
+		"pbr" => {
+			try!(reader.read_members(value, &["type", "base_color", "metallic", "roughness", "base_color_map",
+				"emission"]));
+			let base_color = match value.get("base_color") {
+				Some(color) => try!(reader.read_color(color)),
+				None => RayTraceColor::white()
+			};
+			let metallic = match value.get("metallic") {
+				Some(m) => try!(reader.read_f32(m)),
+				None => 0.0
+			};
+			let roughness = match value.get("roughness") {
+				Some(r) => try!(reader.read_f32(r)),
+				None => 1.0
+			};
+
+			let mut material = RayTracePbrMaterial::new(base_color, metallic, roughness);
+			if let Some(map) = value.get("base_color_map") {
+				material.set_base_color_map(Some(try!(read_image(reader, map))));
+			}
+			if let Some(emission) = value.get("emission") {
+				material.set_emission(try!(reader.read_color(emission)));
+			}
+
+			Ok(box material)
+		},
+		"mtl" => {
+			try!(reader.read_members(value, &["type", "diffuse", "specular", "shininess", "dissolve",
+				"optical_density", "illumination", "diffuse_map"]));
+			let mut material = RayTraceMtlMaterial::new();
+			if let Some(diffuse) = value.get("diffuse") {
+				material.set_diffuse(try!(reader.read_color(diffuse)));
+			}
+			if let Some(specular) = value.get("specular") {
+				material.set_specular(try!(reader.read_color(specular)));
+			}
+			if let Some(shininess) = value.get("shininess") {
+				material.set_shininess(try!(reader.read_f32(shininess)));
+			}
+			if let Some(dissolve) = value.get("dissolve") {
+				material.set_dissolve(try!(reader.read_f32(dissolve)));
+			}
+			if let Some(density) = value.get("optical_density") {
+				material.set_optical_density(try!(reader.read_f32(density)));
+			}
+			if let Some(illumination) = value.get("illumination") {
+				material.set_illumination(try!(reader.read_usize(illumination)));
+			}
+			if let Some(map) = value.get("diffuse_map") {
+				material.set_diffuse_map(Some(try!(read_image(reader, map))));
+			}
+
+			Ok(box material)
+		},
+		"texture" => {
+			try!(reader.read_members(value, &["type", "file", "srgb", "filter", "wrap", "mipmap", "scale",
+				"offset", "reflectance", "glossiness"]));
+			let image = try!(read_image(reader, try!(reader.require(value, "file"))));
+			let srgb = match value.get("srgb") {
+				Some(srgb) => try!(reader.read_bool(srgb)),
+				None => true
+			};
+
+			let mut texture = RayTraceTexture::new_with(image, srgb);
+			try!(read_texture_settings(reader, value, &mut texture));
+			let mut material = RayTraceTextureMaterial::new(texture);
+			if let Some(reflectance) = value.get("reflectance") {
+				material.set_reflectance(try!(reader.read_f32(reflectance)));
+			}
+			material.set_glossiness(try!(read_glossiness(reader, value)));
+
+			Ok(box material)
+		},
+		"noise" => {
+			try!(reader.read_members(value, &["type", "pattern", "basis", "seed", "space", "scale", "octaves",
+				"distortion", "ramp", "reflectance", "glossiness"]));
+			let pattern = try!(reader.require(value, "pattern"));
+			let mut material = RayTraceNoiseMaterial::new(match try!(reader.read_str(pattern)) {
+				"noise" => RayTraceNoisePattern::Noise,
+				"clouds" => RayTraceNoisePattern::Clouds,
+				"turbulence" => RayTraceNoisePattern::Turbulence,
+				"marble" => RayTraceNoisePattern::Marble,
+				"wood" => RayTraceNoisePattern::Wood,
+				"granite" => RayTraceNoisePattern::Granite,
+				"cells" => RayTraceNoisePattern::Cells,
+				_ => {
+					return reader.error(pattern, "Expected \"noise\", \"clouds\", \"turbulence\", \"marble\", \
+						\"wood\", \"granite\" or \"cells\"");
+				}
+			});
+
+			let basis = match value.get("basis") {
+				Some(basis) => match try!(reader.read_str(basis)) {
+					"perlin" => RayTraceNoiseBasis::Perlin,
+					"simplex" => RayTraceNoiseBasis::Simplex,
+					_ => { return reader.error(basis, "Expected \"perlin\" or \"simplex\""); }
+				},
+				None => RayTraceNoiseBasis::Perlin
+			};
+			let seed = match value.get("seed") {
+				Some(seed) => match try!(reader.read_usize(seed)) {
+					number if number <= u32::MAX as usize => number as u32,
+					_ => { return reader.error(seed, &format!("Expected a seed up to {}", u32::MAX)); }
+				},
+				None => 0
+			};
+			material.set_noise(seed, basis);
+
+			if let Some(space) = value.get("space") {
+				material.set_space(match try!(reader.read_str(space)) {
+					"texture" => RayTraceNoiseSpace::Texture,
+					"object" => RayTraceNoiseSpace::Object,
+					_ => { return reader.error(space, "Expected \"texture\" or \"object\""); }
+				});
+			}
+			if let Some(scale) = value.get("scale") {
+				material.set_scale(try!(reader.read_positive(scale)));
+			}
+			if let Some(octaves) = value.get("octaves") {
+				match try!(reader.read_f64(octaves)) {
+					number if number >= 1.0 && number <= MAX_NOISE_OCTAVES => { material.set_octaves(number); },
+					_ => { return reader.error(octaves, &format!("Expected 1 to {} octaves", MAX_NOISE_OCTAVES)); }
+				}
+			}
+			if let Some(distortion) = value.get("distortion") {
+				material.set_distortion(try!(reader.read_f64(distortion)));
+			}
+			if let Some(ramp) = value.get("ramp") {
+				material.set_ramp(try!(read_color_ramp(reader, ramp)));
+			}
+			if let Some(reflectance) = value.get("reflectance") {
+				material.set_reflectance(try!(reader.read_f32(reflectance)));
+			}
+			material.set_glossiness(try!(read_glossiness(reader, value)));
+
+			Ok(box material)
+		},
+		"normal_map" => {
+			try!(reader.read_members(value, &["type", "material", "kind", "file", "srgb", "filter", "wrap", "mipmap",
+				"scale", "offset", "strength", "flip_green"]));
+			// The wrapped material is given inline
+			let material = try!(create_material(reader, try!(reader.require(value, "material"))));
+			let kind = match value.get("kind") {
+				Some(kind) => match try!(reader.read_str(kind)) {
+					"normal" => RayTraceNormalMapKind::Normal,
+					"bump" => RayTraceNormalMapKind::Bump,
+					_ => { return reader.error(kind, "Expected \"normal\" or \"bump\""); }
+				},
+				None => RayTraceNormalMapKind::Normal
+			};
+
+			// Maps hold directions or heights, not colors
+			let image = try!(read_image(reader, try!(reader.require(value, "file"))));
+			let srgb = match value.get("srgb") {
+				Some(srgb) => try!(reader.read_bool(srgb)),
+				None => false
+			};
+			let mut map = RayTraceTexture::new_with(image, srgb);
+			try!(read_texture_settings(reader, value, &mut map));
+
+			let mut material = RayTraceNormalMapMaterial::new(material, map, kind);
+			if let Some(strength) = value.get("strength") {
+				material.set_strength(try!(reader.read_f64(strength)));
+			}
+			if let Some(flip) = value.get("flip_green") {
+				material.set_flip_green(try!(reader.read_bool(flip)));
+			}
+
+			Ok(box material)
+		},
+		_ => reader.unknown_type(value, kind, &MATERIAL_TYPES)
+	}
+}
+
+fn read_texture_settings(reader: &SceneFileReader, value: &JsonValue, texture: &mut RayTraceTexture)
+		-> Result<(), RayTraceSceneFileError> {
+	if let Some(filter) = value.get("filter") {
+		texture.set_filter(match try!(reader.read_str(filter)) {
+			"nearest" => RayTraceTextureFilter::Nearest,
+			"bilinear" => RayTraceTextureFilter::Bilinear,
+			"bicubic" => RayTraceTextureFilter::Bicubic,
+			_ => { return reader.error(filter, "Expected \"nearest\", \"bilinear\" or \"bicubic\""); }
+		});
+	}
+	if let Some(wrap) = value.get("wrap") {
+		texture.set_wrap(match try!(reader.read_str(wrap)) {
+			"repeat" => RayTraceTextureWrap::Repeat,
+			"clamp" => RayTraceTextureWrap::Clamp,
+			"mirror" => RayTraceTextureWrap::Mirror,
+			_ => { return reader.error(wrap, "Expected \"repeat\", \"clamp\" or \"mirror\""); }
+		});
+	}
+	if let Some(mipmap) = value.get("mipmap") {
+		texture.set_mipmap(match try!(reader.read_str(mipmap)) {
+			"none" => RayTraceTextureMipmap::None,
+			"trilinear" => RayTraceTextureMipmap::Trilinear,
+			"anisotropic" => RayTraceTextureMipmap::Anisotropic,
+			_ => { return reader.error(mipmap, "Expected \"none\", \"trilinear\" or \"anisotropic\""); }
+		});
+	}
+	if let Some(scale) = value.get("scale") {
+		texture.set_scale(try!(reader.read_vec2(scale)));
+	}
+	if let Some(offset) = value.get("offset") {
+		texture.set_offset(try!(reader.read_vec2(offset)));
+	}
+
+	Ok(())
+}
+
+fn read_color_ramp(reader: &SceneFileReader, value: &JsonValue) -> Result<RayTraceColorRamp, RayTraceSceneFileError> {
+	let mut stops = Vec::new();
+	for stop in try!(reader.read_array(value)).iter() {
+		try!(reader.read_members(stop, &["position", "color"]));
+		let position = try!(reader.read_f32(try!(reader.require(stop, "position"))));
+		let color = try!(reader.read_color(try!(reader.require(stop, "color"))));
+		stops.push((position, color));
+	}
+
+	if stops.is_empty() {
+		return reader.error(value, "Expected at least one color");
+	}
+
+	Ok(RayTraceColorRamp::new(stops))
+}
+
+// Optional phong exponent of glossy reflections, materials without it reflect like mirrors
+fn read_glossiness(reader: &SceneFileReader, value: &JsonValue) -> Result<Option<f32>, RayTraceSceneFileError> {
+	match value.get("glossiness") {
+		Some(glossiness) => Ok(Some(try!(reader.read_positive(glossiness)) as f32)),
+		None => Ok(None)
+	}
+}
+
+fn read_image(reader: &SceneFileReader, value: &JsonValue) -> Result<image::RgbaImage, RayTraceSceneFileError> {
+	let path = try!(reader.read_path(value));
+	let texture = reader.get_assets().load_image(&path);
+	match texture {
+		Ok(texture) => Ok(texture),
+		Err(err) => reader.io_error(value, &format!("Could not load texture {}: {:?}", path, err))
+	}
+}
+
+pub fn read_objects(reader: &SceneFileReader, value: &JsonValue, materials: &SceneFileMaterials,
+		scene: &mut RayTraceScene, screen: &RayTraceOutputParams, fps: f64) -> Result<(), RayTraceSceneFileError> {
+	for object in try!(reader.read_array(value)).iter() {
+		let kind = try!(reader.read_type(object));
+		match kind {
+			"sphere" => {
+				try!(reader.read_members(object, &["type", "name", "tags", "visible", "transform", "material",
+					"center", "size", "size_animation"]));
+				let center = try!(read_vec3_or(reader, object.get("center"), [0.0, 0.0, 0.0]));
+				let size = match object.get("size") {
+					Some(size) => try!(reader.read_positive(size)),
+					None => 1.0
+				};
+
+				let mut sphere = RayTraceObjectSphere::new(center, size,
+					try!(materials.create(reader, object.get("material"))));
+				if let Some(anim) = object.get("size_animation") {
+					sphere.set_anim_size(try!(read_f64_animation(reader, anim)));
+				}
+
+				try!(add_object(reader, object, scene, box sphere));
+			},
+			"cube" => {
+				try!(reader.read_members(object, &["type", "name", "tags", "visible", "transform", "material",
+					"materials", "center", "size", "size_animation"]));
+				let center = try!(read_vec3_or(reader, object.get("center"), [0.0, 0.0, 0.0]));
+				let size = try!(read_vec3_or(reader, object.get("size"), [1.0, 1.0, 1.0]));
+
+				// Either one material for the whole cube or one for each side
+				let mut cube = match object.get("materials") {
+					Some(value) => {
+						let sides = try!(read_material_list(reader, materials, value));
+						if sides.len() != 6 {
+							return reader.error(value, "Expected 6 materials, one for each side");
+						}
+
+						let mut sides = sides.into_iter();
+						RayTraceObjectCube::new_with(center, size, [sides.next().unwrap(), sides.next().unwrap(),
+							sides.next().unwrap(), sides.next().unwrap(), sides.next().unwrap(), sides.next().unwrap()])
+					},
+					None => {
+						let material = try!(materials.create(reader, object.get("material")));
+						RayTraceObjectCube::new(center, size, material)
+					}
+				};
+				if let Some(anim) = object.get("size_animation") {
+					cube.set_anim_size(try!(read_vec3_animation(reader, anim, false)));
+				}
+
+				try!(add_object(reader, object, scene, box cube));
+			},
+			"plane" => {
+				try!(reader.read_members(object, &["type", "name", "tags", "visible", "transform", "material",
+					"center", "rotation"]));
+				let center = try!(read_vec3_or(reader, object.get("center"), [0.0, 0.0, 0.0]));
+				let rotation = rot_deg(try!(read_vec3_or(reader, object.get("rotation"), [0.0, 0.0, 0.0])));
+
+				let plane = RayTraceObjectPlane::new(center, rotation,
+					try!(materials.create(reader, object.get("material"))));
+				try!(add_object(reader, object, scene, box plane));
+			},
+			"mesh" => {
+				try!(reader.read_members(object, &["type", "name", "tags", "visible", "transform", "material",
+					"materials", "face_materials", "vertices", "normals", "texcoords", "faces", "vertex_colors",
+					"smoothing_groups", "crease_angle", "normal_weighting", "interpolation", "back_face_culling",
+					"displacement"]));
+				let mut model = try!(read_mesh(reader, object, materials));
+				try!(read_displacement(reader, object, &mut model));
+				try!(add_object(reader, object, scene, box model));
+			},
+			"voxels" => {
+				try!(reader.read_members(object, &["type", "name", "tags", "visible", "transform", "dimensions",
+					"voxel_size", "sparse", "palette", "voxels"]));
+				let grid = try!(read_voxels(reader, object, materials));
+				try!(add_object(reader, object, scene, box grid));
+			},
+			"obj" | "ply" | "stl" => {
+				try!(reader.read_members(object, &["type", "name", "tags", "visible", "transform", "material",
+					"file", "cache", "displacement"]));
+				let file = try!(reader.require(object, "file"));
+				let file_name = try!(reader.read_path(file));
+				let material = try!(materials.create(reader, object.get("material")));
+				let cached = match object.get("cache") {
+					Some(cache) => try!(reader.read_bool(cache)),
+					None => false
+				};
+				let model = reader.get_assets().load_model(&file_name, material, |file_name, material, assets| {
+					// Material libraries are read through the assets to reuse their textures
+					let loader = |file_name: &str, material: Box<RayTraceMaterial>| match kind {
+						"obj" => obj_load_with(file_name, material, |library| assets.load_library(library)),
+						"ply" => ply_load(file_name, material),
+						_ => stl_load(file_name, material)
+					};
+
+					if cached {
+						cache_load_model(file_name, material, loader)
+					} else {
+						loader(file_name, material)
+					}
+				});
+
+				match model {
+					Ok(mut model) => {
+						try!(read_displacement(reader, object, &mut model));
+						try!(add_object(reader, object, scene, box model));
+					},
+					Err(err) => {
+						return reader.io_error(file, &format!("Could not load model {}: {}", file_name, err));
+					}
+				}
+			},
+			"vox" => {
+				try!(reader.read_members(object, &["type", "name", "tags", "visible", "transform", "file"]));
+				let file = try!(reader.require(object, "file"));
+				let file_name = try!(reader.read_path(file));
+				reader.get_assets().watch(&file_name);
+
+				match vox_load(&file_name) {
+					Ok(grid) => { try!(add_object(reader, object, scene, box grid)); },
+					Err(err) => {
+						return reader.io_error(file, &format!("Could not load voxels {}: {}", file_name, err));
+					}
+				}
+			},
+			"gltf" => {
+				// The nodes of a glTF file bring their own transforms and animations
+				try!(reader.read_members(object, &["type", "tags", "visible", "file"]));
+				let file = try!(reader.require(object, "file"));
+				let file_name = try!(reader.read_path(file));
+				reader.get_assets().watch(&file_name);
+
+				let import = match gltf_load(&file_name, screen, fps) {
+					Ok(import) => import,
+					Err(err) => {
+						return reader.io_error(file, &format!("Could not load glTF {}: {}", file_name, err));
+					}
+				};
+
+				let tags = try!(read_tags(reader, object));
+				let visible = try!(read_visible(reader, object));
+				for (name, model) in import.into_objects() {
+					let handle = scene.add_named_object(&name, box model);
+					for tag in tags.iter() {
+						scene.add_object_tag(&handle, tag);
+					}
+					scene.set_object_visible(&handle, visible);
+				}
+			},
+			_ => { return reader.unknown_type(object, kind, &OBJECT_TYPES); }
+		}
+	}
+
+	Ok(())
+}
+
+// Faces list the vertex, normal and texture coordinate indices of their 3 corners starting with 1, normal and
+// texture coordinate indices of 0 mean that there is none
+fn read_mesh(reader: &SceneFileReader, value: &JsonValue, materials: &SceneFileMaterials)
+		-> Result<RayTraceObjectModel, RayTraceSceneFileError> {
+	let vertices = try!(read_list(reader, Some(try!(reader.require(value, "vertices"))), |v| reader.read_vec3(v)));
+	let normals = try!(read_list(reader, value.get("normals"), |v| reader.read_vec3(v)));
+	let texcoords = try!(read_list(reader, value.get("texcoords"), |v| reader.read_vec2(v)));
+
+	let mut faces = Vec::new();
+	for face in try!(reader.read_array(try!(reader.require(value, "faces")))).iter() {
+		let indices = try!(reader.read_usizes(face, 9));
+		let corners = [[indices[0], indices[1], indices[2]], [indices[3], indices[4], indices[5]],
+			[indices[6], indices[7], indices[8]]];
+
+		for corner in corners.iter() {
+			if corner[0] == 0 || corner[0] > vertices.len() || corner[1] > normals.len() ||
+					corner[2] > texcoords.len() {
+				return reader.error(face, "Face refers to a missing vertex, normal or texture coordinate");
+			}
+		}
+		faces.push(corners);
+	}
+
+	let mut model = RayTraceObjectModel::new(try!(materials.create(reader, value.get("material"))), vertices,
+		normals, texcoords, faces);
+
+	if let Some(colors) = value.get("vertex_colors") {
+		model.set_vertex_colors(try!(read_list(reader, Some(colors), |c| reader.read_color(c))));
+	}
+	if let Some(groups) = value.get("smoothing_groups") {
+		model.set_smoothing_groups(try!(read_list(reader, Some(groups), |g| reader.read_usize(g).map(|g| g as u32))));
+	}
+	if let Some(angle) = value.get("crease_angle") {
+		model.set_crease_angle(try!(reader.read_f64(angle)) * DEG_TO_RAD);
+	}
+	if let Some(weighting) = value.get("normal_weighting") {
+		model.set_normal_weighting(match try!(reader.read_str(weighting)) {
+			"angle" => RayTraceModelNormalWeighting::Angle,
+			"area" => RayTraceModelNormalWeighting::Area,
+			_ => { return reader.error(weighting, "Expected \"angle\" or \"area\""); }
+		});
+	}
+	if let Some(interpolation) = value.get("interpolation") {
+		model.set_interpolation(match try!(reader.read_str(interpolation)) {
+			"average" => RayTraceModelNormalInterpolation::Average,
+			"linear" => RayTraceModelNormalInterpolation::Linear,
+			_ => { return reader.error(interpolation, "Expected \"average\" or \"linear\""); }
+		});
+	}
+	if let Some(culling) = value.get("back_face_culling") {
+		model.set_back_face_culling(try!(reader.read_bool(culling)));
+	}
+
+	if let Some(list) = value.get("materials") {
+		let face_materials = try!(reader.require(value, "face_materials"));
+		let list = try!(read_material_list(reader, materials, list));
+		let indices = try!(read_list(reader, Some(face_materials), |i| reader.read_usize(i)));
+		if indices.iter().any(|i| *i > list.len()) {
+			return reader.error(face_materials, "Face material refers to a missing material");
+		}
+
+		model.set_materials(list, indices);
+	}
+
+	Ok(model)
+}
+
+// The displacement is {"file": height map, "scale": height of white, "subdivisions": count}
+fn read_displacement(reader: &SceneFileReader, value: &JsonValue, model: &mut RayTraceObjectModel)
+		-> Result<(), RayTraceSceneFileError> {
+	let displacement = match value.get("displacement") {
+		Some(displacement) => displacement,
+		None => { return Ok(()); }
+	};
+
+	try!(reader.read_members(displacement, &["file", "scale", "subdivisions"]));
+	let map = RayTraceTexture::new_linear(try!(read_image(reader, try!(reader.require(displacement, "file")))));
+	let scale = match displacement.get("scale") {
+		Some(scale) => try!(reader.read_f64(scale)),
+		None => 1.0
+	};
+	let subdivisions = match displacement.get("subdivisions") {
+		Some(subdivisions) => try!(reader.read_usize(subdivisions)),
+		None => 0
+	};
+
+	// Every subdivision quadruples the faces
+	if subdivisions > 8 {
+		return reader.error(displacement, "Expected at most 8 subdivisions");
+	}
+
+	model.displace(&map, scale, subdivisions);
+	Ok(())
+}
+
+// Voxels are listed as [x, y, z, value] where the value is an index into the palette starting with 1
+fn read_voxels(reader: &SceneFileReader, value: &JsonValue, materials: &SceneFileMaterials)
+		-> Result<RayTraceObjectVoxelGrid, RayTraceSceneFileError> {
+	let size = try!(reader.read_usizes(try!(reader.require(value, "dimensions")), 3));
+	let dimensions = [size[0], size[1], size[2]];
+	let voxel_size = try!(read_vec3_or(reader, value.get("voxel_size"), [1.0, 1.0, 1.0]));
+	let palette = match value.get("palette") {
+		Some(palette) => try!(read_material_list(reader, materials, palette)),
+		None => Vec::new()
+	};
+	let sparse = match value.get("sparse") {
+		Some(sparse) => try!(reader.read_bool(sparse)),
+		None => false
+	};
+
+	let palette_size = palette.len();
+	let mut grid = if sparse {
+		RayTraceObjectVoxelGrid::new_sparse(dimensions, voxel_size, palette)
+	} else {
+		RayTraceObjectVoxelGrid::new(dimensions, voxel_size, palette)
+	};
+
+	if let Some(voxels) = value.get("voxels") {
+		for voxel in try!(reader.read_array(voxels)).iter() {
+			let v = try!(reader.read_usizes(voxel, 4));
+			if v[0] >= dimensions[0] || v[1] >= dimensions[1] || v[2] >= dimensions[2] {
+				return reader.error(voxel, "Voxel is outside of the grid");
+			}
+			if v[3] > palette_size {
+				return reader.error(voxel, "Voxel refers to a missing palette entry");
+			}
+
+			grid.set_voxel(v[0], v[1], v[2], v[3] as u16);
+		}
+	}
+
+	Ok(grid)
+}
+
+fn read_material_list(reader: &SceneFileReader, materials: &SceneFileMaterials, value: &JsonValue)
+		-> Result<Vec<Box<RayTraceMaterial>>, RayTraceSceneFileError> {
+	read_list(reader, Some(value), |m| materials.create(reader, Some(m)))
+}
+
+fn read_list<T, F>(reader: &SceneFileReader, value: Option<&JsonValue>, read: F)
+		-> Result<Vec<T>, RayTraceSceneFileError>
+		where F: Fn(&JsonValue) -> Result<T, RayTraceSceneFileError> {
+	match value {
+		Some(value) => try!(reader.read_array(value)).iter().map(|v| read(v)).collect(),
+		None => Ok(Vec::new())
+	}
+}
+
+fn add_object<T>(reader: &SceneFileReader, value: &JsonValue, scene: &mut RayTraceScene, mut object: Box<T>)
+		-> Result<(), RayTraceSceneFileError> where T: RayTraceObject + RayTraceTransformable + 'static {
+	if let Some(transform) = value.get("transform") {
+		try!(read_transform(reader, transform, object.get_transform_mut()));
+	}
+
+	let handle = match value.get("name") {
+		Some(name) => scene.add_named_object(try!(reader.read_str(name)), object),
+		None => scene.add_object(object)
+	};
+
+	for tag in try!(read_tags(reader, value)).iter() {
+		scene.add_object_tag(&handle, tag);
+	}
+	scene.set_object_visible(&handle, try!(read_visible(reader, value)));
+	Ok(())
+}
+
+pub fn read_lights(reader: &SceneFileReader, value: &JsonValue, scene: &mut RayTraceScene)
+		-> Result<(), RayTraceSceneFileError> {
+	for light in try!(reader.read_array(value)).iter() {
+		let kind = try!(reader.read_type(light));
+		match kind {
+			"spot" => {
+				try!(reader.read_members(light, &["type", "name", "tags", "visible", "transform", "position",
+					"color"]));
+				let position = try!(read_vec3_or(reader, light.get("position"), [0.0, 0.0, 0.0]));
+				let color = try!(read_color_or(reader, light.get("color"), RayTraceColor::white()));
+
+				try!(add_light(reader, light, scene, box RayTraceSpotLight::new(position, color)));
+			},
+			"directed_spot" => {
+				try!(reader.read_members(light, &["type", "name", "tags", "visible", "transform", "position", "color",
+					"size", "size_animation"]));
+				let position = try!(read_vec3_or(reader, light.get("position"), [0.0, 0.0, 0.0]));
+				let color = try!(read_color_or(reader, light.get("color"), RayTraceColor::white()));
+
+				let mut spot = RayTraceDirectedSpotLight::new(position, color);
+				if let Some(size) = light.get("size") {
+					spot.set_size(try!(reader.read_positive(size)));
+				}
+				if let Some(anim) = light.get("size_animation") {
+					spot.set_anim_size(try!(read_f64_animation(reader, anim)));
+				}
+
+				try!(add_light(reader, light, scene, box spot));
+			},
+			_ => { return reader.unknown_type(light, kind, &LIGHT_TYPES); }
+		}
+	}
+
+	Ok(())
+}
+
+fn add_light<T>(reader: &SceneFileReader, value: &JsonValue, scene: &mut RayTraceScene, mut light: Box<T>)
+		-> Result<(), RayTraceSceneFileError> where T: RayTraceLight + RayTraceTransformable + 'static {
+	if let Some(transform) = value.get("transform") {
+		try!(read_transform(reader, transform, light.get_transform_mut()));
+	}
+
+	let handle = match value.get("name") {
+		Some(name) => scene.add_named_light(try!(reader.read_str(name)), light),
+		None => scene.add_light(light)
+	};
+
+	for tag in try!(read_tags(reader, value)).iter() {
+		scene.add_light_tag(&handle, tag);
+	}
+	scene.set_light_visible(&handle, try!(read_visible(reader, value)));
+	Ok(())
+}
+
+fn read_tags(reader: &SceneFileReader, value: &JsonValue) -> Result<Vec<String>, RayTraceSceneFileError> {
+	match value.get("tags") {
+		Some(tags) => reader.read_strings(tags),
+		None => Ok(Vec::new())
+	}
+}
+
+fn read_visible(reader: &SceneFileReader, value: &JsonValue) -> Result<bool, RayTraceSceneFileError> {
+	match value.get("visible") {
+		Some(visible) => reader.read_bool(visible),
+		None => Ok(true)
+	}
+}
+
+fn read_vec3_or(reader: &SceneFileReader, value: Option<&JsonValue>, default: Vector3<f64>)
+		-> Result<Vector3<f64>, RayTraceSceneFileError> {
+	match value {
+		Some(value) => reader.read_vec3(value),
+		None => Ok(default)
+	}
+}
+
+fn read_color_or(reader: &SceneFileReader, value: Option<&JsonValue>, default: RayTraceColor)
+		-> Result<RayTraceColor, RayTraceSceneFileError> {
+	match value {
+		Some(value) => reader.read_color(value),
+		None => Ok(default)
+	}
+}
+
+// Rotations are given in degrees and animations replace the static values of the transform
+pub fn read_transform(reader: &SceneFileReader, value: &JsonValue, transform: &mut RayTraceTransform)
+		-> Result<(), RayTraceSceneFileError> {
+	try!(reader.read_members(value, &["translation", "rotation", "scale", "pivot", "matrix", "animation"]));
+	if let Some(translation) = value.get("translation") {
+		transform.set_translation(try!(reader.read_vec3(translation)));
+	}
+	if let Some(rotation) = value.get("rotation") {
+		transform.set_rotation(rot_deg(try!(reader.read_vec3(rotation))));
+	}
+	if let Some(scale) = value.get("scale") {
+		transform.set_scale(try!(reader.read_vec3(scale)));
+	}
+	if let Some(pivot) = value.get("pivot") {
+		transform.set_pivot(try!(reader.read_vec3(pivot)));
+	}
+	if let Some(matrix) = value.get("matrix") {
+		transform.set_matrix(try!(reader.read_mat4(matrix)));
+	}
+
+	if let Some(animation) = value.get("animation") {
+		try!(reader.read_members(animation, &["translation", "rotation", "scale", "pivot", "matrix"]));
+		if let Some(anim) = animation.get("translation") {
+			transform.set_anim_translation(try!(read_vec3_animation(reader, anim, false)));
+		}
+		if let Some(anim) = animation.get("rotation") {
+			transform.set_anim_rotation(try!(read_vec3_animation(reader, anim, true)));
+		}
+		if let Some(anim) = animation.get("scale") {
+			transform.set_anim_scale(try!(read_vec3_animation(reader, anim, false)));
+		}
+		if let Some(anim) = animation.get("pivot") {
+			transform.set_anim_pivot(try!(read_vec3_animation(reader, anim, false)));
+		}
+		if let Some(anim) = animation.get("matrix") {
+			transform.set_anim_matrix(try!(read_mat4_animation(reader, anim)));
+		}
+	}
+
+	Ok(())
+}
+
+fn read_vec3_animation(reader: &SceneFileReader, value: &JsonValue, degrees: bool)
+		-> Result<Box<RayTraceAnimation<Vector3<f64>> + Sync>, RayTraceSceneFileError> {
+	let read = |v: &JsonValue| reader.read_vec3(v).map(|v| if degrees { rot_deg(v) } else { v });
+	read_animation(reader, value, &read, &linear_vec3)
+}
+
+fn read_f64_animation(reader: &SceneFileReader, value: &JsonValue)
+		-> Result<Box<RayTraceAnimation<f64> + Sync>, RayTraceSceneFileError> {
+	let read = |v: &JsonValue| reader.read_f64(v);
+	read_animation(reader, value, &read, &linear_f64)
+}
+
+// Matrices can not be interpolated linearly, only their frames are supported
+fn read_mat4_animation(reader: &SceneFileReader, value: &JsonValue)
+		-> Result<Box<RayTraceAnimation<Matrix4<f64>> + Sync>, RayTraceSceneFileError> {
+	let kind = try!(reader.read_type(value));
+	if kind != "frames" {
+		return reader.unknown_type(value, kind, &["frames"]);
+	}
+
+	try!(reader.read_members(value, &["type", "values"]));
+	let values = try!(reader.read_array(try!(reader.require(value, "values"))));
+	if values.is_empty() {
+		return reader.error(value, "Expected at least one frame");
+	}
+
+	let frames: Result<Vec<Matrix4<f64>>, RayTraceSceneFileError> = values.iter()
+		.map(|v| reader.read_mat4(v))
+		.collect();
+	Ok(box RayTraceAnimFrames::new(try!(frames)))
+}
+
+fn linear_vec3(initial: Vector3<f64>, delta: Vector3<f64>) -> Box<RayTraceAnimation<Vector3<f64>> + Sync> {
+	box RayTraceAnimVec3Linear::new(initial, delta)
+}
+
+fn linear_f64(initial: f64, delta: f64) -> Box<RayTraceAnimation<f64> + Sync> {
+	box RayTraceAnimF64Linear::new(initial, delta)
+}
+
+// Linear animations start at "initial" and change by "delta" per frame, "frames" lists the value of every
+// frame and a "sequence" switches between animations at their "start" frames
+fn read_animation<T, R, L>(reader: &SceneFileReader, value: &JsonValue, read: &R, linear: &L)
+		-> Result<Box<RayTraceAnimation<T> + Sync>, RayTraceSceneFileError>
+		where T: Clone + Send + Sync + 'static,
+			R: Fn(&JsonValue) -> Result<T, RayTraceSceneFileError>,
+			L: Fn(T, T) -> Box<RayTraceAnimation<T> + Sync> {
+	let kind = try!(reader.read_type(value));
+	match kind {
+		"linear" => {
+			try!(reader.read_members(value, &["type", "initial", "delta"]));
+			let initial = try!(read(try!(reader.require(value, "initial"))));
+			let delta = try!(read(try!(reader.require(value, "delta"))));
+			Ok(linear(initial, delta))
+		},
+		"frames" => {
+			try!(reader.read_members(value, &["type", "values"]));
+			let values = try!(reader.read_array(try!(reader.require(value, "values"))));
+			if values.is_empty() {
+				return reader.error(value, "Expected at least one frame");
+			}
+
+			let frames: Result<Vec<T>, RayTraceSceneFileError> = values.iter().map(|v| read(v)).collect();
+			Ok(box RayTraceAnimFrames::new(try!(frames)))
+		},
+		"sequence" => {
+			try!(reader.read_members(value, &["type", "parts"]));
+			let mut sequence = RayTraceAnimSequence::new();
+			for part in try!(reader.read_array(try!(reader.require(value, "parts")))).iter() {
+				try!(reader.read_members(part, &["start", "animation"]));
+				let start = try!(reader.read_usize(try!(reader.require(part, "start"))));
+				let animation = try!(read_animation(reader, try!(reader.require(part, "animation")), read, linear));
+				sequence.add_animation(animation, start);
+			}
+
+			Ok(box sequence)
+		},
+		_ => reader.unknown_type(value, kind, &ANIMATION_TYPES)
+	}
+}
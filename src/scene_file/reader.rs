@@ -0,0 +1,202 @@
+use std::cell::RefCell;
+use std::cell::RefMut;
+use std::path::Path;
+use std::path::PathBuf;
+
+use vecmath::Matrix4;
+use vecmath::Vector2;
+use vecmath::Vector3;
+
+use color::RayTraceColor;
+use json::JsonValue;
+
+use scene_file::RayTraceSceneAssets;
+use scene_file::RayTraceSceneFileError;
+use scene_file::RayTraceSceneFileErrorKind;
+
+// Reads typed values of a scene document and reports errors at their position
+pub struct SceneFileReader {
+	file_name: String,
+	directory: PathBuf,
+	assets: RefCell<RayTraceSceneAssets>
+}
+
+impl SceneFileReader {
+	pub fn new(file_name: &str, assets: RayTraceSceneAssets) -> Self {
+		Self {
+			file_name: file_name.to_string(),
+			directory: Path::new(file_name).parent().unwrap_or(Path::new("")).to_path_buf(),
+			assets: RefCell::new(assets)
+		}
+	}
+
+	pub fn get_file_name(&self) -> &str {
+		&self.file_name
+	}
+
+	// Models and textures are loaded through the assets to reuse them on reloads
+	pub fn get_assets(&self) -> RefMut<RayTraceSceneAssets> {
+		self.assets.borrow_mut()
+	}
+
+	pub fn into_assets(self) -> RayTraceSceneAssets {
+		self.assets.into_inner()
+	}
+
+	pub fn error<T>(&self, value: &JsonValue, message: &str) -> Result<T, RayTraceSceneFileError> {
+		Err(RayTraceSceneFileError::new(&self.file_name, value.get_line(), value.get_column(), message))
+	}
+
+	// Files which are referenced by the value could not be read or created
+	pub fn io_error<T>(&self, value: &JsonValue, message: &str) -> Result<T, RayTraceSceneFileError> {
+		Err(RayTraceSceneFileError::new_with(RayTraceSceneFileErrorKind::Io, &self.file_name, value.get_line(),
+			value.get_column(), message))
+	}
+
+	// Returns the members of an object and rejects all keys which are not expected
+	pub fn read_members<'a>(&self, value: &'a JsonValue, allowed: &[&str])
+			-> Result<&'a Vec<(String, JsonValue)>, RayTraceSceneFileError> {
+		let members = match value.as_object() {
+			Some(members) => members,
+			None => { return self.error(value, "Expected an object"); }
+		};
+
+		for &(ref key, ref member) in members.iter() {
+			if !allowed.contains(&&key[..]) {
+				return self.error(member, &format!("Unknown key \"{}\", expected one of: {}", key, allowed.join(", ")));
+			}
+		}
+
+		Ok(members)
+	}
+
+	pub fn read_type<'a>(&self, value: &'a JsonValue) -> Result<&'a str, RayTraceSceneFileError> {
+		match value.get("type") {
+			Some(kind) => self.read_str(kind),
+			None => self.error(value, "Missing key \"type\"")
+		}
+	}
+
+	pub fn unknown_type<T>(&self, value: &JsonValue, kind: &str, known: &[&str]) -> Result<T, RayTraceSceneFileError> {
+		let position = value.get("type").unwrap_or(value);
+		self.error(position, &format!("Unknown type \"{}\", expected one of: {}", kind, known.join(", ")))
+	}
+
+	pub fn require<'a>(&self, value: &'a JsonValue, key: &str) -> Result<&'a JsonValue, RayTraceSceneFileError> {
+		match value.get(key) {
+			Some(member) => Ok(member),
+			None => self.error(value, &format!("Missing key \"{}\"", key))
+		}
+	}
+
+	pub fn read_bool(&self, value: &JsonValue) -> Result<bool, RayTraceSceneFileError> {
+		match value.as_bool() {
+			Some(b) => Ok(b),
+			None => self.error(value, "Expected true or false")
+		}
+	}
+
+	pub fn read_f64(&self, value: &JsonValue) -> Result<f64, RayTraceSceneFileError> {
+		match value.as_f64() {
+			Some(number) => Ok(number),
+			None => self.error(value, "Expected a number")
+		}
+	}
+
+	pub fn read_f32(&self, value: &JsonValue) -> Result<f32, RayTraceSceneFileError> {
+		self.read_f64(value).map(|n| n as f32)
+	}
+
+	pub fn read_positive(&self, value: &JsonValue) -> Result<f64, RayTraceSceneFileError> {
+		match value.as_f64() {
+			Some(number) if number > 0.0 => Ok(number),
+			_ => self.error(value, "Expected a number greater than zero")
+		}
+	}
+
+	pub fn read_usize(&self, value: &JsonValue) -> Result<usize, RayTraceSceneFileError> {
+		match value.as_usize() {
+			Some(number) => Ok(number),
+			None => self.error(value, "Expected a non-negative integer")
+		}
+	}
+
+	pub fn read_str<'a>(&self, value: &'a JsonValue) -> Result<&'a str, RayTraceSceneFileError> {
+		match value.as_str() {
+			Some(text) => Ok(text),
+			None => self.error(value, "Expected a string")
+		}
+	}
+
+	pub fn read_vec3(&self, value: &JsonValue) -> Result<Vector3<f64>, RayTraceSceneFileError> {
+		match value.as_f64_vec() {
+			Some(ref v) if v.len() == 3 => Ok([v[0], v[1], v[2]]),
+			_ => self.error(value, "Expected an array of 3 numbers")
+		}
+	}
+
+	pub fn read_vec2(&self, value: &JsonValue) -> Result<Vector2<f64>, RayTraceSceneFileError> {
+		match value.as_f64_vec() {
+			Some(ref v) if v.len() == 2 => Ok([v[0], v[1]]),
+			_ => self.error(value, "Expected an array of 2 numbers")
+		}
+	}
+
+	// Index lists like the faces of a mesh with a fixed number of entries
+	pub fn read_usizes(&self, value: &JsonValue, count: usize) -> Result<Vec<usize>, RayTraceSceneFileError> {
+		let values = match value.as_array() {
+			Some(values) if values.len() == count => values,
+			_ => { return self.error(value, &format!("Expected an array of {} integers", count)); }
+		};
+
+		values.iter().map(|v| self.read_usize(v)).collect()
+	}
+
+	// Matrices are arrays of 4 rows
+	pub fn read_mat4(&self, value: &JsonValue) -> Result<Matrix4<f64>, RayTraceSceneFileError> {
+		let rows = match value.as_array() {
+			Some(rows) if rows.len() == 4 => rows,
+			_ => { return self.error(value, "Expected a matrix as an array of 4 rows"); }
+		};
+
+		let mut matrix = [[0.0; 4]; 4];
+		for (i, row) in rows.iter().enumerate() {
+			match row.as_f64_vec() {
+				Some(ref r) if r.len() == 4 => { matrix[i] = [r[0], r[1], r[2], r[3]]; },
+				_ => { return self.error(row, "Expected a matrix row of 4 numbers"); }
+			}
+		}
+
+		Ok(matrix)
+	}
+
+	// Colors are given as [r, g, b] or [r, g, b, a] in the range 0 to 1
+	pub fn read_color(&self, value: &JsonValue) -> Result<RayTraceColor, RayTraceSceneFileError> {
+		match value.as_f64_vec() {
+			Some(ref c) if c.len() == 3 => Ok(RayTraceColor::new_with(c[0] as f32, c[1] as f32, c[2] as f32, 1.0)),
+			Some(ref c) if c.len() == 4 => Ok(RayTraceColor::new_with(c[0] as f32, c[1] as f32, c[2] as f32,
+				c[3] as f32)),
+			_ => self.error(value, "Expected a color as an array of 3 or 4 numbers")
+		}
+	}
+
+	pub fn read_strings(&self, value: &JsonValue) -> Result<Vec<String>, RayTraceSceneFileError> {
+		match value.as_array() {
+			Some(values) => values.iter().map(|v| self.read_str(v).map(|s| s.to_string())).collect(),
+			None => self.error(value, "Expected an array of strings")
+		}
+	}
+
+	pub fn read_array<'a>(&self, value: &'a JsonValue) -> Result<&'a Vec<JsonValue>, RayTraceSceneFileError> {
+		match value.as_array() {
+			Some(values) => Ok(values),
+			None => self.error(value, "Expected an array")
+		}
+	}
+
+	// Paths are relative to the directory of the scene file
+	pub fn read_path(&self, value: &JsonValue) -> Result<String, RayTraceSceneFileError> {
+		let path = try!(self.read_str(value));
+		Ok(self.directory.join(path).to_string_lossy().into_owned())
+	}
+}
@@ -0,0 +1,340 @@
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+
+use vecmath::Matrix4;
+use vecmath::Vector2;
+use vecmath::Vector3;
+
+use anim::RayTraceAnimation;
+use color::RayTraceColor;
+use json::JsonData;
+use json::JsonValue;
+use material::RayTraceMaterial;
+
+use sink::image::RgbaImage;
+
+use math_util::RAD_TO_DEG;
+use math_util::RayTraceTransform;
+
+// Collects the values of one object of a scene file, types write themselves through their serialize method
+pub struct RayTraceSceneWriter<'a> {
+	context: &'a mut SceneWriterContext,
+	path: String,
+	members: Vec<(String, JsonValue)>
+}
+
+// Path points to the value which could not be written like objects[2].material
+#[derive(Debug)]
+pub struct RayTraceSceneWriteError {
+	path: String,
+	message: String
+}
+
+pub struct SceneWriterContext {
+	frames: usize,
+	directory: PathBuf,
+	texture_prefix: String,
+	texture_count: usize
+}
+
+impl SceneWriterContext {
+	pub fn new(frames: usize, directory: PathBuf, texture_prefix: String) -> Self {
+		Self {
+			frames: frames,
+			directory: directory,
+			texture_prefix: texture_prefix,
+			texture_count: 0
+		}
+	}
+}
+
+#[allow(dead_code)]
+impl<'a> RayTraceSceneWriter<'a> {
+	pub fn new(context: &'a mut SceneWriterContext, path: String) -> Self {
+		Self {
+			context: context,
+			path: path,
+			members: Vec::new()
+		}
+	}
+
+	pub fn into_value(self) -> JsonValue {
+		JsonValue::new_object(self.members)
+	}
+
+	pub fn get_path(&self) -> &str {
+		&self.path
+	}
+
+	// Number of frames for which animations are sampled
+	pub fn get_frames(&self) -> usize {
+		self.context.frames
+	}
+
+	pub fn error(&self, message: &str) -> RayTraceSceneWriteError {
+		RayTraceSceneWriteError::new(&self.path, message)
+	}
+
+	// Error for types which do not support serialization
+	pub fn unsupported(&self, kind: &str) -> RayTraceSceneWriteError {
+		self.error(&format!("This {} type can not be written to a scene file", kind))
+	}
+
+	pub fn set_type(&mut self, kind: &str) {
+		self.set_value("type", JsonValue::new_string(kind));
+	}
+
+	pub fn set_bool(&mut self, key: &str, value: bool) {
+		self.set_value(key, JsonValue::new(JsonData::Bool(value)));
+	}
+
+	pub fn set_f64(&mut self, key: &str, value: f64) -> Result<(), RayTraceSceneWriteError> {
+		try!(self.check_finite(key, &[value]));
+		self.set_value(key, JsonValue::new_number(value));
+		Ok(())
+	}
+
+	pub fn set_usize(&mut self, key: &str, value: usize) {
+		self.set_value(key, JsonValue::new_number(value as f64));
+	}
+
+	pub fn set_str(&mut self, key: &str, value: &str) {
+		self.set_value(key, JsonValue::new_string(value));
+	}
+
+	pub fn set_vec3(&mut self, key: &str, value: Vector3<f64>) -> Result<(), RayTraceSceneWriteError> {
+		try!(self.check_finite(key, &value));
+		self.set_value(key, vec3_value(value));
+		Ok(())
+	}
+
+	pub fn set_color(&mut self, key: &str, color: &RayTraceColor) -> Result<(), RayTraceSceneWriteError> {
+		let (r, g, b, a) = color.get();
+		try!(self.check_finite(key, &[r as f64, g as f64, b as f64, a as f64]));
+		self.set_value(key, color_value(color));
+		Ok(())
+	}
+
+	pub fn set_f64_list(&mut self, key: &str, values: &[f64]) {
+		self.set_value(key, JsonValue::new_array(values.iter().map(|v| JsonValue::new_number(*v)).collect()));
+	}
+
+	pub fn set_usize_list(&mut self, key: &str, values: &[usize]) {
+		self.set_value(key, JsonValue::new_array(values.iter().map(|v| JsonValue::new_number(*v as f64)).collect()));
+	}
+
+	// Rows like the indices of faces are kept on one line each
+	pub fn set_usize_rows(&mut self, key: &str, rows: &[Vec<usize>]) {
+		self.set_value(key, JsonValue::new_array(rows.iter()
+			.map(|row| JsonValue::new_array(row.iter().map(|v| JsonValue::new_number(*v as f64)).collect()))
+			.collect()));
+	}
+
+	pub fn set_vec2_list(&mut self, key: &str, values: &[Vector2<f64>]) {
+		self.set_value(key, JsonValue::new_array(values.iter()
+			.map(|v| JsonValue::new_array(vec![JsonValue::new_number(v[0]), JsonValue::new_number(v[1])]))
+			.collect()));
+	}
+
+	pub fn set_vec3_list(&mut self, key: &str, values: &[Vector3<f64>]) {
+		self.set_value(key, JsonValue::new_array(values.iter().map(|v| vec3_value(*v)).collect()));
+	}
+
+	pub fn set_color_list(&mut self, key: &str, colors: &[RayTraceColor]) {
+		self.set_value(key, JsonValue::new_array(colors.iter().map(color_value).collect()));
+	}
+
+	pub fn set_str_list(&mut self, key: &str, values: &[String]) {
+		self.set_value(key, JsonValue::new_array(values.iter().map(|v| JsonValue::new_string(v)).collect()));
+	}
+
+	// Writes a nested object through the given function
+	pub fn set_child<F>(&mut self, key: &str, write: F) -> Result<(), RayTraceSceneWriteError>
+			where F: FnOnce(&mut RayTraceSceneWriter) -> Result<(), RayTraceSceneWriteError> {
+		let value = {
+			let mut writer = RayTraceSceneWriter::new(&mut *self.context, child_path(&self.path, key));
+			try!(write(&mut writer));
+			writer.into_value()
+		};
+
+		self.set_value(key, value);
+		Ok(())
+	}
+
+	// Writes an array of nested objects, the function is called with the index of each one
+	pub fn set_child_list<F>(&mut self, key: &str, count: usize, mut write: F) -> Result<(), RayTraceSceneWriteError>
+			where F: FnMut(usize, &mut RayTraceSceneWriter) -> Result<(), RayTraceSceneWriteError> {
+		let mut values = Vec::with_capacity(count);
+		for i in 0..count {
+			let path = format!("{}[{}]", child_path(&self.path, key), i);
+			let mut writer = RayTraceSceneWriter::new(&mut *self.context, path);
+			try!(write(i, &mut writer));
+			values.push(writer.into_value());
+		}
+
+		self.set_value(key, JsonValue::new_array(values));
+		Ok(())
+	}
+
+	pub fn set_material(&mut self, key: &str, material: &RayTraceMaterial) -> Result<(), RayTraceSceneWriteError> {
+		self.set_child(key, |writer| material.serialize(writer))
+	}
+
+	pub fn set_material_list(&mut self, key: &str, materials: &[Box<RayTraceMaterial>])
+			-> Result<(), RayTraceSceneWriteError> {
+		self.set_child_list(key, materials.len(), |i, writer| materials[i].serialize(writer))
+	}
+
+	// Images are stored as .png files next to the scene file
+	pub fn set_image(&mut self, key: &str, image: &RgbaImage) -> Result<(), RayTraceSceneWriteError> {
+		self.context.texture_count += 1;
+		let name = format!("{}{:04}.png", self.context.texture_prefix, self.context.texture_count);
+		let path = self.context.directory.join(&name);
+
+		if let Some(parent) = path.parent() {
+			if let Err(err) = fs::create_dir_all(parent) {
+				return Err(self.error(&format!("Could not create {}: {}", parent.display(), err)));
+			}
+		}
+		if let Err(err) = image.save(&path) {
+			return Err(self.error(&format!("Could not write texture {}: {}", path.display(), err)));
+		}
+
+		self.set_str(key, &name);
+		Ok(())
+	}
+
+	// Animations are sampled for every frame of the output as they may be closures
+	pub fn set_anim_f64(&mut self, key: &str, anim: &RayTraceAnimation<f64>) {
+		let values = (0..self.context.frames).map(|f| JsonValue::new_number(anim.next_frame(f))).collect();
+		self.set_value(key, frames_value(values));
+	}
+
+	pub fn set_anim_vec3(&mut self, key: &str, anim: &RayTraceAnimation<Vector3<f64>>) {
+		let values = (0..self.context.frames).map(|f| vec3_value(anim.next_frame(f))).collect();
+		self.set_value(key, frames_value(values));
+	}
+
+	pub fn set_transform(&mut self, transform: &RayTraceTransform) {
+		let mut members = Vec::new();
+		members.push(("translation".to_string(), vec3_value(*transform.get_translation())));
+		members.push(("rotation".to_string(), vec3_value(to_degrees(*transform.get_rotation()))));
+		members.push(("scale".to_string(), vec3_value(*transform.get_scale())));
+		members.push(("pivot".to_string(), vec3_value(*transform.get_pivot())));
+		members.push(("matrix".to_string(), matrix_value(transform.get_matrix())));
+
+		let frames = self.context.frames;
+		let mut anims = Vec::new();
+		if let Some(anim) = transform.get_anim_translation() {
+			anims.push(("translation".to_string(), frames_value((0..frames)
+				.map(|f| vec3_value(anim.next_frame(f))).collect())));
+		}
+		if let Some(anim) = transform.get_anim_rotation() {
+			anims.push(("rotation".to_string(), frames_value((0..frames)
+				.map(|f| vec3_value(to_degrees(anim.next_frame(f)))).collect())));
+		}
+		if let Some(anim) = transform.get_anim_scale() {
+			anims.push(("scale".to_string(), frames_value((0..frames)
+				.map(|f| vec3_value(anim.next_frame(f))).collect())));
+		}
+		if let Some(anim) = transform.get_anim_pivot() {
+			anims.push(("pivot".to_string(), frames_value((0..frames)
+				.map(|f| vec3_value(anim.next_frame(f))).collect())));
+		}
+		if let Some(anim) = transform.get_anim_matrix() {
+			anims.push(("matrix".to_string(), frames_value((0..frames)
+				.map(|f| matrix_value(&anim.next_frame(f))).collect())));
+		}
+		if !anims.is_empty() {
+			members.push(("animation".to_string(), JsonValue::new_object(anims)));
+		}
+
+		self.set_value("transform", JsonValue::new_object(members));
+	}
+
+	// JSON has no representation for infinite numbers and NaN
+	fn check_finite(&self, key: &str, values: &[f64]) -> Result<(), RayTraceSceneWriteError> {
+		if values.iter().all(|v| v.is_finite()) {
+			Ok(())
+		} else {
+			Err(RayTraceSceneWriteError::new(&child_path(&self.path, key), "Numbers have to be finite"))
+		}
+	}
+
+	pub fn set_value(&mut self, key: &str, value: JsonValue) {
+		match self.members.iter().position(|m| m.0 == key) {
+			Some(index) => { self.members[index].1 = value; },
+			None => { self.members.push((key.to_string(), value)); }
+		}
+	}
+}
+
+#[allow(dead_code)]
+impl RayTraceSceneWriteError {
+	pub fn new(path: &str, message: &str) -> Self {
+		Self {
+			path: path.to_string(),
+			message: message.to_string()
+		}
+	}
+
+	pub fn get_path(&self) -> &str {
+		&self.path
+	}
+
+	pub fn get_message(&self) -> &str {
+		&self.message
+	}
+}
+
+impl fmt::Display for RayTraceSceneWriteError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		if self.path.is_empty() {
+			write!(f, "{}", self.message)
+		} else {
+			write!(f, "{}: {}", self.path, self.message)
+		}
+	}
+}
+
+impl Error for RayTraceSceneWriteError {
+	fn description(&self) -> &str {
+		&self.message
+	}
+}
+
+fn child_path(path: &str, key: &str) -> String {
+	if path.is_empty() {
+		key.to_string()
+	} else {
+		format!("{}.{}", path, key)
+	}
+}
+
+fn to_degrees(angle: Vector3<f64>) -> Vector3<f64> {
+	[angle[0] * RAD_TO_DEG, angle[1] * RAD_TO_DEG, angle[2] * RAD_TO_DEG]
+}
+
+fn vec3_value(value: Vector3<f64>) -> JsonValue {
+	JsonValue::new_array(value.iter().map(|v| JsonValue::new_number(*v)).collect())
+}
+
+fn color_value(color: &RayTraceColor) -> JsonValue {
+	let (r, g, b, a) = color.get();
+	JsonValue::new_array(vec![r, g, b, a].into_iter().map(|v| JsonValue::new_number(v as f64)).collect())
+}
+
+fn matrix_value(matrix: &Matrix4<f64>) -> JsonValue {
+	JsonValue::new_array(matrix.iter()
+		.map(|row| JsonValue::new_array(row.iter().map(|v| JsonValue::new_number(*v)).collect()))
+		.collect())
+}
+
+fn frames_value(values: Vec<JsonValue>) -> JsonValue {
+	JsonValue::new_object(vec![
+		("type".to_string(), JsonValue::new_string("frames")),
+		("values".to_string(), JsonValue::new_array(values))
+	])
+}
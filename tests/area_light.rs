@@ -0,0 +1,175 @@
+extern crate ray_tracer;
+
+use std::f64;
+
+use ray_tracer::camera::RayTraceCamera;
+use ray_tracer::camera::RayTracerCameraPerspective;
+use ray_tracer::color::RayTraceColor;
+use ray_tracer::light::RayTracePhongShading;
+use ray_tracer::light::RayTraceShading;
+use ray_tracer::light::lights::RayTraceAreaLight;
+use ray_tracer::light::lights::RayTraceAreaLightShape;
+use ray_tracer::material::RayTracePbrMaterial;
+use ray_tracer::material::RayTraceSimpleMaterial;
+use ray_tracer::math_util::RayTraceTransformable;
+use ray_tracer::object::RayTraceHitable;
+use ray_tracer::object::RayTraceObject;
+use ray_tracer::object::RayTraceObjectCube;
+use ray_tracer::object::RayTraceObjectPlane;
+use ray_tracer::object::RayTraceObjectSphere;
+use ray_tracer::params::RayTraceOutputParams;
+use ray_tracer::params::RayTraceParams;
+use ray_tracer::ray::RayTraceRay;
+use ray_tracer::scene::RayTraceScene;
+
+fn create_emissive(emission: f32) -> Box<RayTraceSimpleMaterial> {
+	let mut material = RayTraceSimpleMaterial::new(RayTraceColor::white());
+	material.set_emission(RayTraceColor::new_with(emission, emission, emission, 1.0));
+	Box::new(material)
+}
+
+fn distance(a: [f64; 3], b: [f64; 3]) -> f64 {
+	((a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2) + (a[2] - b[2]).powi(2)).sqrt()
+}
+
+#[test]
+fn emissive_objects_become_area_lights() {
+	let mut scene = RayTraceScene::new();
+	scene.add_object(Box::new(RayTraceObjectSphere::new([0.0, 3.0, 0.0], 2.0, create_emissive(1.0))));
+	scene.add_object(Box::new(RayTraceObjectCube::new([0.0, 0.0, 0.0], [1.0, 1.0, 1.0],
+		Box::new(RayTraceSimpleMaterial::new(RayTraceColor::white())))));
+	scene.init(0);
+
+	let lights: Vec<_> = scene.get_area_lights().collect();
+	assert_eq!(lights.len(), 1);
+	match *lights[0].0.get_shape() {
+		RayTraceAreaLightShape::Sphere(center, radius) => {
+			assert!(distance(center, [0.0, 3.0, 0.0]) < 1e-9);
+			assert!((radius - 1.0).abs() < 1e-9);
+		},
+		_ => { panic!("Expected a sphere"); }
+	}
+}
+
+#[test]
+fn only_uniformly_scaled_spheres_become_area_lights() {
+	let mut scene = RayTraceScene::new();
+	let mut uniform = RayTraceObjectSphere::new([0.0, 3.0, 0.0], 2.0, create_emissive(1.0));
+	uniform.get_transform_mut().set_scale([2.0, 2.0, 2.0]);
+	uniform.get_transform_mut().set_rotation([30.0, 45.0, 0.0]);
+	let mut stretched = RayTraceObjectSphere::new([0.0, -3.0, 0.0], 2.0, create_emissive(1.0));
+	stretched.get_transform_mut().set_scale([3.0, 1.0, 1.0]);
+	scene.add_object(Box::new(uniform));
+	scene.add_object(Box::new(stretched));
+	scene.init(0);
+
+	let lights: Vec<_> = scene.get_area_lights().collect();
+	assert_eq!(lights.len(), 1);
+	assert_eq!(lights[0].0.get_object(), 0);
+	match *lights[0].0.get_shape() {
+		RayTraceAreaLightShape::Sphere(_, radius) => { assert!((radius - 2.0).abs() < 1e-9); },
+		_ => { panic!("Expected a sphere"); }
+	}
+}
+
+#[test]
+fn edited_emissive_objects_update_the_area_lights() {
+	let mut scene = RayTraceScene::new();
+	let sphere = scene.add_object(Box::new(RayTraceObjectSphere::new([0.0, 3.0, 0.0], 2.0, create_emissive(1.0))));
+	scene.init(0);
+	assert_eq!(scene.get_area_lights().count(), 1);
+
+	scene.set_object_visible(&sphere, false);
+	assert_eq!(scene.get_area_lights().count(), 0);
+	scene.set_object_visible(&sphere, true);
+	assert_eq!(scene.get_area_lights().count(), 1);
+
+	// Lights of objects added after the initialization are known right away
+	let cube = scene.add_object(Box::new(RayTraceObjectCube::new([0.0, 0.0, 0.0], [1.0, 1.0, 1.0],
+		create_emissive(1.0))));
+	assert_eq!(scene.get_area_lights().count(), 2);
+
+	let (sphere, _) = scene.replace_object(sphere, Box::new(RayTraceObjectSphere::new([0.0, 3.0, 0.0], 2.0,
+		Box::new(RayTraceSimpleMaterial::new(RayTraceColor::white())))));
+	let lights: Vec<_> = scene.get_area_lights().map(|(l, _)| l.get_object()).collect();
+	assert_eq!(lights, vec![1]);
+
+	scene.remove_object(cube);
+	assert_eq!(scene.get_area_lights().count(), 0);
+	scene.remove_object(sphere);
+	assert_eq!(scene.get_area_lights().count(), 0);
+}
+
+#[test]
+fn triangle_samples_have_solid_angle_density() {
+	let light = RayTraceAreaLight::new(0, RayTraceAreaLightShape::Triangles(vec![
+		[[0.0, 2.0, 0.0], [1.0, 2.0, 0.0], [0.0, 2.0, 1.0]],
+		[[1.0, 2.0, 0.0], [1.0, 2.0, 1.0], [0.0, 2.0, 1.0]]
+	]));
+	assert!((light.get_area() - 1.0).abs() < 1e-9);
+
+	for &u in [[0.1, 0.2, 0.3], [0.7, 0.5, 0.9], [0.99, 0.01, 0.5]].iter() {
+		let (point, pdf) = light.sample([0.0, 0.0, 0.0], u).unwrap();
+		assert!((point[1] - 2.0).abs() < 1e-9);
+		assert!(point[0] >= 0.0 && point[0] <= 1.0 && point[2] >= 0.0 && point[2] <= 1.0);
+
+		let d = distance(point, [0.0, 0.0, 0.0]);
+		let cos = 2.0 / d;
+		assert!((pdf - d * d / cos).abs() < 1e-9);
+	}
+}
+
+#[test]
+fn sphere_samples_lie_in_the_visible_cone() {
+	let light = RayTraceAreaLight::new(0, RayTraceAreaLightShape::Sphere([0.0, 0.0, 4.0], 2.0));
+	let cos_max = (1.0 - 4.0 / 16.0 as f64).sqrt();
+
+	for &u in [[0.0, 0.0, 0.0], [0.5, 0.25, 0.0], [1.0, 0.75, 0.0]].iter() {
+		let (point, pdf) = light.sample([0.0, 0.0, 0.0], u).unwrap();
+		assert!((distance(point, [0.0, 0.0, 4.0]) - 2.0).abs() < 1e-6);
+		assert!((pdf - 1.0 / (2.0 * f64::consts::PI * (1.0 - cos_max))).abs() < 1e-9);
+	}
+
+	// Positions inside of the light do not see it as a light
+	assert!(light.sample([0.0, 0.0, 3.0], [0.5, 0.5, 0.5]).is_none());
+}
+
+#[test]
+fn emissive_panels_light_other_objects() {
+	let out_params = RayTraceOutputParams::new(32, 24, 1);
+	let camera: Box<RayTraceCamera> = Box::new(RayTracerCameraPerspective::new(&out_params, 1.0, 1.0));
+	let mut params = RayTraceParams::new();
+	params.set_light_samples(16);
+	let shading = RayTracePhongShading::new_with(0.0, 1.0, 0.0);
+
+	let mut plane = RayTraceObjectPlane::new([0.0, 0.0, 0.0], [0.0, 0.0, 0.0],
+		Box::new(RayTraceSimpleMaterial::new(RayTraceColor::white())));
+	plane.init(0);
+	let ray = RayTraceRay::new_to([3.0, 1.0, 0.0], [0.0, 0.0, 0.0]);
+	let hit = plane.next_hit(&ray).unwrap();
+
+	let mut scene = RayTraceScene::new();
+	scene.add_object(Box::new(RayTraceObjectCube::new([0.0, 2.0, 0.0], [1.0, 0.01, 1.0], create_emissive(4.0))));
+	scene.init(0);
+
+	let (lit, _) = shading.apply(&ray, &hit, &camera, &scene, &params);
+	// About the irradiance of a small panel with the area 1 at the distance 2 reflected by a Lambertian surface
+	let expected = 1.0 / f64::consts::PI as f32;
+	assert!(lit.get_r() > 0.5 * expected && lit.get_r() < 1.5 * expected, "Light {}", lit.get_r());
+
+	// Rough dielectric materials reflect about the same diffuse light
+	let mut pbr_plane = RayTraceObjectPlane::new([0.0, 0.0, 0.0], [0.0, 0.0, 0.0],
+		Box::new(RayTracePbrMaterial::new(RayTraceColor::white(), 0.0, 1.0)));
+	pbr_plane.init(0);
+	let pbr_hit = pbr_plane.next_hit(&ray).unwrap();
+	let (pbr_lit, _) = shading.apply(&ray, &pbr_hit, &camera, &scene, &params);
+	assert!((pbr_lit.get_r() / lit.get_r() - 1.0).abs() < 0.2, "Light {} and {}", lit.get_r(), pbr_lit.get_r());
+
+	// Objects between the panel and the hit cast a shadow
+	scene.add_object(Box::new(RayTraceObjectCube::new([0.0, 1.0, 0.0], [3.0, 0.1, 3.0],
+		Box::new(RayTraceSimpleMaterial::new(RayTraceColor::white())))));
+	scene.init(0);
+
+	let (shadowed, _) = shading.apply(&ray, &hit, &camera, &scene, &params);
+	assert_eq!(shadowed.get_r(), 0.0);
+}
@@ -0,0 +1,141 @@
+extern crate ray_tracer;
+
+use ray_tracer::aabb::AABB;
+use ray_tracer::color::RayTraceColor;
+use ray_tracer::hit::RayTraceMaterialHit;
+use ray_tracer::hit::RayTraceRayHit;
+use ray_tracer::light::RayTraceLight;
+use ray_tracer::object::RayTraceHitable;
+use ray_tracer::object::RayTraceObject;
+use ray_tracer::ray::RayTraceRay;
+use ray_tracer::scene::RayTraceScene;
+use ray_tracer::vecmath::Vector3;
+
+// Disc facing up along the y axis which is implemented outside of the crate
+struct Disc {
+	center: Vector3<f64>,
+	radius: f64,
+	aabb: Option<AABB>
+}
+
+impl Disc {
+	fn new(center: Vector3<f64>, radius: f64) -> Self {
+		Self {
+			center: center,
+			radius: radius,
+			aabb: None
+		}
+	}
+}
+
+impl RayTraceObject for Disc {
+	fn init(&mut self, _frame: usize) {
+		let (x, y, z) = (self.center[0], self.center[1], self.center[2]);
+		self.aabb = Some(AABB::new([x - self.radius, y - 0.001, z - self.radius],
+			[x + self.radius, y + 0.001, z + self.radius]));
+	}
+
+	fn get_aabb(&self) -> Option<&AABB> {
+		self.aabb.as_ref()
+	}
+}
+
+impl RayTraceHitable for Disc {
+	fn next_hit(&self, ray: &RayTraceRay) -> Option<RayTraceRayHit> {
+		let direction = ray.get_direction();
+		if direction[1] == 0.0 {
+			return None;
+		}
+
+		let distance = (self.center[1] - ray.get_position()[1]) / direction[1];
+		if distance <= 0.0 {
+			return None;
+		}
+
+		let position = ray.get_position_on_ray(distance);
+		let (dx, dz) = (position[0] - self.center[0], position[2] - self.center[2]);
+		if dx * dx + dz * dz > self.radius * self.radius {
+			return None;
+		}
+
+		let normal = if direction[1] < 0.0 { [0.0, 1.0, 0.0] } else { [0.0, -1.0, 0.0] };
+		Some(RayTraceRayHit::new(distance, position, normal, RayTraceMaterialHit::new(RayTraceColor::red())))
+	}
+}
+
+struct ConstantLight {
+	position: Vector3<f64>
+}
+
+impl RayTraceLight for ConstantLight {
+	fn init(&mut self, _frame: usize) { }
+
+	fn get_position(&self) -> Vector3<f64> {
+		self.position
+	}
+
+	fn get_light(&self, _ray: &RayTraceRay) -> RayTraceColor {
+		RayTraceColor::white()
+	}
+}
+
+#[test]
+fn external_object_is_hit() {
+	let mut disc = Disc::new([0.0, 1.0, 0.0], 2.0);
+	disc.init(0);
+
+	let ray = RayTraceRay::new_to([0.5, 5.0, 0.5], [0.5, 0.0, 0.5]);
+	assert!(disc.get_aabb().unwrap().is_hit(&ray));
+
+	let hit = disc.next_hit(&ray).expect("Ray should hit the disc");
+	assert!((hit.get_distance() - 4.0).abs() < 1e-9);
+	assert_eq!(*hit.get_surface_normal(), [0.0, 1.0, 0.0]);
+	assert_eq!(*hit.get_geometric_normal(), [0.0, 1.0, 0.0]);
+	assert!(hit.get_triangle().is_none());
+	assert_eq!(hit.get_surface_material().get_color().get_r(), 1.0);
+}
+
+#[test]
+fn external_object_is_missed() {
+	let mut disc = Disc::new([0.0, 1.0, 0.0], 2.0);
+	disc.init(0);
+
+	let beside = RayTraceRay::new_to([3.0, 5.0, 0.0], [3.0, 0.0, 0.0]);
+	assert!(disc.next_hit(&beside).is_none());
+
+	let away = RayTraceRay::new_to([0.0, 5.0, 0.0], [0.0, 10.0, 0.0]);
+	assert!(disc.next_hit(&away).is_none());
+}
+
+#[test]
+fn external_types_are_added_to_scene() {
+	let mut scene = RayTraceScene::new();
+	let disc = scene.add_object(Box::new(Disc::new([0.0, 0.0, 0.0], 1.0)));
+	let light = scene.add_light(Box::new(ConstantLight { position: [0.0, 5.0, 0.0] }));
+	scene.init(0);
+
+	let aabb = scene.get_objects().next().unwrap().get_aabb().unwrap();
+	assert!(aabb.contains([0.5, 0.0, -0.5]));
+	assert!(!aabb.contains([0.0, 1.0, 0.0]));
+	assert_eq!(scene.get_lights().next().unwrap().get_position(), [0.0, 5.0, 0.0]);
+
+	// Objects are changed through their handles between frames
+	scene.get_object_mut(&disc).radius = 2.0;
+	scene.get_light_mut(&light).position = [0.0, 3.0, 0.0];
+	scene.init(1);
+
+	assert_eq!(scene.get_object(&disc).radius, 2.0);
+	assert!(scene.get_objects().next().unwrap().get_aabb().unwrap().contains([1.5, 0.0, 0.0]));
+	assert_eq!(scene.get_light(&light).get_position(), [0.0, 3.0, 0.0]);
+}
+
+#[test]
+#[should_panic]
+fn handles_of_other_scenes_are_rejected() {
+	let mut scene = RayTraceScene::new();
+	let mut other = RayTraceScene::new();
+
+	let disc = scene.add_object(Box::new(Disc::new([0.0, 0.0, 0.0], 1.0)));
+	other.add_object(Box::new(Disc::new([0.0, 0.0, 0.0], 1.0)));
+	other.get_object(&disc);
+}
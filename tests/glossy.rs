@@ -0,0 +1,90 @@
+extern crate ray_tracer;
+
+use ray_tracer::color::RayTraceColor;
+use ray_tracer::material::RayTraceMaterial;
+use ray_tracer::material::RayTraceMicrofacetBrdf;
+use ray_tracer::material::RayTraceSimpleMaterial;
+use ray_tracer::math_util::sample_phong_lobe;
+
+fn dot(a: [f64; 3], b: [f64; 3]) -> f64 {
+	a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+// Stratified random numbers on a grid
+fn grid(count: usize) -> Vec<[f64; 2]> {
+	(0..count * count).map(|i| [((i / count) as f64 + 0.5) / count as f64, ((i % count) as f64 + 0.5) / count as f64])
+		.collect()
+}
+
+#[test]
+fn phong_lobes_narrow_with_the_exponent() {
+	let axis = [0.0, 0.6, 0.8];
+
+	for &exponent in [1.0, 10.0, 100.0].iter() {
+		let samples = grid(32);
+		let mean = samples.iter().map(|&u| dot(sample_phong_lobe(axis, exponent, u), axis)).sum::<f64>()
+			/ samples.len() as f64;
+
+		// The mean cosine of the lobe cos^n is (n + 1) / (n + 2)
+		let expected = (exponent + 1.0) / (exponent + 2.0);
+		assert!((mean - expected).abs() < 1e-3, "Mean {} for exponent {}", mean, exponent);
+	}
+}
+
+#[test]
+fn rough_metals_blur_reflections() {
+	let normal = [0.0, 0.0, 1.0];
+	let view = [0.0, 0.6, 0.8];
+	let mirror = [0.0, -0.6, 0.8];
+
+	let spread = |roughness: f64| {
+		let brdf = RayTraceMicrofacetBrdf::new(RayTraceColor::white(), 1.0, roughness);
+		let samples: Vec<_> = grid(16).into_iter().filter_map(|u| brdf.sample_specular(normal, view, u)).collect();
+		for &(direction, weight) in samples.iter() {
+			assert!(dot(direction, normal) > 0.0);
+			assert!(weight.get_r() > 0.0 && weight.get_r() <= 1.0);
+		}
+		samples.iter().map(|&(d, _)| 1.0 - dot(d, mirror)).sum::<f64>() / samples.len() as f64
+	};
+
+	assert!(spread(0.1) < 0.01);
+	assert!(spread(0.5) > spread(0.1) * 10.0);
+}
+
+#[test]
+fn specular_samples_are_tinted_by_the_fresnel_term() {
+	let normal = [0.0, 0.0, 1.0];
+	let view = [0.0, 0.6, 0.8];
+
+	// Metals tint their reflections with the base color
+	let metal = RayTraceMicrofacetBrdf::new(RayTraceColor::new_with(1.0, 0.5, 0.0, 1.0), 1.0, 0.3);
+	for (_, weight) in grid(8).into_iter().filter_map(|u| metal.sample_specular(normal, view, u)) {
+		assert!(weight.get_g() < weight.get_r() && weight.get_b() < weight.get_g());
+	}
+
+	// Dielectrics reflect little light at normal incidence and more at grazing angles
+	let dielectric = RayTraceMicrofacetBrdf::new(RayTraceColor::white(), 0.0, 0.0);
+	let facing = dielectric.get_mirror_weight(normal, normal).get_r();
+	let grazing = dielectric.get_mirror_weight(normal, [0.0, 0.995, 0.0999]).get_r();
+	assert!((facing - 0.04).abs() < 1e-6);
+	assert!(grazing > 0.5);
+}
+
+#[test]
+fn smooth_surfaces_are_mirrors() {
+	assert!(RayTraceMicrofacetBrdf::new(RayTraceColor::white(), 1.0, 0.0).is_mirror());
+	assert!(!RayTraceMicrofacetBrdf::new(RayTraceColor::white(), 1.0, 0.3).is_mirror());
+}
+
+#[test]
+fn materials_without_glossiness_are_mirrors() {
+	let mut material = RayTraceSimpleMaterial::new_with(RayTraceColor::white(), 1.0);
+	let hit = material.get_hit(0.0, 0.0);
+	assert_eq!(hit.get_glossiness(), None);
+	assert!(hit.is_mirror());
+
+	material.set_glossiness(Some(50.0));
+	assert!(!material.get_hit(0.0, 0.0).is_mirror());
+	material.set_glossiness(Some(5000.0));
+	assert!(material.get_hit(0.0, 0.0).is_mirror());
+}
@@ -0,0 +1,65 @@
+extern crate ray_tracer;
+
+use std::env;
+use std::fs::File;
+use std::io::Write;
+
+use ray_tracer::gltf::gltf_load;
+use ray_tracer::params::RayTraceOutputParams;
+
+fn write_gltf(name: &str, text: &str) -> String {
+	let path = env::temp_dir().join(name);
+	let mut file = File::create(&path).unwrap();
+	file.write_all(text.as_bytes()).unwrap();
+	path.to_string_lossy().into_owned()
+}
+
+fn load_error(name: &str, nodes: &str) -> String {
+	let file_name = write_gltf(name, &format!(r#"{{ "asset": {{ "version": "2.0" }}, "scene": 0,
+		"scenes": [{{ "nodes": [0] }}], "nodes": {} }}"#, nodes));
+	match gltf_load(&file_name, &RayTraceOutputParams::new(32, 24, 1), 25.0) {
+		Ok(_) => { panic!("Loaded invalid nodes {}", nodes); },
+		Err(err) => format!("{}", err)
+	}
+}
+
+#[test]
+fn cyclic_node_hierarchies_are_rejected() {
+	let err = load_error("ray_tracer_gltf_cycle.gltf", r#"[{ "children": [1] }, { "children": [0] }]"#);
+	assert!(err.contains("more than once"), "{}", err);
+}
+
+#[test]
+fn nodes_with_several_parents_are_rejected() {
+	let err = load_error("ray_tracer_gltf_parents.gltf",
+		r#"[{ "children": [1, 2] }, { "children": [2] }, { }]"#);
+	assert!(err.contains("more than one parent"), "{}", err);
+}
+
+#[test]
+fn invalid_children_are_rejected() {
+	let err = load_error("ray_tracer_gltf_children.gltf", r#"[{ "children": [3] }]"#);
+	assert!(err.contains("Invalid child"), "{}", err);
+}
+
+#[test]
+fn accessors_outside_of_their_buffers_are_rejected() {
+	// Triangle with 36 bytes of positions
+	let view = r#""bufferView": 0, "componentType": 5126, "type": "VEC3""#;
+	let accessors = [
+		format!(r#"{{ {}, "count": 4 }}"#, view),
+		format!(r#"{{ {}, "count": 1152921504606846976 }}"#, view),
+		format!(r#"{{ {}, "count": 3, "byteOffset": 18446744073709551615 }}"#, view),
+		r#"{ "componentType": 5126, "count": 1152921504606846976, "type": "VEC3" }"#.to_string()
+	];
+
+	for (i, accessor) in accessors.iter().enumerate() {
+		let file_name = write_gltf(&format!("ray_tracer_gltf_accessor_{}.gltf", i), &format!(r#"{{
+			"asset": {{ "version": "2.0" }}, "scene": 0, "scenes": [{{ "nodes": [0] }}], "nodes": [{{ "mesh": 0 }}],
+			"meshes": [{{ "primitives": [{{ "attributes": {{ "POSITION": 0 }} }}] }}], "accessors": [{}],
+			"bufferViews": [{{ "buffer": 0, "byteLength": 36 }}], "buffers": [{{ "byteLength": 36,
+				"uri": "data:application/octet-stream;base64,AAAAAAAAAAAAAAAAAACAPwAAAAAAAAAAAAAAAAAAgD8AAAAA" }}]
+		}}"#, accessor));
+		assert!(gltf_load(&file_name, &RayTraceOutputParams::new(32, 24, 1), 25.0).is_err(), "Loaded {}", accessor);
+	}
+}
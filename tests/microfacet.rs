@@ -0,0 +1,85 @@
+extern crate ray_tracer;
+
+use std::f64;
+
+use ray_tracer::color::RayTraceColor;
+use ray_tracer::material::RayTraceMaterial;
+use ray_tracer::material::RayTraceMicrofacetBrdf;
+use ray_tracer::material::RayTracePbrMaterial;
+
+const NORMAL: [f64; 3] = [0.0, 0.0, 1.0];
+
+fn direction(theta: f64, phi: f64) -> [f64; 3] {
+	[theta.sin() * phi.cos(), theta.sin() * phi.sin(), theta.cos()]
+}
+
+// Integrates over the hemisphere around the normal with the midpoint rule
+fn integrate<F>(f: F) -> f64 where F: Fn([f64; 3]) -> f64 {
+	let (n_theta, n_phi) = (200, 400);
+	let (d_theta, d_phi) = (f64::consts::FRAC_PI_2 / n_theta as f64, 2.0 * f64::consts::PI / n_phi as f64);
+	let mut sum = 0.0;
+	for i in 0..n_theta {
+		let theta = (i as f64 + 0.5) * d_theta;
+		for j in 0..n_phi {
+			let phi = (j as f64 + 0.5) * d_phi;
+			sum += f(direction(theta, phi)) * theta.sin() * d_theta * d_phi;
+		}
+	}
+	sum
+}
+
+#[test]
+fn white_surfaces_do_not_create_energy() {
+	// Very rough metals lose more energy since light which is scattered several times is ignored
+	let view = direction(0.5, 0.0);
+	for &(metallic, roughness) in [(0.0, 0.5), (0.0, 1.0), (1.0, 0.3), (1.0, 0.6)].iter() {
+		let brdf = RayTraceMicrofacetBrdf::new(RayTraceColor::white(), metallic, roughness);
+		let albedo = integrate(|light| brdf.evaluate(NORMAL, view, light).get_g() as f64);
+		assert!(albedo <= 1.01, "Albedo {} for metallic {} and roughness {}", albedo, metallic, roughness);
+		assert!(albedo > 0.75, "Albedo {} for metallic {} and roughness {}", albedo, metallic, roughness);
+	}
+}
+
+#[test]
+fn density_is_normalized() {
+	let view = direction(0.3, 1.0);
+	let brdf = RayTraceMicrofacetBrdf::new(RayTraceColor::new_with(0.8, 0.4, 0.2, 1.0), 0.5, 0.6);
+	let total = integrate(|light| brdf.pdf(NORMAL, view, light));
+	// Some reflected directions of the specular lobe are below the surface
+	assert!(total <= 1.01 && total > 0.9, "Density integrates to {}", total);
+}
+
+#[test]
+fn samples_are_weighted_by_their_density() {
+	let view = direction(0.7, 2.0);
+	let brdf = RayTraceMicrofacetBrdf::new(RayTraceColor::new_with(0.9, 0.6, 0.3, 1.0), 0.3, 0.4);
+
+	for &u in [[0.05, 0.3, 0.7], [0.5, 0.5, 0.5], [0.95, 0.1, 0.2], [0.2, 0.9, 0.9]].iter() {
+		if let Some((light, weight, pdf)) = brdf.sample(NORMAL, view, u) {
+			assert!((pdf - brdf.pdf(NORMAL, view, light)).abs() < 1e-9);
+			let value = brdf.evaluate(NORMAL, view, light);
+			assert!((weight.get_r() as f64 * pdf - value.get_r() as f64).abs() < 1e-4);
+		}
+	}
+}
+
+#[test]
+fn reflection_is_reciprocal() {
+	let brdf = RayTraceMicrofacetBrdf::new(RayTraceColor::white(), 1.0, 0.4);
+	let (a, b) = (direction(0.4, 0.3), direction(1.1, 2.5));
+
+	// The cosine of the light direction is part of the result
+	let ab = brdf.evaluate(NORMAL, a, b).get_r() as f64 / b[2];
+	let ba = brdf.evaluate(NORMAL, b, a).get_r() as f64 / a[2];
+	assert!((ab - ba).abs() < 1e-4 * ab.max(1.0));
+}
+
+#[test]
+fn pbr_materials_provide_the_brdf() {
+	let material = RayTracePbrMaterial::new(RayTraceColor::red(), 1.0, 0.25);
+	let hit = material.get_hit(0.0, 0.0);
+	let brdf = hit.get_brdf().unwrap();
+	assert_eq!(brdf.get_metallic(), 1.0);
+	let specular = brdf.get_specular_color();
+	assert!((specular[0] - 1.0).abs() < 1e-9 && specular[1].abs() < 1e-9 && specular[2].abs() < 1e-9);
+}
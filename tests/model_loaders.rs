@@ -0,0 +1,221 @@
+extern crate ray_tracer;
+
+use std::env;
+use std::fs::File;
+use std::io::Write;
+
+use ray_tracer::color::RayTraceColor;
+use ray_tracer::gltf::gltf_load;
+use ray_tracer::material::RayTraceSimpleMaterial;
+use ray_tracer::object::RayTraceHitable;
+use ray_tracer::object::RayTraceObject;
+use ray_tracer::object::RayTraceObjectModel;
+use ray_tracer::object::model::cache_build;
+use ray_tracer::object::model::cache_decode;
+use ray_tracer::object::model::cache_encode;
+use ray_tracer::object::model::mtl_load_with;
+use ray_tracer::object::model::obj_load;
+use ray_tracer::object::model::ply_load;
+use ray_tracer::object::model::stl_load;
+use ray_tracer::params::RayTraceOutputParams;
+use ray_tracer::ray::RayTraceRay;
+use ray_tracer::sink::image::RgbaImage;
+
+fn write_file(name: &str, content: &[u8]) -> String {
+	let path = env::temp_dir().join(name);
+	let mut file = File::create(&path).unwrap();
+	file.write_all(content).unwrap();
+	path.to_string_lossy().into_owned()
+}
+
+fn create_material() -> Box<RayTraceSimpleMaterial> {
+	Box::new(RayTraceSimpleMaterial::new(RayTraceColor::white()))
+}
+
+// Distance to the model along a ray pointing down the z axis
+fn hit_distance(model: &mut RayTraceObjectModel, x: f64, y: f64) -> Option<f64> {
+	model.init(0);
+	model.next_hit(&RayTraceRay::new_to([x, y, 1.0], [x, y, 0.0])).map(|h| h.get_distance())
+}
+
+// Red component of the material hit by a ray pointing down the z axis
+fn hit_red(model: &mut RayTraceObjectModel, x: f64, y: f64) -> Option<f32> {
+	model.init(0);
+	model.next_hit(&RayTraceRay::new_to([x, y, 1.0], [x, y, 0.0])).map(|h| h.get_surface_material().get_color().get_r())
+}
+
+// Two triangles with a red and a blue material in a group, the second one uses relative indices
+fn load_obj() -> RayTraceObjectModel {
+	write_file("ray_tracer_obj_test.mtl", b"newmtl red\nKd 1 0 0\nnewmtl blue\nKd 0 0 1\n");
+	let file_name = write_file("ray_tracer_obj_test.obj", b"mtllib ray_tracer_obj_test.mtl\n\
+		g pair\nv 0 0 0\nv 1 0 0\nv 0 1 0\nusemtl red\nf 1 2 3\n\
+		v 2 0 0\nv 3 0 0\nv 2 1 0\nusemtl blue\nf -3 -2 -1\n");
+	obj_load(&file_name, create_material()).unwrap()
+}
+
+fn push_f32(content: &mut Vec<u8>, value: f32) {
+	let bits = value.to_bits();
+	content.extend((0..4).map(|i| (bits >> (i * 8)) as u8));
+}
+
+fn ply_triangle(face: &str) -> String {
+	format!("ply\nformat ascii 1.0\nelement vertex 3\nproperty float x\nproperty float y\nproperty float z\n\
+		element face 1\nproperty list uchar int vertex_indices\nend_header\n0 0 0\n1 0 0\n0 1 0\n{}\n", face)
+}
+
+// Binary file with a triangle in the xy plane followed by some padding
+fn stl_binary_triangle(padding: usize) -> Vec<u8> {
+	let mut content = vec![0u8; 80];
+	content.extend([1u8, 0, 0, 0].iter());
+	for &value in [0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0].iter() {
+		push_f32(&mut content, value);
+	}
+	content.extend([0u8, 0].iter());
+	content.extend(vec![0u8; padding]);
+	content
+}
+
+#[test]
+fn obj_files_use_relative_indices_and_their_materials() {
+	let mut model = load_obj();
+	assert_eq!(model.get_face_materials(), &vec![1, 2]);
+	assert_eq!(model.get_group("pair").map(|g| g.get_faces().len()), Some(2));
+
+	assert_eq!(hit_red(&mut model, 0.2, 0.2), Some(1.0));
+	assert_eq!(hit_red(&mut model, 2.2, 0.2), Some(0.0));
+	assert!(hit_red(&mut model, 1.2, 0.2).is_none());
+}
+
+#[test]
+fn mtl_texture_options_are_skipped() {
+	let file_name = write_file("ray_tracer_mtl_options.mtl", b"newmtl a\n\
+		map_Kd -s 2 2 1 -o 0.5 -bm 1 -clamp on a b.png\n\
+		newmtl b\nmap_Kd -mm 0 1 -t 0.1 b.png\nnewmtl c\nmap_Kd -s 2 c.png\n");
+	let mut files = Vec::new();
+	let materials = mtl_load_with(&file_name, |file| {
+		files.push(file.to_string());
+		Ok(RgbaImage::new(1, 1))
+	}).unwrap();
+
+	assert_eq!(materials.len(), 3);
+	assert!(materials.iter().all(|&(_, ref m)| m.get_diffuse_map().is_some()));
+	let expected = ["a b.png", "b.png", "c.png"];
+	for (file, expected) in files.iter().zip(expected.iter()) {
+		assert!(file.ends_with(expected) && !file.ends_with(&format!(" {}", expected)), "{}", file);
+	}
+
+	for &(name, line) in [("missing", "map_Kd -s 1 1 1"), ("unknown", "map_Kd -x 1 a.png")].iter() {
+		let content = format!("newmtl a\n{}\n", line);
+		let file_name = write_file(&format!("ray_tracer_mtl_{}.mtl", name), content.as_bytes());
+		assert!(mtl_load_with(&file_name, |_| Ok(RgbaImage::new(1, 1))).is_err(), "Loaded {}", line);
+	}
+}
+
+#[test]
+fn cached_models_are_restored() {
+	let mut model = load_obj();
+	cache_build(&mut model);
+	let content = cache_encode(&model, 42, true).unwrap();
+
+	assert!(cache_decode(&content, "test", create_material(), 43).is_err());
+	let mut cached = match cache_decode(&content, "test", create_material(), 42) {
+		Ok(cached) => cached,
+		Err(_) => { panic!("Could not decode the cache"); }
+	};
+
+	assert_eq!(cached.get_face_materials(), model.get_face_materials());
+	assert_eq!(cached.get_group("pair").map(|g| g.get_faces().len()), Some(2));
+	for &(x, y) in [(0.2, 0.2), (2.2, 0.2), (1.2, 0.2)].iter() {
+		assert_eq!(hit_distance(&mut cached, x, y), hit_distance(&mut model, x, y));
+	}
+}
+
+#[test]
+fn ply_files_are_loaded_from_ascii_and_binary() {
+	let file_name = write_file("ray_tracer_ply_ascii.ply", ply_triangle("3 0 1 2").as_bytes());
+	let mut model = ply_load(&file_name, create_material()).unwrap();
+	assert!((hit_distance(&mut model, 0.2, 0.2).unwrap() - 1.0).abs() < 1e-9);
+
+	let mut content = b"ply\nformat binary_little_endian 1.0\nelement vertex 3\nproperty float x\n\
+		property float y\nproperty float z\nelement face 1\nproperty list uchar int vertex_indices\n\
+		end_header\n".to_vec();
+	for &value in [2.0, 0.0, -1.0, 3.0, 0.0, -1.0, 2.0, 1.0, -1.0].iter() {
+		push_f32(&mut content, value);
+	}
+	content.extend([3u8, 0, 0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0].iter());
+	let file_name = write_file("ray_tracer_ply_binary.ply", &content);
+	let mut model = ply_load(&file_name, create_material()).unwrap();
+	assert!((hit_distance(&mut model, 2.2, 0.2).unwrap() - 2.0).abs() < 1e-6);
+	assert!(hit_distance(&mut model, 0.2, 0.2).is_none());
+}
+
+#[test]
+fn ply_indices_outside_of_the_vertices_are_rejected() {
+	let file_name = write_file("ray_tracer_ply_valid.ply", ply_triangle("3 0 1 2").as_bytes());
+	let mut model = ply_load(&file_name, create_material()).unwrap();
+	assert!((hit_distance(&mut model, 0.2, 0.2).unwrap() - 1.0).abs() < 1e-9);
+
+	for &(name, face) in [("negative", "3 0 1 -1"), ("large", "3 0 1 3"), ("fraction", "3 0 1 1.5")].iter() {
+		let file_name = write_file(&format!("ray_tracer_ply_{}.ply", name), ply_triangle(face).as_bytes());
+		assert!(ply_load(&file_name, create_material()).is_err(), "Loaded face {}", face);
+	}
+}
+
+#[test]
+fn ascii_stl_files_are_loaded() {
+	let file_name = write_file("ray_tracer_stl_ascii.stl", b"solid test\nfacet normal 0 0 1\nouter loop\n\
+		vertex 0 0 0\nvertex 1 0 0\nvertex 0 1 0\nendloop\nendfacet\nendsolid test\n");
+	let mut model = stl_load(&file_name, create_material()).unwrap();
+	assert!((hit_distance(&mut model, 0.2, 0.2).unwrap() - 1.0).abs() < 1e-9);
+}
+
+#[test]
+fn padded_binary_stl_files_are_loaded() {
+	for &padding in [0, 16].iter() {
+		let file_name = write_file("ray_tracer_stl_padded.stl", &stl_binary_triangle(padding));
+		let mut model = stl_load(&file_name, create_material()).unwrap();
+		assert!((hit_distance(&mut model, 0.2, 0.2).unwrap() - 1.0).abs() < 1e-9);
+		assert!(hit_distance(&mut model, 0.8, 0.8).is_none());
+	}
+}
+
+#[test]
+fn gltf_nodes_are_placed_relative_to_their_parents() {
+	let file_name = write_file("ray_tracer_gltf_hierarchy.gltf", br#"{ "asset": { "version": "2.0" }, "scene": 0,
+		"scenes": [{ "nodes": [0] }],
+		"nodes": [{ "translation": [0, 0, -2], "children": [1] }, { "name": "child", "translation": [1, 0, 0],
+			"mesh": 0 }],
+		"meshes": [{ "primitives": [{ "attributes": { "POSITION": 0 } }] }],
+		"accessors": [{ "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3" }],
+		"bufferViews": [{ "buffer": 0, "byteLength": 36 }],
+		"buffers": [{ "byteLength": 36,
+			"uri": "data:application/octet-stream;base64,AAAAAAAAAAAAAAAAAACAPwAAAAAAAAAAAAAAAAAAgD8AAAAA" }]
+	}"#);
+	let mut objects = gltf_load(&file_name, &RayTraceOutputParams::new(32, 24, 1), 25.0).unwrap().into_objects();
+	assert_eq!(objects.len(), 1);
+	assert_eq!(objects[0].0, "child");
+
+	let model = &mut objects[0].1;
+	assert!((hit_distance(model, 1.2, 0.2).unwrap() - 3.0).abs() < 1e-9);
+	assert!(hit_distance(model, 0.2, 0.2).is_none());
+}
+
+#[test]
+fn rays_through_shared_edges_and_vertices_hit() {
+	// Square made of four triangles around its center
+	let vertices = vec![[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [1.0, 1.0, 0.0], [0.0, 1.0, 0.0], [0.5, 0.5, 0.0]];
+	let faces = vec![
+		[[1, 0, 0], [2, 0, 0], [5, 0, 0]], [[2, 0, 0], [3, 0, 0], [5, 0, 0]],
+		[[3, 0, 0], [4, 0, 0], [5, 0, 0]], [[4, 0, 0], [1, 0, 0], [5, 0, 0]]
+	];
+	let mut model = RayTraceObjectModel::new(create_material(), vertices, Vec::new(), Vec::new(), faces);
+
+	let mut points = vec![(0.5, 0.5), (0.0, 0.0), (1.0, 1.0), (0.5, 0.0), (0.0, 0.7)];
+	points.extend((1..100).map(|i| i as f64 / 100.0).flat_map(|t| vec![(t, t), (t, 1.0 - t)]));
+	for &(x, y) in points.iter() {
+		match hit_distance(&mut model, x, y) {
+			Some(distance) => { assert!((distance - 1.0).abs() < 1e-9); },
+			None => { panic!("Missed the model at {} {}", x, y); }
+		}
+	}
+}
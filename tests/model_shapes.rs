@@ -0,0 +1,167 @@
+extern crate ray_tracer;
+
+use ray_tracer::color::RayTraceColor;
+use ray_tracer::hit::RayTraceMaterialHit;
+use ray_tracer::hit::RayTraceRayHit;
+use ray_tracer::hit::RayTraceShadingContext;
+use ray_tracer::material::RayTraceMaterial;
+use ray_tracer::math_util::triangulate_polygon;
+use ray_tracer::object::RayTraceHitable;
+use ray_tracer::object::RayTraceObject;
+use ray_tracer::object::RayTraceObjectModel;
+use ray_tracer::object::model::RayTraceModelProfile;
+use ray_tracer::object::model::extrude_create;
+use ray_tracer::object::model::lathe_create;
+use ray_tracer::ray::RayTraceRay;
+
+// Writes the texture coordinates into the color of the hit
+struct TexcoordMaterial;
+
+impl RayTraceMaterial for TexcoordMaterial {
+	fn get_hit(&self, _x: f64, _y: f64) -> RayTraceMaterialHit {
+		RayTraceMaterialHit::new(RayTraceColor::black())
+	}
+
+	fn get_hit_context(&self, context: &RayTraceShadingContext) -> RayTraceMaterialHit {
+		let texcoord = context.get_texcoord();
+		RayTraceMaterialHit::new(RayTraceColor::new_with(texcoord[0] as f32, texcoord[1] as f32, 0.0, 1.0))
+	}
+}
+
+// L-shaped polygon with a reflex corner at (1, 1)
+const L_SHAPE: [[f64; 2]; 6] = [[0.0, 0.0], [2.0, 0.0], [2.0, 1.0], [1.0, 1.0], [1.0, 2.0], [0.0, 2.0]];
+
+fn dot(a: &[f64; 3], b: [f64; 3]) -> f64 {
+	a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn trace(model: &RayTraceObjectModel, from: [f64; 3], to: [f64; 3]) -> Option<RayTraceRayHit> {
+	model.next_hit(&RayTraceRay::new_to(from, to))
+}
+
+fn triangle_area(points: &[[f64; 2]], t: &[usize; 3]) -> f64 {
+	let (a, b, c) = (points[t[0]], points[t[1]], points[t[2]]);
+	0.5 * ((b[0] - a[0]) * (c[1] - a[1]) - (b[1] - a[1]) * (c[0] - a[0]))
+}
+
+// Hits on the outside of a closed model which only shows its front faces
+fn assert_outward_hit(model: &RayTraceObjectModel, from: [f64; 3], to: [f64; 3]) -> RayTraceRayHit {
+	let hit = match trace(model, from, to) {
+		Some(hit) => hit,
+		None => { panic!("Missed the model from {:?}", from); }
+	};
+
+	let dir = [to[0] - from[0], to[1] - from[1], to[2] - from[2]];
+	assert!(dot(hit.get_geometric_normal(), dir) < 0.0, "Inward face hit from {:?}", from);
+	assert!(dot(hit.get_surface_normal(), dir) < 0.0, "Inward normal hit from {:?}", from);
+
+	let color = hit.get_surface_material().get_color();
+	for &t in [color.get_r(), color.get_g()].iter() {
+		assert!(t >= -1e-6 && t <= 1.0 + 1e-6, "Texture coordinate {} from {:?}", t, from);
+	}
+	hit
+}
+
+#[test]
+fn lathe_profiles_are_revolved_with_outward_normals() {
+	// Closed cylinder with the radius 1 from -1 to 1
+	let profile = RayTraceModelProfile::Polyline(vec![[0.0, -1.0], [1.0, -1.0], [1.0, 1.0], [0.0, 1.0]]);
+	let mut model = lathe_create(&profile, 32, Box::new(TexcoordMaterial));
+	model.set_back_face_culling(true);
+	model.init(0);
+
+	for i in 0..8 {
+		let angle = i as f64 * 0.8;
+		let (sin, cos) = (angle.sin(), angle.cos());
+		let hit = assert_outward_hit(&model, [3.0 * sin, 0.3, 3.0 * cos], [0.0, 0.3, 0.0]);
+		// The sides are at most half a percent inside of the circle
+		assert!(hit.get_distance() > 2.0 - 1e-9 && hit.get_distance() < 2.01, "Distance {}", hit.get_distance());
+	}
+
+	let hit = assert_outward_hit(&model, [0.2, 5.0, 0.1], [0.2, 0.0, 0.1]);
+	assert!((hit.get_distance() - 4.0).abs() < 1e-9);
+	let hit = assert_outward_hit(&model, [-0.3, -5.0, 0.4], [-0.3, 0.0, 0.4]);
+	assert!((hit.get_distance() - 4.0).abs() < 1e-9);
+
+	// All faces point away from the inside
+	for &to in [[1.0, 0.2, 0.0], [0.0, 1.0, 0.3], [0.2, -1.0, 0.0], [-0.5, 0.5, 0.5]].iter() {
+		assert!(trace(&model, [0.0, 0.0, 0.0], to).is_none(), "Hit from the inside towards {:?}", to);
+	}
+}
+
+#[test]
+fn spline_profiles_are_sampled_through_their_control_points() {
+	let controls = vec![[1.0, 0.0], [2.0, 1.0], [1.0, 2.0], [2.0, 3.0]];
+	let points = RayTraceModelProfile::Spline(controls.clone(), 4).get_points();
+	assert_eq!(points.len(), 13);
+	for (i, control) in controls.iter().enumerate() {
+		let point = points[i * 4];
+		assert!((point[0] - control[0]).abs() < 1e-9 && (point[1] - control[1]).abs() < 1e-9, "{:?}", point);
+	}
+
+	// Evenly spaced points on a line stay on it
+	let line = RayTraceModelProfile::Spline(vec![[1.0, 0.0], [1.0, 1.0], [1.0, 2.0], [1.0, 3.0]], 2).get_points();
+	assert_eq!(line.len(), 7);
+	assert!(line.iter().all(|p| (p[0] - 1.0).abs() < 1e-9));
+	assert!((line[3][1] - 1.5).abs() < 1e-9);
+
+	// The lathe uses the sampled points as its profile
+	let mut model = lathe_create(&RayTraceModelProfile::Spline(controls, 4), 64, Box::new(TexcoordMaterial));
+	model.init(0);
+	let hit = trace(&model, [5.0, 2.0, 0.05], [0.0, 2.0, 0.05]).unwrap();
+	assert!((hit.get_distance() - 4.0).abs() < 0.01, "Distance {}", hit.get_distance());
+}
+
+#[test]
+fn concave_polygons_are_triangulated_without_overlaps() {
+	let triangles = triangulate_polygon(&L_SHAPE);
+	assert_eq!(triangles.len(), L_SHAPE.len() - 2);
+
+	let mut area = 0.0;
+	for t in triangles.iter() {
+		let triangle = triangle_area(&L_SHAPE, t);
+		assert!(triangle > 0.0, "Triangle {:?} is flipped or degenerated", t);
+		area += triangle;
+
+		// Triangles over the reflex corner would have their center outside of the polygon
+		let center = [0, 1].iter().map(|&i| (L_SHAPE[t[0]][i] + L_SHAPE[t[1]][i] + L_SHAPE[t[2]][i]) / 3.0)
+			.collect::<Vec<f64>>();
+		assert!(center[0] < 1.0 || center[1] < 1.0, "Triangle {:?} covers the notch", t);
+	}
+	assert!((area - 3.0).abs() < 1e-9);
+
+	// Clockwise polygons keep their winding
+	let reversed: Vec<[f64; 2]> = L_SHAPE.iter().rev().cloned().collect();
+	let triangles = triangulate_polygon(&reversed);
+	assert_eq!(triangles.len(), 4);
+	assert!(triangles.iter().all(|t| triangle_area(&reversed, t) < 0.0));
+	let area: f64 = triangles.iter().map(|t| triangle_area(&reversed, t)).sum();
+	assert!((area + 3.0).abs() < 1e-9);
+}
+
+#[test]
+fn extrusions_face_outward() {
+	// Clockwise polygons are turned around as well
+	let polygon: Vec<[f64; 2]> = L_SHAPE.iter().rev().cloned().collect();
+	let mut model = extrude_create(&polygon, 1.0, Box::new(TexcoordMaterial));
+	model.set_back_face_culling(true);
+	model.init(0);
+
+	let cases = [
+		([0.5, 0.5, 5.0], [0.5, 0.5, 0.0], [0.0, 0.0, 1.0], 4.5),
+		([1.5, 0.5, -5.0], [1.5, 0.5, 0.0], [0.0, 0.0, -1.0], 4.5),
+		([5.0, 0.5, 0.2], [0.0, 0.5, 0.2], [1.0, 0.0, 0.0], 3.0),
+		([1.5, 1.5, 0.0], [0.0, 1.5, 0.0], [1.0, 0.0, 0.0], 0.5),
+		([1.5, 1.5, 0.0], [1.5, 0.0, 0.0], [0.0, 1.0, 0.0], 0.5),
+		([0.5, -5.0, -0.3], [0.5, 0.0, -0.3], [0.0, -1.0, 0.0], 5.0)
+	];
+	for &(from, to, normal, distance) in cases.iter() {
+		let hit = assert_outward_hit(&model, from, to);
+		assert!((hit.get_distance() - distance).abs() < 1e-9, "Distance {} from {:?}", hit.get_distance(), from);
+		assert!(dot(hit.get_geometric_normal(), normal) > 1.0 - 1e-9, "Normal from {:?}", from);
+	}
+
+	for &to in [[0.5, 0.5, 1.0], [0.5, 0.5, -1.0], [2.0, 0.5, 0.0], [0.5, 2.0, 0.0]].iter() {
+		assert!(trace(&model, [0.5, 0.5, 0.0], to).is_none(), "Hit from the inside towards {:?}", to);
+	}
+}
@@ -0,0 +1,78 @@
+extern crate ray_tracer;
+
+use ray_tracer::color::RayTraceColor;
+use ray_tracer::hit::RayTraceShadingContext;
+use ray_tracer::material::RayTraceColorRamp;
+use ray_tracer::material::RayTraceMaterial;
+use ray_tracer::material::RayTraceNoise;
+use ray_tracer::material::RayTraceNoiseBasis;
+use ray_tracer::material::RayTraceNoiseMaterial;
+use ray_tracer::material::RayTraceNoisePattern;
+use ray_tracer::material::RayTraceNoiseSpace;
+use ray_tracer::ray::RayTraceRay;
+
+fn sample_positions() -> Vec<[f64; 3]> {
+	(0..1000).map(|i| {
+		let i = i as f64;
+		[i * 0.137 - 40.0, (i * 0.731).sin() * 20.0, i * 0.0123]
+	}).collect()
+}
+
+#[test]
+fn noise_is_repeatable_and_bounded() {
+	for &basis in [RayTraceNoiseBasis::Perlin, RayTraceNoiseBasis::Simplex].iter() {
+		let noise = RayTraceNoise::new_with(7, basis);
+		let same = RayTraceNoise::new_with(7, basis);
+		let other = RayTraceNoise::new_with(8, basis);
+
+		let mut differs = false;
+		for p in sample_positions() {
+			let value = noise.get(p);
+			assert!(value.abs() <= 1.1, "{} is out of range at {:?}", value, p);
+			assert_eq!(value, same.get(p));
+			differs |= value != other.get(p);
+		}
+		assert!(differs);
+	}
+
+	assert_eq!(RayTraceNoise::new().perlin([3.0, -2.0, 5.0]), 0.0);
+}
+
+#[test]
+fn cellular_noise_is_ordered() {
+	let noise = RayTraceNoise::new();
+	for p in sample_positions() {
+		let cells = noise.worley(p);
+		assert!(0.0 <= cells[0] && cells[0] <= cells[1], "{:?} at {:?}", cells, p);
+		// Every unit cube has a feature point
+		assert!(cells[0] <= 3.0_f64.sqrt());
+	}
+}
+
+#[test]
+fn ramp_interpolates_colors() {
+	let ramp = RayTraceColorRamp::new(vec![
+		(1.0, RayTraceColor::white()),
+		(0.5, RayTraceColor::black())
+	]);
+
+	assert_eq!(ramp.get_color(0.0).get_r(), 0.0);
+	assert_eq!(ramp.get_color(0.75).get_r(), 0.5);
+	assert_eq!(ramp.get_color(2.0).get_r(), 1.0);
+}
+
+#[test]
+fn object_space_patterns_ignore_texture_coordinates() {
+	let mut material = RayTraceNoiseMaterial::new(RayTraceNoisePattern::Marble);
+	let ray = RayTraceRay::new_to([0.0, 0.0, 1.0], [0.0, 0.0, 0.0]);
+	let red = |material: &RayTraceNoiseMaterial, texcoord| {
+		let context = RayTraceShadingContext::new(&ray, 1.0, [0.3, 1.7, -2.2], [0.0, 0.0, 1.0], texcoord);
+		material.get_hit_context(&context).get_color().get_r()
+	};
+
+	assert_eq!(red(&material, [0.1, 0.2]), red(&material, [0.8, 0.6]));
+
+	material.set_space(RayTraceNoiseSpace::Texture);
+	let a = red(&material, [0.1, 0.2]);
+	assert_eq!(a, material.get_hit(0.1, 0.2).get_color().get_r());
+}
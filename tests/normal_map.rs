@@ -0,0 +1,83 @@
+extern crate ray_tracer;
+
+use ray_tracer::color::RayTraceColor;
+use ray_tracer::material::RayTraceMaterial;
+use ray_tracer::material::RayTraceNormalMapKind;
+use ray_tracer::material::RayTraceNormalMapMaterial;
+use ray_tracer::material::RayTraceSimpleMaterial;
+use ray_tracer::material::RayTraceTexture;
+use ray_tracer::material::RayTraceTextureWrap;
+use ray_tracer::object::RayTraceHitable;
+use ray_tracer::object::RayTraceObject;
+use ray_tracer::object::RayTraceObjectModel;
+use ray_tracer::object::RayTraceObjectPlane;
+use ray_tracer::ray::RayTraceRay;
+use ray_tracer::sink::image::RgbaImage;
+
+fn create_material() -> Box<RayTraceMaterial> {
+	Box::new(RayTraceSimpleMaterial::new(RayTraceColor::white()))
+}
+
+// Hits the plane through the origin, which spans the x- and z-axis, from above
+fn get_plane_normal(material: RayTraceNormalMapMaterial) -> [f64; 3] {
+	let mut plane = RayTraceObjectPlane::new([0.0, 0.0, 0.0], [0.0, 0.0, 0.0], Box::new(material));
+	plane.init(0);
+
+	let hit = plane.next_hit(&RayTraceRay::new_to([0.5, 5.0, 0.5], [0.5, 0.0, 0.5])).unwrap();
+	*hit.get_surface_normal()
+}
+
+#[test]
+fn flat_normal_maps_keep_the_normal() {
+	let image = RgbaImage::from_raw(1, 1, vec![128, 128, 255, 255]).unwrap();
+	let material = RayTraceNormalMapMaterial::new(create_material(), RayTraceTexture::new_linear(image),
+		RayTraceNormalMapKind::Normal);
+
+	let normal = get_plane_normal(material);
+	assert!(normal[0].abs() < 0.01 && normal[2].abs() < 0.01);
+	assert!((normal[1] - 1.0).abs() < 1e-3);
+}
+
+#[test]
+fn normal_maps_tilt_along_the_tangent() {
+	// Tilted by 45 degrees towards the first texture coordinate
+	let image = RgbaImage::from_raw(1, 1, vec![218, 128, 218, 255]).unwrap();
+	let material = RayTraceNormalMapMaterial::new(create_material(), RayTraceTexture::new_linear(image),
+		RayTraceNormalMapKind::Normal);
+
+	let normal = get_plane_normal(material);
+	assert!((normal[0] - normal[1]).abs() < 0.02);
+	assert!(normal[0] > 0.6);
+}
+
+#[test]
+fn bump_maps_tilt_against_the_slope() {
+	// The height grows with the first texture coordinate
+	let image = RgbaImage::from_raw(4, 1, vec![0, 0, 0, 255, 85, 85, 85, 255, 170, 170, 170, 255, 255, 255, 255, 255])
+		.unwrap();
+	let mut map = RayTraceTexture::new_linear(image);
+	map.set_wrap(RayTraceTextureWrap::Clamp);
+
+	let mut material = RayTraceNormalMapMaterial::new(create_material(), map, RayTraceNormalMapKind::Bump);
+	material.set_strength(0.1);
+
+	let normal = get_plane_normal(material);
+	assert!(normal[0] < -0.05);
+	assert!(normal[1] > 0.0);
+}
+
+#[test]
+fn displacement_moves_the_surface_along_the_normals() {
+	// A triangle in the plane of the x- and z-axis facing up
+	let mut model = RayTraceObjectModel::new(create_material(),
+		vec![[0.0, 0.0, 0.0], [0.0, 0.0, 1.0], [1.0, 0.0, 0.0]], Vec::new(),
+		vec![[0.0, 0.0], [0.0, 1.0], [1.0, 0.0]], vec![[[1, 0, 1], [2, 0, 2], [3, 0, 3]]]);
+
+	let image = RgbaImage::from_raw(1, 1, vec![255, 255, 255, 255]).unwrap();
+	model.displace(&RayTraceTexture::new_linear(image), 0.5, 2);
+	model.init(0);
+
+	let hit = model.next_hit(&RayTraceRay::new_to([0.2, 5.0, 0.2], [0.2, 0.0, 0.2])).unwrap();
+	assert!((hit.get_distance() - 4.5).abs() < 1e-6);
+	assert!((hit.get_surface_normal()[1] - 1.0).abs() < 1e-6);
+}
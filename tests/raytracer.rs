@@ -0,0 +1,63 @@
+use std::env;
+use std::fs::File;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::Command;
+
+const SCENE: &'static str = r#"{
+	"output": { "width": 8, "height": 6, "frames": 2 },
+	"camera": { "type": "perspective" }
+}"#;
+
+// The binary is built next to the directory of the test executables
+fn raytracer() -> Command {
+	let mut path = env::current_exe().unwrap();
+	path.pop();
+	if path.ends_with("deps") {
+		path.pop();
+	}
+	Command::new(path.join(format!("raytracer{}", env::consts::EXE_SUFFIX)))
+}
+
+fn write_scene(name: &str, text: &str) -> PathBuf {
+	let path = env::temp_dir().join(name);
+	let mut file = File::create(&path).unwrap();
+	file.write_all(text.as_bytes()).unwrap();
+	path
+}
+
+fn exit_code(command: &mut Command) -> i32 {
+	command.arg("--quiet").output().unwrap().status.code().unwrap()
+}
+
+#[test]
+fn invalid_arguments_are_usage_errors() {
+	assert_eq!(exit_code(raytracer().arg("--unknown")), 1);
+	assert_eq!(exit_code(&mut raytracer()), 1);
+
+	let scene = write_scene("ray_tracer_cli_frames.json", SCENE);
+	let output = env::temp_dir().join("ray_tracer_cli_frames.png");
+	assert_eq!(exit_code(raytracer().arg(&scene).arg("-o").arg(&output).args(&["--frames", "5:6"])), 1);
+}
+
+#[test]
+fn invalid_scenes_are_scene_errors() {
+	let scene = write_scene("ray_tracer_cli_syntax.json", "{ \"output\": ");
+	assert_eq!(exit_code(raytracer().arg(&scene)), 2);
+
+	let scene = write_scene("ray_tracer_cli_semantic.json", r#"{ "output": { "width": 8, "height": 6 } }"#);
+	assert_eq!(exit_code(raytracer().arg(&scene)), 2);
+}
+
+#[test]
+fn unreadable_files_are_io_errors() {
+	let scene = env::temp_dir().join("ray_tracer_cli_missing.json");
+	assert_eq!(exit_code(raytracer().arg(&scene)), 3);
+
+	let scene = write_scene("ray_tracer_cli_model.json", r#"{
+	"output": { "width": 8, "height": 6 },
+	"camera": { "type": "perspective" },
+	"objects": [ { "type": "obj", "file": "ray_tracer_cli_missing.obj" } ]
+}"#);
+	assert_eq!(exit_code(raytracer().arg(&scene)), 3);
+}
@@ -0,0 +1,112 @@
+extern crate ray_tracer;
+
+use ray_tracer::anim::RayTraceAnimVec3Linear;
+use ray_tracer::color::RayTraceColor;
+use ray_tracer::light::lights::RayTraceSpotLight;
+use ray_tracer::material::RayTraceSimpleMaterial;
+use ray_tracer::math_util::RayTraceTransformable;
+use ray_tracer::object::RayTraceObjectCube;
+use ray_tracer::object::RayTraceObjectSphere;
+use ray_tracer::scene::RayTraceScene;
+
+fn sphere(x: f64) -> Box<RayTraceObjectSphere> {
+	let material = Box::new(RayTraceSimpleMaterial::new(RayTraceColor::red()));
+	Box::new(RayTraceObjectSphere::new([x, 0.0, 0.0], 1.0, material))
+}
+
+#[test]
+fn objects_are_found_by_name_and_tag() {
+	let mut scene = RayTraceScene::new();
+	let first = scene.add_named_object("first", sphere(0.0));
+	let second = scene.add_object(sphere(2.0));
+	scene.add_object_tag(&first, "spheres");
+	scene.add_object_tag(&second, "spheres");
+
+	let found = scene.find_object::<RayTraceObjectSphere>("first");
+	assert_eq!(found.map(|h| h.get_index()), Some(first.get_index()));
+	assert!(scene.find_object::<RayTraceObjectCube>("first").is_none());
+	assert!(scene.find_object::<RayTraceObjectSphere>("second").is_none());
+	assert_eq!(scene.find_objects_by_tag::<RayTraceObjectSphere>("spheres").len(), 2);
+
+	scene.remove_object_tag(&first, "spheres");
+	assert_eq!(scene.find_objects_by_tag::<RayTraceObjectSphere>("spheres").len(), 1);
+}
+
+#[test]
+fn removed_and_hidden_objects_are_not_rendered() {
+	let mut scene = RayTraceScene::new();
+	let first = scene.add_object(sphere(0.0));
+	let second = scene.add_object(sphere(2.0));
+	let third = scene.add_object(sphere(4.0));
+	scene.init(0);
+
+	scene.remove_object(first);
+	scene.set_object_visible(&third, false);
+
+	assert!(!scene.contains_object(&first));
+	assert!(scene.contains_object(&second));
+	assert_eq!(scene.get_objects().count(), 1);
+
+	// Queries list hidden objects as well
+	let infos = scene.query_objects();
+	assert_eq!(infos.len(), 2);
+	assert!(infos.iter().all(|i| i.get_aabb().is_some()));
+	assert!(!infos.iter().find(|i| i.get_index() == third.get_index()).unwrap().is_visible());
+}
+
+#[test]
+fn replaced_objects_keep_their_entry() {
+	let mut scene = RayTraceScene::new();
+	let handle = scene.add_named_object("ball", sphere(0.0));
+	scene.add_object_tag(&handle, "movable");
+	scene.init(0);
+
+	let (handle, old) = scene.replace_object(handle, sphere(5.0));
+	assert_eq!(*old.get_transform().get_translation(), [0.0, 0.0, 0.0]);
+
+	// The new object is initialized for the current frame
+	let aabb = scene.query_objects()[0].get_aabb().unwrap().clone();
+	assert!(aabb.contains([5.0, 0.0, 0.0]));
+	assert_eq!(scene.get_object_name(&handle), Some("ball"));
+	assert_eq!(scene.get_object_tags(&handle), &vec!["movable".to_string()]);
+}
+
+#[test]
+fn lights_can_be_switched_off() {
+	let mut scene = RayTraceScene::new();
+	let light = Box::new(RayTraceSpotLight::new([0.0, 5.0, 0.0], RayTraceColor::white()));
+	let light = scene.add_named_light("key", light);
+	scene.init(0);
+
+	assert_eq!(scene.get_lights().count(), 1);
+	scene.set_light_visible(&light, false);
+	assert_eq!(scene.get_lights().count(), 0);
+	assert_eq!(scene.query_lights()[0].get_name(), Some("key"));
+}
+
+#[test]
+#[allow(deprecated)]
+fn deprecated_setters_change_the_transform() {
+	let mut object = sphere(1.0);
+	assert_eq!(object.get_position(), &[1.0, 0.0, 0.0]);
+
+	object.set_position([0.0, 2.0, 0.0]);
+	object.set_rotation([0.0, 90.0, 0.0]);
+	assert_eq!(object.get_transform().get_translation(), &[0.0, 2.0, 0.0]);
+	assert_eq!(object.get_transform().get_rotation(), &[0.0, 90.0, 0.0]);
+}
+
+#[test]
+#[allow(deprecated)]
+fn deprecated_animation_setters_animate_the_transform() {
+	let mut object = sphere(1.0);
+	object.set_anim_pos(Box::new(RayTraceAnimVec3Linear::new([0.0, 0.0, 0.0], [0.5, 0.0, 0.0])));
+	object.set_anim_rot_opt(Some(Box::new(RayTraceAnimVec3Linear::new([0.0, 0.0, 0.0], [0.0, 10.0, 0.0]))));
+	object.get_transform_mut().init(4);
+	assert_eq!(object.get_transform().get_translation(), &[2.0, 0.0, 0.0]);
+	assert_eq!(object.get_transform().get_rotation(), &[0.0, 40.0, 0.0]);
+
+	object.set_anim_pos_opt(None);
+	object.get_transform_mut().init(8);
+	assert_eq!(object.get_transform().get_translation(), &[2.0, 0.0, 0.0]);
+}
@@ -0,0 +1,247 @@
+extern crate ray_tracer;
+
+use std::env;
+use std::f32;
+use std::f64;
+use std::fs;
+use std::fs::File;
+use std::io::Write;
+
+use ray_tracer::anim::RayTraceAnimFunc;
+use ray_tracer::camera::RayTracerCameraPerspective;
+use ray_tracer::color::RayTraceColor;
+use ray_tracer::hit::RayTraceMaterialHit;
+use ray_tracer::material::RayTraceMaterial;
+use ray_tracer::material::RayTraceSimpleMaterial;
+use ray_tracer::math_util::RayTraceTransformable;
+use ray_tracer::object::RayTraceObjectCube;
+use ray_tracer::object::RayTraceObjectModel;
+use ray_tracer::object::RayTraceObjectSphere;
+use ray_tracer::params::RayTraceOutputParams;
+use ray_tracer::params::RayTraceParams;
+use ray_tracer::scene::RayTraceScene;
+use ray_tracer::scene_file::RayTraceSceneAssets;
+use ray_tracer::scene_file::RayTraceSceneFileErrorKind;
+use ray_tracer::scene_file::RayTraceSceneFileOptions;
+use ray_tracer::scene_file::scene_file_load;
+use ray_tracer::scene_file::scene_file_load_cached;
+use ray_tracer::scene_file::scene_file_save;
+use ray_tracer::sink::image::RgbaImage;
+use ray_tracer::source::RayTraceSource;
+
+// Material which can not be written to a scene file
+struct GradientMaterial;
+
+impl RayTraceMaterial for GradientMaterial {
+	fn get_hit(&self, x: f64, y: f64) -> RayTraceMaterialHit {
+		RayTraceMaterialHit::new(RayTraceColor::new_with(x as f32, y as f32, 0.0, 1.0))
+	}
+}
+
+fn write_scene(name: &str, text: &str) -> String {
+	let path = env::temp_dir().join(name);
+	let mut file = File::create(&path).unwrap();
+	file.write_all(text.as_bytes()).unwrap();
+	path.to_string_lossy().into_owned()
+}
+
+#[test]
+fn scene_is_loaded() {
+	let file_name = write_scene("ray_tracer_scene_file_valid.json", r#"{
+	"output": { "width": 64, "height": 48, "frames": 10 },
+	"params": { "max_depth": 2, "reflection_samples": 8, "filter": { "type": "box", "size": 1.0 } },
+	"camera": { "type": "perspective", "transform": { "translation": [0, 0, 5] } },
+	"materials": { "red": { "type": "simple", "color": [1, 0, 0] } },
+	"objects": [
+		{ "type": "sphere", "name": "ball", "material": "red", "tags": ["round"],
+			"transform": { "animation": {
+				"translation": { "type": "linear", "initial": [0, 0, 0], "delta": [0.1, 0, 0] } } } },
+		{ "type": "cube", "material": { "type": "pbr", "metallic": 1 }, "visible": false }
+	],
+	"lights": [ { "type": "spot", "position": [0, 5, 0], "color": [1, 1, 1, 1] } ]
+}"#);
+
+	let mut scene_file = scene_file_load(&file_name).unwrap();
+	assert!(!scene_file.has_sink());
+
+	let source = scene_file.get_source_mut().get();
+	assert_eq!(source.out_params.get_frames(), 10);
+	assert_eq!(source.params.get_reflection_samples(), 8);
+	assert!(source.scene.find_object::<RayTraceObjectSphere>("ball").is_some());
+	assert_eq!(source.scene.find_objects_by_tag::<RayTraceObjectSphere>("round").len(), 1);
+	assert_eq!(source.scene.query_objects().len(), 2);
+	assert_eq!(source.scene.get_objects().count(), 1);
+	assert_eq!(source.scene.query_lights().len(), 1);
+}
+
+#[test]
+fn unknown_types_are_reported_with_position() {
+	let file_name = write_scene("ray_tracer_scene_file_type.json", r#"{
+	"output": { "width": 64, "height": 48 },
+	"camera": { "type": "perspective" },
+	"objects": [
+		{ "type": "torus" }
+	]
+}"#);
+
+	let err = scene_file_load(&file_name).err().expect("Loading should fail");
+	assert_eq!((err.get_line(), err.get_column()), (5, 13));
+	assert_eq!(err.get_kind(), RayTraceSceneFileErrorKind::Semantic);
+	assert!(err.get_message().contains("torus"));
+	assert!(format!("{}", err).starts_with(&format!("{}:5:13:", file_name)));
+}
+
+#[test]
+fn bad_values_are_reported_with_position() {
+	let file_name = write_scene("ray_tracer_scene_file_value.json", r#"{
+	"output": { "width": 64, "height": 48 },
+	"camera": { "type": "orthographic", "scale": "large" }
+}"#);
+
+	let err = scene_file_load(&file_name).err().expect("Loading should fail");
+	assert_eq!((err.get_line(), err.get_column()), (3, 47));
+
+	let file_name = write_scene("ray_tracer_scene_file_key.json", r#"{
+	"output": { "width": 64, "height": 48, "depth": 3 },
+	"camera": { "type": "orthographic" }
+}"#);
+
+	let err = scene_file_load(&file_name).err().expect("Loading should fail");
+	assert_eq!(err.get_line(), 2);
+	assert!(err.get_message().contains("depth"));
+}
+
+#[test]
+fn noise_settings_are_validated() {
+	let settings = [("seed", "4294967296"), ("seed", "-1"), ("octaves", "0.5"), ("octaves", "1000")];
+	for (i, &(key, value)) in settings.iter().enumerate() {
+		let file_name = write_scene(&format!("ray_tracer_scene_file_noise{}.json", i), &format!(r#"{{
+	"output": {{ "width": 64, "height": 48 }},
+	"camera": {{ "type": "perspective" }},
+	"objects": [ {{ "type": "sphere", "material": {{ "type": "noise", "{}": {} }} }} ]
+}}"#, key, value));
+
+		let err = scene_file_load(&file_name).err().expect("Loading should fail");
+		assert_eq!(err.get_line(), 4, "{} {}", key, value);
+		assert_eq!(err.get_kind(), RayTraceSceneFileErrorKind::Semantic);
+	}
+}
+
+fn create_source(scene: RayTraceScene) -> RayTraceSource {
+	let out_params = RayTraceOutputParams::new(32, 24, 4);
+	let camera = RayTracerCameraPerspective::new(&out_params, 1.0, 1.0);
+	RayTraceSource::new(scene, Box::new(camera), out_params, RayTraceParams::new())
+}
+
+#[test]
+fn saved_scene_is_loaded_with_baked_animations() {
+	let mut scene = RayTraceScene::new();
+	let mut sphere = RayTraceObjectSphere::new([0.0, 0.0, 0.0], 1.0,
+		Box::new(RayTraceSimpleMaterial::new(RayTraceColor::red())));
+	sphere.get_transform_mut().set_anim_translation(Box::new(RayTraceAnimFunc::new(
+		Box::new(|frame| [frame as f64, 0.0, 0.0]))));
+	let handle = scene.add_named_object("ball", Box::new(sphere));
+	scene.add_object_tag(&handle, "round");
+
+	let cube = scene.add_object(Box::new(RayTraceObjectCube::new([0.0, 2.0, 0.0], [1.0, 1.0, 1.0],
+		Box::new(RayTraceSimpleMaterial::new(RayTraceColor::blue())))));
+	scene.set_object_visible(&cube, false);
+
+	let file_name = env::temp_dir().join("ray_tracer_scene_file_saved.json").to_string_lossy().into_owned();
+	scene_file_save(&file_name, &mut create_source(scene)).unwrap();
+
+	let mut scene_file = scene_file_load(&file_name).unwrap();
+	let source = scene_file.get_source_mut().get();
+	assert_eq!(source.out_params.get_frames(), 4);
+	assert_eq!(source.scene.query_objects().len(), 2);
+	assert_eq!(source.scene.get_objects().count(), 1);
+
+	let ball = source.scene.find_object::<RayTraceObjectSphere>("ball").expect("The sphere should be loaded");
+	assert_eq!(source.scene.get_object_tags(&ball), &vec!["round".to_string()]);
+
+	let anim = source.scene.get_object(&ball).get_transform().get_anim_translation().unwrap();
+	assert_eq!(anim.next_frame(3), [3.0, 0.0, 0.0]);
+}
+
+#[test]
+fn unsupported_types_are_reported_with_path() {
+	let mut scene = RayTraceScene::new();
+	scene.add_object(Box::new(RayTraceObjectSphere::new([0.0, 0.0, 0.0], 1.0,
+		Box::new(RayTraceSimpleMaterial::new(RayTraceColor::red())))));
+	scene.add_object(Box::new(RayTraceObjectSphere::new([0.0, 0.0, 0.0], 1.0, Box::new(GradientMaterial))));
+
+	let file_name = env::temp_dir().join("ray_tracer_scene_file_unsupported.json").to_string_lossy().into_owned();
+	let err = scene_file_save(&file_name, &mut create_source(scene)).err().expect("Saving should fail");
+	assert_eq!(err.get_path(), "objects[1].material");
+	assert!(err.get_message().contains("material"));
+}
+
+#[test]
+fn non_finite_numbers_are_reported_with_path() {
+	let mut scene = RayTraceScene::new();
+	scene.add_object(Box::new(RayTraceObjectSphere::new([0.0, 0.0, 0.0], f64::NAN,
+		Box::new(RayTraceSimpleMaterial::new(RayTraceColor::red())))));
+
+	let file_name = env::temp_dir().join("ray_tracer_scene_file_nan.json").to_string_lossy().into_owned();
+	let err = scene_file_save(&file_name, &mut create_source(scene)).err().expect("Saving should fail");
+	assert_eq!(err.get_path(), "objects[0].size");
+
+	let mut material = RayTraceSimpleMaterial::new(RayTraceColor::red());
+	material.set_emission(RayTraceColor::new_with(f32::INFINITY, 0.0, 0.0, 1.0));
+	let mut scene = RayTraceScene::new();
+	scene.add_object(Box::new(RayTraceObjectSphere::new([0.0, 0.0, 0.0], 1.0, Box::new(material))));
+
+	let err = scene_file_save(&file_name, &mut create_source(scene)).err().expect("Saving should fail");
+	assert_eq!(err.get_path(), "objects[0].material.emission");
+}
+
+#[test]
+fn assets_know_the_files_of_the_scene() {
+	let file_name = write_scene("ray_tracer_scene_file_watched.json", r#"{
+	"output": { "width": 64, "height": 48 },
+	"camera": { "type": "perspective" },
+	"objects": [ { "type": "sphere" } ]
+}"#);
+
+	let mut assets = RayTraceSceneAssets::new();
+	scene_file_load_cached(&file_name, &RayTraceSceneFileOptions::new(), &mut assets).unwrap();
+	assert_eq!(assets.get_files(), vec![&file_name[..]]);
+	assert!(!assets.has_changes());
+
+	fs::remove_file(&file_name).unwrap();
+	assert!(assets.has_changes());
+	assert_eq!(assets.get_changed_files(), vec![&file_name[..]]);
+}
+
+#[test]
+fn reused_models_keep_their_materials() {
+	let directory = env::temp_dir();
+	let texture = directory.join("ray_tracer_scene_file_reused.png").to_string_lossy().into_owned();
+	RgbaImage::from_raw(1, 1, vec![255, 0, 0, 255]).unwrap().save(&texture).unwrap();
+	let library = write_scene("ray_tracer_scene_file_reused.mtl",
+		"newmtl red\nKd 1 0 0\nmap_Kd -s 1 1 1 ray_tracer_scene_file_reused.png\n");
+	let model = write_scene("ray_tracer_scene_file_reused.obj",
+		"mtllib ray_tracer_scene_file_reused.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\nusemtl red\nf 1 2 3\n");
+	let file_name = write_scene("ray_tracer_scene_file_reused.json", r#"{
+	"output": { "width": 64, "height": 48 },
+	"camera": { "type": "perspective" },
+	"objects": [ { "type": "obj", "name": "triangle", "file": "ray_tracer_scene_file_reused.obj" } ]
+}"#);
+
+	let mut assets = RayTraceSceneAssets::new();
+	for _ in 0..2 {
+		let mut scene_file = scene_file_load_cached(&file_name, &RayTraceSceneFileOptions::new(), &mut assets)
+			.unwrap();
+		let source = scene_file.get_source_mut().get();
+		let handle = source.scene.find_object::<RayTraceObjectModel>("triangle").unwrap();
+		let model = source.scene.get_object(&handle);
+		assert_eq!(model.get_materials().len(), 1);
+		assert_eq!(model.get_face_materials(), &vec![1]);
+	}
+
+	// The material library and its textures are watched as well
+	let files = assets.get_files();
+	for file in [&file_name, &model, &library, &texture].iter() {
+		assert!(files.contains(&&file[..]), "{} is not watched", file);
+	}
+}
@@ -0,0 +1,54 @@
+extern crate ray_tracer;
+
+use ray_tracer::color::RayTraceColor;
+use ray_tracer::hit::RayTraceMaterialHit;
+use ray_tracer::hit::RayTraceShadingContext;
+use ray_tracer::material::RayTraceMaterial;
+use ray_tracer::object::RayTraceHitable;
+use ray_tracer::object::RayTraceObject;
+use ray_tracer::object::RayTraceObjectCube;
+use ray_tracer::object::RayTraceObjectSphere;
+use ray_tracer::ray::RayTraceRay;
+
+// Writes parts of the shading context into the color of the hit
+struct ContextMaterial;
+
+impl RayTraceMaterial for ContextMaterial {
+	fn get_hit(&self, _x: f64, _y: f64) -> RayTraceMaterialHit {
+		RayTraceMaterialHit::new(RayTraceColor::black())
+	}
+
+	fn get_hit_context(&self, context: &RayTraceShadingContext) -> RayTraceMaterialHit {
+		let view = context.get_view_direction();
+		let normal = context.get_shading_normal();
+		let facing = view[0] * normal[0] + view[1] * normal[1] + view[2] * normal[2];
+
+		RayTraceMaterialHit::new(RayTraceColor::new_with(context.get_object_position()[2] as f32,
+			context.get_tangent()[0] as f32, context.get_primitive() as f32, facing as f32))
+	}
+}
+
+#[test]
+fn materials_receive_the_surface_of_spheres() {
+	let mut sphere = RayTraceObjectSphere::new([0.0, 3.0, 0.0], 2.0, Box::new(ContextMaterial));
+	sphere.init(0);
+
+	let hit = sphere.next_hit(&RayTraceRay::new_to([0.0, 3.0, 5.0], [0.0, 3.0, 0.0])).unwrap();
+	let color = hit.get_surface_material().get_color();
+	assert!((color.get_r() - 1.0).abs() < 1e-6);
+	// The first texture coordinate grows around the y-axis
+	assert!((color.get_g() + 1.0).abs() < 1e-6);
+	assert!((color.get_a() - 1.0).abs() < 1e-6);
+}
+
+#[test]
+fn materials_receive_the_side_of_cubes() {
+	let mut cube = RayTraceObjectCube::new([0.0, 0.0, 0.0], [2.0, 2.0, 2.0], Box::new(ContextMaterial));
+	cube.init(0);
+
+	let hit = cube.next_hit(&RayTraceRay::new_to([5.0, 0.0, 0.0], [0.0, 0.0, 0.0])).unwrap();
+	assert_eq!(hit.get_surface_material().get_color().get_b(), 0.0);
+
+	let hit = cube.next_hit(&RayTraceRay::new_to([0.0, -5.0, 0.0], [0.0, 0.0, 0.0])).unwrap();
+	assert_eq!(hit.get_surface_material().get_color().get_b(), 3.0);
+}
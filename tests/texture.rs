@@ -0,0 +1,100 @@
+extern crate ray_tracer;
+
+use ray_tracer::hit::RayTraceShadingContext;
+use ray_tracer::material::RayTraceCheckerboardMaterial;
+use ray_tracer::material::RayTraceFootprint;
+use ray_tracer::material::RayTraceMaterial;
+use ray_tracer::material::RayTraceTexture;
+use ray_tracer::material::RayTraceTextureFilter;
+use ray_tracer::material::RayTraceTextureMipmap;
+use ray_tracer::material::RayTraceTextureWrap;
+use ray_tracer::ray::RayTraceRay;
+use ray_tracer::sink::image::RgbaImage;
+
+// Two texels in a row, black on the left and white on the right
+fn create_texture(srgb: bool) -> RayTraceTexture {
+	let image = RgbaImage::from_raw(2, 1, vec![0, 0, 0, 255, 255, 255, 255, 255]).unwrap();
+	RayTraceTexture::new_with(image, srgb)
+}
+
+fn assert_near(value: f32, expected: f32) {
+	assert!((value - expected).abs() < 1e-4, "{} is not {}", value, expected);
+}
+
+fn get_red(material: &RayTraceMaterial, x: f64, y: f64, footprint: Option<RayTraceFootprint>) -> f32 {
+	let ray = RayTraceRay::new_to([0.0, 0.0, 1.0], [0.0, 0.0, 0.0]);
+	let mut context = RayTraceShadingContext::new(&ray, 1.0, [0.0, 0.0, 0.0], [0.0, 0.0, 1.0], [x, y]);
+	context.set_footprint(footprint);
+	material.get_hit_context(&context).get_color().get_r()
+}
+
+#[test]
+fn texels_are_wrapped() {
+	let mut texture = create_texture(false);
+	texture.set_filter(RayTraceTextureFilter::Nearest);
+
+	assert_near(texture.sample(0.25, 0.5).get_r(), 0.0);
+	assert_near(texture.sample(0.75, 0.5).get_r(), 1.0);
+	assert_near(texture.sample(1.25, 0.5).get_r(), 0.0);
+
+	texture.set_wrap(RayTraceTextureWrap::Clamp);
+	assert_near(texture.sample(1.25, 0.5).get_r(), 1.0);
+	assert_near(texture.sample(-0.75, 0.5).get_r(), 0.0);
+
+	texture.set_wrap(RayTraceTextureWrap::Mirror);
+	assert_near(texture.sample(1.25, 0.5).get_r(), 1.0);
+	assert_near(texture.sample(1.75, 0.5).get_r(), 0.0);
+}
+
+#[test]
+fn texels_are_interpolated() {
+	let mut texture = create_texture(false);
+	texture.set_wrap(RayTraceTextureWrap::Clamp);
+	assert_near(texture.sample(0.5, 0.5).get_r(), 0.5);
+
+	texture.set_filter(RayTraceTextureFilter::Bicubic);
+	assert_near(texture.sample(0.5, 0.5).get_r(), 0.5);
+	assert_near(texture.sample(0.75, 0.5).get_r(), 1.0);
+
+	texture.set_scale([0.5, 1.0]);
+	texture.set_offset([0.5, 0.0]);
+	assert_near(texture.sample(0.5, 0.5).get_r(), 1.0);
+}
+
+#[test]
+fn srgb_textures_are_linear() {
+	let image = RgbaImage::from_raw(1, 1, vec![128, 255, 0, 128]).unwrap();
+	let color = RayTraceTexture::new(image).sample(0.5, 0.5);
+
+	assert_near(color.get_r(), 0.21586);
+	assert_near(color.get_g(), 1.0);
+	assert_near(color.get_b(), 0.0);
+	assert_near(color.get_a(), 128.0 / 255.0);
+}
+
+#[test]
+fn minified_textures_are_averaged() {
+	let mut texture = create_texture(false);
+	texture.set_filter(RayTraceTextureFilter::Nearest);
+	assert_eq!(texture.get_level_count(), 2);
+
+	// The footprint covers the whole texture
+	let footprint = RayTraceFootprint::new([1.0, 0.0], [0.0, 1.0]);
+	assert_near(texture.sample_filtered(0.25, 0.5, Some(&footprint)).get_r(), 0.5);
+	assert_near(texture.sample_filtered(0.25, 0.5, None).get_r(), 0.0);
+
+	texture.set_mipmap(RayTraceTextureMipmap::None);
+	assert_near(texture.sample_filtered(0.25, 0.5, Some(&footprint)).get_r(), 0.0);
+}
+
+#[test]
+fn checkerboard_is_filtered_to_grey() {
+	let material = RayTraceCheckerboardMaterial::new();
+	assert_near(get_red(&material, 0.5, 0.5, None), 0.0);
+	assert_near(get_red(&material, 0.5, 0.5, Some(RayTraceFootprint::new([4.0, 0.0], [0.0, 4.0]))), 0.5);
+
+	// Half of the pixel is on the next square
+	let footprint = RayTraceFootprint::new([0.5, 0.0], [0.0, 0.5]);
+	assert_near(get_red(&material, 1.0, 0.5, Some(footprint.clone())), 0.5);
+	assert_near(get_red(&material, 1.25, 0.5, Some(footprint)), 1.0);
+}
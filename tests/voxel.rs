@@ -0,0 +1,208 @@
+extern crate ray_tracer;
+
+use std::env;
+use std::fs::File;
+use std::io::Write;
+
+use ray_tracer::color::RayTraceColor;
+use ray_tracer::hit::RayTraceMaterialHit;
+use ray_tracer::hit::RayTraceRayHit;
+use ray_tracer::hit::RayTraceShadingContext;
+use ray_tracer::material::RayTraceMaterial;
+use ray_tracer::object::RayTraceHitable;
+use ray_tracer::object::RayTraceObject;
+use ray_tracer::object::RayTraceObjectVoxelGrid;
+use ray_tracer::object::voxel::vox_load;
+use ray_tracer::ray::RayTraceRay;
+
+// Writes the texture coordinates and an id of the palette entry into the color of the hit
+struct FaceMaterial(f32);
+
+impl RayTraceMaterial for FaceMaterial {
+	fn get_hit(&self, _x: f64, _y: f64) -> RayTraceMaterialHit {
+		RayTraceMaterialHit::new(RayTraceColor::black())
+	}
+
+	fn get_hit_context(&self, context: &RayTraceShadingContext) -> RayTraceMaterialHit {
+		let texcoord = context.get_texcoord();
+		RayTraceMaterialHit::new(RayTraceColor::new_with(texcoord[0] as f32, texcoord[1] as f32, self.0, 1.0))
+	}
+}
+
+// Grid of 4x4x4 unit voxels around the origin with two filled voxels next to each other
+fn create_grid(sparse: bool) -> RayTraceObjectVoxelGrid {
+	let palette: Vec<Box<RayTraceMaterial>> = vec![Box::new(FaceMaterial(1.0)), Box::new(FaceMaterial(2.0))];
+	let mut grid = if sparse {
+		RayTraceObjectVoxelGrid::new_sparse([4, 4, 4], [1.0, 1.0, 1.0], palette)
+	} else {
+		RayTraceObjectVoxelGrid::new([4, 4, 4], [1.0, 1.0, 1.0], palette)
+	};
+	grid.set_voxel(1, 1, 1, 1);
+	grid.set_voxel(2, 1, 1, 2);
+	grid.init(0);
+	grid
+}
+
+fn trace(grid: &RayTraceObjectVoxelGrid, from: [f64; 3], to: [f64; 3]) -> Option<RayTraceRayHit> {
+	grid.next_hit(&RayTraceRay::new_to(from, to))
+}
+
+fn assert_close(actual: &[f64; 3], expected: [f64; 3]) {
+	for i in 0..3 {
+		assert!((actual[i] - expected[i]).abs() < 1e-9, "{:?} != {:?}", actual, expected);
+	}
+}
+
+fn push_u32(content: &mut Vec<u8>, value: u32) {
+	content.extend((0..4).map(|i| (value >> (i * 8)) as u8));
+}
+
+fn vox_chunk(id: &[u8], content: &[u8]) -> Vec<u8> {
+	let mut chunk = id.to_vec();
+	push_u32(&mut chunk, content.len() as u32);
+	push_u32(&mut chunk, 0);
+	chunk.extend(content.iter());
+	chunk
+}
+
+// File with a MAIN chunk containing the given chunks
+fn vox_file(name: &str, chunks: &[Vec<u8>]) -> String {
+	let children: Vec<u8> = chunks.iter().flat_map(|c| c.iter().cloned()).collect();
+	let mut content = b"VOX ".to_vec();
+	push_u32(&mut content, 150);
+	content.extend(b"MAIN".iter());
+	push_u32(&mut content, 0);
+	push_u32(&mut content, children.len() as u32);
+	content.extend(children);
+
+	let path = env::temp_dir().join(name);
+	let mut file = File::create(&path).unwrap();
+	file.write_all(&content).unwrap();
+	path.to_string_lossy().into_owned()
+}
+
+fn vox_size(x: u32, y: u32, z: u32) -> Vec<u8> {
+	let mut content = Vec::new();
+	for &value in [x, y, z].iter() {
+		push_u32(&mut content, value);
+	}
+	vox_chunk(b"SIZE", &content)
+}
+
+fn vox_voxels(count: u32, voxels: &[[u8; 4]]) -> Vec<u8> {
+	let mut content = Vec::new();
+	push_u32(&mut content, count);
+	for voxel in voxels.iter() {
+		content.extend(voxel.iter());
+	}
+	vox_chunk(b"XYZI", &content)
+}
+
+#[test]
+fn rays_hit_the_faces_of_filled_voxels() {
+	let grid = create_grid(false);
+
+	// Top face of the first voxel which spans from -1 to 0 in x and y
+	let hit = trace(&grid, [-0.75, -0.6, 5.0], [-0.75, -0.6, 0.0]).unwrap();
+	assert!((hit.get_distance() - 5.0).abs() < 1e-9);
+	assert_close(hit.get_surface_normal(), [0.0, 0.0, 1.0]);
+	let color = hit.get_surface_material().get_color();
+	assert!((color.get_r() - 0.25).abs() < 1e-6 && (color.get_g() - 0.4).abs() < 1e-6);
+	assert_eq!(color.get_b(), 1.0);
+
+	// Rays along the x axis pass the empty voxels and stop at the first filled one
+	let hit = trace(&grid, [-5.0, -0.6, -0.5], [0.0, -0.6, -0.5]).unwrap();
+	assert!((hit.get_distance() - 4.0).abs() < 1e-9);
+	assert_close(hit.get_surface_normal(), [-1.0, 0.0, 0.0]);
+	let color = hit.get_surface_material().get_color();
+	assert!((color.get_r() - 0.5).abs() < 1e-6 && (color.get_g() - 0.4).abs() < 1e-6);
+	assert_eq!(color.get_b(), 1.0);
+
+	let hit = trace(&grid, [5.0, -0.6, -0.5], [0.0, -0.6, -0.5]).unwrap();
+	assert!((hit.get_distance() - 4.0).abs() < 1e-9);
+	assert_close(hit.get_surface_normal(), [1.0, 0.0, 0.0]);
+	assert_eq!(hit.get_surface_material().get_color().get_b(), 2.0);
+
+	assert!(trace(&grid, [1.5, -0.6, 5.0], [1.5, -0.6, 0.0]).is_none());
+	assert!(trace(&grid, [-0.5, 1.5, 5.0], [-0.5, 1.5, 0.0]).is_none());
+}
+
+#[test]
+fn dense_and_sparse_grids_are_hit_the_same() {
+	let dense = create_grid(false);
+	let mut sparse = create_grid(true);
+
+	let steps: Vec<f64> = (0..9).map(|i| -2.25 + i as f64 * 0.5).collect();
+	for &a in steps.iter() {
+		for &b in steps.iter() {
+			let rays = [([a, b, 5.0], [a, b, 0.0]), ([5.0, a, b], [0.0, a, b]), ([a, -5.0, b], [a, 0.0, b])];
+			for &(from, to) in rays.iter() {
+				let expected = trace(&dense, from, to).map(|h| (h.get_distance(), *h.get_surface_normal()));
+				let actual = trace(&sparse, from, to).map(|h| (h.get_distance(), *h.get_surface_normal()));
+				assert_eq!(actual, expected, "Ray from {:?}", from);
+			}
+		}
+	}
+
+	// Emptied voxels are removed from the sparse storage
+	sparse.set_voxel(1, 1, 1, 0);
+	sparse.init(0);
+	assert_eq!(sparse.get_voxel(1, 1, 1), 0);
+	let hit = trace(&sparse, [-5.0, -0.6, -0.5], [0.0, -0.6, -0.5]).unwrap();
+	assert!((hit.get_distance() - 5.0).abs() < 1e-9);
+	assert!(trace(&sparse, [-0.75, -0.6, 5.0], [-0.75, -0.6, 0.0]).is_none());
+}
+
+#[test]
+fn vox_files_are_converted_to_y_up() {
+	let mut colors = Vec::new();
+	for i in 0..256 {
+		colors.extend(match i {
+			0 => [255u8, 0, 0, 255],
+			1 => [0, 255, 0, 255],
+			_ => [0, 0, 0, 255]
+		}.iter());
+	}
+	let file_name = vox_file("ray_tracer_vox_valid.vox", &[
+		vox_size(2, 3, 4),
+		vox_voxels(2, &[[0, 0, 0, 1], [1, 2, 3, 2]]),
+		vox_chunk(b"RGBA", &colors)
+	]);
+
+	let grid = vox_load(&file_name).unwrap();
+	assert_eq!(grid.get_dimensions(), &[2, 4, 3]);
+	assert_eq!(grid.get_voxel(0, 0, 2), 1);
+	assert_eq!(grid.get_voxel(1, 3, 0), 2);
+	assert_eq!(grid.get_voxel(0, 0, 0), 0);
+
+	let red = grid.get_palette()[0].get_hit(0.0, 0.0);
+	assert_eq!(red.get_color().get_r(), 1.0);
+	assert_eq!(red.get_color().get_g(), 0.0);
+	assert_eq!(grid.get_palette()[1].get_hit(0.0, 0.0).get_color().get_g(), 1.0);
+
+	// Files without a palette use the default one of MagicaVoxel
+	let file_name = vox_file("ray_tracer_vox_default.vox", &[vox_size(1, 1, 1), vox_voxels(1, &[[0, 0, 0, 1]])]);
+	let grid = vox_load(&file_name).unwrap();
+	assert_eq!(grid.get_palette().len(), 255);
+	assert_eq!(grid.get_voxel(0, 0, 0), 1);
+}
+
+#[test]
+fn corrupt_vox_files_are_rejected() {
+	let mut oversized = vox_chunk(b"XYZI", &[0, 0, 0, 0]);
+	oversized[4] = 0xff;
+	let cases = [
+		("empty", vec![vox_size(0, 1, 1), vox_voxels(0, &[])]),
+		("large", vec![vox_size(257, 1, 1), vox_voxels(0, &[])]),
+		("huge", vec![vox_size(0xffffffff, 0xffffffff, 0xffffffff), vox_voxels(0, &[])]),
+		("count", vec![vox_size(1, 1, 1), vox_voxels(0xffffffff, &[[0, 0, 0, 1]])]),
+		("chunk", vec![vox_size(1, 1, 1), oversized]),
+		("outside", vec![vox_size(1, 1, 1), vox_voxels(1, &[[1, 0, 0, 1]])]),
+		("missing", vec![vox_size(1, 1, 1)])
+	];
+
+	for &(name, ref chunks) in cases.iter() {
+		let file_name = vox_file(&format!("ray_tracer_vox_{}.vox", name), chunks);
+		assert!(vox_load(&file_name).is_err(), "Loaded {}", name);
+	}
+}